@@ -10,7 +10,6 @@ pub enum AppError {
     BadRequest(String),
     #[error("not found: {0}")]
     NotFound(String),
-    #[allow(dead_code)]
     #[error("conflict: {0}")]
     Conflict(String),
     #[error("unauthorized")]
@@ -1,11 +1,15 @@
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
 use axum::response::Json;
-use engine::models::{AgentRole, AgentRuntime, AgentStatus, ModelProvider, VpsProvider};
+use engine::models::{
+    Agent, AgentRole, AgentRuntime, AgentStatus, DiscordChannels, ModelProvider, VpsProvider,
+};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::api::errors::AppError;
 use crate::api::handlers::channels::TelegramSettings;
+use crate::api::handlers::deployments::DeploymentJobResponse;
 use crate::api::handlers::AppState;
 use crate::api::services::agents::AgentService;
 
@@ -28,6 +32,8 @@ pub struct CreateAgentRequest {
     pub runtime_config: Option<serde_json::Value>,
     pub responsibility: Option<String>,
     pub emoji: Option<String>,
+    /// Open tasks the master may assign to the agent at once (default 3).
+    pub max_concurrent_tasks: Option<i32>,
 }
 
 #[derive(Deserialize)]
@@ -39,6 +45,23 @@ pub struct DeployMultiRequest {
     pub telegram_settings: Option<TelegramSettings>,
 }
 
+/// Fields left out are unchanged.
+#[derive(Deserialize)]
+pub struct UpdateAgentRequest {
+    pub personality: Option<String>,
+    pub skills: Option<Vec<String>>,
+    pub model_provider: Option<ModelProvider>,
+    pub model_api_key: Option<String>,
+    pub model_endpoint: Option<String>,
+    pub runtime_config: Option<serde_json::Value>,
+    pub discord_channel_id: Option<String>,
+    pub discord_channels: Option<DiscordChannels>,
+    pub telegram_settings: Option<TelegramSettings>,
+    pub max_concurrent_tasks: Option<i32>,
+    /// Needed to push the change to a Railway deployment.
+    pub railway_api_key: Option<String>,
+}
+
 #[derive(Serialize)]
 pub struct AgentResponse {
     pub id: Uuid,
@@ -48,15 +71,62 @@ pub struct AgentResponse {
     pub runtime: AgentRuntime,
     pub responsibility: Option<String>,
     pub emoji: Option<String>,
+    pub max_concurrent_tasks: Option<i32>,
+}
+
+impl From<Agent> for AgentResponse {
+    fn from(agent: Agent) -> Self {
+        Self {
+            id: agent.id,
+            name: agent.name,
+            role: agent.role,
+            status: agent.status,
+            runtime: agent.runtime,
+            responsibility: agent.responsibility,
+            emoji: agent.emoji,
+            max_concurrent_tasks: agent.max_concurrent_tasks,
+        }
+    }
+}
+
+/// `deployment_job` is set when the agent is deployed: the new settings are saved once the
+/// job has pushed them.
+#[derive(Serialize)]
+pub struct UpdateAgentResponse {
+    #[serde(flatten)]
+    pub agent: AgentResponse,
+    pub deployment_job: Option<DeploymentJobResponse>,
+}
+
+#[derive(Deserialize)]
+pub struct DestroyAgentQuery {
+    /// Also delete the agent's persistent volume; by default it is kept for the next deploy.
+    #[serde(default)]
+    pub delete_data: bool,
+}
+
+/// Optional body for `POST /api/agents/:id/{stop,start,restart}`.
+#[derive(Deserialize, Default)]
+pub struct AgentLifecycleRequest {
+    /// Required when the agent is deployed on Railway.
+    pub railway_api_key: Option<String>,
+}
+
+/// The agent record plus the job deploying it; poll `/api/deployment-jobs/:id` for progress.
+#[derive(Serialize)]
+pub struct CreateAgentResponse {
+    #[serde(flatten)]
+    pub agent: AgentResponse,
+    pub deployment_job: DeploymentJobResponse,
 }
 
 pub async fn create_agent(
     State(state): State<AppState>,
     Json(req): Json<CreateAgentRequest>,
-) -> Result<Json<AgentResponse>, AppError> {
+) -> Result<(StatusCode, Json<CreateAgentResponse>), AppError> {
     let service = AgentService::new(&state);
     let response = service.create_agent(req).await?;
-    Ok(Json(response))
+    Ok((StatusCode::ACCEPTED, Json(response)))
 }
 
 pub async fn list_agents(
@@ -67,6 +137,21 @@ pub async fn list_agents(
     Ok(Json(response))
 }
 
+pub async fn update_agent(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateAgentRequest>,
+) -> Result<(StatusCode, Json<UpdateAgentResponse>), AppError> {
+    let service = AgentService::new(&state);
+    let response = service.update_agent(id, req).await?;
+    let status = if response.deployment_job.is_some() {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, Json(response)))
+}
+
 pub async fn get_agent_status(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
@@ -79,17 +164,53 @@ pub async fn get_agent_status(
 pub async fn deploy_agents_multi(
     State(state): State<AppState>,
     Json(req): Json<DeployMultiRequest>,
-) -> Result<Json<crate::api::handlers::deployments::DeploymentResponse>, AppError> {
+) -> Result<(StatusCode, Json<DeploymentJobResponse>), AppError> {
     let service = AgentService::new(&state);
-    let deployment = service.deploy_agents_multi(req).await?;
-    Ok(Json(deployment.into()))
+    let job = service.deploy_agents_multi(req).await?;
+    Ok((StatusCode::ACCEPTED, Json(job.into())))
 }
 
 pub async fn destroy_agent(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
-) -> Result<axum::http::StatusCode, AppError> {
+    Query(query): Query<DestroyAgentQuery>,
+) -> Result<StatusCode, AppError> {
+    let service = AgentService::new(&state);
+    service.destroy_agent(id, query.delete_data).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn stop_agent(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    req: Option<Json<AgentLifecycleRequest>>,
+) -> Result<Json<AgentResponse>, AppError> {
+    let service = AgentService::new(&state);
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+    let agent = service.stop_agent(id, req).await?;
+    Ok(Json(agent))
+}
+
+/// Returns 202: the agent stays `deploying` until the reconciler sees it running.
+pub async fn start_agent(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    req: Option<Json<AgentLifecycleRequest>>,
+) -> Result<(StatusCode, Json<AgentResponse>), AppError> {
+    let service = AgentService::new(&state);
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+    let agent = service.start_agent(id, req).await?;
+    Ok((StatusCode::ACCEPTED, Json(agent)))
+}
+
+/// Returns 202: the agent stays `deploying` until the reconciler sees it running.
+pub async fn restart_agent(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    req: Option<Json<AgentLifecycleRequest>>,
+) -> Result<(StatusCode, Json<AgentResponse>), AppError> {
     let service = AgentService::new(&state);
-    service.destroy_agent(id).await?;
-    Ok(axum::http::StatusCode::NO_CONTENT)
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+    let agent = service.restart_agent(id, req).await?;
+    Ok((StatusCode::ACCEPTED, Json(agent)))
 }
@@ -1,7 +1,16 @@
 use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::Json;
-use engine::models::Deployment;
+use chrono::{DateTime, Utc};
+use engine::adapters::LogEntry;
+use engine::models::{
+    AgentRuntime, Deployment, DeploymentJob, DeploymentJobKind, DeploymentJobStatus,
+    DeploymentRevision, VpsProvider,
+};
+use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
 use uuid::Uuid;
 
 use crate::api::errors::AppError;
@@ -39,11 +48,98 @@ impl From<Deployment> for DeploymentResponse {
     }
 }
 
+#[derive(Serialize)]
+pub struct DeploymentJobResponse {
+    pub id: Uuid,
+    pub kind: DeploymentJobKind,
+    pub status: DeploymentJobStatus,
+    pub agent_ids: Vec<Uuid>,
+    pub provider: VpsProvider,
+    pub region: Option<String>,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub deployment_id: Option<Uuid>,
+    pub last_error: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<DeploymentJob> for DeploymentJobResponse {
+    fn from(job: DeploymentJob) -> Self {
+        Self {
+            id: job.id,
+            kind: job.kind,
+            status: job.status,
+            agent_ids: job.agent_ids,
+            provider: job.provider,
+            region: job.region,
+            attempts: job.attempts,
+            max_attempts: job.max_attempts,
+            deployment_id: job.deployment_id,
+            last_error: job.last_error,
+            created_at: job.created_at,
+            updated_at: job.updated_at,
+        }
+    }
+}
+
+/// Revisions are listed without their rendered env values, which contain secrets.
+#[derive(Serialize)]
+pub struct DeploymentRevisionResponse {
+    pub id: Uuid,
+    pub deployment_id: Uuid,
+    pub revision: i32,
+    pub runtime: AgentRuntime,
+    pub provider: VpsProvider,
+    pub region: Option<String>,
+    pub env_keys: Vec<String>,
+    pub init_script_hash: String,
+    pub restored_from: Option<i32>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<DeploymentRevision> for DeploymentRevisionResponse {
+    fn from(revision: DeploymentRevision) -> Self {
+        Self {
+            id: revision.id,
+            deployment_id: revision.deployment_id,
+            revision: revision.revision,
+            runtime: revision.runtime,
+            provider: revision.provider,
+            region: revision.region,
+            env_keys: revision.env.into_keys().collect(),
+            init_script_hash: revision.init_script_hash,
+            restored_from: revision.restored_from,
+            created_at: revision.created_at,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct RollbackRequest {
+    /// Defaults to the revision before the latest one.
+    pub revision: Option<i32>,
+    pub railway_api_key: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct DeploymentLogsQuery {
     pub lines: Option<i32>,
 }
 
+#[derive(Deserialize)]
+pub struct DeploymentLogStreamQuery {
+    /// Keep the connection open and send new entries as they arrive.
+    #[serde(default)]
+    pub follow: bool,
+    /// Only entries at or after this RFC 3339 timestamp.
+    pub since: Option<DateTime<Utc>>,
+    /// Case-insensitive text the message must contain.
+    pub filter: Option<String>,
+    /// Existing entries to start from when `since` isn't set (default 100).
+    pub tail: Option<usize>,
+}
+
 pub async fn list_deployments(
     State(state): State<AppState>,
 ) -> Result<Json<Vec<DeploymentResponse>>, AppError> {
@@ -75,3 +171,62 @@ pub async fn get_deployment_logs(
     let logs = service.get_deployment_logs(id, query.lines).await?;
     Ok(Json(logs))
 }
+
+/// Server-Sent Events: a `log` event per entry (JSON), or a final `error` event if the
+/// provider stream fails.
+pub async fn stream_deployment_logs(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<DeploymentLogStreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let service = DeploymentService::new(&state);
+    let entries = service.stream_deployment_logs(id, query).await?;
+    let events = entries.map(|entry| Ok(log_event(entry)));
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+fn log_event(entry: anyhow::Result<LogEntry>) -> Event {
+    let event = match entry {
+        Ok(entry) => Event::default().event("log").json_data(entry),
+        Err(err) => Ok(Event::default().event("error").data(format!("{:#}", err))),
+    };
+    event.unwrap_or_else(|_| {
+        Event::default()
+            .event("error")
+            .data("failed to encode log entry")
+    })
+}
+
+pub async fn get_deployment_job(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<DeploymentJobResponse>, AppError> {
+    let service = DeploymentService::new(&state);
+    let job = service.get_deployment_job(id).await?;
+    Ok(Json(job.into()))
+}
+
+pub async fn list_deployment_revisions(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<DeploymentRevisionResponse>>, AppError> {
+    let service = DeploymentService::new(&state);
+    let revisions = service.list_revisions(id).await?;
+    Ok(Json(
+        revisions
+            .into_iter()
+            .map(DeploymentRevisionResponse::from)
+            .collect(),
+    ))
+}
+
+pub async fn rollback_deployment(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    req: Option<Json<RollbackRequest>>,
+) -> Result<(StatusCode, Json<DeploymentJobResponse>), AppError> {
+    let service = DeploymentService::new(&state);
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+    let job = service.rollback(id, req).await?;
+    Ok((StatusCode::ACCEPTED, Json(job.into())))
+}
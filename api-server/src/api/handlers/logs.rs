@@ -0,0 +1,33 @@
+use axum::extract::{Query, State};
+use axum::response::Json;
+use chrono::{DateTime, Utc};
+use engine::models::DeploymentLog;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::api::errors::AppError;
+use crate::api::handlers::AppState;
+use crate::api::services::logs::LogService;
+
+#[derive(Deserialize)]
+pub struct LogSearchQuery {
+    pub agent_id: Option<Uuid>,
+    pub deployment_id: Option<Uuid>,
+    /// Minimum level, e.g. `warn` returns warnings and errors.
+    pub level: Option<String>,
+    /// Case-insensitive text the message must contain.
+    pub q: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Defaults to 100, at most 1000.
+    pub limit: Option<i64>,
+}
+
+pub async fn search_logs(
+    State(state): State<AppState>,
+    Query(query): Query<LogSearchQuery>,
+) -> Result<Json<Vec<DeploymentLog>>, AppError> {
+    let service = LogService::new(&state);
+    let logs = service.search_logs(query).await?;
+    Ok(Json(logs))
+}
@@ -1,11 +1,15 @@
 pub mod agents;
 pub mod channels;
 pub mod deployments;
+pub mod logs;
+pub mod providers;
+pub mod slack;
 pub mod tasks;
 pub mod teams;
 pub mod validation;
 
 use engine::coordinator::Coordinator;
+use engine::deployment::jobs::DeploymentJobQueue;
 use engine::deployment::manager::DeploymentManager;
 use engine::storage::Database;
 use std::time::Instant;
@@ -14,14 +18,27 @@ use std::time::Instant;
 pub struct AppState {
     pub db: Database,
     pub deployment_manager: DeploymentManager,
-    #[allow(dead_code)]
+    pub deployment_jobs: DeploymentJobQueue,
     pub coordinator: Coordinator,
     pub api_key: Option<String>,
     pub start_time: Instant,
 }
 
-pub use agents::{create_agent, deploy_agents_multi, destroy_agent, get_agent_status, list_agents};
-pub use deployments::{get_deployment, get_deployment_logs, list_deployments};
-pub use tasks::{aggregate_task, get_agent_tasks, send_task, update_task};
-pub use teams::{assign_agent_to_team, create_team, get_team_roster, list_teams};
+pub use agents::{
+    create_agent, deploy_agents_multi, destroy_agent, get_agent_status, list_agents, restart_agent,
+    start_agent, stop_agent, update_agent,
+};
+pub use deployments::{
+    get_deployment, get_deployment_job, get_deployment_logs, list_deployment_revisions,
+    list_deployments, rollback_deployment, stream_deployment_logs,
+};
+pub use logs::search_logs;
+pub use providers::list_providers;
+pub use slack::slack_events;
+pub use tasks::{
+    aggregate_task, get_agent_tasks, get_task_attempts, run_team_command, send_task, update_task,
+};
+pub use teams::{
+    assign_agent_to_team, create_team, delete_team, get_team_roster, list_teams, update_team,
+};
 pub use validation::{get_server_health_with_state, get_server_status};
@@ -0,0 +1,23 @@
+use axum::extract::State;
+use axum::response::Json;
+use engine::adapters::ProviderCapabilities;
+use engine::models::VpsProvider;
+use serde::Serialize;
+
+use crate::api::errors::AppError;
+use crate::api::handlers::AppState;
+use crate::api::services::providers::ProviderService;
+
+#[derive(Serialize)]
+pub struct ProviderResponse {
+    pub provider: VpsProvider,
+    #[serde(flatten)]
+    pub capabilities: ProviderCapabilities,
+}
+
+pub async fn list_providers(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ProviderResponse>>, AppError> {
+    let service = ProviderService::new(&state);
+    Ok(Json(service.list_providers()))
+}
@@ -0,0 +1,37 @@
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::Json;
+use serde::Serialize;
+
+use crate::api::errors::AppError;
+use crate::api::handlers::AppState;
+use crate::api::services::slack::SlackService;
+
+#[derive(Serialize)]
+pub struct SlackEventResponse {
+    /// Echoed back when Slack verifies the request URL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>,
+}
+
+/// Events API requests from Slack, authenticated by their signature rather than the API key.
+pub async fn slack_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<SlackEventResponse>, AppError> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+    let service = SlackService::new(&state);
+    let challenge = service.handle_event(
+        header("x-slack-request-timestamp"),
+        header("x-slack-signature"),
+        &body,
+    )?;
+    Ok(Json(SlackEventResponse { challenge }))
+}
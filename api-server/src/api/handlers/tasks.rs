@@ -6,16 +6,30 @@ use uuid::Uuid;
 use crate::api::errors::AppError;
 use crate::api::handlers::AppState;
 use crate::api::services::tasks::TaskService;
-use engine::models::{Task, TaskStatus};
+use engine::models::{Task, TaskAttempt, TaskStatus};
 
 #[derive(Deserialize)]
 pub struct SendTaskRequest {
     pub description: String,
+    /// Tasks (in the same team) that must complete before this one is dispatched.
+    #[serde(default)]
+    pub depends_on: Vec<Uuid>,
+    /// Seconds the agent has for each attempt; defaults to the team's `task_timeout_secs`.
+    pub timeout_secs: Option<u32>,
+    /// Attempts before a timed-out task is given up on; defaults to the team's
+    /// `max_task_attempts`, or 1.
+    pub max_attempts: Option<u32>,
 }
 
 #[derive(Serialize)]
 pub struct TaskAggregateResponse {
     pub tasks: Vec<Task>,
+    /// The task's and its subtasks' results combined, as posted once they all finish.
+    pub summary: String,
+    /// Tasks the requested task depends on.
+    pub dependencies: Vec<Task>,
+    /// Tasks waiting on the requested task.
+    pub dependents: Vec<Task>,
 }
 
 #[derive(Deserialize)]
@@ -24,13 +38,25 @@ pub struct UpdateTaskRequest {
     pub result: Option<String>,
 }
 
+#[derive(Deserialize)]
+pub struct TeamCommandRequest {
+    /// A task command in its text form, e.g. `!task-complete <task id> <result>`.
+    pub command: String,
+}
+
+#[derive(Serialize)]
+pub struct TeamCommandResponse {
+    pub reply: String,
+    pub task: Option<Task>,
+}
+
 pub async fn send_task(
     State(state): State<AppState>,
     Path(agent_id): Path<Uuid>,
     Json(req): Json<SendTaskRequest>,
 ) -> Result<Json<Task>, AppError> {
     let service = TaskService::new(&state);
-    let task = service.send_task(agent_id, req.description).await?;
+    let task = service.send_task(agent_id, req).await?;
     Ok(Json(task))
 }
 
@@ -61,3 +87,23 @@ pub async fn aggregate_task(
     let response = service.aggregate_task(task_id).await?;
     Ok(Json(response))
 }
+
+/// Task commands from teams that don't coordinate over Discord, such as webhook receivers.
+pub async fn run_team_command(
+    State(state): State<AppState>,
+    Path(team_id): Path<Uuid>,
+    Json(req): Json<TeamCommandRequest>,
+) -> Result<Json<TeamCommandResponse>, AppError> {
+    let service = TaskService::new(&state);
+    let response = service.run_team_command(team_id, req).await?;
+    Ok(Json(response))
+}
+
+pub async fn get_task_attempts(
+    State(state): State<AppState>,
+    Path(task_id): Path<Uuid>,
+) -> Result<Json<Vec<TaskAttempt>>, AppError> {
+    let service = TaskService::new(&state);
+    let attempts = service.get_task_attempts(task_id).await?;
+    Ok(Json(attempts))
+}
@@ -1,6 +1,7 @@
 use axum::extract::{Path, State};
+use axum::http::StatusCode;
 use axum::response::Json;
-use engine::models::{AgentRole, DiscordChannels};
+use engine::models::{AgentRole, DiscordChannels, SlackChannels, TeamSettings};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -14,9 +15,22 @@ pub struct CreateTeamRequest {
     pub name: String,
     pub master_id: Uuid,
     pub slave_ids: Vec<Uuid>,
+    #[serde(default)]
     pub discord_channel_id: String, // Legacy: single channel
     pub discord_channels: Option<DiscordChannels>, // New: multiple channels
+    /// Provision the team's channels in this guild instead of passing them in.
+    pub discord_guild_id: Option<String>,
+    /// Required when `settings.transport` is Slack.
+    pub slack_channels: Option<SlackChannels>,
     pub telegram_settings: Option<TelegramSettings>,
+    pub settings: Option<TeamSettings>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateTeamRequest {
+    pub name: Option<String>,
+    pub slack_channels: Option<SlackChannels>,
+    pub settings: Option<TeamSettings>,
 }
 
 #[derive(Serialize)]
@@ -26,6 +40,12 @@ pub struct TeamResponse {
     pub master_id: Uuid,
     pub slave_ids: Vec<Uuid>,
     pub discord_channel_id: String,
+    pub discord_channels: DiscordChannels,
+    /// Set when the channels were provisioned for the team.
+    pub discord_category_id: Option<String>,
+    pub slack_channels: Option<SlackChannels>,
+    /// Credentials are redacted.
+    pub settings: TeamSettings,
 }
 
 #[derive(Deserialize)]
@@ -68,6 +88,26 @@ pub async fn list_teams(
     Ok(Json(response))
 }
 
+pub async fn update_team(
+    State(state): State<AppState>,
+    Path(team_id): Path<Uuid>,
+    Json(req): Json<UpdateTeamRequest>,
+) -> Result<Json<TeamResponse>, AppError> {
+    let service = TeamService::new(&state);
+    let response = service.update_team(team_id, req).await?;
+    Ok(Json(response))
+}
+
+/// Also deletes the team's tasks and, if they were provisioned, its Discord channels.
+pub async fn delete_team(
+    State(state): State<AppState>,
+    Path(team_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let service = TeamService::new(&state);
+    service.delete_team(team_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn assign_agent_to_team(
     State(state): State<AppState>,
     Path(team_id): Path<Uuid>,
@@ -7,6 +7,7 @@ pub mod services;
 use anyhow::Result;
 use axum::Router;
 use engine::coordinator::Coordinator;
+use engine::deployment::jobs::DeploymentJobQueue;
 use engine::deployment::manager::DeploymentManager;
 use engine::storage::Database;
 use std::time::Instant;
@@ -19,12 +20,20 @@ impl ApiServer {
     pub async fn new(
         db: Database,
         deployment_manager: DeploymentManager,
+        deployment_jobs: DeploymentJobQueue,
         coordinator: Coordinator,
         api_key: Option<String>,
         start_time: Instant,
     ) -> Result<Self> {
-        let router =
-            routes::create_router(db, deployment_manager, coordinator, api_key, start_time).await?;
+        let router = routes::create_router(
+            db,
+            deployment_manager,
+            deployment_jobs,
+            coordinator,
+            api_key,
+            start_time,
+        )
+        .await?;
 
         Ok(Self { router })
     }
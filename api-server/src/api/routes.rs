@@ -3,6 +3,7 @@ use crate::api::middleware;
 use axum::middleware as axum_middleware;
 use axum::Router;
 use engine::coordinator::Coordinator;
+use engine::deployment::jobs::DeploymentJobQueue;
 use engine::deployment::manager::DeploymentManager;
 use engine::storage::Database;
 use tower_http::cors::CorsLayer;
@@ -10,6 +11,7 @@ use tower_http::cors::CorsLayer;
 pub async fn create_router(
     db: Database,
     deployment_manager: DeploymentManager,
+    deployment_jobs: DeploymentJobQueue,
     coordinator: Coordinator,
     api_key: Option<String>,
     start_time: std::time::Instant,
@@ -17,6 +19,7 @@ pub async fn create_router(
     let state = handlers::AppState {
         db,
         deployment_manager,
+        deployment_jobs,
         coordinator,
         api_key,
         start_time,
@@ -29,6 +32,10 @@ pub async fn create_router(
         )
         .route("/api/teams", axum::routing::post(handlers::create_team))
         .route("/api/teams", axum::routing::get(handlers::list_teams))
+        .route(
+            "/api/teams/:id",
+            axum::routing::patch(handlers::update_team).delete(handlers::delete_team),
+        )
         .route(
             "/api/teams/:id/assign",
             axum::routing::post(handlers::assign_agent_to_team),
@@ -45,7 +52,19 @@ pub async fn create_router(
         )
         .route(
             "/api/agents/:id",
-            axum::routing::delete(handlers::destroy_agent),
+            axum::routing::delete(handlers::destroy_agent).patch(handlers::update_agent),
+        )
+        .route(
+            "/api/agents/:id/stop",
+            axum::routing::post(handlers::stop_agent),
+        )
+        .route(
+            "/api/agents/:id/start",
+            axum::routing::post(handlers::start_agent),
+        )
+        .route(
+            "/api/agents/:id/restart",
+            axum::routing::post(handlers::restart_agent),
         )
         .route(
             "/api/agents/:id/tasks",
@@ -55,6 +74,10 @@ pub async fn create_router(
             "/api/agents/:id/tasks",
             axum::routing::get(handlers::get_agent_tasks),
         )
+        .route(
+            "/api/teams/:id/commands",
+            axum::routing::post(handlers::run_team_command),
+        )
         .route(
             "/api/teams/:id/roster",
             axum::routing::get(handlers::get_team_roster),
@@ -67,6 +90,10 @@ pub async fn create_router(
             "/api/server/status",
             axum::routing::get(handlers::get_server_status),
         )
+        .route(
+            "/api/providers",
+            axum::routing::get(handlers::list_providers),
+        )
         .route(
             "/api/deployments",
             axum::routing::get(handlers::list_deployments),
@@ -79,6 +106,23 @@ pub async fn create_router(
             "/api/deployments/:id/logs",
             axum::routing::get(handlers::get_deployment_logs),
         )
+        .route("/api/logs", axum::routing::get(handlers::search_logs))
+        .route(
+            "/api/deployments/:id/logs/stream",
+            axum::routing::get(handlers::stream_deployment_logs),
+        )
+        .route(
+            "/api/deployments/:id/revisions",
+            axum::routing::get(handlers::list_deployment_revisions),
+        )
+        .route(
+            "/api/deployments/:id/rollback",
+            axum::routing::post(handlers::rollback_deployment),
+        )
+        .route(
+            "/api/deployment-jobs/:id",
+            axum::routing::get(handlers::get_deployment_job),
+        )
         .route(
             "/api/tasks/:id",
             axum::routing::patch(handlers::update_task),
@@ -87,10 +131,19 @@ pub async fn create_router(
             "/api/tasks/:id/aggregate",
             axum::routing::get(handlers::aggregate_task),
         )
+        .route(
+            "/api/tasks/:id/attempts",
+            axum::routing::get(handlers::get_task_attempts),
+        )
         .layer(axum_middleware::from_fn_with_state(
             state.clone(),
             middleware::require_api_key,
         ))
+        // Slack can't send the API key; its requests are verified by signature instead
+        .route(
+            "/api/slack/events",
+            axum::routing::post(handlers::slack_events),
+        )
         .layer(CorsLayer::permissive())
         .with_state(state);
 
@@ -1,13 +1,20 @@
 use crate::api::errors::AppError;
-use crate::api::handlers::agents::{AgentResponse, CreateAgentRequest, DeployMultiRequest};
-use crate::api::handlers::channels::{apply_telegram_settings_to_agents, TelegramSettings};
+use crate::api::handlers::agents::{
+    AgentLifecycleRequest, AgentResponse, CreateAgentRequest, CreateAgentResponse,
+    DeployMultiRequest, UpdateAgentRequest, UpdateAgentResponse,
+};
+use crate::api::handlers::channels::apply_telegram_settings_to_agents;
 use crate::api::handlers::channels::{
     openclaw_context_from_agent, openclaw_context_from_request,
     openclaw_telegram_defaults_from_adapters, OpenClawConfig,
 };
 use crate::api::handlers::AppState;
-use engine::models::{Agent, AgentRole, AgentRuntime, AgentStatus, VpsProvider};
-use engine::storage::repositories::{AgentRepository, TeamRepository};
+use engine::models::{
+    Agent, AgentRole, AgentRuntime, AgentStatus, DeploymentJobKind, DeploymentStatus, VpsProvider,
+};
+use engine::storage::repositories::{
+    AgentRepository, DeploymentJobRepository, DeploymentRepository, TeamRepository,
+};
 use uuid::Uuid;
 
 pub struct AgentService<'a> {
@@ -19,7 +26,11 @@ impl<'a> AgentService<'a> {
         Self { state }
     }
 
-    pub async fn create_agent(&self, req: CreateAgentRequest) -> Result<AgentResponse, AppError> {
+    pub async fn create_agent(
+        &self,
+        req: CreateAgentRequest,
+    ) -> Result<CreateAgentResponse, AppError> {
+        validate_max_concurrent_tasks(req.max_concurrent_tasks)?;
         let railway_api_key = sanitize_optional_secret(req.railway_api_key.clone());
         if matches!(&req.provider, VpsProvider::Railway) && railway_api_key.is_none() {
             return Err(AppError::BadRequest(
@@ -88,10 +99,21 @@ impl<'a> AgentService<'a> {
             runtime_config,
             responsibility: req.responsibility,
             emoji: req.emoji,
+            max_concurrent_tasks: req.max_concurrent_tasks,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
         };
 
+        self.state
+            .deployment_manager
+            .check_capabilities(
+                std::slice::from_ref(&agent),
+                req.provider.clone(),
+                req.region.as_deref(),
+                false,
+            )
+            .map_err(|err| AppError::BadRequest(err.to_string()))?;
+
         let agent_repo = AgentRepository::new(self.state.db.db().clone());
         agent_repo
             .create(&agent)
@@ -122,20 +144,145 @@ impl<'a> AgentService<'a> {
                 .map_err(AppError::Internal)?;
         }
 
+        let job = self
+            .state
+            .deployment_jobs
+            .enqueue_deploy(agent.id, req.provider, req.region, railway_api_key)
+            .await
+            .map_err(AppError::Internal)?;
+
+        Ok(CreateAgentResponse {
+            agent: AgentResponse {
+                id: agent.id,
+                name: agent.name,
+                role: agent.role,
+                status: agent.status,
+                runtime: agent.runtime,
+                responsibility: agent.responsibility,
+                emoji: agent.emoji,
+                max_concurrent_tasks: agent.max_concurrent_tasks,
+            },
+            deployment_job: job.into(),
+        })
+    }
+
+    /// Applies the changed settings and revalidates the runtime config and the plan of the
+    /// agent's whole deployment. Undeployed agents are saved right away; for deployed ones a
+    /// job is queued that pushes the new plan and saves the settings once it is live.
+    pub async fn update_agent(
+        &self,
+        id: Uuid,
+        req: UpdateAgentRequest,
+    ) -> Result<UpdateAgentResponse, AppError> {
+        let agent_repo = AgentRepository::new(self.state.db.db().clone());
+        let mut agent = agent_repo
+            .get_by_id(id)
+            .await
+            .map_err(AppError::Internal)?
+            .ok_or_else(|| AppError::NotFound("agent not found".to_string()))?;
+
+        if let Some(personality) = req.personality {
+            agent.personality = Some(personality);
+        }
+        if let Some(skills) = req.skills {
+            agent.skills = skills;
+        }
+        if let Some(model_provider) = req.model_provider {
+            agent.model_provider = model_provider;
+        }
+        if let Some(model_api_key) = sanitize_optional_secret(req.model_api_key) {
+            agent.model_api_key = Some(model_api_key);
+        }
+        if let Some(model_endpoint) = req.model_endpoint {
+            agent.model_endpoint = Some(model_endpoint);
+        }
+        if let Some(discord_channel_id) = req.discord_channel_id {
+            agent.discord_channel_id = Some(discord_channel_id);
+        }
+        if let Some(discord_channels) = req.discord_channels {
+            agent.discord_channels = Some(discord_channels);
+        }
+        if let Some(runtime_config) = req.runtime_config {
+            agent.runtime_config = Some(runtime_config);
+        }
+        if let Some(max_concurrent_tasks) = req.max_concurrent_tasks {
+            validate_max_concurrent_tasks(Some(max_concurrent_tasks))?;
+            agent.max_concurrent_tasks = Some(max_concurrent_tasks);
+        }
+
+        if agent.runtime == AgentRuntime::OpenClaw {
+            let defaults =
+                openclaw_telegram_defaults_from_adapters(&openclaw_context_from_agent(&agent))?;
+            let mut config = OpenClawConfig::from_value(agent.runtime_config.clone())?;
+            config.apply_defaults(defaults);
+            if let Some(settings) = &req.telegram_settings {
+                config.apply_telegram_settings(settings);
+            }
+            config.validate()?;
+            agent.runtime_config = Some(config.to_value()?);
+        } else if req.telegram_settings.is_some() {
+            return Err(AppError::BadRequest(
+                "telegram_settings are only supported for openclaw agents".to_string(),
+            ));
+        }
+
+        let plan_agents = self
+            .state
+            .deployment_manager
+            .plan_agents(&agent)
+            .await
+            .map_err(AppError::Internal)?;
         self.state
             .deployment_manager
-            .deploy_agent(agent.clone(), req.provider, req.region, railway_api_key)
+            .build_plan(&plan_agents)
+            .map_err(|err| AppError::BadRequest(format!("invalid runtime plan: {err}")))?;
+
+        let railway_api_key = sanitize_optional_secret(req.railway_api_key);
+        let deployment = match agent.deployment_id {
+            Some(deployment_id) => DeploymentRepository::new(self.state.db.db().clone())
+                .get_by_id(deployment_id)
+                .await
+                .map_err(AppError::Internal)?,
+            None => None,
+        };
+
+        let Some(deployment) = deployment else {
+            self.state
+                .deployment_manager
+                .update_agent_settings(&agent, None)
+                .await
+                .map_err(AppError::Internal)?;
+            return Ok(UpdateAgentResponse {
+                agent: agent.into(),
+                deployment_job: None,
+            });
+        };
+
+        if matches!(deployment.provider, VpsProvider::Railway) && railway_api_key.is_none() {
+            return Err(AppError::BadRequest(
+                "railway_api_key is required to update Railway deployments".to_string(),
+            ));
+        }
+        let pending = DeploymentJobRepository::new(self.state.db.db().clone())
+            .has_open_job(agent.id, &DeploymentJobKind::UpdateSettings)
             .await
             .map_err(AppError::Internal)?;
+        if pending {
+            return Err(AppError::Conflict(
+                "a settings update for this agent is still being deployed".to_string(),
+            ));
+        }
 
-        Ok(AgentResponse {
-            id: agent.id,
-            name: agent.name,
-            role: agent.role,
-            status: agent.status,
-            runtime: agent.runtime,
-            responsibility: agent.responsibility,
-            emoji: agent.emoji,
+        let job = self
+            .state
+            .deployment_jobs
+            .enqueue_update_settings(agent.clone(), &deployment, railway_api_key)
+            .await
+            .map_err(AppError::Internal)?;
+
+        Ok(UpdateAgentResponse {
+            agent: agent.into(),
+            deployment_job: Some(job.into()),
         })
     }
 
@@ -143,18 +290,7 @@ impl<'a> AgentService<'a> {
         let repo = AgentRepository::new(self.state.db.db().clone());
         let agents = repo.list_all().await.map_err(AppError::Internal)?;
 
-        Ok(agents
-            .into_iter()
-            .map(|agent| AgentResponse {
-                id: agent.id,
-                name: agent.name,
-                role: agent.role,
-                status: agent.status,
-                runtime: agent.runtime,
-                responsibility: agent.responsibility,
-                emoji: agent.emoji,
-            })
-            .collect())
+        Ok(agents.into_iter().map(AgentResponse::from).collect())
     }
 
     pub async fn get_agent_status(&self, id: Uuid) -> Result<AgentStatus, AppError> {
@@ -167,19 +303,113 @@ impl<'a> AgentService<'a> {
         Ok(status)
     }
 
-    pub async fn destroy_agent(&self, id: Uuid) -> Result<(), AppError> {
+    pub async fn destroy_agent(&self, id: Uuid, delete_data: bool) -> Result<(), AppError> {
         self.state
             .deployment_manager
-            .destroy_agent(id)
+            .destroy_agent(id, delete_data)
             .await
             .map_err(AppError::Internal)?;
         Ok(())
     }
 
+    pub async fn stop_agent(
+        &self,
+        id: Uuid,
+        req: AgentLifecycleRequest,
+    ) -> Result<AgentResponse, AppError> {
+        self.change_lifecycle(id, LifecycleAction::Stop, req).await
+    }
+
+    pub async fn start_agent(
+        &self,
+        id: Uuid,
+        req: AgentLifecycleRequest,
+    ) -> Result<AgentResponse, AppError> {
+        self.change_lifecycle(id, LifecycleAction::Start, req).await
+    }
+
+    pub async fn restart_agent(
+        &self,
+        id: Uuid,
+        req: AgentLifecycleRequest,
+    ) -> Result<AgentResponse, AppError> {
+        self.change_lifecycle(id, LifecycleAction::Restart, req)
+            .await
+    }
+
+    async fn change_lifecycle(
+        &self,
+        id: Uuid,
+        action: LifecycleAction,
+        req: AgentLifecycleRequest,
+    ) -> Result<AgentResponse, AppError> {
+        let agent_repo = AgentRepository::new(self.state.db.db().clone());
+        let agent = agent_repo
+            .get_by_id(id)
+            .await
+            .map_err(AppError::Internal)?
+            .ok_or_else(|| AppError::NotFound("agent not found".to_string()))?;
+        let deployment_id = agent
+            .deployment_id
+            .ok_or_else(|| AppError::Conflict("agent is not deployed".to_string()))?;
+        let deployment = DeploymentRepository::new(self.state.db.db().clone())
+            .get_by_id(deployment_id)
+            .await
+            .map_err(AppError::Internal)?
+            .ok_or_else(|| AppError::NotFound("deployment not found".to_string()))?;
+
+        match (action, &deployment.status) {
+            (_, DeploymentStatus::Pending | DeploymentStatus::Creating) => {
+                return Err(AppError::Conflict(
+                    "deployment is still starting".to_string(),
+                ));
+            }
+            (_, DeploymentStatus::Destroyed | DeploymentStatus::Lost) => {
+                return Err(AppError::Conflict(
+                    "deployment no longer exists; redeploy the agent".to_string(),
+                ));
+            }
+            (LifecycleAction::Stop, DeploymentStatus::Stopped) => {
+                return Err(AppError::Conflict("agent is already stopped".to_string()));
+            }
+            (LifecycleAction::Start, DeploymentStatus::Running) => {
+                return Err(AppError::Conflict("agent is already running".to_string()));
+            }
+            (LifecycleAction::Restart, DeploymentStatus::Stopped) => {
+                return Err(AppError::Conflict(
+                    "agent is stopped; start it instead".to_string(),
+                ));
+            }
+            _ => {}
+        }
+
+        let railway_api_key = sanitize_optional_secret(req.railway_api_key);
+        if matches!(deployment.provider, VpsProvider::Railway) && railway_api_key.is_none() {
+            return Err(AppError::BadRequest(
+                "railway_api_key is required for Railway deployments".to_string(),
+            ));
+        }
+
+        let manager = &self.state.deployment_manager;
+        match action {
+            LifecycleAction::Stop => manager.stop_agent(id, railway_api_key).await,
+            LifecycleAction::Start => manager.start_agent(id, railway_api_key).await,
+            LifecycleAction::Restart => manager.restart_agent(id, railway_api_key).await,
+        }
+        .map_err(AppError::Internal)?;
+
+        let agent = agent_repo
+            .get_by_id(id)
+            .await
+            .map_err(AppError::Internal)?
+            .ok_or_else(|| AppError::NotFound("agent not found".to_string()))?;
+        Ok(agent.into())
+    }
+
     pub async fn deploy_agents_multi(
         &self,
         req: DeployMultiRequest,
-    ) -> Result<engine::models::Deployment, AppError> {
+    ) -> Result<engine::models::DeploymentJob, AppError> {
         let railway_api_key = sanitize_optional_secret(req.railway_api_key.clone());
         if matches!(&req.provider, VpsProvider::Railway) && railway_api_key.is_none() {
             return Err(AppError::BadRequest(
@@ -204,32 +434,43 @@ impl<'a> AgentService<'a> {
             agents.push(agent);
         }
 
+        self.state
+            .deployment_manager
+            .check_capabilities(&agents, req.provider.clone(), req.region.as_deref(), true)
+            .map_err(|err| AppError::BadRequest(err.to_string()))?;
+
+        // Persisted before enqueueing; the worker reloads the agents when the job runs
         if let Some(settings) = req.telegram_settings.clone() {
             apply_telegram_settings_to_agents(&agent_repo, &settings, &agents).await?;
-            for agent in &mut agents {
-                if agent.runtime != AgentRuntime::OpenClaw {
-                    continue;
-                }
-                let config = apply_openclaw_telegram_settings(
-                    agent.runtime_config.clone(),
-                    &settings,
-                    agent,
-                )?;
-                agent.runtime_config = Some(config);
-            }
         }
 
-        let deployment = self
+        let job = self
             .state
-            .deployment_manager
-            .deploy_agents_multi(agents, req.provider, req.region, railway_api_key)
+            .deployment_jobs
+            .enqueue_deploy_multi(req.agent_ids, req.provider, req.region, railway_api_key)
             .await
             .map_err(AppError::Internal)?;
 
-        Ok(deployment)
+        Ok(job)
     }
 }
 
+#[derive(Clone, Copy)]
+enum LifecycleAction {
+    Stop,
+    Start,
+    Restart,
+}
+
+fn validate_max_concurrent_tasks(value: Option<i32>) -> Result<(), AppError> {
+    if value.is_some_and(|value| value < 1) {
+        return Err(AppError::BadRequest(
+            "max_concurrent_tasks must be at least 1".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 fn sanitize_optional_secret(value: Option<String>) -> Option<String> {
     value.and_then(|token| {
         let trimmed = token.trim();
@@ -240,16 +481,3 @@ fn sanitize_optional_secret(value: Option<String>) -> Option<String> {
         }
     })
 }
-
-fn apply_openclaw_telegram_settings(
-    runtime_config: Option<serde_json::Value>,
-    settings: &TelegramSettings,
-    agent: &Agent,
-) -> Result<serde_json::Value, AppError> {
-    let defaults = openclaw_telegram_defaults_from_adapters(&openclaw_context_from_agent(agent))?;
-    let mut config = OpenClawConfig::from_value(runtime_config)?;
-    config.apply_defaults(defaults);
-    config.apply_telegram_settings(settings);
-    config.validate()?;
-    config.to_value()
-}
@@ -1,7 +1,14 @@
 use crate::api::errors::AppError;
+use crate::api::handlers::deployments::{DeploymentLogStreamQuery, RollbackRequest};
 use crate::api::handlers::AppState;
-use engine::models::Deployment;
-use engine::storage::repositories::DeploymentRepository;
+use engine::adapters::{LogStream, LogStreamOptions};
+use engine::models::{
+    Deployment, DeploymentJob, DeploymentJobKind, DeploymentRevision, VpsProvider,
+};
+use engine::storage::repositories::{
+    DeploymentJobRepository, DeploymentRepository, DeploymentRevisionRepository,
+};
+use futures::StreamExt;
 use uuid::Uuid;
 
 pub struct DeploymentService<'a> {
@@ -27,6 +34,74 @@ impl<'a> DeploymentService<'a> {
             .ok_or_else(|| AppError::NotFound("deployment not found".to_string()))
     }
 
+    pub async fn list_revisions(&self, id: Uuid) -> Result<Vec<DeploymentRevision>, AppError> {
+        self.get_deployment(id).await?;
+        let repo = DeploymentRevisionRepository::new(self.state.db.db().clone());
+        repo.list_by_deployment(id)
+            .await
+            .map_err(AppError::Internal)
+    }
+
+    pub async fn rollback(
+        &self,
+        id: Uuid,
+        req: RollbackRequest,
+    ) -> Result<DeploymentJob, AppError> {
+        let deployment = self.get_deployment(id).await?;
+        let repo = DeploymentRevisionRepository::new(self.state.db.db().clone());
+        let latest = repo
+            .get_latest(id)
+            .await
+            .map_err(AppError::Internal)?
+            .ok_or_else(|| AppError::BadRequest("deployment has no revisions".to_string()))?;
+
+        let target = req.revision.unwrap_or(latest.revision - 1);
+        if target == latest.revision {
+            return Err(AppError::BadRequest(format!(
+                "deployment is already at revision {}",
+                target
+            )));
+        }
+        if repo
+            .get(id, target)
+            .await
+            .map_err(AppError::Internal)?
+            .is_none()
+        {
+            return Err(AppError::NotFound(format!("revision {} not found", target)));
+        }
+
+        if matches!(deployment.provider, VpsProvider::Railway) && req.railway_api_key.is_none() {
+            return Err(AppError::BadRequest(
+                "railway_api_key is required to roll back Railway deployments".to_string(),
+            ));
+        }
+        let pending = DeploymentJobRepository::new(self.state.db.db().clone())
+            .has_open_job(deployment.agent_id, &DeploymentJobKind::Rollback)
+            .await
+            .map_err(AppError::Internal)?;
+        if pending {
+            return Err(AppError::Conflict(
+                "a rollback of this deployment is still running".to_string(),
+            ));
+        }
+
+        self.state
+            .deployment_jobs
+            .enqueue_rollback(&deployment, target, req.railway_api_key)
+            .await
+            .map_err(AppError::Internal)
+    }
+
+    pub async fn get_deployment_job(&self, id: Uuid) -> Result<DeploymentJob, AppError> {
+        self.state
+            .deployment_jobs
+            .get(id)
+            .await
+            .map_err(AppError::Internal)?
+            .ok_or_else(|| AppError::NotFound("deployment job not found".to_string()))
+    }
+
     pub async fn get_deployment_logs(
         &self,
         id: Uuid,
@@ -59,4 +134,62 @@ impl<'a> DeploymentService<'a> {
             .await
             .map_err(AppError::Internal)
     }
+
+    /// Provider log stream with `since` and `filter` also applied here, since not every
+    /// provider can filter server-side.
+    pub async fn stream_deployment_logs(
+        &self,
+        id: Uuid,
+        query: DeploymentLogStreamQuery,
+    ) -> Result<LogStream, AppError> {
+        let deployment = self.get_deployment(id).await?;
+        let provider = self
+            .state
+            .deployment_manager
+            .vps_adapters
+            .get_provider(deployment.provider.clone())
+            .ok_or_else(|| AppError::BadRequest("vps provider not configured".to_string()))?;
+        if query.follow && !provider.capabilities().log_streaming {
+            return Err(AppError::BadRequest(format!(
+                "{} does not support following logs",
+                provider.provider_name()
+            )));
+        }
+
+        let deployment_id = engine::adapters::trait_def::DeploymentId {
+            id: deployment.id,
+            provider_id: deployment
+                .provider_id
+                .unwrap_or_else(|| format!("{:?}-{}", deployment.provider, deployment.id)),
+        };
+        let options = LogStreamOptions {
+            follow: query.follow,
+            since: query.since,
+            tail: query.tail,
+        };
+        let entries = provider
+            .stream_logs(&deployment_id, options)
+            .await
+            .map_err(AppError::Internal)?;
+
+        let since = query.since;
+        let filter = query
+            .filter
+            .map(|filter| filter.to_lowercase())
+            .filter(|filter| !filter.is_empty());
+        Ok(entries
+            .filter(move |entry| {
+                let keep = match entry {
+                    Ok(entry) => {
+                        since.is_none_or(|since| entry.timestamp.is_none_or(|ts| ts >= since))
+                            && filter
+                                .as_ref()
+                                .is_none_or(|filter| entry.message.to_lowercase().contains(filter))
+                    }
+                    Err(_) => true,
+                };
+                futures::future::ready(keep)
+            })
+            .boxed())
+    }
 }
@@ -0,0 +1,50 @@
+use crate::api::errors::AppError;
+use crate::api::handlers::logs::LogSearchQuery;
+use crate::api::handlers::AppState;
+use engine::adapters::LogLevel;
+use engine::models::DeploymentLog;
+use engine::storage::repositories::{DeploymentLogRepository, LogSearch};
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+pub struct LogService<'a> {
+    state: &'a AppState,
+}
+
+impl<'a> LogService<'a> {
+    pub fn new(state: &'a AppState) -> Self {
+        Self { state }
+    }
+
+    /// Searches archived logs, newest first.
+    pub async fn search_logs(&self, query: LogSearchQuery) -> Result<Vec<DeploymentLog>, AppError> {
+        let min_level = query
+            .level
+            .as_deref()
+            .map(|level| {
+                LogLevel::parse(level)
+                    .ok_or_else(|| AppError::BadRequest(format!("invalid log level: {}", level)))
+            })
+            .transpose()?;
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(AppError::BadRequest(format!(
+                "limit must be between 1 and {}",
+                MAX_LIMIT
+            )));
+        }
+
+        let search = LogSearch {
+            agent_id: query.agent_id,
+            deployment_id: query.deployment_id,
+            min_level,
+            text: query.q.filter(|text| !text.is_empty()),
+            since: query.since,
+            until: query.until,
+            limit,
+        };
+        let repo = DeploymentLogRepository::new(self.state.db.db().clone());
+        repo.search(&search).await.map_err(AppError::Internal)
+    }
+}
@@ -1,4 +1,7 @@
 pub mod agents;
 pub mod deployments;
+pub mod logs;
+pub mod providers;
+pub mod slack;
 pub mod tasks;
 pub mod teams;
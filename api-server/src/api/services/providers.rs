@@ -0,0 +1,26 @@
+use crate::api::handlers::providers::ProviderResponse;
+use crate::api::handlers::AppState;
+
+pub struct ProviderService<'a> {
+    state: &'a AppState,
+}
+
+impl<'a> ProviderService<'a> {
+    pub fn new(state: &'a AppState) -> Self {
+        Self { state }
+    }
+
+    /// Providers that can currently be deployed to, with what each of them supports.
+    pub fn list_providers(&self) -> Vec<ProviderResponse> {
+        self.state
+            .deployment_manager
+            .vps_adapters
+            .available()
+            .into_iter()
+            .map(|(provider, capabilities)| ProviderResponse {
+                provider,
+                capabilities,
+            })
+            .collect()
+    }
+}
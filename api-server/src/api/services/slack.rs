@@ -0,0 +1,33 @@
+use crate::api::errors::AppError;
+use crate::api::handlers::AppState;
+
+pub struct SlackService<'a> {
+    state: &'a AppState,
+}
+
+impl<'a> SlackService<'a> {
+    pub fn new(state: &'a AppState) -> Self {
+        Self { state }
+    }
+
+    /// Verifies the request's signature and hands it to the Slack client. Returns the
+    /// challenge for URL verification requests.
+    pub fn handle_event(
+        &self,
+        timestamp: &str,
+        signature: &str,
+        body: &[u8],
+    ) -> Result<Option<String>, AppError> {
+        let slack = self
+            .state
+            .coordinator
+            .slack()
+            .ok_or_else(|| AppError::NotFound("Slack is not configured".to_string()))?;
+        if !slack.verify_signature(timestamp, body, signature) {
+            return Err(AppError::Unauthorized);
+        }
+        slack
+            .handle_callback(body)
+            .map_err(|e| AppError::BadRequest(e.to_string()))
+    }
+}
@@ -1,7 +1,12 @@
 use crate::api::errors::AppError;
-use crate::api::handlers::tasks::{TaskAggregateResponse, UpdateTaskRequest};
+use crate::api::handlers::tasks::{
+    SendTaskRequest, TaskAggregateResponse, TeamCommandRequest, TeamCommandResponse,
+    UpdateTaskRequest,
+};
 use crate::api::handlers::AppState;
-use engine::models::{AgentRole, Task, TaskStatus};
+use engine::coordinator::commands::TaskCommand;
+use engine::coordinator::master::summarize_results;
+use engine::models::{Task, TaskAttempt, TaskStatus};
 use engine::storage::repositories::{AgentRepository, TaskRepository, TeamRepository};
 use uuid::Uuid;
 
@@ -14,7 +19,20 @@ impl<'a> TaskService<'a> {
         Self { state }
     }
 
-    pub async fn send_task(&self, agent_id: Uuid, description: String) -> Result<Task, AppError> {
+    /// A task with unfinished dependencies is stored as `blocked` and dispatched by the
+    /// coordinator once they all complete; otherwise it is dispatched right away.
+    pub async fn send_task(&self, agent_id: Uuid, req: SendTaskRequest) -> Result<Task, AppError> {
+        if req.timeout_secs == Some(0) {
+            return Err(AppError::BadRequest(
+                "timeout_secs must be at least 1".to_string(),
+            ));
+        }
+        if req.max_attempts == Some(0) {
+            return Err(AppError::BadRequest(
+                "max_attempts must be at least 1".to_string(),
+            ));
+        }
+
         let agent_repo = AgentRepository::new(self.state.db.db().clone());
         let agent = agent_repo
             .get_by_id(agent_id)
@@ -25,45 +43,91 @@ impl<'a> TaskService<'a> {
         let team_id = agent
             .team_id
             .ok_or_else(|| AppError::BadRequest("agent not in a team".to_string()))?;
+        let team_repo = TeamRepository::new(self.state.db.db().clone());
+        let team = team_repo
+            .get_by_id(team_id)
+            .await
+            .map_err(AppError::Internal)?
+            .ok_or_else(|| AppError::NotFound("team not found".to_string()))?;
+
+        let task_repo = TaskRepository::new(self.state.db.db().clone());
+        let mut depends_on = req.depends_on;
+        depends_on.sort();
+        depends_on.dedup();
+        let dependencies = task_repo
+            .get_by_ids(&depends_on)
+            .await
+            .map_err(AppError::Internal)?;
+        if let Some(missing) = depends_on
+            .iter()
+            .find(|id| !dependencies.iter().any(|dep| dep.id == **id))
+        {
+            return Err(AppError::BadRequest(format!(
+                "dependency {} not found",
+                missing
+            )));
+        }
+        for dependency in &dependencies {
+            if dependency.team_id != team_id {
+                return Err(AppError::BadRequest(format!(
+                    "dependency {} belongs to another team",
+                    dependency.id
+                )));
+            }
+            if dependency.status.is_final() && dependency.status != TaskStatus::Completed {
+                return Err(AppError::BadRequest(format!(
+                    "dependency {} will never complete (status {:?})",
+                    dependency.id, dependency.status
+                )));
+            }
+        }
+        let blocked = dependencies
+            .iter()
+            .any(|dependency| dependency.status != TaskStatus::Completed);
 
-        let mut task = Task {
+        let task = Task {
             id: Uuid::new_v4(),
             team_id,
             parent_task_id: None,
             assigned_to: Some(agent_id),
-            status: TaskStatus::Pending,
-            description,
+            status: if blocked {
+                TaskStatus::Blocked
+            } else {
+                TaskStatus::Pending
+            },
+            description: req.description,
             result: None,
+            routing_reason: None,
+            required_skills: Vec::new(),
+            depends_on,
+            timeout_secs: req
+                .timeout_secs
+                .or(team.settings.task_timeout_secs)
+                .map(|secs| secs.min(i32::MAX as u32) as i32),
+            max_attempts: req
+                .max_attempts
+                .or(team.settings.max_task_attempts)
+                .unwrap_or(1)
+                .min(i32::MAX as u32) as i32,
+            attempts: 0,
+            deadline_at: None,
+            progress: None,
+            progress_note: None,
+            thread_id: None,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
         };
 
-        let task_repo = TaskRepository::new(self.state.db.db().clone());
         task_repo.create(&task).await.map_err(AppError::Internal)?;
 
-        if matches!(agent.role, AgentRole::Master) {
-            let team_repo = TeamRepository::new(self.state.db.db().clone());
-            let team = team_repo
-                .get_by_id(team_id)
-                .await
-                .map_err(AppError::Internal)?
-                .ok_or_else(|| AppError::NotFound("team not found".to_string()))?;
-
-            self.state
-                .coordinator
-                .master()
-                .delegate_task(&team, &task)
-                .await
-                .map_err(AppError::Internal)?;
-
-            task_repo
-                .update_fields(task.id, Some(TaskStatus::InProgress), None)
-                .await
-                .map_err(AppError::Internal)?;
-            task.status = TaskStatus::InProgress;
+        if blocked {
+            return Ok(task);
         }
-
-        Ok(task)
+        self.state
+            .coordinator
+            .dispatch_task(&task)
+            .await
+            .map_err(AppError::Internal)
     }
 
     pub async fn get_agent_tasks(&self, agent_id: Uuid) -> Result<Vec<Task>, AppError> {
@@ -75,6 +139,8 @@ impl<'a> TaskService<'a> {
         Ok(tasks)
     }
 
+    /// Finishing a task releases or cancels its dependents and frees a slot on its agent, so
+    /// the coordinator follows up right away instead of waiting for the next dispatcher pass.
     pub async fn update_task(
         &self,
         task_id: Uuid,
@@ -86,9 +152,65 @@ impl<'a> TaskService<'a> {
             .await
             .map_err(AppError::Internal)?
             .ok_or_else(|| AppError::NotFound("task not found".to_string()))?;
+
+        // Best-effort; the dispatcher picks up anything left ready or queued
+        if let Err(e) = self.state.coordinator.task_finished(&updated).await {
+            tracing::warn!(task_id = %updated.id, error = %e, "failed to follow up finished task");
+        }
         Ok(updated)
     }
 
+    /// Runs the command on one of the team's tasks. Its log entry goes out over the team's
+    /// transport, as if the command had been received there.
+    pub async fn run_team_command(
+        &self,
+        team_id: Uuid,
+        req: TeamCommandRequest,
+    ) -> Result<TeamCommandResponse, AppError> {
+        let team_repo = TeamRepository::new(self.state.db.db().clone());
+        let team = team_repo
+            .get_by_id(team_id)
+            .await
+            .map_err(AppError::Internal)?
+            .ok_or_else(|| AppError::NotFound("team not found".to_string()))?;
+
+        let command = TaskCommand::parse(&req.command)
+            .ok_or_else(|| AppError::BadRequest("not a task command".to_string()))?
+            .map_err(AppError::BadRequest)?;
+        let task_repo = TaskRepository::new(self.state.db.db().clone());
+        task_repo
+            .get_by_id(command.task_id())
+            .await
+            .map_err(AppError::Internal)?
+            .filter(|task| task.team_id == team.id)
+            .ok_or_else(|| AppError::NotFound("task not found".to_string()))?;
+
+        let outcome = self
+            .state
+            .coordinator
+            .run_command(&team, &command)
+            .await
+            .map_err(AppError::Internal)?;
+        Ok(TeamCommandResponse {
+            reply: outcome.reply,
+            task: outcome.task,
+        })
+    }
+
+    pub async fn get_task_attempts(&self, task_id: Uuid) -> Result<Vec<TaskAttempt>, AppError> {
+        let task_repo = TaskRepository::new(self.state.db.db().clone());
+        task_repo
+            .get_by_id(task_id)
+            .await
+            .map_err(AppError::Internal)?
+            .ok_or_else(|| AppError::NotFound("task not found".to_string()))?;
+
+        task_repo
+            .list_attempts(task_id)
+            .await
+            .map_err(AppError::Internal)
+    }
+
     pub async fn aggregate_task(&self, task_id: Uuid) -> Result<TaskAggregateResponse, AppError> {
         let task_repo = TaskRepository::new(self.state.db.db().clone());
         let task = task_repo
@@ -102,9 +224,24 @@ impl<'a> TaskService<'a> {
             .await
             .map_err(AppError::Internal)?;
 
+        let dependencies = task_repo
+            .get_by_ids(&task.depends_on)
+            .await
+            .map_err(AppError::Internal)?;
+        let dependents = task_repo
+            .get_dependents(task_id)
+            .await
+            .map_err(AppError::Internal)?;
+
+        let summary = summarize_results(&task, &subtasks);
         let mut results = vec![task];
         results.extend(subtasks);
 
-        Ok(TaskAggregateResponse { tasks: results })
+        Ok(TaskAggregateResponse {
+            tasks: results,
+            summary,
+            dependencies,
+            dependents,
+        })
     }
 }
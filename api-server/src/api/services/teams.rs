@@ -1,10 +1,14 @@
 use crate::api::errors::AppError;
-use crate::api::handlers::channels::apply_telegram_settings_to_agents_tx;
+use crate::api::handlers::channels::{apply_telegram_settings_to_agents_tx, TelegramSettings};
 use crate::api::handlers::teams::{
-    CreateTeamRequest, TeamResponse, TeamRosterMember, TeamRosterResponse,
+    CreateTeamRequest, TeamResponse, TeamRosterMember, TeamRosterResponse, UpdateTeamRequest,
 };
 use crate::api::handlers::AppState;
-use engine::models::{AgentRole, Team};
+use engine::coordinator::discord::DiscordClient;
+use engine::models::{
+    AgentRole, DiscordChannels, PlannerConfig, SlackChannels, Team, TeamSettings, TransportConfig,
+    REDACTED,
+};
 use engine::storage::repositories::{AgentRepository, TeamRepository};
 use uuid::Uuid;
 
@@ -18,13 +22,9 @@ impl<'a> TeamService<'a> {
     }
 
     pub async fn create_team(&self, req: CreateTeamRequest) -> Result<TeamResponse, AppError> {
-        let discord_channels =
-            req.discord_channels
-                .unwrap_or_else(|| engine::models::DiscordChannels {
-                    coordination_logs: req.discord_channel_id.clone(),
-                    slave_communication: req.discord_channel_id.clone(),
-                    master_orders: req.discord_channel_id.clone(),
-                });
+        let settings = req.settings.unwrap_or_default();
+        validate_settings(&settings)?;
+        validate_transport(&settings, req.slack_channels.as_ref())?;
 
         let slave_ids: Vec<Uuid> = req
             .slave_ids
@@ -32,17 +32,63 @@ impl<'a> TeamService<'a> {
             .filter(|id| id != &req.master_id)
             .collect();
 
-        let team = Team {
+        let mut team = Team {
             id: Uuid::new_v4(),
             name: req.name,
             master_id: req.master_id,
             slave_ids,
-            discord_channel_id: req.discord_channel_id,
-            discord_channels,
+            discord_channel_id: req.discord_channel_id.clone(),
+            discord_channels: req.discord_channels.unwrap_or_else(|| DiscordChannels {
+                coordination_logs: req.discord_channel_id.clone(),
+                slave_communication: req.discord_channel_id.clone(),
+                master_orders: req.discord_channel_id,
+            }),
+            discord_category_id: None,
+            slack_channels: req.slack_channels,
+            settings,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
         };
 
+        let Some(guild_id) = req.discord_guild_id else {
+            self.insert_team(&team, req.telegram_settings).await?;
+            return Ok(team_response(team));
+        };
+        let discord = self.provisioning_client(&guild_id)?;
+        let agent_repo = AgentRepository::new(self.state.db.db().clone());
+        let mut agent_ids = vec![team.master_id];
+        agent_ids.extend(team.slave_ids.iter().copied());
+        let agents = fetch_agents(&agent_repo, &agent_ids).await?;
+        let provisioned = discord
+            .provision_team_channels(
+                &guild_id,
+                &team.name,
+                &agents,
+                &team.settings.operator_role_ids,
+            )
+            .await
+            .map_err(|err| {
+                AppError::Internal(err.context("failed to provision the team's Discord channels"))
+            })?;
+        team.discord_channel_id = provisioned.channels.coordination_logs.clone();
+        team.discord_channels = provisioned.channels;
+        team.discord_category_id = Some(provisioned.category_id);
+
+        if let Err(err) = self.insert_team(&team, req.telegram_settings).await {
+            if let Err(cleanup) = discord.delete_team_channels(&team).await {
+                tracing::warn!(team_id = %team.id, error = %cleanup, "failed to remove channels of a team that wasn't created");
+            }
+            return Err(err);
+        }
+        Ok(team_response(team))
+    }
+
+    /// Stores a new team and makes its agents members.
+    async fn insert_team(
+        &self,
+        team: &Team,
+        telegram_settings: Option<TelegramSettings>,
+    ) -> Result<(), AppError> {
         let team_repo = TeamRepository::new(self.state.db.db().clone());
         let agent_repo = AgentRepository::new(self.state.db.db().clone());
         let mut tx = self
@@ -54,7 +100,7 @@ impl<'a> TeamService<'a> {
             .map_err(|err| AppError::Internal(err.into()))?;
 
         team_repo
-            .create_tx(&mut tx, &team)
+            .create_tx(&mut tx, team)
             .await
             .map_err(AppError::Internal)?;
 
@@ -90,7 +136,7 @@ impl<'a> TeamService<'a> {
                 .map_err(AppError::Internal)?;
         }
 
-        if let Some(settings) = req.telegram_settings {
+        if let Some(settings) = telegram_settings {
             let mut agent_ids = Vec::with_capacity(team.slave_ids.len() + 1);
             agent_ids.push(team.master_id);
             agent_ids.extend(team.slave_ids.iter().copied());
@@ -100,31 +146,117 @@ impl<'a> TeamService<'a> {
 
         tx.commit()
             .await
-            .map_err(|err| AppError::Internal(err.into()))?;
+            .map_err(|err| AppError::Internal(err.into()))
+    }
 
-        Ok(TeamResponse {
-            id: team.id,
-            name: team.name,
-            master_id: team.master_id,
-            slave_ids: team.slave_ids,
-            discord_channel_id: team.discord_channel_id,
+    /// The Discord client, when the server can provision channels in the guild.
+    fn provisioning_client(&self, guild_id: &str) -> Result<&DiscordClient, AppError> {
+        if guild_id.is_empty() || !guild_id.chars().all(|c| c.is_ascii_digit()) {
+            return Err(AppError::BadRequest(
+                "discord_guild_id must be a numeric Discord id".to_string(),
+            ));
+        }
+        self.state.coordinator.discord().ok_or_else(|| {
+            AppError::BadRequest(
+                "Discord is not configured on this server; pass the team's channels instead"
+                    .to_string(),
+            )
         })
     }
 
+    /// Removes the team, its tasks and its agents' membership, then deletes its Discord
+    /// channels if they were provisioned for it.
+    pub async fn delete_team(&self, team_id: Uuid) -> Result<(), AppError> {
+        let team_repo = TeamRepository::new(self.state.db.db().clone());
+        let agent_repo = AgentRepository::new(self.state.db.db().clone());
+        let team = team_repo
+            .get_by_id(team_id)
+            .await
+            .map_err(AppError::Internal)?
+            .ok_or_else(|| AppError::NotFound("team not found".to_string()))?;
+
+        let mut tx = self
+            .state
+            .db
+            .db()
+            .begin()
+            .await
+            .map_err(|err| AppError::Internal(err.into()))?;
+        for agent_id in std::iter::once(&team.master_id).chain(&team.slave_ids) {
+            agent_repo
+                .update_team_membership_tx(&mut tx, *agent_id, None, None, None)
+                .await
+                .map_err(AppError::Internal)?;
+        }
+        team_repo
+            .delete_tx(&mut tx, team.id)
+            .await
+            .map_err(AppError::Internal)?;
+        tx.commit()
+            .await
+            .map_err(|err| AppError::Internal(err.into()))?;
+
+        if team.discord_category_id.is_some() {
+            match self.state.coordinator.discord() {
+                Some(discord) => {
+                    if let Err(e) = discord.delete_team_channels(&team).await {
+                        tracing::warn!(team_id = %team.id, error = %e, "failed to delete the team's Discord channels");
+                    }
+                }
+                None => tracing::warn!(
+                    team_id = %team.id,
+                    "Discord is not configured; the team's channels were left in place"
+                ),
+            }
+        }
+        Ok(())
+    }
+
     pub async fn list_teams(&self) -> Result<Vec<TeamResponse>, AppError> {
         let repo = TeamRepository::new(self.state.db.db().clone());
         let teams = repo.list_all().await.map_err(AppError::Internal)?;
 
-        Ok(teams
-            .into_iter()
-            .map(|team| TeamResponse {
-                id: team.id,
-                name: team.name,
-                master_id: team.master_id,
-                slave_ids: team.slave_ids,
-                discord_channel_id: team.discord_channel_id,
-            })
-            .collect())
+        Ok(teams.into_iter().map(team_response).collect())
+    }
+
+    /// Renames the team and/or replaces its settings.
+    pub async fn update_team(
+        &self,
+        team_id: Uuid,
+        req: UpdateTeamRequest,
+    ) -> Result<TeamResponse, AppError> {
+        let repo = TeamRepository::new(self.state.db.db().clone());
+        let mut team = repo
+            .get_by_id(team_id)
+            .await
+            .map_err(AppError::Internal)?
+            .ok_or_else(|| AppError::NotFound("team not found".to_string()))?;
+
+        if let Some(name) = req.name {
+            if name.trim().is_empty() {
+                return Err(AppError::BadRequest("name must not be empty".to_string()));
+            }
+            team.name = name;
+        }
+        if let Some(mut settings) = req.settings {
+            settings.keep_credentials(&team.settings);
+            validate_settings(&settings)?;
+            team.settings = settings;
+        }
+        if let Some(slack_channels) = req.slack_channels {
+            team.slack_channels = Some(slack_channels);
+        }
+        validate_transport(&team.settings, team.slack_channels.as_ref())?;
+
+        repo.update_details(
+            team.id,
+            &team.name,
+            team.slack_channels.as_ref(),
+            &team.settings,
+        )
+        .await
+        .map_err(AppError::Internal)?;
+        Ok(team_response(team))
     }
 
     pub async fn assign_agent_to_team(
@@ -190,13 +322,17 @@ impl<'a> TeamService<'a> {
             .await
             .map_err(|err| AppError::Internal(err.into()))?;
 
-        Ok(TeamResponse {
-            id: team.id,
-            name: team.name,
+        if let Some(discord) = self.state.coordinator.discord() {
+            if let Err(e) = discord.grant_team_access(&team, &agent).await {
+                tracing::warn!(team_id = %team.id, agent_id = %agent.id, error = %e, "failed to give the agent access to the team's Discord channels");
+            }
+        }
+
+        Ok(team_response(Team {
             master_id,
             slave_ids,
-            discord_channel_id: team.discord_channel_id,
-        })
+            ..team
+        }))
     }
 
     pub async fn get_team_roster(&self, team_id: Uuid) -> Result<TeamRosterResponse, AppError> {
@@ -255,3 +391,90 @@ async fn fetch_agents(
     }
     Ok(agents)
 }
+
+fn team_response(team: Team) -> TeamResponse {
+    TeamResponse {
+        id: team.id,
+        name: team.name,
+        master_id: team.master_id,
+        slave_ids: team.slave_ids,
+        discord_channel_id: team.discord_channel_id,
+        discord_channels: team.discord_channels,
+        discord_category_id: team.discord_category_id,
+        slack_channels: team.slack_channels,
+        settings: team.settings.redacted(),
+    }
+}
+
+/// Slack teams need their channels; channel and user ids are checked by Slack itself.
+fn validate_transport(
+    settings: &TeamSettings,
+    slack_channels: Option<&SlackChannels>,
+) -> Result<(), AppError> {
+    if matches!(settings.transport, TransportConfig::Slack) && slack_channels.is_none() {
+        return Err(AppError::BadRequest(
+            "slack_channels are required for the slack transport".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_settings(settings: &TeamSettings) -> Result<(), AppError> {
+    if let PlannerConfig::Model(model) = &settings.planner {
+        let endpoint = model.endpoint.trim();
+        if !(endpoint.starts_with("http://") || endpoint.starts_with("https://")) {
+            return Err(AppError::BadRequest(
+                "planner endpoint must be an http(s) URL".to_string(),
+            ));
+        }
+        if model.model.trim().is_empty() {
+            return Err(AppError::BadRequest(
+                "planner model must not be empty".to_string(),
+            ));
+        }
+        if model.max_subtasks == Some(0) {
+            return Err(AppError::BadRequest(
+                "planner max_subtasks must be at least 1".to_string(),
+            ));
+        }
+        if model.api_key.as_deref() == Some(REDACTED) {
+            return Err(AppError::BadRequest(
+                "planner api_key must be sent again when the endpoint changes".to_string(),
+            ));
+        }
+    }
+    if let TransportConfig::Webhook(webhook) = &settings.transport {
+        let url = webhook.url.trim();
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(AppError::BadRequest(
+                "transport webhook url must be an http(s) URL".to_string(),
+            ));
+        }
+        if webhook.secret.as_deref() == Some(REDACTED) {
+            return Err(AppError::BadRequest(
+                "transport webhook secret must be sent again when the url changes".to_string(),
+            ));
+        }
+    }
+    if settings.task_timeout_secs == Some(0) {
+        return Err(AppError::BadRequest(
+            "task_timeout_secs must be at least 1".to_string(),
+        ));
+    }
+    if settings.max_task_attempts == Some(0) {
+        return Err(AppError::BadRequest(
+            "max_task_attempts must be at least 1".to_string(),
+        ));
+    }
+    if let Some(role_id) = settings
+        .operator_role_ids
+        .iter()
+        .find(|role_id| role_id.parse::<u64>().is_err())
+    {
+        return Err(AppError::BadRequest(format!(
+            "operator role id {} is not a Discord id",
+            role_id
+        )));
+    }
+    Ok(())
+}
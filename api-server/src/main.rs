@@ -40,18 +40,83 @@ async fn main() -> Result<()> {
         deployment::manager::DeploymentManager::new(db.clone(), vps_adapters).await?;
     tracing::info!("deployment manager initialized");
 
+    // Start deployment job workers (resumes jobs interrupted by a restart)
+    tracing::info!("starting deployment job workers");
+    let deployment_jobs =
+        deployment::jobs::DeploymentJobQueue::new(db.clone(), deployment_manager.clone());
+    deployment_jobs.start().await?;
+    tracing::info!("deployment job workers started");
+
+    // Keep deployment status in sync with what providers report
+    tracing::info!("starting deployment reconciler");
+    deployment::reconciler::DeploymentReconciler::new(
+        db.clone(),
+        deployment_manager.vps_adapters.clone(),
+    )
+    .with_interval(std::time::Duration::from_secs(
+        config.reconcile_interval_secs.max(1),
+    ))
+    .start();
+
+    // Copy provider logs into the archive so they outlive destroyed deployments
+    tracing::info!("starting deployment log archiver");
+    deployment::log_archive::LogArchiver::new(db.clone(), deployment_manager.vps_adapters.clone())
+        .with_interval(std::time::Duration::from_secs(
+            config.log_archive_interval_secs.max(1),
+        ))
+        .with_retention(deployment::log_archive::LogRetention {
+            days: config.log_retention_days,
+            error_days: config.log_error_retention_days,
+        })
+        .start();
+
     // Initialize coordinator
     tracing::info!("initializing coordinator");
+    let slack_config =
+        config
+            .slack_bot_token
+            .clone()
+            .map(|bot_token| coordinator::slack::SlackConfig {
+                bot_token,
+                app_token: config.slack_app_token.clone(),
+                signing_secret: config.slack_signing_secret.clone(),
+                api_url: config.slack_api_url.clone(),
+            });
+    let discord_config =
+        config
+            .discord_bot_token
+            .clone()
+            .map(|bot_token| coordinator::discord::DiscordConfig {
+                bot_token,
+                api_url: config.discord_api_url.clone(),
+            });
     let coordinator =
-        coordinator::Coordinator::new(db.clone(), config.discord_bot_token.clone()).await?;
+        coordinator::Coordinator::new(db.clone(), discord_config, slack_config).await?;
     tracing::info!("coordinator initialized");
 
+    // Dispatch tasks as their dependencies complete and slaves free up
+    tracing::info!("starting task dispatcher");
+    coordinator::dispatcher::TaskDispatcher::new(coordinator.clone())
+        .with_interval(std::time::Duration::from_secs(
+            config.task_dispatch_interval_secs.max(1),
+        ))
+        .start();
+
+    // Retry or give up on tasks whose agent never answered
+    tracing::info!("starting task timeout sweeper");
+    coordinator::sweeper::TaskSweeper::new(db.clone(), coordinator.clone())
+        .with_interval(std::time::Duration::from_secs(
+            config.task_sweep_interval_secs.max(1),
+        ))
+        .start();
+
     // Initialize API server
     tracing::info!("initializing API server");
     let start_time = std::time::Instant::now();
     let api_server = api::ApiServer::new(
         db.clone(),
         deployment_manager,
+        deployment_jobs,
         coordinator,
         config.api_key.clone(),
         start_time,
@@ -50,7 +50,7 @@ pub struct RuntimeContext {
     pub agents: Vec<RuntimeAgent>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RuntimeServicePort {
     pub port: u16,
     pub handlers: Vec<String>,
@@ -1,18 +1,66 @@
-use crate::adapters::trait_def::{AgentConfig, DeploymentId, VpsAgentStatus, VpsProvider};
+use crate::adapters::logs::{poll_log_stream, LogEntry, LogStream, LogStreamOptions};
+use crate::adapters::trait_def::{
+    all_runtimes, AgentConfig, DeploymentId, ProviderCapabilities, ResourceNotFound,
+    VpsAgentStatus, VpsProvider,
+};
 use crate::config::Config;
 use crate::models::DeploymentStatus;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use reqwest::Client;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Url};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::time::Duration;
 use uuid::Uuid;
 
+const EC2_API_VERSION: &str = "2016-11-15";
+const LOGS_GET_EVENTS_TARGET: &str = "Logs_20140328.GetLogEvents";
+const STOP_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const STOP_POLL_ATTEMPTS: u32 = 36;
+const LOG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const LOG_EVENTS_PAGE_SIZE: usize = 1000;
+/// Where the user data script copies its output for the CloudWatch agent to ship.
+const AGENT_LOG_FILE: &str = "/var/log/clawguild-agent.log";
+const REGIONS: &[&str] = &[
+    "us-east-1",
+    "us-east-2",
+    "us-west-1",
+    "us-west-2",
+    "ca-central-1",
+    "sa-east-1",
+    "eu-west-1",
+    "eu-west-2",
+    "eu-west-3",
+    "eu-central-1",
+    "eu-north-1",
+    "ap-south-1",
+    "ap-northeast-1",
+    "ap-northeast-2",
+    "ap-southeast-1",
+    "ap-southeast-2",
+];
+
+/// Runs each agent on its own EC2 instance, with the runtime init script as user data.
+///
+/// Requests are signed with SigV4 against the EC2 Query API and the CloudWatch Logs
+/// JSON API, so no AWS SDK is needed and `aws_endpoint_url` can point both at a local
+/// AWS-compatible stand-in. Logs come from CloudWatch when instances are launched with an
+/// instance profile to ship them, and from the console output otherwise.
+#[derive(Clone)]
 pub struct AwsAdapter {
-    #[allow(dead_code)]
     client: Client,
-    #[allow(dead_code)]
     access_key_id: String,
-    #[allow(dead_code)]
     secret_access_key: String,
+    session_token: Option<String>,
+    region: String,
+    endpoint_url: Option<String>,
+    ami_id: Option<String>,
+    instance_type: String,
+    log_group: String,
+    instance_profile: Option<String>,
 }
 
 impl AwsAdapter {
@@ -32,73 +80,935 @@ impl AwsAdapter {
             client: Client::new(),
             access_key_id,
             secret_access_key,
+            session_token: config.aws_session_token.clone(),
+            region: config.aws_region.clone(),
+            endpoint_url: config
+                .aws_endpoint_url
+                .as_ref()
+                .map(|url| url.trim_end_matches('/').to_string()),
+            ami_id: config.aws_ami_id.clone(),
+            instance_type: config.aws_instance_type.clone(),
+            log_group: config.aws_log_group.clone(),
+            instance_profile: config.aws_instance_profile.clone(),
+        })
+    }
+
+    fn endpoint(&self, service: &str, region: &str) -> String {
+        self.endpoint_url
+            .clone()
+            .unwrap_or_else(|| format!("https://{}.{}.amazonaws.com", service, region))
+    }
+
+    async fn signed_post(
+        &self,
+        service: &str,
+        region: &str,
+        content_type: &str,
+        target: Option<&str>,
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response> {
+        let url = Url::parse(&format!("{}/", self.endpoint(service, region)))?;
+        let host = url.host_str().context("AWS endpoint has no host")?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        };
+        let now = Utc::now();
+
+        let mut headers = BTreeMap::new();
+        headers.insert("content-type".to_string(), content_type.to_string());
+        headers.insert("host".to_string(), host);
+        headers.insert(
+            "x-amz-date".to_string(),
+            now.format("%Y%m%dT%H%M%SZ").to_string(),
+        );
+        if let Some(token) = &self.session_token {
+            headers.insert("x-amz-security-token".to_string(), token.clone());
+        }
+        if let Some(target) = target {
+            headers.insert("x-amz-target".to_string(), target.to_string());
+        }
+
+        let authorization = sign_v4(
+            &self.access_key_id,
+            &self.secret_access_key,
+            region,
+            service,
+            "POST",
+            &url,
+            &headers,
+            &body,
+            now,
+        );
+
+        // reqwest derives Host from the URL, which is what was signed
+        let mut request = self.client.post(url).header("Authorization", authorization);
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name.as_str(), value.as_str());
+        }
+
+        request
+            .body(body)
+            .send()
+            .await
+            .with_context(|| format!("AWS {} request failed", service))
+    }
+
+    /// The log group instances ship their output to, when they can: shipping needs an
+    /// instance profile allowed to write to CloudWatch.
+    fn cloudwatch_log_group(&self) -> Option<&str> {
+        self.instance_profile
+            .as_ref()
+            .map(|_| self.log_group.as_str())
+    }
+
+    /// Calls an EC2 Query API action and returns the raw XML response.
+    async fn ec2(&self, region: &str, action: &str, params: &[(String, String)]) -> Result<String> {
+        let body = [
+            ("Action".to_string(), action.to_string()),
+            ("Version".to_string(), EC2_API_VERSION.to_string()),
+        ]
+        .iter()
+        .chain(params)
+        .map(|(key, value)| format!("{}={}", uri_encode(key), uri_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+
+        let response = self
+            .signed_post(
+                "ec2",
+                region,
+                "application/x-www-form-urlencoded; charset=utf-8",
+                None,
+                body.into_bytes(),
+            )
+            .await?;
+        let status = response.status();
+        let text = response.text().await?;
+
+        if !status.is_success() {
+            anyhow::bail!(
+                "AWS EC2 {} failed ({}): {} {}",
+                action,
+                status,
+                xml_tag(&text, "Code").unwrap_or_default(),
+                xml_tag(&text, "Message").unwrap_or_else(|| text.clone())
+            );
+        }
+        Ok(text)
+    }
+
+    /// The instance's XML, or `None` once EC2 no longer knows about it.
+    async fn describe_instance(&self, region: &str, instance_id: &str) -> Result<Option<String>> {
+        let xml = match self
+            .ec2(
+                region,
+                "DescribeInstances",
+                &[("InstanceId.1".to_string(), instance_id.to_string())],
+            )
+            .await
+        {
+            Ok(xml) => xml,
+            Err(e) if e.to_string().contains("InvalidInstanceID.NotFound") => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        Ok(xml_tag(&xml, "instancesSet").filter(|set| set.contains("<instanceId>")))
+    }
+
+    /// Provider IDs are `aws-{region}/{instance_id}`; bare `aws-{instance_id}` uses the default region.
+    fn parse_provider_id<'a>(
+        &'a self,
+        deployment_id: &'a DeploymentId,
+    ) -> Result<(&'a str, &'a str)> {
+        let id = deployment_id
+            .provider_id
+            .strip_prefix("aws-")
+            .ok_or_else(|| anyhow::anyhow!("Invalid provider ID"))?;
+
+        Ok(match id.split_once('/') {
+            Some((region, instance_id)) => (region, instance_id),
+            None => (self.region.as_str(), id),
         })
     }
+
+    /// One page of the instance's CloudWatch log stream and the forward token for the next,
+    /// or `None` if the stream doesn't exist yet.
+    async fn fetch_log_events(
+        &self,
+        region: &str,
+        instance_id: &str,
+        query: LogEventsQuery,
+    ) -> Result<Option<(Vec<LogEntry>, Option<String>)>> {
+        let mut body = serde_json::json!({
+            "logGroupName": self.log_group,
+            "logStreamName": instance_id,
+            "limit": query.limit,
+            "startFromHead": query.start_from_head
+        });
+        if let Some(start_time) = query.start_time {
+            body["startTime"] = start_time.timestamp_millis().into();
+        }
+        if let Some(next_token) = query.next_token {
+            body["nextToken"] = next_token.into();
+        }
+
+        let response = self
+            .signed_post(
+                "logs",
+                region,
+                "application/x-amz-json-1.1",
+                Some(LOGS_GET_EVENTS_TARGET),
+                serde_json::to_vec(&body)?,
+            )
+            .await?;
+        let status = response.status();
+        let payload: serde_json::Value = response.json().await.unwrap_or_default();
+
+        if !status.is_success() {
+            let error_type = payload["__type"].as_str().unwrap_or_default();
+            if error_type.ends_with("ResourceNotFoundException") {
+                return Ok(None);
+            }
+            anyhow::bail!(
+                "Failed to fetch CloudWatch logs ({}): {}",
+                status,
+                payload["message"]
+                    .as_str()
+                    .or_else(|| payload["Message"].as_str())
+                    .unwrap_or(error_type)
+            );
+        }
+
+        let entries = payload["events"]
+            .as_array()
+            .map(|events| {
+                events
+                    .iter()
+                    .filter_map(|event| {
+                        let message = event["message"].as_str()?.trim_end();
+                        let timestamp = event["timestamp"]
+                            .as_i64()
+                            .and_then(DateTime::<Utc>::from_timestamp_millis);
+                        Some(LogEntry::from_line(timestamp, instance_id, message))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let next_token = payload["nextForwardToken"]
+            .as_str()
+            .map(|token| token.to_string());
+
+        Ok(Some((entries, next_token)))
+    }
+
+    /// Lines of the instance's console output, oldest first. EC2 keeps the last 64 KB.
+    async fn console_output(&self, region: &str, instance_id: &str) -> Result<Vec<String>> {
+        let xml = match self
+            .ec2(
+                region,
+                "GetConsoleOutput",
+                &[
+                    ("InstanceId".to_string(), instance_id.to_string()),
+                    ("Latest".to_string(), "true".to_string()),
+                ],
+            )
+            .await
+        {
+            Ok(xml) => xml,
+            Err(e) if e.to_string().contains("InvalidInstanceID.NotFound") => {
+                return Err(ResourceNotFound {
+                    provider_id: format!("aws-{}/{}", region, instance_id),
+                }
+                .into());
+            }
+            Err(e) => return Err(e),
+        };
+
+        // Empty until the instance has booted far enough to write to the console
+        let Some(output) = xml_tag(&xml, "output").filter(|output| !output.trim().is_empty())
+        else {
+            return Ok(Vec::new());
+        };
+        let output = base64::engine::general_purpose::STANDARD
+            .decode(output.trim())
+            .context("EC2 console output is not valid base64")?;
+        Ok(String::from_utf8_lossy(&output)
+            .lines()
+            .map(|line| line.trim_end().to_string())
+            .filter(|line| !line.is_empty())
+            .collect())
+    }
+
+    /// Runs a single-instance EC2 action such as `StopInstances` or `RebootInstances`.
+    async fn instance_action(&self, deployment_id: &DeploymentId, action: &str) -> Result<()> {
+        let (region, instance_id) = self.parse_provider_id(deployment_id)?;
+        match self
+            .ec2(
+                region,
+                action,
+                &[("InstanceId.1".to_string(), instance_id.to_string())],
+            )
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) if e.to_string().contains("InvalidInstanceID.NotFound") => {
+                Err(ResourceNotFound {
+                    provider_id: deployment_id.provider_id.clone(),
+                }
+                .into())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn wait_until_stopped(&self, region: &str, instance_id: &str) -> Result<()> {
+        for _ in 0..STOP_POLL_ATTEMPTS {
+            let instance = self
+                .describe_instance(region, instance_id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("EC2 instance {} not found", instance_id))?;
+            if instance_state(&instance).as_deref() == Some("stopped") {
+                return Ok(());
+            }
+            tokio::time::sleep(STOP_POLL_INTERVAL).await;
+        }
+        anyhow::bail!("Timed out waiting for EC2 instance {} to stop", instance_id)
+    }
 }
 
 #[async_trait]
 impl VpsProvider for AwsAdapter {
     async fn deploy_agent(&self, config: AgentConfig) -> Result<DeploymentId> {
-        // AWS implementation would use AWS SDK (aws-sdk-ecs, aws-sdk-ec2, etc.)
-        // For now, return a placeholder with a note that full AWS SDK integration is needed
-        // This requires additional dependencies: aws-config, aws-sdk-ecs, etc.
+        let ami_id = self
+            .ami_id
+            .as_ref()
+            .context("AWS AMI ID not configured (set AWS_AMI_ID)")?;
+        let region = config.region.as_deref().unwrap_or(&self.region);
+        let name = match &config.agents {
+            Some(agents) if agents.len() > 1 => format!("clawguild-multi-{}", config.agent.id),
+            _ => format!("clawguild-{}", config.agent.id),
+        };
 
-        tracing::debug!(
-            "AWS adapter placeholder: runtime {:?} prepared for deployment",
-            config.runtime
-        );
+        let mut params = vec![
+            ("ImageId".to_string(), ami_id.clone()),
+            ("InstanceType".to_string(), self.instance_type.clone()),
+            ("MinCount".to_string(), "1".to_string()),
+            ("MaxCount".to_string(), "1".to_string()),
+            (
+                "UserData".to_string(),
+                encode_user_data(&config, self.cloudwatch_log_group()),
+            ),
+            (
+                "TagSpecification.1.ResourceType".to_string(),
+                "instance".to_string(),
+            ),
+            (
+                "TagSpecification.1.Tag.1.Key".to_string(),
+                "Name".to_string(),
+            ),
+            ("TagSpecification.1.Tag.1.Value".to_string(), name),
+            (
+                "TagSpecification.1.Tag.2.Key".to_string(),
+                "clawguild:agent_id".to_string(),
+            ),
+            (
+                "TagSpecification.1.Tag.2.Value".to_string(),
+                config.agent.id.to_string(),
+            ),
+        ];
+        if let Some(profile) = &self.instance_profile {
+            params.push(("IamInstanceProfile.Name".to_string(), profile.clone()));
+        }
 
-        tracing::warn!("AWS adapter: Full implementation requires AWS SDK. Using placeholder.");
-        let provider_id = format!("aws-{}", Uuid::new_v4());
+        let xml = self.ec2(region, "RunInstances", &params).await?;
+        let instance_id = xml_tag(&xml, "instanceId").ok_or_else(|| {
+            anyhow::anyhow!("RunInstances response did not include an instance ID")
+        })?;
 
         Ok(DeploymentId {
             id: config.agent.deployment_id.unwrap_or_else(Uuid::new_v4),
-            provider_id,
+            provider_id: format!("aws-{}/{}", region, instance_id),
         })
     }
 
     async fn get_status(&self, deployment_id: &DeploymentId) -> Result<VpsAgentStatus> {
-        // AWS status check would query ECS service or EC2 instance status
-        // Placeholder implementation
-        tracing::warn!("AWS adapter: Status check requires AWS SDK implementation");
+        let (region, instance_id) = self.parse_provider_id(deployment_id)?;
+        let instance = self
+            .describe_instance(region, instance_id)
+            .await?
+            .ok_or_else(|| ResourceNotFound {
+                provider_id: deployment_id.provider_id.clone(),
+            })?;
+
+        let status = match instance_state(&instance).as_deref().unwrap_or("unknown") {
+            "pending" => DeploymentStatus::Creating,
+            "running" => DeploymentStatus::Running,
+            "stopping" | "stopped" => DeploymentStatus::Stopped,
+            "shutting-down" | "terminated" => DeploymentStatus::Failed,
+            _ => DeploymentStatus::Pending,
+        };
+
+        let endpoint = xml_tag(&instance, "ipAddress")
+            .or_else(|| xml_tag(&instance, "dnsName"))
+            .filter(|host| !host.is_empty())
+            .map(|host| format!("http://{}", host));
+
         Ok(VpsAgentStatus {
             deployment_id: deployment_id.clone(),
-            status: DeploymentStatus::Running,
-            endpoint: None,
-            gateway_url: None,
+            status,
+            endpoint: endpoint.clone(),
+            gateway_url: endpoint,
         })
     }
 
-    async fn destroy_agent(&self, _deployment_id: &DeploymentId) -> Result<()> {
-        // AWS deletion would stop/terminate ECS service or EC2 instance
-        tracing::warn!("AWS adapter: Deletion requires AWS SDK implementation");
-        Ok(())
+    async fn destroy_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        let (region, instance_id) = self.parse_provider_id(deployment_id)?;
+
+        match self
+            .ec2(
+                region,
+                "TerminateInstances",
+                &[("InstanceId.1".to_string(), instance_id.to_string())],
+            )
+            .await
+        {
+            Ok(_) => Ok(()),
+            // Already gone
+            Err(e) if e.to_string().contains("InvalidInstanceID.NotFound") => Ok(()),
+            Err(e) => Err(e),
+        }
     }
 
-    async fn update_config(
-        &self,
-        _deployment_id: &DeploymentId,
-        _config: AgentConfig,
-    ) -> Result<()> {
-        // AWS config update would modify ECS task definition or EC2 user data
-        tracing::warn!("AWS adapter: Config update requires AWS SDK implementation");
+    async fn update_config(&self, deployment_id: &DeploymentId, config: AgentConfig) -> Result<()> {
+        // EC2 only accepts new user data while the instance is stopped
+        let (region, instance_id) = self.parse_provider_id(deployment_id)?;
+        let instance_param = ("InstanceId.1".to_string(), instance_id.to_string());
+
+        self.ec2(
+            region,
+            "StopInstances",
+            std::slice::from_ref(&instance_param),
+        )
+        .await?;
+        self.wait_until_stopped(region, instance_id).await?;
+
+        self.ec2(
+            region,
+            "ModifyInstanceAttribute",
+            &[
+                ("InstanceId".to_string(), instance_id.to_string()),
+                (
+                    "UserData.Value".to_string(),
+                    encode_user_data(&config, self.cloudwatch_log_group()),
+                ),
+            ],
+        )
+        .await?;
+
+        self.ec2(region, "StartInstances", &[instance_param])
+            .await?;
         Ok(())
     }
 
+    fn update_config_restarts(&self) -> bool {
+        true
+    }
+
     async fn get_logs(
         &self,
-        _deployment_id: &DeploymentId,
-        _lines: Option<usize>,
+        deployment_id: &DeploymentId,
+        lines: Option<usize>,
     ) -> Result<Vec<String>> {
-        // AWS logs would use CloudWatch Logs API
-        // Placeholder implementation
-        tracing::warn!("AWS adapter: Log retrieval requires AWS SDK implementation");
-        Ok(vec![
-            "AWS logs require CloudWatch Logs API integration.".to_string()
-        ])
+        let (region, instance_id) = self.parse_provider_id(deployment_id)?;
+        let lines = lines.unwrap_or(100);
+
+        if self.cloudwatch_log_group().is_some() {
+            let query = LogEventsQuery {
+                limit: lines,
+                ..LogEventsQuery::default()
+            };
+            if let Some((entries, _)) = self.fetch_log_events(region, instance_id, query).await? {
+                return Ok(entries.into_iter().map(|entry| entry.message).collect());
+            }
+            // The stream only exists once the CloudWatch agent is up; until then the console
+            // shows how boot is going
+        }
+
+        let mut output = self.console_output(region, instance_id).await?;
+        if output.is_empty() {
+            return Ok(vec![format!("No console output yet for {}", instance_id)]);
+        }
+        let skip = output.len().saturating_sub(lines);
+        Ok(output.split_off(skip))
+    }
+
+    async fn stream_logs(
+        &self,
+        deployment_id: &DeploymentId,
+        options: LogStreamOptions,
+    ) -> Result<LogStream> {
+        let (region, instance_id) = self.parse_provider_id(deployment_id)?;
+        let region = region.to_string();
+        let instance_id = instance_id.to_string();
+        let adapter = self.clone();
+
+        if self.cloudwatch_log_group().is_none() {
+            // Console output has no timestamps, so `since` can't be applied
+            let first = ConsoleCursor {
+                tail: Some(options.tail.unwrap_or(100)),
+                seen: Vec::new(),
+            };
+            return Ok(poll_log_stream(
+                first,
+                options.follow,
+                LOG_POLL_INTERVAL,
+                move |cursor: ConsoleCursor| {
+                    let adapter = adapter.clone();
+                    let region = region.clone();
+                    let instance_id = instance_id.clone();
+                    async move {
+                        let output = adapter.console_output(&region, &instance_id).await?;
+                        let mut new_lines = new_console_lines(&cursor.seen, &output);
+                        if let Some(tail) = cursor.tail {
+                            new_lines = &new_lines[new_lines.len().saturating_sub(tail)..];
+                        }
+                        let entries = new_lines
+                            .iter()
+                            .map(|line| LogEntry::from_line(None, instance_id.as_str(), line))
+                            .collect();
+                        let next = ConsoleCursor {
+                            tail: None,
+                            seen: output,
+                        };
+                        Ok((entries, next))
+                    }
+                },
+            ));
+        }
+
+        let first = LogEventsQuery {
+            // Reading forwards from `since` is what lets the next token follow new events
+            start_from_head: options.since.is_some(),
+            start_time: options.since,
+            next_token: None,
+            limit: options.tail.unwrap_or(100),
+        };
+
+        Ok(poll_log_stream(
+            first,
+            options.follow,
+            LOG_POLL_INTERVAL,
+            move |query: LogEventsQuery| {
+                let adapter = adapter.clone();
+                let region = region.clone();
+                let instance_id = instance_id.clone();
+                async move {
+                    let next = |next_token| LogEventsQuery {
+                        start_from_head: true,
+                        start_time: None,
+                        next_token,
+                        limit: LOG_EVENTS_PAGE_SIZE,
+                    };
+                    match adapter
+                        .fetch_log_events(&region, &instance_id, query.clone())
+                        .await?
+                    {
+                        Some((entries, next_token)) => {
+                            Ok((entries, next(next_token.or(query.next_token))))
+                        }
+                        // The stream is created on first boot; keep waiting for it
+                        None => Ok((Vec::new(), query)),
+                    }
+                }
+            },
+        ))
+    }
+
+    async fn stop_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.instance_action(deployment_id, "StopInstances").await
+    }
+
+    async fn start_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.instance_action(deployment_id, "StartInstances").await
+    }
+
+    async fn restart_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.instance_action(deployment_id, "RebootInstances").await
+    }
+
+    async fn create_volume(&self, _config: &AgentConfig) -> Result<String> {
+        anyhow::bail!("AWS adapter does not support volumes")
+    }
+
+    async fn delete_volume(&self, _volume_id: &str) -> Result<()> {
+        anyhow::bail!("AWS adapter does not support volumes")
     }
 
     fn provider_name(&self) -> &str {
         "aws"
     }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        let mut regions: Vec<String> = REGIONS.iter().map(|region| region.to_string()).collect();
+        if !regions.contains(&self.region) {
+            regions.push(self.region.clone());
+        }
+
+        ProviderCapabilities {
+            runtimes: all_runtimes(),
+            multi_agent: true,
+            volumes: false,
+            // Instances get a public address, but ports are only reachable if the default
+            // security group allows them
+            exposed_ports: false,
+            regions,
+            log_streaming: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ConsoleCursor {
+    /// How many lines of the first fetch to keep.
+    tail: Option<usize>,
+    /// The console output as of the last fetch.
+    seen: Vec<String>,
+}
+
+/// The lines of `current` that weren't in `previous`. The console only grows at the end and
+/// drops lines at the start once it's full, so the new lines are those after where the end of
+/// `previous` lines up with `current`; if it doesn't anywhere (e.g. after a reboot), all of
+/// `current` is new.
+fn new_console_lines<'a>(previous: &[String], current: &'a [String]) -> &'a [String] {
+    let Some(last) = previous.last() else {
+        return current;
+    };
+    for end in (0..current.len()).rev() {
+        if &current[end] != last {
+            continue;
+        }
+        let overlap = (end + 1).min(previous.len());
+        if current[end + 1 - overlap..=end] == previous[previous.len() - overlap..] {
+            return &current[end + 1..];
+        }
+    }
+    current
+}
+
+#[derive(Debug, Clone)]
+struct LogEventsQuery {
+    start_from_head: bool,
+    start_time: Option<DateTime<Utc>>,
+    next_token: Option<String>,
+    limit: usize,
+}
+
+impl Default for LogEventsQuery {
+    fn default() -> Self {
+        Self {
+            start_from_head: false,
+            start_time: None,
+            next_token: None,
+            limit: 100,
+        }
+    }
+}
+
+/// Multipart user data: cloud-config that re-runs user scripts on every boot (so
+/// `update_config` takes effect after a restart), followed by the runtime env and init script.
+/// With a `log_group`, the script first sets up the CloudWatch agent to ship its output there.
+fn encode_user_data(config: &AgentConfig, log_group: Option<&str>) -> String {
+    let exports: String = config
+        .runtime_env
+        .iter()
+        .map(|(key, value)| format!("export {}={}\n", key, shell_quote(value)))
+        .collect();
+    let log_shipping = log_group.map(cloudwatch_agent_setup).unwrap_or_default();
+
+    let user_data = format!(
+        "Content-Type: multipart/mixed; boundary=\"==CLAWGUILD==\"\n\
+         MIME-Version: 1.0\n\
+         \n\
+         --==CLAWGUILD==\n\
+         Content-Type: text/cloud-config; charset=\"us-ascii\"\n\
+         \n\
+         cloud_final_modules:\n\
+         - [scripts-user, always]\n\
+         \n\
+         --==CLAWGUILD==\n\
+         Content-Type: text/x-shellscript; charset=\"us-ascii\"\n\
+         \n\
+         #!/bin/bash\n\
+         {}{}{}\n\
+         --==CLAWGUILD==--\n",
+        log_shipping, exports, config.runtime_init_script
+    );
+
+    base64::engine::general_purpose::STANDARD.encode(user_data)
+}
+
+/// Shell that installs the CloudWatch agent (on first boot), points it at [`AGENT_LOG_FILE`]
+/// with the instance ID as the stream name, and copies the rest of the script's output there.
+fn cloudwatch_agent_setup(log_group: &str) -> String {
+    let agent_config = serde_json::json!({
+        "logs": {
+            "logs_collected": {
+                "files": {
+                    "collect_list": [{
+                        "file_path": AGENT_LOG_FILE,
+                        "log_group_name": log_group,
+                        "log_stream_name": "{instance_id}",
+                    }]
+                }
+            }
+        }
+    });
+
+    format!(
+        "if [ ! -x /opt/aws/amazon-cloudwatch-agent/bin/amazon-cloudwatch-agent-ctl ]; then\n\
+         \x20 arch=$(dpkg --print-architecture)\n\
+         \x20 curl -fsSL -o /tmp/amazon-cloudwatch-agent.deb \
+         https://amazoncloudwatch-agent.s3.amazonaws.com/debian/$arch/latest/amazon-cloudwatch-agent.deb\n\
+         \x20 dpkg -i /tmp/amazon-cloudwatch-agent.deb\n\
+         fi\n\
+         cat > /opt/aws/amazon-cloudwatch-agent/etc/clawguild.json <<'CLAWGUILD_CWAGENT'\n\
+         {}\n\
+         CLAWGUILD_CWAGENT\n\
+         /opt/aws/amazon-cloudwatch-agent/bin/amazon-cloudwatch-agent-ctl -a fetch-config -m ec2 \
+         -s -c file:/opt/aws/amazon-cloudwatch-agent/etc/clawguild.json\n\
+         exec > >(tee -a {}) 2>&1\n",
+        agent_config, AGENT_LOG_FILE
+    )
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn instance_state(instance: &str) -> Option<String> {
+    xml_tag(instance, "instanceState").and_then(|state| xml_tag(&state, "name"))
+}
+
+/// Text of the first `<tag>…</tag>` element. The EC2 responses we read are small and
+/// flat enough that this is simpler than pulling in an XML parser.
+fn xml_tag(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+
+    Some(
+        xml[start..end]
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
+/// RFC 3986 percent-encoding as required by SigV4 (everything but unreserved characters).
+fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Builds the SigV4 `Authorization` header. `headers` must be keyed by lowercase name
+/// and include every header that will be sent (they are all signed).
+#[allow(clippy::too_many_arguments)]
+fn sign_v4(
+    access_key_id: &str,
+    secret_access_key: &str,
+    region: &str,
+    service: &str,
+    method: &str,
+    url: &Url,
+    headers: &BTreeMap<String, String>,
+    payload: &[u8],
+    now: DateTime<Utc>,
+) -> String {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date_stamp = now.format("%Y%m%d").to_string();
+
+    let mut query: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(key, value)| (uri_encode(&key), uri_encode(&value)))
+        .collect();
+    query.sort();
+    let canonical_query = query
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed_headers = headers.keys().cloned().collect::<Vec<_>>().join(";");
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        url.path(),
+        canonical_query,
+        canonical_headers,
+        signed_headers,
+        hex::encode(Sha256::digest(payload))
+    );
+
+    let scope = format!("{}/{}/{}/aws4_request", date_stamp, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let signing_key = [region, service, "aws4_request"].iter().fold(
+        hmac_sha256(
+            format!("AWS4{}", secret_access_key).as_bytes(),
+            date_stamp.as_bytes(),
+        ),
+        |key, part| hmac_sha256(&key, part.as_bytes()),
+    );
+    let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        access_key_id, scope, signed_headers, signature
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // Credentials and date of the examples in the AWS SigV4 documentation and test suite
+    const ACCESS_KEY_ID: &str = "AKIDEXAMPLE";
+    const SECRET_ACCESS_KEY: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+
+    fn example_time() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2015, 8, 30, 12, 36, 0).unwrap()
+    }
+
+    fn headers(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn sign_v4_matches_the_test_suite_get_vanilla_vector() {
+        let url = Url::parse("https://example.amazonaws.com/").unwrap();
+        let headers = headers(&[
+            ("host", "example.amazonaws.com"),
+            ("x-amz-date", "20150830T123600Z"),
+        ]);
+
+        let authorization = sign_v4(
+            ACCESS_KEY_ID,
+            SECRET_ACCESS_KEY,
+            "us-east-1",
+            "service",
+            "GET",
+            &url,
+            &headers,
+            b"",
+            example_time(),
+        );
+
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn sign_v4_matches_the_documented_iam_list_users_vector() {
+        let url =
+            Url::parse("https://iam.amazonaws.com/?Action=ListUsers&Version=2010-05-08").unwrap();
+        let headers = headers(&[
+            (
+                "content-type",
+                "application/x-www-form-urlencoded; charset=utf-8",
+            ),
+            ("host", "iam.amazonaws.com"),
+            ("x-amz-date", "20150830T123600Z"),
+        ]);
+
+        let authorization = sign_v4(
+            ACCESS_KEY_ID,
+            SECRET_ACCESS_KEY,
+            "us-east-1",
+            "iam",
+            "GET",
+            &url,
+            &headers,
+            b"",
+            example_time(),
+        );
+
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date, \
+             Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        );
+    }
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn new_console_lines_follows_a_growing_console() {
+        assert_eq!(new_console_lines(&[], &lines("a b")), lines("a b"));
+        assert_eq!(
+            new_console_lines(&lines("a b"), &lines("a b c d")),
+            lines("c d")
+        );
+        assert!(new_console_lines(&lines("a b"), &lines("a b")).is_empty());
+    }
+
+    #[test]
+    fn new_console_lines_handles_repeated_lines_and_truncation() {
+        // "b" appears twice; only the alignment with the whole previous tail counts
+        assert_eq!(
+            new_console_lines(&lines("a b"), &lines("a b c b")),
+            lines("c b")
+        );
+        assert_eq!(
+            new_console_lines(&lines("x b c b"), &lines("b c b b")),
+            lines("b")
+        );
+        // The start of the console was dropped
+        assert_eq!(
+            new_console_lines(&lines("a b c"), &lines("b c d")),
+            lines("d")
+        );
+        // Nothing lines up, e.g. after a reboot
+        assert_eq!(
+            new_console_lines(&lines("a b"), &lines("c d")),
+            lines("c d")
+        );
+    }
 }
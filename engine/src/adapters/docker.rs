@@ -0,0 +1,626 @@
+use crate::adapters::logs::{LogEntry, LogStream, LogStreamOptions};
+use crate::adapters::trait_def::{
+    all_runtimes, AgentConfig, DeploymentId, ProviderCapabilities, ResourceNotFound,
+    VpsAgentStatus, VpsProvider,
+};
+use crate::config::Config;
+use crate::models::DeploymentStatus;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use hyper::body::HttpBody;
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request, Response, StatusCode};
+use hyperlocal::{UnixClientExt, UnixConnector};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use uuid::Uuid;
+
+const DEFAULT_DOCKER_HOST: &str = "unix:///var/run/docker.sock";
+const AGENT_IMAGE: &str = "debian:bookworm-slim";
+
+enum DockerTransport {
+    Unix {
+        client: Client<UnixConnector>,
+        socket_path: String,
+    },
+    Tcp {
+        client: Client<HttpConnector>,
+        base_url: String,
+    },
+}
+
+/// Runs agents as containers on the orchestrator host via the Docker Engine API.
+pub struct DockerAdapter {
+    transport: DockerTransport,
+}
+
+impl DockerAdapter {
+    pub fn new(config: &Config) -> Result<Self> {
+        let host = config.docker_host.as_deref().unwrap_or(DEFAULT_DOCKER_HOST);
+
+        Self::from_host(host)
+    }
+
+    /// Accepts `unix:///path/to/docker.sock`, `tcp://host:port` or `http://host:port`.
+    pub fn from_host(host: &str) -> Result<Self> {
+        let transport = if let Some(socket_path) = host.strip_prefix("unix://") {
+            DockerTransport::Unix {
+                client: Client::unix(),
+                socket_path: socket_path.to_string(),
+            }
+        } else if let Some(address) = host.strip_prefix("tcp://") {
+            DockerTransport::Tcp {
+                client: Client::new(),
+                base_url: format!("http://{}", address.trim_end_matches('/')),
+            }
+        } else if host.starts_with("http://") {
+            DockerTransport::Tcp {
+                client: Client::new(),
+                base_url: host.trim_end_matches('/').to_string(),
+            }
+        } else {
+            anyhow::bail!("Unsupported Docker host: {}", host);
+        };
+
+        Ok(Self { transport })
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<(StatusCode, Vec<u8>)> {
+        let response = self.send(method, path, body).await?;
+        let status = response.status();
+        let bytes = hyper::body::to_bytes(response.into_body()).await?;
+        Ok((status, bytes.to_vec()))
+    }
+
+    /// Like `request`, but hands back the response with its body unread, for streaming.
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Response<Body>> {
+        let body = match body {
+            Some(value) => Body::from(serde_json::to_vec(&value)?),
+            None => Body::empty(),
+        };
+        let builder = Request::builder()
+            .method(method)
+            .header("Content-Type", "application/json");
+
+        let response = match &self.transport {
+            DockerTransport::Unix {
+                client,
+                socket_path,
+            } => {
+                let uri: hyper::Uri = hyperlocal::Uri::new(socket_path, path).into();
+                client.request(builder.uri(uri).body(body)?).await
+            }
+            DockerTransport::Tcp { client, base_url } => {
+                let uri: hyper::Uri = format!("{}{}", base_url, path).parse()?;
+                client.request(builder.uri(uri).body(body)?).await
+            }
+        }
+        .with_context(|| format!("Docker Engine API request to {} failed", path))?;
+
+        Ok(response)
+    }
+
+    async fn request_json(&self, method: Method, path: &str, body: Option<Value>) -> Result<Value> {
+        let (status, bytes) = self.request(method, path, body).await?;
+        if !status.is_success() {
+            anyhow::bail!(
+                "Docker API request {} failed ({}): {}",
+                path,
+                status,
+                String::from_utf8_lossy(&bytes)
+            );
+        }
+
+        if bytes.is_empty() {
+            return Ok(Value::Null);
+        }
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// Pulls the agent image unless the host already has it, so agents can run without
+    /// registry access once the image is there.
+    async fn ensure_image(&self) -> Result<()> {
+        let (status, bytes) = self
+            .request(Method::GET, &format!("/images/{}/json", AGENT_IMAGE), None)
+            .await?;
+        if status.is_success() {
+            return Ok(());
+        }
+        if status != StatusCode::NOT_FOUND {
+            anyhow::bail!(
+                "Failed to inspect Docker image {}: {}",
+                AGENT_IMAGE,
+                String::from_utf8_lossy(&bytes)
+            );
+        }
+
+        self.pull_image().await
+    }
+
+    async fn pull_image(&self) -> Result<()> {
+        let (repo, tag) = AGENT_IMAGE
+            .split_once(':')
+            .unwrap_or((AGENT_IMAGE, "latest"));
+        let (status, bytes) = self
+            .request(
+                Method::POST,
+                &format!("/images/create?fromImage={}&tag={}", repo, tag),
+                None,
+            )
+            .await?;
+
+        if !status.is_success() {
+            anyhow::bail!(
+                "Failed to pull Docker image {}: {}",
+                AGENT_IMAGE,
+                String::from_utf8_lossy(&bytes)
+            );
+        }
+        // Errors found once the pull has started are reported in the 200 progress stream
+        let failure = bytes
+            .split(|byte| *byte == b'\n')
+            .filter_map(|line| serde_json::from_slice::<Value>(line).ok())
+            .find_map(|progress| progress["error"].as_str().map(|error| error.to_string()));
+        if let Some(error) = failure {
+            anyhow::bail!("Failed to pull Docker image {}: {}", AGENT_IMAGE, error);
+        }
+        Ok(())
+    }
+
+    async fn remove_container(&self, name: &str) -> Result<()> {
+        let (status, bytes) = self
+            .request(
+                Method::DELETE,
+                &format!("/containers/{}?force=true", name),
+                None,
+            )
+            .await?;
+
+        if !status.is_success() && status != StatusCode::NOT_FOUND {
+            anyhow::bail!(
+                "Failed to remove Docker container {}: {}",
+                name,
+                String::from_utf8_lossy(&bytes)
+            );
+        }
+        Ok(())
+    }
+
+    /// POSTs a lifecycle action (`stop`, `start`, `restart`) to the deployment's container.
+    async fn container_action(&self, deployment_id: &DeploymentId, action: &str) -> Result<()> {
+        let container_name = container_name(deployment_id)?;
+        let (status, bytes) = self
+            .request(
+                Method::POST,
+                &format!("/containers/{}/{}", container_name, action),
+                None,
+            )
+            .await?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(ResourceNotFound {
+                provider_id: deployment_id.provider_id.clone(),
+            }
+            .into());
+        }
+        // 304 means the container was already stopped/started
+        if !status.is_success() && status != StatusCode::NOT_MODIFIED {
+            anyhow::bail!(
+                "Failed to {} Docker container {}: {}",
+                action,
+                container_name,
+                String::from_utf8_lossy(&bytes)
+            );
+        }
+        Ok(())
+    }
+
+    /// (Re)creates the named container from the agent config and starts it.
+    async fn run_container(&self, name: &str, config: &AgentConfig) -> Result<()> {
+        self.ensure_image().await?;
+        self.remove_container(name).await?;
+
+        let env: Vec<String> = config
+            .runtime_env
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+
+        let mut exposed_ports = serde_json::Map::new();
+        let mut port_bindings = serde_json::Map::new();
+        for service in &config.runtime_services {
+            let key = format!("{}/tcp", service.internal_port);
+            exposed_ports.insert(key.clone(), json!({}));
+            // Empty HostPort lets Docker pick a free port on the host
+            port_bindings.insert(key, json!([{ "HostIp": "127.0.0.1", "HostPort": "" }]));
+        }
+
+        let mut container_config = json!({
+            "Image": AGENT_IMAGE,
+            "Cmd": ["/bin/bash", "-c", config.runtime_init_script],
+            "Env": env,
+            "ExposedPorts": exposed_ports,
+            "Labels": {
+                "clawguild.agent_id": config.agent.id.to_string(),
+                "clawguild.agent_name": config.agent.name,
+            },
+            "HostConfig": {
+                "PortBindings": port_bindings
+            }
+        });
+
+        if let Some(volume_id) = &config.volume_id {
+            container_config["HostConfig"]["Mounts"] = json!([{
+                "Type": "volume",
+                "Source": volume_id,
+                "Target": config.volume_mount_path(),
+            }]);
+        }
+
+        self.request_json(
+            Method::POST,
+            &format!("/containers/create?name={}", name),
+            Some(container_config),
+        )
+        .await
+        .context("Failed to create Docker container")?;
+
+        self.request_json(Method::POST, &format!("/containers/{}/start", name), None)
+            .await
+            .context("Failed to start Docker container")?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VpsProvider for DockerAdapter {
+    async fn deploy_agent(&self, config: AgentConfig) -> Result<DeploymentId> {
+        // Container names double as Docker API ids, so update_config can recreate in place
+        let container_name = match &config.agents {
+            Some(agents) if agents.len() > 1 => format!("clawguild-multi-{}", config.agent.id),
+            _ => format!("clawguild-{}", config.agent.id),
+        };
+
+        self.run_container(&container_name, &config).await?;
+
+        Ok(DeploymentId {
+            id: config.agent.deployment_id.unwrap_or_else(Uuid::new_v4),
+            provider_id: format!("docker-{}", container_name),
+        })
+    }
+
+    async fn get_status(&self, deployment_id: &DeploymentId) -> Result<VpsAgentStatus> {
+        let container_name = container_name(deployment_id)?;
+
+        let path = format!("/containers/{}/json", container_name);
+        let (status, bytes) = self.request(Method::GET, &path, None).await?;
+        if status == StatusCode::NOT_FOUND {
+            return Err(ResourceNotFound {
+                provider_id: deployment_id.provider_id.clone(),
+            }
+            .into());
+        }
+        if !status.is_success() {
+            anyhow::bail!(
+                "Failed to inspect Docker container {}: {}",
+                container_name,
+                String::from_utf8_lossy(&bytes)
+            );
+        }
+        let container: Value = serde_json::from_slice(&bytes)?;
+
+        let state = &container["State"];
+        let status = match state["Status"].as_str().unwrap_or("unknown") {
+            "running" => DeploymentStatus::Running,
+            "created" | "restarting" => DeploymentStatus::Creating,
+            "paused" | "removing" => DeploymentStatus::Stopped,
+            "exited" | "dead" if state["ExitCode"].as_i64() == Some(0) => DeploymentStatus::Stopped,
+            "exited" | "dead" => DeploymentStatus::Failed,
+            _ => DeploymentStatus::Pending,
+        };
+
+        let endpoint = container["NetworkSettings"]["Ports"]
+            .as_object()
+            .and_then(|ports| {
+                ports
+                    .values()
+                    .filter_map(|bindings| bindings.as_array())
+                    .flatten()
+                    .find_map(|binding| binding["HostPort"].as_str())
+            })
+            .map(|port| format!("http://127.0.0.1:{}", port));
+
+        Ok(VpsAgentStatus {
+            deployment_id: deployment_id.clone(),
+            status,
+            endpoint: endpoint.clone(),
+            gateway_url: endpoint,
+        })
+    }
+
+    async fn destroy_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        let container_name = container_name(deployment_id)?;
+        self.remove_container(container_name).await
+    }
+
+    async fn update_config(&self, deployment_id: &DeploymentId, config: AgentConfig) -> Result<()> {
+        // Docker cannot change the environment of an existing container, so recreate it
+        let container_name = container_name(deployment_id)?;
+        self.run_container(container_name, &config).await
+    }
+
+    fn update_config_restarts(&self) -> bool {
+        true
+    }
+
+    async fn get_logs(
+        &self,
+        deployment_id: &DeploymentId,
+        lines: Option<usize>,
+    ) -> Result<Vec<String>> {
+        let container_name = container_name(deployment_id)?;
+        let limit = lines.unwrap_or(100);
+
+        let (status, bytes) = self
+            .request(
+                Method::GET,
+                &format!(
+                    "/containers/{}/logs?stdout=true&stderr=true&tail={}",
+                    container_name, limit
+                ),
+                None,
+            )
+            .await?;
+
+        if !status.is_success() {
+            anyhow::bail!(
+                "Failed to fetch Docker container logs: {}",
+                String::from_utf8_lossy(&bytes)
+            );
+        }
+
+        Ok(demux_log_stream(&bytes))
+    }
+
+    async fn stream_logs(
+        &self,
+        deployment_id: &DeploymentId,
+        options: LogStreamOptions,
+    ) -> Result<LogStream> {
+        let container_name = container_name(deployment_id)?;
+        let tail = match (options.tail, options.since) {
+            (Some(tail), _) => tail.to_string(),
+            (None, Some(_)) => "all".to_string(),
+            (None, None) => "100".to_string(),
+        };
+        let mut path = format!(
+            "/containers/{}/logs?stdout=true&stderr=true&timestamps=true&follow={}&tail={}",
+            container_name, options.follow, tail
+        );
+        if let Some(since) = options.since {
+            path.push_str(&format!("&since={}", since.timestamp()));
+        }
+
+        let response = self.send(Method::GET, &path, None).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(ResourceNotFound {
+                provider_id: deployment_id.provider_id.clone(),
+            }
+            .into());
+        }
+        if !response.status().is_success() {
+            let status = response.status();
+            let bytes = hyper::body::to_bytes(response.into_body()).await?;
+            anyhow::bail!(
+                "Failed to stream Docker container logs ({}): {}",
+                status,
+                String::from_utf8_lossy(&bytes)
+            );
+        }
+
+        let reader = LogStreamReader {
+            body: Some(response.into_body()),
+            buffer: Vec::new(),
+            pending: VecDeque::new(),
+            framed: None,
+        };
+        Ok(stream::unfold(reader, |mut reader| async move {
+            loop {
+                if let Some(entry) = reader.pending.pop_front() {
+                    return Some((Ok(entry), reader));
+                }
+                let body = reader.body.as_mut()?;
+                match body.data().await {
+                    Some(Ok(bytes)) => {
+                        reader.buffer.extend_from_slice(&bytes);
+                        reader.drain(false);
+                    }
+                    Some(Err(e)) => {
+                        reader.body = None;
+                        return Some((Err(e.into()), reader));
+                    }
+                    None => {
+                        reader.body = None;
+                        reader.drain(true);
+                    }
+                }
+            }
+        })
+        .boxed())
+    }
+
+    async fn stop_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.container_action(deployment_id, "stop").await
+    }
+
+    async fn start_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.container_action(deployment_id, "start").await
+    }
+
+    async fn restart_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.container_action(deployment_id, "restart").await
+    }
+
+    async fn create_volume(&self, config: &AgentConfig) -> Result<String> {
+        let volume_name = format!("clawguild-{}-data", config.agent.id);
+        let volume = self
+            .request_json(
+                Method::POST,
+                "/volumes/create",
+                Some(json!({
+                    "Name": volume_name,
+                    "Labels": {
+                        "clawguild.agent_id": config.agent.id.to_string(),
+                    }
+                })),
+            )
+            .await
+            .context("Failed to create Docker volume")?;
+
+        Ok(volume["Name"]
+            .as_str()
+            .map(|name| name.to_string())
+            .unwrap_or(volume_name))
+    }
+
+    async fn delete_volume(&self, volume_id: &str) -> Result<()> {
+        let (status, bytes) = self
+            .request(Method::DELETE, &format!("/volumes/{}", volume_id), None)
+            .await?;
+
+        if !status.is_success() && status != StatusCode::NOT_FOUND {
+            anyhow::bail!(
+                "Failed to remove Docker volume {}: {}",
+                volume_id,
+                String::from_utf8_lossy(&bytes)
+            );
+        }
+        Ok(())
+    }
+
+    fn provider_name(&self) -> &str {
+        "docker"
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            runtimes: all_runtimes(),
+            multi_agent: true,
+            volumes: true,
+            exposed_ports: true,
+            regions: Vec::new(),
+            log_streaming: true,
+        }
+    }
+}
+
+fn container_name(deployment_id: &DeploymentId) -> Result<&str> {
+    deployment_id
+        .provider_id
+        .strip_prefix("docker-")
+        .ok_or_else(|| anyhow::anyhow!("Invalid provider ID"))
+}
+
+/// Incrementally splits a `follow`ed logs response into entries, handling both the
+/// multiplexed frame format and raw TTY output (see [`demux_log_stream`]).
+struct LogStreamReader {
+    body: Option<Body>,
+    buffer: Vec<u8>,
+    pending: VecDeque<LogEntry>,
+    /// Decided from the first 8 bytes of output.
+    framed: Option<bool>,
+}
+
+impl LogStreamReader {
+    fn drain(&mut self, finished: bool) {
+        if self.framed.is_none() {
+            if self.buffer.len() < 8 && !finished {
+                return;
+            }
+            let header = &self.buffer;
+            self.framed = Some(header.len() >= 8 && header[0] <= 2 && header[1..4] == [0, 0, 0]);
+        }
+
+        if self.framed == Some(true) {
+            while self.buffer.len() >= 8 {
+                let size = u32::from_be_bytes([
+                    self.buffer[4],
+                    self.buffer[5],
+                    self.buffer[6],
+                    self.buffer[7],
+                ]) as usize;
+                if self.buffer.len() < 8 + size {
+                    break;
+                }
+                let source = if self.buffer[0] == 2 {
+                    "stderr"
+                } else {
+                    "stdout"
+                };
+                let frame: Vec<u8> = self.buffer.drain(..8 + size).skip(8).collect();
+                for line in String::from_utf8_lossy(&frame).lines() {
+                    self.pending.push_back(parse_log_line(source, line));
+                }
+            }
+        } else {
+            while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                self.pending
+                    .push_back(parse_log_line("stdout", line.trim_end()));
+            }
+            if finished && !self.buffer.is_empty() {
+                let line = String::from_utf8_lossy(&self.buffer).into_owned();
+                self.buffer.clear();
+                self.pending
+                    .push_back(parse_log_line("stdout", line.trim_end()));
+            }
+        }
+    }
+}
+
+/// Lines requested with `timestamps=true` start with an RFC 3339 timestamp.
+fn parse_log_line(source: &str, line: &str) -> LogEntry {
+    let parsed = line.split_once(' ').and_then(|(timestamp, message)| {
+        DateTime::parse_from_rfc3339(timestamp)
+            .ok()
+            .map(|timestamp| (timestamp.with_timezone(&Utc), message))
+    });
+
+    match parsed {
+        Some((timestamp, message)) => LogEntry::from_line(Some(timestamp), source, message),
+        None => LogEntry::from_line(None, source, line),
+    }
+}
+
+/// Containers without a TTY multiplex stdout/stderr into frames with an 8-byte header
+/// (stream type, 3 padding bytes, big-endian payload length).
+fn demux_log_stream(bytes: &[u8]) -> Vec<String> {
+    let mut output = String::new();
+    let mut rest = bytes;
+
+    while rest.len() >= 8 && rest[0] <= 2 && rest[1..4] == [0, 0, 0] {
+        let size = u32::from_be_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        let end = (8 + size).min(rest.len());
+        output.push_str(&String::from_utf8_lossy(&rest[8..end]));
+        rest = &rest[end..];
+    }
+
+    // Anything left is raw output (e.g. a TTY container)
+    output.push_str(&String::from_utf8_lossy(rest));
+
+    output.lines().map(|line| line.to_string()).collect()
+}
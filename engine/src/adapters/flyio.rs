@@ -1,14 +1,39 @@
-use crate::adapters::trait_def::{AgentConfig, DeploymentId, VpsAgentStatus, VpsProvider};
+use crate::adapters::logs::{poll_log_stream, LogEntry, LogLevel, LogStream, LogStreamOptions};
+use crate::adapters::trait_def::{
+    all_runtimes, AgentConfig, DeploymentId, ProviderCapabilities, ResourceNotFound,
+    VpsAgentStatus, VpsProvider,
+};
 use crate::config::Config;
 use crate::models::DeploymentStatus;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use reqwest::Client;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use uuid::Uuid;
 
+const MACHINES_API_URL: &str = "https://api.machines.dev/v1";
+const LOGS_API_URL: &str = "https://api.fly.io/api/v1";
+/// Apps are created in this organization, so it is where legacy machines are looked up.
+const ORG_SLUG: &str = "personal";
+const VOLUME_SIZE_GB: u32 = 1;
+const LOG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const REGIONS: &[&str] = &[
+    "ams", "arn", "atl", "bog", "bom", "bos", "cdg", "den", "dfw", "ewr", "eze", "fra", "gdl",
+    "gig", "gru", "hkg", "iad", "jnb", "lax", "lhr", "mad", "mia", "nrt", "ord", "otp", "phx",
+    "qro", "scl", "sea", "sin", "sjc", "syd", "waw", "yul", "yyz",
+];
+
+#[derive(Clone)]
 pub struct FlyIoAdapter {
     client: Client,
     api_token: String,
+    api_url: String,
+    logs_url: String,
+    /// Apps of legacy machine IDs, which don't record them, once looked up.
+    legacy_apps: Arc<Mutex<HashMap<String, String>>>,
 }
 
 impl FlyIoAdapter {
@@ -19,37 +44,34 @@ impl FlyIoAdapter {
             .context("Fly.io API token not configured")?
             .clone();
 
+        let (api_url, logs_url) = match &config.fly_api_url {
+            Some(url) => {
+                let url = url.trim_end_matches('/');
+                (format!("{}/v1", url), format!("{}/api/v1", url))
+            }
+            None => (MACHINES_API_URL.to_string(), LOGS_API_URL.to_string()),
+        };
+
         Ok(Self {
             client: Client::new(),
             api_token,
+            api_url,
+            logs_url,
+            legacy_apps: Arc::default(),
         })
     }
-}
-
-#[async_trait]
-impl VpsProvider for FlyIoAdapter {
-    async fn deploy_agent(&self, config: AgentConfig) -> Result<DeploymentId> {
-        // Fly.io API: Create app (if it doesn't exist)
-        let app_name = match &config.agents {
-            Some(agents) if agents.len() > 1 => format!(
-                "clawguild-multi-{}",
-                config.agent.name.to_lowercase().replace(' ', "-")
-            ),
-            _ => format!(
-                "clawguild-{}",
-                config.agent.name.to_lowercase().replace(' ', "-")
-            ),
-        };
 
+    /// Returns the app's ID, creating the app if it doesn't exist yet.
+    async fn ensure_app(&self, app_name: &str) -> Result<String> {
         // Try to get existing app or create new one
         let app_response = self
             .client
-            .get(format!("https://api.machines.dev/v1/apps/{}", app_name))
+            .get(format!("{}/apps/{}", self.api_url, app_name))
             .header("Authorization", format!("Bearer {}", self.api_token))
             .send()
             .await;
 
-        let app_id = if let Ok(response) = app_response {
+        if let Ok(response) = app_response {
             if response.status().is_success() {
                 let app: serde_json::Value = response.json().await?;
                 app["id"]
@@ -61,12 +83,12 @@ impl VpsProvider for FlyIoAdapter {
                 // App doesn't exist, create it
                 let create_response = self
                     .client
-                    .post("https://api.machines.dev/v1/apps")
+                    .post(format!("{}/apps", self.api_url))
                     .header("Authorization", format!("Bearer {}", self.api_token))
                     .header("Content-Type", "application/json")
                     .json(&serde_json::json!({
                         "app_name": app_name,
-                        "org_slug": "personal"
+                        "org_slug": ORG_SLUG
                     }))
                     .send()
                     .await?;
@@ -82,12 +104,12 @@ impl VpsProvider for FlyIoAdapter {
             // Create new app
             let create_response = self
                 .client
-                .post("https://api.machines.dev/v1/apps")
+                .post(format!("{}/apps", self.api_url))
                 .header("Authorization", format!("Bearer {}", self.api_token))
                 .header("Content-Type", "application/json")
                 .json(&serde_json::json!({
                     "app_name": app_name,
-                    "org_slug": "personal"
+                    "org_slug": ORG_SLUG
                 }))
                 .send()
                 .await?;
@@ -98,59 +120,243 @@ impl VpsProvider for FlyIoAdapter {
                 .or_else(|| app["name"].as_str())
                 .map(|s| s.to_string())
                 .ok_or_else(|| anyhow::anyhow!("Failed to get app ID"))
-        }?;
+        }
+    }
 
-        // Create a Fly.io machine (VM) - OpenClaw runs directly on the VM, not in Docker
-        let region = config.region.as_deref().unwrap_or("iad"); // Default to iad (Washington, D.C.)
+    /// One page of the app's logs (optionally a single machine's) from the Fly.io logs API,
+    /// with the token for the next page.
+    async fn fetch_logs(
+        &self,
+        app_name: &str,
+        machine_id: Option<&str>,
+        next_token: Option<String>,
+    ) -> Result<(Vec<LogEntry>, Option<String>)> {
+        let mut query = Vec::new();
+        if let Some(machine_id) = machine_id {
+            query.push(("instance", machine_id.to_string()));
+        }
+        if let Some(next_token) = next_token {
+            query.push(("next_token", next_token));
+        }
+
+        let response = self
+            .client
+            .get(format!("{}/apps/{}/logs", self.logs_url, app_name))
+            .query(&query)
+            .header("Authorization", format!("Bearer {}", self.api_token))
+            .send()
+            .await?;
 
-        let mut env_vars = serde_json::Map::new();
-        for (key, value) in &config.runtime_env {
-            env_vars.insert(key.clone(), serde_json::Value::String(value.clone()));
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            anyhow::bail!("Fly.io app {} not found", app_name);
+        }
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            anyhow::bail!("Failed to fetch Fly.io logs: {}", error_text);
         }
 
-        let init_script = config.runtime_init_script.as_str();
+        let logs: serde_json::Value = response.json().await?;
+        let entries = logs["data"]
+            .as_array()
+            .map(|items| {
+                items
+                    .iter()
+                    .map(|item| {
+                        let attributes = &item["attributes"];
+                        let message = attributes["message"].as_str().unwrap_or_default();
+                        LogEntry {
+                            timestamp: attributes["timestamp"]
+                                .as_str()
+                                .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+                                .map(|ts| ts.with_timezone(&Utc)),
+                            level: attributes["level"]
+                                .as_str()
+                                .and_then(LogLevel::parse)
+                                .or_else(|| LogLevel::detect(message)),
+                            source: attributes["instance"]
+                                .as_str()
+                                .unwrap_or(app_name)
+                                .to_string(),
+                            message: message.to_string(),
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let next_token = logs["meta"]["next_token"]
+            .as_str()
+            .filter(|token| !token.is_empty())
+            .map(|token| token.to_string());
 
-        let mut machine_config = serde_json::json!({
-            "name": format!("{}-machine", app_name),
-            "region": region,
-            "config": {
-                "image": "debian:bookworm-slim",
-                "init": {
-                    "cmd": ["/bin/bash", "-c", init_script]
-                },
-                "env": env_vars
+        Ok((entries, next_token))
+    }
+
+    /// The app holding `machine_id`, for legacy provider IDs that only recorded the machine.
+    /// Looks through this organization's `clawguild-` apps, so `None` means no such app has it.
+    async fn find_machine_app(&self, machine_id: &str) -> Result<Option<String>> {
+        if let Some(app_name) = self.legacy_apps.lock().unwrap().get(machine_id) {
+            return Ok(Some(app_name.clone()));
+        }
+
+        let response = self
+            .client
+            .get(format!("{}/apps", self.api_url))
+            .query(&[("org_slug", ORG_SLUG)])
+            .header("Authorization", format!("Bearer {}", self.api_token))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            anyhow::bail!("Failed to list Fly.io apps: {}", error_text);
+        }
+
+        let apps: serde_json::Value = response.json().await?;
+        let app_names = apps["apps"]
+            .as_array()
+            .map(|apps| {
+                apps.iter()
+                    .filter_map(|app| app["name"].as_str())
+                    .filter(|name| name.starts_with("clawguild-"))
+                    .map(|name| name.to_string())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        for app_name in app_names {
+            let response = self
+                .client
+                .get(format!(
+                    "{}/apps/{}/machines/{}",
+                    self.api_url, app_name, machine_id
+                ))
+                .header("Authorization", format!("Bearer {}", self.api_token))
+                .send()
+                .await?;
+
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                continue;
+            }
+            if !response.status().is_success() {
+                let error_text = response.text().await?;
+                anyhow::bail!(
+                    "Failed to look up Fly.io machine {}: {}",
+                    machine_id,
+                    error_text
+                );
             }
-        });
 
-        if !config.runtime_services.is_empty() {
-            let services: Vec<serde_json::Value> = config
-                .runtime_services
-                .iter()
-                .map(|service| {
-                    serde_json::json!({
-                        "ports": [
-                            {
-                                "port": service.port,
-                                "handlers": service.handlers.clone(),
-                                "force_https": true
-                            }
-                        ],
-                        "protocol": "tcp",
-                        "internal_port": service.internal_port
-                    })
-                })
-                .collect();
+            self.legacy_apps
+                .lock()
+                .unwrap()
+                .insert(machine_id.to_string(), app_name.clone());
+            return Ok(Some(app_name));
+        }
+
+        Ok(None)
+    }
+
+    /// The app and machine a provider ID points at, looking up the app of legacy IDs. `None`
+    /// when a legacy machine isn't in any of the apps deployments are made in.
+    async fn resolve(&self, deployment_id: &DeploymentId) -> Result<Option<(String, String)>> {
+        let (app_name, machine_id) = parse_provider_id(deployment_id)?;
+        let app_name = match app_name {
+            Some(app_name) => app_name.to_string(),
+            None => match self.find_machine_app(machine_id).await? {
+                Some(app_name) => app_name,
+                None => return Ok(None),
+            },
+        };
+        Ok(Some((app_name, machine_id.to_string())))
+    }
+
+    /// Like [`resolve`](Self::resolve), for operations that need the machine. A legacy machine
+    /// that can't be found isn't reported as gone: it may be in an app the lookup doesn't see.
+    async fn require_machine(&self, deployment_id: &DeploymentId) -> Result<(String, String)> {
+        self.resolve(deployment_id).await?.ok_or_else(|| {
+            anyhow::anyhow!(
+                "No Fly.io app found for legacy machine ID {}",
+                deployment_id.provider_id
+            )
+        })
+    }
+
+    /// Every machine in the app.
+    async fn app_machine_ids(&self, app_name: &str) -> Result<Vec<String>> {
+        let response = self
+            .client
+            .get(format!("{}/apps/{}/machines", self.api_url, app_name))
+            .header("Authorization", format!("Bearer {}", self.api_token))
+            .send()
+            .await?;
 
-            machine_config["config"]["services"] = serde_json::Value::Array(services);
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            anyhow::bail!("Fly.io app {} not found", app_name);
+        }
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            anyhow::bail!("Failed to list Fly.io machines: {}", error_text);
         }
 
-        let machine_response = self
+        let machines: serde_json::Value = response.json().await?;
+        Ok(machines
+            .as_array()
+            .map(|machines| {
+                machines
+                    .iter()
+                    .filter_map(|machine| machine["id"].as_str().map(|id| id.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// POSTs a lifecycle action (`stop`, `start`, `restart`) to the deployment's machine.
+    async fn machine_action(&self, deployment_id: &DeploymentId, action: &str) -> Result<()> {
+        let (app_name, machine_id) = self.require_machine(deployment_id).await?;
+
+        let response = self
             .client
             .post(format!(
-                "https://api.machines.dev/v1/apps/{}/machines",
-                &app_id
+                "{}/apps/{}/machines/{}/{}",
+                self.api_url, app_name, machine_id, action
             ))
             .header("Authorization", format!("Bearer {}", self.api_token))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            anyhow::bail!(
+                "Failed to {} Fly.io machine {}: {}",
+                action,
+                machine_id,
+                error_text
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VpsProvider for FlyIoAdapter {
+    async fn deploy_agent(&self, config: AgentConfig) -> Result<DeploymentId> {
+        let app_name = app_name(&config);
+        let app_id = self.ensure_app(&app_name).await?;
+
+        // Create a Fly.io machine (VM) - OpenClaw runs directly on the VM, not in Docker
+        let region = config.region.as_deref().unwrap_or("iad"); // Default to iad (Washington, D.C.)
+
+        let machine_config = serde_json::json!({
+            "name": format!("{}-machine", app_name),
+            "region": region,
+            "config": machine_config(&config)?
+        });
+
+        let machine_response = self
+            .client
+            .post(format!("{}/apps/{}/machines", self.api_url, &app_id))
+            .header("Authorization", format!("Bearer {}", self.api_token))
             .header("Content-Type", "application/json")
             .json(&machine_config)
             .send()
@@ -167,7 +373,7 @@ impl VpsProvider for FlyIoAdapter {
             .or_else(|| machine["name"].as_str())
             .ok_or_else(|| anyhow::anyhow!("Failed to get machine ID"))?;
 
-        let provider_id = format!("flyio-{}", machine_id);
+        let provider_id = format!("flyio-{}/{}", app_name, machine_id);
 
         Ok(DeploymentId {
             id: config.agent.deployment_id.unwrap_or_else(Uuid::new_v4),
@@ -176,29 +382,44 @@ impl VpsProvider for FlyIoAdapter {
     }
 
     async fn get_status(&self, deployment_id: &DeploymentId) -> Result<VpsAgentStatus> {
-        let app_id = deployment_id
-            .provider_id
-            .strip_prefix("flyio-")
-            .ok_or_else(|| anyhow::anyhow!("Invalid provider ID"))?;
+        let (app_name, machine_id) = self.require_machine(deployment_id).await?;
+        let url = format!("{}/apps/{}/machines/{}", self.api_url, app_name, machine_id);
 
         let response = self
             .client
-            .get(format!("https://api.machines.dev/v1/apps/{}", app_id))
+            .get(url)
             .header("Authorization", format!("Bearer {}", self.api_token))
             .send()
             .await?;
 
-        let app: serde_json::Value = response.json().await?;
-        let status_str = app["status"].as_str().unwrap_or("unknown");
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(ResourceNotFound {
+                provider_id: deployment_id.provider_id.clone(),
+            }
+            .into());
+        }
+
+        let resource: serde_json::Value = response.json().await?;
+        let status_str = resource["state"]
+            .as_str()
+            .or_else(|| resource["status"].as_str())
+            .unwrap_or("unknown");
 
         let status = match status_str {
             "running" | "started" => DeploymentStatus::Running,
-            "starting" | "stopping" => DeploymentStatus::Creating,
-            "stopped" | "failed" => DeploymentStatus::Failed,
+            "created" | "starting" | "replacing" => DeploymentStatus::Creating,
+            "stopping" | "stopped" | "suspended" => DeploymentStatus::Stopped,
+            "failed" => DeploymentStatus::Failed,
+            "destroying" | "destroyed" => {
+                return Err(ResourceNotFound {
+                    provider_id: deployment_id.provider_id.clone(),
+                }
+                .into());
+            }
             _ => DeploymentStatus::Pending,
         };
 
-        let endpoint = app["hostname"].as_str().map(|s| format!("https://{}", s));
+        let endpoint = Some(format!("https://{}.fly.dev", app_name));
 
         Ok(VpsAgentStatus {
             deployment_id: deployment_id.clone(),
@@ -209,94 +430,307 @@ impl VpsProvider for FlyIoAdapter {
     }
 
     async fn destroy_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
-        let app_id = deployment_id
-            .provider_id
-            .strip_prefix("flyio-")
-            .ok_or_else(|| anyhow::anyhow!("Invalid provider ID"))?;
+        // A legacy machine in none of the apps deployments are made in is already gone
+        let Some((app_name, machine_id)) = self.resolve(deployment_id).await? else {
+            return Ok(());
+        };
 
-        self.client
-            .delete(format!("https://api.machines.dev/v1/apps/{}", app_id))
+        // Only the machine goes: the app holds the agent's volume, which outlives redeploys
+        let response = self
+            .client
+            .delete(format!(
+                "{}/apps/{}/machines/{}?force=true",
+                self.api_url, app_name, machine_id
+            ))
             .header("Authorization", format!("Bearer {}", self.api_token))
             .send()
             .await?;
 
+        if !response.status().is_success() && response.status() != reqwest::StatusCode::NOT_FOUND {
+            let error_text = response.text().await?;
+            anyhow::bail!(
+                "Failed to destroy Fly.io machine {}: {}",
+                machine_id,
+                error_text
+            );
+        }
+
         Ok(())
     }
 
+    /// Replaces the machine's config, which Fly.io applies by restarting the machine.
     async fn update_config(&self, deployment_id: &DeploymentId, config: AgentConfig) -> Result<()> {
-        let app_id = deployment_id
-            .provider_id
-            .strip_prefix("flyio-")
-            .ok_or_else(|| anyhow::anyhow!("Invalid provider ID"))?;
-
-        let mut secrets_map = serde_json::Map::new();
-        for (key, value) in &config.runtime_env {
-            secrets_map.insert(key.clone(), serde_json::Value::String(value.clone()));
-        }
-        let secrets = serde_json::Value::Object(secrets_map);
+        let (app_name, machine_id) = self.require_machine(deployment_id).await?;
+        let machine_config = serde_json::json!({ "config": machine_config(&config)? });
 
-        self.client
+        let response = self
+            .client
             .post(format!(
-                "https://api.machines.dev/v1/apps/{}/secrets",
-                app_id
+                "{}/apps/{}/machines/{}",
+                self.api_url, app_name, machine_id
             ))
             .header("Authorization", format!("Bearer {}", self.api_token))
             .header("Content-Type", "application/json")
-            .json(&secrets)
+            .json(&machine_config)
             .send()
             .await?;
 
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(ResourceNotFound {
+                provider_id: deployment_id.provider_id.clone(),
+            }
+            .into());
+        }
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            anyhow::bail!(
+                "Failed to update Fly.io machine {}: {}",
+                machine_id,
+                error_text
+            );
+        }
+
         Ok(())
     }
 
+    fn update_config_restarts(&self) -> bool {
+        true
+    }
+
     async fn get_logs(
         &self,
         deployment_id: &DeploymentId,
         lines: Option<usize>,
     ) -> Result<Vec<String>> {
-        let machine_id = deployment_id
-            .provider_id
-            .strip_prefix("flyio-")
-            .ok_or_else(|| anyhow::anyhow!("Invalid provider ID"))?;
+        let (app_name, machine_id) = self.require_machine(deployment_id).await?;
+        let (entries, _) = self.fetch_logs(&app_name, Some(&machine_id), None).await?;
 
-        // Extract app name from machine ID (format: app-name-machine-id)
-        // For now, we'll use the machine ID directly
         let limit = lines.unwrap_or(100);
+        let skip = entries.len().saturating_sub(limit);
+        Ok(entries
+            .into_iter()
+            .skip(skip)
+            .map(|entry| entry.message)
+            .collect())
+    }
+
+    async fn stream_logs(
+        &self,
+        deployment_id: &DeploymentId,
+        options: LogStreamOptions,
+    ) -> Result<LogStream> {
+        let (app_name, machine_id) = self.require_machine(deployment_id).await?;
+        let adapter = self.clone();
+        let since = options.since;
+        // The logs API has no limit, so `tail` is applied to the first page
+        let first = LogCursor {
+            next_token: None,
+            tail: match (options.tail, since) {
+                (Some(tail), _) => Some(tail),
+                (None, Some(_)) => None,
+                (None, None) => Some(100),
+            },
+        };
+
+        Ok(poll_log_stream(
+            first,
+            options.follow,
+            LOG_POLL_INTERVAL,
+            move |cursor: LogCursor| {
+                let adapter = adapter.clone();
+                let app_name = app_name.clone();
+                let machine_id = machine_id.clone();
+                async move {
+                    let (mut entries, next) = adapter
+                        .fetch_logs(&app_name, Some(&machine_id), cursor.next_token.clone())
+                        .await?;
+                    if let Some(since) = since {
+                        entries.retain(|entry| entry.timestamp.is_none_or(|ts| ts >= since));
+                    }
+                    if let Some(tail) = cursor.tail {
+                        entries.drain(..entries.len().saturating_sub(tail));
+                    }
+                    let next = LogCursor {
+                        // An empty page leaves the token unchanged
+                        next_token: next.or(cursor.next_token),
+                        tail: None,
+                    };
+                    Ok((entries, next))
+                }
+            },
+        ))
+    }
+
+    async fn stop_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.machine_action(deployment_id, "stop").await
+    }
+
+    async fn start_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.machine_action(deployment_id, "start").await
+    }
+
+    async fn restart_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.machine_action(deployment_id, "restart").await
+    }
+
+    async fn create_volume(&self, config: &AgentConfig) -> Result<String> {
+        let app_name = app_name(config);
+        self.ensure_app(&app_name).await?;
 
-        // Fly.io API: Get machine logs
         let response = self
             .client
-            .get(format!(
-                "https://api.machines.dev/v1/apps/{}/machines/{}/logs?limit={}",
-                "clawguild", // This should be extracted from deployment_id or stored
-                machine_id,
-                limit
-            ))
+            .post(format!("{}/apps/{}/volumes", self.api_url, app_name))
             .header("Authorization", format!("Bearer {}", self.api_token))
+            .header("Content-Type", "application/json")
+            .json(&serde_json::json!({
+                "name": "clawguild_data",
+                "region": config.region.as_deref().unwrap_or("iad"),
+                "size_gb": VOLUME_SIZE_GB
+            }))
             .send()
             .await?;
 
         if !response.status().is_success() {
-            // If logs endpoint doesn't exist, return empty or use alternative method
-            return Ok(vec![
-                "Logs not available via API. Use 'fly logs' CLI command.".to_string(),
-            ]);
+            let error_text = response.text().await?;
+            anyhow::bail!("Failed to create Fly.io volume: {}", error_text);
         }
 
-        let logs: serde_json::Value = response.json().await?;
-        let log_lines = logs["logs"]
-            .as_array()
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                    .collect()
-            })
-            .unwrap_or_default();
+        let volume: serde_json::Value = response.json().await?;
+        let volume_id = volume["id"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get volume ID"))?;
+
+        Ok(format!("{}/{}", app_name, volume_id))
+    }
+
+    async fn delete_volume(&self, volume_id: &str) -> Result<()> {
+        let (app_name, volume_id) = parse_volume_id(volume_id)?;
+
+        let response = self
+            .client
+            .delete(format!(
+                "{}/apps/{}/volumes/{}",
+                self.api_url, app_name, volume_id
+            ))
+            .header("Authorization", format!("Bearer {}", self.api_token))
+            .send()
+            .await?;
+
+        if !response.status().is_success() && response.status() != reqwest::StatusCode::NOT_FOUND {
+            let error_text = response.text().await?;
+            anyhow::bail!("Failed to delete Fly.io volume: {}", error_text);
+        }
+
+        // With its data gone the app is only worth keeping if something still runs in it
+        if self.app_machine_ids(app_name).await?.is_empty() {
+            self.client
+                .delete(format!("{}/apps/{}", self.api_url, app_name))
+                .header("Authorization", format!("Bearer {}", self.api_token))
+                .send()
+                .await?;
+        }
 
-        Ok(log_lines)
+        Ok(())
     }
 
     fn provider_name(&self) -> &str {
         "flyio"
     }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            runtimes: all_runtimes(),
+            multi_agent: true,
+            volumes: true,
+            exposed_ports: true,
+            regions: REGIONS.iter().map(|region| region.to_string()).collect(),
+            log_streaming: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LogCursor {
+    next_token: Option<String>,
+    /// How many entries of the first page to keep.
+    tail: Option<usize>,
+}
+
+/// The machine's `config`: the init script run on a plain Debian image with the runtime's env,
+/// services and volume.
+fn machine_config(config: &AgentConfig) -> Result<serde_json::Value> {
+    let env_vars: serde_json::Map<String, serde_json::Value> = config
+        .runtime_env
+        .iter()
+        .map(|(key, value)| (key.clone(), serde_json::Value::String(value.clone())))
+        .collect();
+
+    let mut machine_config = serde_json::json!({
+        "image": "debian:bookworm-slim",
+        "init": {
+            "cmd": ["/bin/bash", "-c", config.runtime_init_script]
+        },
+        "env": env_vars
+    });
+
+    if !config.runtime_services.is_empty() {
+        let services: Vec<serde_json::Value> = config
+            .runtime_services
+            .iter()
+            .map(|service| {
+                serde_json::json!({
+                    "ports": [
+                        {
+                            "port": service.port,
+                            "handlers": service.handlers.clone(),
+                            "force_https": true
+                        }
+                    ],
+                    "protocol": "tcp",
+                    "internal_port": service.internal_port
+                })
+            })
+            .collect();
+
+        machine_config["services"] = serde_json::Value::Array(services);
+    }
+
+    if let Some(volume_id) = &config.volume_id {
+        let (_, volume_id) = parse_volume_id(volume_id)?;
+        machine_config["mounts"] = serde_json::json!([{
+            "volume": volume_id,
+            "path": config.volume_mount_path()
+        }]);
+    }
+
+    Ok(machine_config)
+}
+
+fn app_name(config: &AgentConfig) -> String {
+    let slug = config.agent.name.to_lowercase().replace(' ', "-");
+    match &config.agents {
+        Some(agents) if agents.len() > 1 => format!("clawguild-multi-{}", slug),
+        _ => format!("clawguild-{}", slug),
+    }
+}
+
+/// Volume IDs are `{app}/{volume_id}`, since Fly.io volumes are scoped to an app.
+fn parse_volume_id(volume_id: &str) -> Result<(&str, &str)> {
+    volume_id
+        .split_once('/')
+        .ok_or_else(|| anyhow::anyhow!("Invalid Fly.io volume ID: {}", volume_id))
+}
+
+/// Provider IDs are `flyio-{app}/{machine_id}`; older deployments recorded only the machine,
+/// as `flyio-{machine_id}`, so their app has to be looked up.
+fn parse_provider_id(deployment_id: &DeploymentId) -> Result<(Option<&str>, &str)> {
+    let id = deployment_id
+        .provider_id
+        .strip_prefix("flyio-")
+        .ok_or_else(|| anyhow::anyhow!("Invalid provider ID"))?;
+
+    Ok(match id.split_once('/') {
+        Some((app_name, machine_id)) => (Some(app_name), machine_id),
+        None => (None, id),
+    })
 }
@@ -0,0 +1,153 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::time::Duration;
+
+/// Stream returned by [`VpsProvider::stream_logs`](crate::adapters::VpsProvider::stream_logs).
+pub type LogStream = BoxStream<'static, Result<LogEntry>>;
+
+/// Ordered by severity, so `level >= LogLevel::Warn` selects warnings and errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 5] = [
+        LogLevel::Trace,
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Warn,
+        LogLevel::Error,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Trace => "trace",
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "trace" => Some(Self::Trace),
+            "debug" => Some(Self::Debug),
+            "info" | "notice" => Some(Self::Info),
+            "warn" | "warning" => Some(Self::Warn),
+            "error" | "err" | "fatal" | "crit" | "critical" | "panic" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    /// Best-effort level for plain-text lines, from the first level-looking word
+    /// (`ERROR ...`, `[warn] ...`, `level=info ...`).
+    pub fn detect(message: &str) -> Option<Self> {
+        message
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .filter(|word| !word.is_empty())
+            .take(4)
+            .find_map(Self::parse)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    /// When the provider recorded the line, if it reports one.
+    pub timestamp: Option<DateTime<Utc>>,
+    pub level: Option<LogLevel>,
+    /// Where the line came from, e.g. `stdout`, a machine ID or a log stream name.
+    pub source: String,
+    pub message: String,
+}
+
+impl LogEntry {
+    /// An entry from a plain-text line, with the level guessed from its contents.
+    pub fn from_line(
+        timestamp: Option<DateTime<Utc>>,
+        source: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        let message = message.into();
+        Self {
+            timestamp,
+            level: LogLevel::detect(&message),
+            source: source.into(),
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LogStreamOptions {
+    /// Keep the stream open and yield new entries as they arrive.
+    pub follow: bool,
+    /// Only entries at or after this time (providers without timestamps ignore it).
+    pub since: Option<DateTime<Utc>>,
+    /// How many existing entries to start from when `since` isn't set.
+    pub tail: Option<usize>,
+}
+
+/// Builds a [`LogStream`] for providers that only offer a paged log API: `fetch` is called
+/// with the current cursor and returns a batch plus the next cursor. Without `follow` the
+/// stream ends after the first batch; otherwise it polls every `interval` until dropped.
+pub(crate) fn poll_log_stream<C, F, Fut>(
+    cursor: C,
+    follow: bool,
+    interval: Duration,
+    fetch: F,
+) -> LogStream
+where
+    C: Send + 'static,
+    F: FnMut(C) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(Vec<LogEntry>, C)>> + Send + 'static,
+{
+    struct State<C, F> {
+        cursor: Option<C>,
+        fetch: F,
+        buffered: VecDeque<LogEntry>,
+        polled: bool,
+    }
+
+    let state = State {
+        cursor: Some(cursor),
+        fetch,
+        buffered: VecDeque::new(),
+        polled: false,
+    };
+
+    stream::unfold(state, move |mut state| async move {
+        loop {
+            if let Some(entry) = state.buffered.pop_front() {
+                return Some((Ok(entry), state));
+            }
+            let cursor = state.cursor.take()?;
+            if state.polled {
+                tokio::time::sleep(interval).await;
+            }
+            state.polled = true;
+
+            match (state.fetch)(cursor).await {
+                Ok((entries, next)) => {
+                    state.buffered.extend(entries);
+                    if follow {
+                        state.cursor = Some(next);
+                    }
+                }
+                // Ends the stream: `cursor` is left empty
+                Err(e) => return Some((Err(e), state)),
+            }
+        }
+    })
+    .boxed()
+}
@@ -0,0 +1,550 @@
+use crate::adapters::logs::{poll_log_stream, LogEntry, LogStream, LogStreamOptions};
+use crate::adapters::trait_def::{
+    all_runtimes, AgentConfig, DeploymentId, ProviderCapabilities, ResourceNotFound,
+    VpsAgentStatus, VpsProvider,
+};
+use crate::models::DeploymentStatus;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Scripted behaviour for a single deployment created by [`MockVpsProvider`].
+#[derive(Debug, Clone)]
+pub struct MockScript {
+    /// Statuses returned by successive `get_status` calls; the last one repeats forever.
+    pub statuses: Vec<DeploymentStatus>,
+    /// Delay applied to every provider call for this deployment.
+    pub latency: Duration,
+    pub logs: Vec<String>,
+    pub endpoint: Option<String>,
+    pub deploy_error: Option<String>,
+    pub status_error: Option<String>,
+    pub destroy_error: Option<String>,
+    pub update_error: Option<String>,
+}
+
+impl Default for MockScript {
+    fn default() -> Self {
+        Self::running()
+    }
+}
+
+impl MockScript {
+    /// Pending → Creating → Running.
+    pub fn running() -> Self {
+        Self::with_statuses(vec![
+            DeploymentStatus::Pending,
+            DeploymentStatus::Creating,
+            DeploymentStatus::Running,
+        ])
+    }
+
+    /// Pending → Creating → Failed.
+    pub fn failing() -> Self {
+        Self::with_statuses(vec![
+            DeploymentStatus::Pending,
+            DeploymentStatus::Creating,
+            DeploymentStatus::Failed,
+        ])
+    }
+
+    /// Stays in Creating, for exercising deployment timeouts.
+    pub fn never_ready() -> Self {
+        Self::with_statuses(vec![DeploymentStatus::Pending, DeploymentStatus::Creating])
+    }
+
+    pub fn with_statuses(statuses: Vec<DeploymentStatus>) -> Self {
+        Self {
+            statuses,
+            latency: Duration::ZERO,
+            logs: Vec::new(),
+            endpoint: None,
+            deploy_error: None,
+            status_error: None,
+            destroy_error: None,
+            update_error: None,
+        }
+    }
+
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn logs<I, S>(mut self, lines: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.logs = lines.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    pub fn fail_deploy(mut self, message: impl Into<String>) -> Self {
+        self.deploy_error = Some(message.into());
+        self
+    }
+
+    pub fn fail_status(mut self, message: impl Into<String>) -> Self {
+        self.status_error = Some(message.into());
+        self
+    }
+
+    pub fn fail_destroy(mut self, message: impl Into<String>) -> Self {
+        self.destroy_error = Some(message.into());
+        self
+    }
+
+    pub fn fail_update(mut self, message: impl Into<String>) -> Self {
+        self.update_error = Some(message.into());
+        self
+    }
+}
+
+/// Every call made against the mock, in order, so tests can assert on the interaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockCall {
+    Deploy { provider_id: String, agent_id: Uuid },
+    GetStatus { provider_id: String },
+    Destroy { provider_id: String },
+    UpdateConfig { provider_id: String },
+    Stop { provider_id: String },
+    Start { provider_id: String },
+    Restart { provider_id: String },
+    CreateVolume { volume_id: String },
+    DeleteVolume { volume_id: String },
+    GetLogs { provider_id: String },
+    StreamLogs { provider_id: String },
+}
+
+#[derive(Debug, Clone)]
+struct MockDeployment {
+    script: MockScript,
+    config: AgentConfig,
+    status_calls: usize,
+    destroyed: bool,
+    stopped: bool,
+}
+
+struct MockState {
+    default_script: MockScript,
+    capabilities: ProviderCapabilities,
+    queued_scripts: VecDeque<MockScript>,
+    deployments: HashMap<String, MockDeployment>,
+    volumes: BTreeSet<String>,
+    calls: Vec<MockCall>,
+    next_id: u64,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        Self {
+            default_script: MockScript::default(),
+            capabilities: ProviderCapabilities {
+                runtimes: all_runtimes(),
+                multi_agent: true,
+                volumes: true,
+                exposed_ports: true,
+                regions: Vec::new(),
+                log_streaming: true,
+            },
+            queued_scripts: VecDeque::new(),
+            deployments: HashMap::new(),
+            volumes: BTreeSet::new(),
+            calls: Vec::new(),
+            next_id: 0,
+        }
+    }
+}
+
+/// In-memory provider whose lifecycle is scripted per test. Clones share state.
+#[derive(Clone, Default)]
+pub struct MockVpsProvider {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockVpsProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Script used for deployments that have no queued script.
+    pub fn with_default_script(self, script: MockScript) -> Self {
+        self.lock().default_script = script;
+        self
+    }
+
+    /// Capabilities to report; defaults to supporting everything.
+    pub fn with_capabilities(self, capabilities: ProviderCapabilities) -> Self {
+        self.lock().capabilities = capabilities;
+        self
+    }
+
+    /// Queue a script for the next `deploy_agent` call (FIFO).
+    pub fn push_script(&self, script: MockScript) {
+        self.lock().queued_scripts.push_back(script);
+    }
+
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.lock().calls.clone()
+    }
+
+    pub fn provider_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.lock().deployments.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Volumes created and not yet deleted.
+    pub fn volumes(&self) -> Vec<String> {
+        self.lock().volumes.iter().cloned().collect()
+    }
+
+    pub fn is_stopped(&self, provider_id: &str) -> bool {
+        self.lock()
+            .deployments
+            .get(provider_id)
+            .map(|deployment| deployment.stopped)
+            .unwrap_or(false)
+    }
+
+    pub fn is_destroyed(&self, provider_id: &str) -> bool {
+        self.lock()
+            .deployments
+            .get(provider_id)
+            .map(|deployment| deployment.destroyed)
+            .unwrap_or(false)
+    }
+
+    /// The most recent config pushed by `deploy_agent` or `update_config`.
+    pub fn config(&self, provider_id: &str) -> Option<AgentConfig> {
+        self.lock()
+            .deployments
+            .get(provider_id)
+            .map(|deployment| deployment.config.clone())
+    }
+
+    /// Append log lines to a live deployment, e.g. to simulate agent output.
+    pub fn push_logs<I, S>(&self, provider_id: &str, lines: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        if let Some(deployment) = self.lock().deployments.get_mut(provider_id) {
+            deployment
+                .script
+                .logs
+                .extend(lines.into_iter().map(Into::into));
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn latency_for(&self, provider_id: &str) -> Duration {
+        self.lock()
+            .deployments
+            .get(provider_id)
+            .map(|deployment| deployment.script.latency)
+            .unwrap_or_default()
+    }
+
+    async fn set_stopped(
+        &self,
+        deployment_id: &DeploymentId,
+        stopped: bool,
+        call: impl FnOnce(String) -> MockCall,
+    ) -> Result<()> {
+        let provider_id = &deployment_id.provider_id;
+        self.simulate_latency(self.latency_for(provider_id)).await;
+
+        let mut state = self.lock();
+        state.calls.push(call(provider_id.clone()));
+
+        let deployment = state
+            .deployments
+            .get_mut(provider_id)
+            .filter(|deployment| !deployment.destroyed)
+            .ok_or_else(|| ResourceNotFound {
+                provider_id: provider_id.clone(),
+            })?;
+        deployment.stopped = stopped;
+
+        Ok(())
+    }
+
+    async fn simulate_latency(&self, latency: Duration) {
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+    }
+}
+
+#[async_trait]
+impl VpsProvider for MockVpsProvider {
+    async fn deploy_agent(&self, config: AgentConfig) -> Result<DeploymentId> {
+        let (provider_id, script) = {
+            let mut state = self.lock();
+            state.next_id += 1;
+            let provider_id = format!("mock-{}", state.next_id);
+            let script = state
+                .queued_scripts
+                .pop_front()
+                .unwrap_or_else(|| state.default_script.clone());
+            state.calls.push(MockCall::Deploy {
+                provider_id: provider_id.clone(),
+                agent_id: config.agent.id,
+            });
+            (provider_id, script)
+        };
+
+        self.simulate_latency(script.latency).await;
+
+        if let Some(error) = &script.deploy_error {
+            anyhow::bail!("{}", error);
+        }
+
+        let deployment_id = DeploymentId {
+            id: config.agent.deployment_id.unwrap_or_else(Uuid::new_v4),
+            provider_id: provider_id.clone(),
+        };
+
+        self.lock().deployments.insert(
+            provider_id,
+            MockDeployment {
+                script,
+                config,
+                status_calls: 0,
+                destroyed: false,
+                stopped: false,
+            },
+        );
+
+        Ok(deployment_id)
+    }
+
+    async fn get_status(&self, deployment_id: &DeploymentId) -> Result<VpsAgentStatus> {
+        let provider_id = &deployment_id.provider_id;
+        self.simulate_latency(self.latency_for(provider_id)).await;
+
+        let mut state = self.lock();
+        state.calls.push(MockCall::GetStatus {
+            provider_id: provider_id.clone(),
+        });
+
+        let deployment = state
+            .deployments
+            .get_mut(provider_id)
+            .filter(|deployment| !deployment.destroyed)
+            .ok_or_else(|| ResourceNotFound {
+                provider_id: provider_id.clone(),
+            })?;
+
+        if let Some(error) = &deployment.script.status_error {
+            anyhow::bail!("{}", error);
+        }
+
+        let statuses = &deployment.script.statuses;
+        let status = if deployment.stopped {
+            DeploymentStatus::Stopped
+        } else {
+            statuses
+                .get(deployment.status_calls)
+                .or_else(|| statuses.last())
+                .cloned()
+                .unwrap_or(DeploymentStatus::Running)
+        };
+        deployment.status_calls += 1;
+
+        let endpoint = deployment.script.endpoint.clone();
+        Ok(VpsAgentStatus {
+            deployment_id: deployment_id.clone(),
+            status,
+            endpoint: endpoint.clone(),
+            gateway_url: endpoint,
+        })
+    }
+
+    async fn destroy_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        let provider_id = &deployment_id.provider_id;
+        self.simulate_latency(self.latency_for(provider_id)).await;
+
+        let mut state = self.lock();
+        state.calls.push(MockCall::Destroy {
+            provider_id: provider_id.clone(),
+        });
+
+        if let Some(deployment) = state.deployments.get_mut(provider_id) {
+            if let Some(error) = &deployment.script.destroy_error {
+                anyhow::bail!("{}", error);
+            }
+            deployment.destroyed = true;
+        }
+
+        Ok(())
+    }
+
+    async fn update_config(&self, deployment_id: &DeploymentId, config: AgentConfig) -> Result<()> {
+        let provider_id = &deployment_id.provider_id;
+        self.simulate_latency(self.latency_for(provider_id)).await;
+
+        let mut state = self.lock();
+        state.calls.push(MockCall::UpdateConfig {
+            provider_id: provider_id.clone(),
+        });
+
+        let deployment = state
+            .deployments
+            .get_mut(provider_id)
+            .filter(|deployment| !deployment.destroyed)
+            .ok_or_else(|| ResourceNotFound {
+                provider_id: provider_id.clone(),
+            })?;
+
+        if let Some(error) = &deployment.script.update_error {
+            anyhow::bail!("{}", error);
+        }
+        deployment.config = config;
+
+        Ok(())
+    }
+
+    async fn stop_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.set_stopped(deployment_id, true, |provider_id| MockCall::Stop {
+            provider_id,
+        })
+        .await
+    }
+
+    async fn start_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.set_stopped(deployment_id, false, |provider_id| MockCall::Start {
+            provider_id,
+        })
+        .await
+    }
+
+    async fn restart_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.set_stopped(deployment_id, false, |provider_id| MockCall::Restart {
+            provider_id,
+        })
+        .await
+    }
+
+    async fn create_volume(&self, _config: &AgentConfig) -> Result<String> {
+        let mut state = self.lock();
+        state.next_id += 1;
+        let volume_id = format!("mock-vol-{}", state.next_id);
+        state.calls.push(MockCall::CreateVolume {
+            volume_id: volume_id.clone(),
+        });
+        state.volumes.insert(volume_id.clone());
+        Ok(volume_id)
+    }
+
+    async fn delete_volume(&self, volume_id: &str) -> Result<()> {
+        let mut state = self.lock();
+        state.calls.push(MockCall::DeleteVolume {
+            volume_id: volume_id.to_string(),
+        });
+        state.volumes.remove(volume_id);
+        Ok(())
+    }
+
+    async fn get_logs(
+        &self,
+        deployment_id: &DeploymentId,
+        lines: Option<usize>,
+    ) -> Result<Vec<String>> {
+        let provider_id = &deployment_id.provider_id;
+        self.simulate_latency(self.latency_for(provider_id)).await;
+
+        let mut state = self.lock();
+        state.calls.push(MockCall::GetLogs {
+            provider_id: provider_id.clone(),
+        });
+
+        let logs = state
+            .deployments
+            .get(provider_id)
+            .map(|deployment| deployment.script.logs.clone())
+            .ok_or_else(|| ResourceNotFound {
+                provider_id: provider_id.clone(),
+            })?;
+
+        let limit = lines.unwrap_or(100);
+        let skip = logs.len().saturating_sub(limit);
+        Ok(logs.into_iter().skip(skip).collect())
+    }
+
+    /// Follows lines added with [`MockVpsProvider::push_logs`]; mock lines have no timestamps.
+    async fn stream_logs(
+        &self,
+        deployment_id: &DeploymentId,
+        options: LogStreamOptions,
+    ) -> Result<LogStream> {
+        let provider_id = deployment_id.provider_id.clone();
+        let start = {
+            let mut state = self.lock();
+            state.calls.push(MockCall::StreamLogs {
+                provider_id: provider_id.clone(),
+            });
+            let deployment =
+                state
+                    .deployments
+                    .get(&provider_id)
+                    .ok_or_else(|| ResourceNotFound {
+                        provider_id: provider_id.clone(),
+                    })?;
+            let total = deployment.script.logs.len();
+            total.saturating_sub(options.tail.unwrap_or(total))
+        };
+
+        let mock = self.clone();
+        Ok(poll_log_stream(
+            start,
+            options.follow,
+            Duration::from_millis(50),
+            move |offset: usize| {
+                let lines: Vec<String> = mock
+                    .lock()
+                    .deployments
+                    .get(&provider_id)
+                    .map(|deployment| {
+                        deployment
+                            .script
+                            .logs
+                            .iter()
+                            .skip(offset)
+                            .cloned()
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let next = offset + lines.len();
+                let entries = lines
+                    .into_iter()
+                    .map(|line| LogEntry::from_line(None, "mock", line))
+                    .collect();
+                async move { Ok((entries, next)) }
+            },
+        ))
+    }
+
+    fn provider_name(&self) -> &str {
+        "mock"
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.lock().capabilities.clone()
+    }
+}
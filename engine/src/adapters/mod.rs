@@ -1,5 +1,8 @@
 pub mod aws;
+pub mod docker;
 pub mod flyio;
+pub mod logs;
+pub mod mock;
 pub mod railway;
 pub mod trait_def;
 
@@ -7,13 +10,15 @@ use crate::config::Config;
 use anyhow::Result;
 use std::sync::Arc;
 
-pub use trait_def::VpsProvider;
+pub use logs::{LogEntry, LogLevel, LogStream, LogStreamOptions};
+pub use trait_def::{ProviderCapabilities, VpsProvider};
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct VpsAdapters {
     pub railway: Option<Arc<dyn VpsProvider>>,
     pub flyio: Option<Arc<dyn VpsProvider>>,
     pub aws: Option<Arc<dyn VpsProvider>>,
+    pub docker: Option<Arc<dyn VpsProvider>>,
 }
 
 impl VpsAdapters {
@@ -36,11 +41,64 @@ impl VpsAdapters {
             None
         };
 
+        let docker = if config.docker_host.is_some() {
+            Some(Arc::new(docker::DockerAdapter::new(config)?) as Arc<dyn VpsProvider>)
+        } else {
+            None
+        };
+
         Ok(VpsAdapters {
             railway,
             flyio,
             aws,
+            docker,
+        })
+    }
+
+    /// Register an adapter for a provider, replacing any configured one (e.g. a mock in tests).
+    pub fn with_provider(
+        mut self,
+        provider: crate::models::VpsProvider,
+        adapter: Arc<dyn VpsProvider>,
+    ) -> Self {
+        let slot = match provider {
+            crate::models::VpsProvider::Railway => &mut self.railway,
+            crate::models::VpsProvider::FlyIo => &mut self.flyio,
+            crate::models::VpsProvider::Aws => &mut self.aws,
+            crate::models::VpsProvider::Docker => &mut self.docker,
+        };
+        *slot = Some(adapter);
+        self
+    }
+
+    /// Capabilities of a provider that can be deployed to. Railway counts even without a
+    /// configured key, since keys may be supplied per request.
+    pub fn capabilities(
+        &self,
+        provider: crate::models::VpsProvider,
+    ) -> Option<ProviderCapabilities> {
+        match (provider, &self.railway) {
+            (crate::models::VpsProvider::Railway, None) => Some(railway::capabilities()),
+            (provider, _) => self
+                .get_provider(provider)
+                .map(|adapter| adapter.capabilities()),
+        }
+    }
+
+    /// Every provider that can be deployed to, with its capabilities.
+    pub fn available(&self) -> Vec<(crate::models::VpsProvider, ProviderCapabilities)> {
+        [
+            crate::models::VpsProvider::Railway,
+            crate::models::VpsProvider::FlyIo,
+            crate::models::VpsProvider::Aws,
+            crate::models::VpsProvider::Docker,
+        ]
+        .into_iter()
+        .filter_map(|provider| {
+            self.capabilities(provider.clone())
+                .map(|capabilities| (provider, capabilities))
         })
+        .collect()
     }
 
     pub fn get_provider(
@@ -51,6 +109,7 @@ impl VpsAdapters {
             crate::models::VpsProvider::Railway => self.railway.clone(),
             crate::models::VpsProvider::FlyIo => self.flyio.clone(),
             crate::models::VpsProvider::Aws => self.aws.clone(),
+            crate::models::VpsProvider::Docker => self.docker.clone(),
         }
     }
 }
@@ -1,8 +1,12 @@
-use crate::adapters::trait_def::{AgentConfig, DeploymentId, VpsAgentStatus, VpsProvider};
+use crate::adapters::logs::{LogEntry, LogStream, LogStreamOptions};
+use crate::adapters::trait_def::{
+    AgentConfig, DeploymentId, ProviderCapabilities, ResourceNotFound, VpsAgentStatus, VpsProvider,
+};
 use crate::config::Config;
 use crate::models::{AgentRuntime, DeploymentStatus};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
 use reqwest::Client;
 use uuid::Uuid;
 
@@ -28,6 +32,39 @@ impl RailwayAdapter {
             api_key,
         }
     }
+
+    /// POSTs a lifecycle action (`stop`, `start`, `restart`) to the deployment's service.
+    async fn service_action(&self, deployment_id: &DeploymentId, action: &str) -> Result<()> {
+        let service_id = service_id(deployment_id)?;
+
+        let response = self
+            .client
+            .post(format!(
+                "https://api.railway.app/v1/services/{}/{}",
+                service_id, action
+            ))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(ResourceNotFound {
+                provider_id: deployment_id.provider_id.clone(),
+            }
+            .into());
+        }
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            anyhow::bail!(
+                "Failed to {} Railway service {}: {}",
+                action,
+                service_id,
+                error_text
+            );
+        }
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -103,11 +140,7 @@ impl VpsProvider for RailwayAdapter {
     }
 
     async fn get_status(&self, deployment_id: &DeploymentId) -> Result<VpsAgentStatus> {
-        // Extract service ID from provider_id
-        let service_id = deployment_id
-            .provider_id
-            .strip_prefix("railway-")
-            .ok_or_else(|| anyhow::anyhow!("Invalid provider ID"))?;
+        let service_id = service_id(deployment_id)?;
 
         let response = self
             .client
@@ -119,12 +152,20 @@ impl VpsProvider for RailwayAdapter {
             .send()
             .await?;
 
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(ResourceNotFound {
+                provider_id: deployment_id.provider_id.clone(),
+            }
+            .into());
+        }
+
         let service: serde_json::Value = response.json().await?;
         let status_str = service["service"]["status"].as_str().unwrap_or("unknown");
 
         let status = match status_str {
             "DEPLOYED" | "RUNNING" => DeploymentStatus::Running,
             "DEPLOYING" | "BUILDING" => DeploymentStatus::Creating,
+            "SLEEPING" | "STOPPED" => DeploymentStatus::Stopped,
             "FAILED" | "CRASHED" => DeploymentStatus::Failed,
             _ => DeploymentStatus::Pending,
         };
@@ -140,10 +181,7 @@ impl VpsProvider for RailwayAdapter {
     }
 
     async fn destroy_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
-        let service_id = deployment_id
-            .provider_id
-            .strip_prefix("railway-")
-            .ok_or_else(|| anyhow::anyhow!("Invalid provider ID"))?;
+        let service_id = service_id(deployment_id)?;
 
         self.client
             .delete(format!(
@@ -158,10 +196,7 @@ impl VpsProvider for RailwayAdapter {
     }
 
     async fn update_config(&self, deployment_id: &DeploymentId, config: AgentConfig) -> Result<()> {
-        let service_id = deployment_id
-            .provider_id
-            .strip_prefix("railway-")
-            .ok_or_else(|| anyhow::anyhow!("Invalid provider ID"))?;
+        let service_id = service_id(deployment_id)?;
 
         // Update environment variables
         let mut env_vars_map = serde_json::Map::new();
@@ -170,7 +205,8 @@ impl VpsProvider for RailwayAdapter {
         }
         let env_vars = serde_json::Value::Object(env_vars_map);
 
-        self.client
+        let response = self
+            .client
             .post(format!(
                 "https://api.railway.app/v1/services/{}/variables",
                 service_id
@@ -181,6 +217,21 @@ impl VpsProvider for RailwayAdapter {
             .send()
             .await?;
 
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(ResourceNotFound {
+                provider_id: deployment_id.provider_id.clone(),
+            }
+            .into());
+        }
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            anyhow::bail!(
+                "Failed to update Railway service {} variables: {}",
+                service_id,
+                error_text
+            );
+        }
+
         Ok(())
     }
 
@@ -189,10 +240,7 @@ impl VpsProvider for RailwayAdapter {
         deployment_id: &DeploymentId,
         lines: Option<usize>,
     ) -> Result<Vec<String>> {
-        let service_id = deployment_id
-            .provider_id
-            .strip_prefix("railway-")
-            .ok_or_else(|| anyhow::anyhow!("Invalid provider ID"))?;
+        let service_id = service_id(deployment_id)?;
 
         let limit = lines.unwrap_or(100);
 
@@ -226,7 +274,70 @@ impl VpsProvider for RailwayAdapter {
         Ok(log_lines)
     }
 
+    async fn stream_logs(
+        &self,
+        deployment_id: &DeploymentId,
+        options: LogStreamOptions,
+    ) -> Result<LogStream> {
+        if options.follow {
+            anyhow::bail!("Railway adapter does not support following logs");
+        }
+
+        // Railway's log lines carry no timestamps, so `since` can't be applied
+        let lines = self.get_logs(deployment_id, options.tail).await?;
+        Ok(stream::iter(
+            lines
+                .into_iter()
+                .map(|line| Ok(LogEntry::from_line(None, "railway", line))),
+        )
+        .boxed())
+    }
+
+    async fn stop_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.service_action(deployment_id, "stop").await
+    }
+
+    async fn start_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.service_action(deployment_id, "start").await
+    }
+
+    async fn restart_agent(&self, deployment_id: &DeploymentId) -> Result<()> {
+        self.service_action(deployment_id, "restart").await
+    }
+
+    async fn create_volume(&self, _config: &AgentConfig) -> Result<String> {
+        anyhow::bail!("Railway adapter does not support volumes")
+    }
+
+    async fn delete_volume(&self, _volume_id: &str) -> Result<()> {
+        anyhow::bail!("Railway adapter does not support volumes")
+    }
+
     fn provider_name(&self) -> &str {
         "railway"
     }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        capabilities()
+    }
+}
+
+/// Railway keys are supplied per request, so its capabilities are needed without an adapter.
+/// Services are built from the OpenClaw template rather than our init script.
+pub fn capabilities() -> ProviderCapabilities {
+    ProviderCapabilities {
+        runtimes: vec![AgentRuntime::OpenClaw],
+        multi_agent: false,
+        volumes: false,
+        exposed_ports: true,
+        regions: Vec::new(),
+        log_streaming: false,
+    }
+}
+
+fn service_id(deployment_id: &DeploymentId) -> Result<&str> {
+    deployment_id
+        .provider_id
+        .strip_prefix("railway-")
+        .ok_or_else(|| anyhow::anyhow!("Invalid provider ID"))
 }
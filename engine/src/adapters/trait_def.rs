@@ -1,7 +1,9 @@
+use crate::adapters::logs::{LogStream, LogStreamOptions};
 use crate::models::{Agent, AgentRuntime, DeploymentStatus};
 use anyhow::Result;
 use async_trait::async_trait;
 use claws_runtime_core::RuntimeServicePort;
+use serde::Serialize;
 use std::collections::BTreeMap;
 use uuid::Uuid;
 
@@ -17,6 +19,25 @@ pub struct AgentConfig {
     pub runtime_init_script: String,
     pub runtime_env: BTreeMap<String, String>,
     pub runtime_services: Vec<RuntimeServicePort>,
+    /// Persistent volume to mount at [`AgentConfig::volume_mount_path`], if the provider supports it.
+    pub volume_id: Option<String>,
+}
+
+impl AgentConfig {
+    /// The agent's `workspace_dir` for single-agent deployments, otherwise the runtime home
+    /// that holds every agent's workspace. Machines run as root, so `~` is `/root`.
+    pub fn volume_mount_path(&self) -> String {
+        let workspace_dir = match &self.agents {
+            Some(agents) if agents.len() > 1 => None,
+            _ => self.agent.workspace_dir.as_deref(),
+        };
+        let path = workspace_dir.unwrap_or("~/.openclaw");
+
+        match path.strip_prefix("~/") {
+            Some(rest) => format!("/root/{}", rest),
+            None => path.to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -35,16 +56,82 @@ pub struct VpsAgentStatus {
     pub gateway_url: Option<String>,
 }
 
+/// What a provider can host, checked before a deployment is created.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderCapabilities {
+    pub runtimes: Vec<AgentRuntime>,
+    /// Can host several agents on one machine (`deploy-multi`).
+    pub multi_agent: bool,
+    /// Can attach persistent storage that survives redeploys.
+    pub volumes: bool,
+    /// Publishes the runtime's service ports (e.g. the OpenClaw gateway).
+    pub exposed_ports: bool,
+    /// Accepted regions; empty when the provider doesn't take one.
+    pub regions: Vec<String>,
+    pub log_streaming: bool,
+}
+
+impl ProviderCapabilities {
+    pub fn supports_runtime(&self, runtime: AgentRuntime) -> bool {
+        self.runtimes.contains(&runtime)
+    }
+
+    pub fn supports_region(&self, region: &str) -> bool {
+        self.regions.is_empty() || self.regions.iter().any(|r| r == region)
+    }
+}
+
+/// Every runtime, for providers that boot a plain Linux machine and run the init script.
+pub(crate) fn all_runtimes() -> Vec<AgentRuntime> {
+    vec![
+        AgentRuntime::OpenClaw,
+        AgentRuntime::ZeroClaw,
+        AgentRuntime::PicoClaw,
+        AgentRuntime::NanoClaw,
+    ]
+}
+
+/// Returned (via `anyhow`) when the provider no longer knows about a deployment's
+/// resource, so callers can tell a deleted machine from a transient API error.
+#[derive(Debug, thiserror::Error)]
+#[error("provider resource {provider_id} not found")]
+pub struct ResourceNotFound {
+    pub provider_id: String,
+}
+
 #[async_trait]
 pub trait VpsProvider: Send + Sync {
     async fn deploy_agent(&self, config: AgentConfig) -> Result<DeploymentId>;
     async fn get_status(&self, deployment_id: &DeploymentId) -> Result<VpsAgentStatus>;
     async fn destroy_agent(&self, deployment_id: &DeploymentId) -> Result<()>;
+    /// Pushes a new env and service config to the deployment. Unless
+    /// [`update_config_restarts`](Self::update_config_restarts), a running agent keeps its
+    /// old env until `restart_agent`.
     async fn update_config(&self, deployment_id: &DeploymentId, config: AgentConfig) -> Result<()>;
+    /// Whether `update_config` restarts the agent itself to apply the change.
+    fn update_config_restarts(&self) -> bool {
+        false
+    }
+    /// Halts the agent's machine/service while keeping it (and its config) around for `start_agent`.
+    async fn stop_agent(&self, deployment_id: &DeploymentId) -> Result<()>;
+    async fn start_agent(&self, deployment_id: &DeploymentId) -> Result<()>;
+    async fn restart_agent(&self, deployment_id: &DeploymentId) -> Result<()>;
+    /// Creates a persistent volume for `config` and returns its ID. `destroy_agent` leaves
+    /// volumes in place; only `delete_volume` removes the data.
+    async fn create_volume(&self, config: &AgentConfig) -> Result<String>;
+    async fn delete_volume(&self, volume_id: &str) -> Result<()>;
     async fn get_logs(
         &self,
         deployment_id: &DeploymentId,
         lines: Option<usize>,
     ) -> Result<Vec<String>>;
+    /// Structured log entries, oldest first. With `options.follow` the stream stays open
+    /// until dropped; providers that can't follow (see `log_streaming`) return an error.
+    async fn stream_logs(
+        &self,
+        deployment_id: &DeploymentId,
+        options: LogStreamOptions,
+    ) -> Result<LogStream>;
     fn provider_name(&self) -> &str;
+    fn capabilities(&self) -> ProviderCapabilities;
 }
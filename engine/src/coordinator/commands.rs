@@ -0,0 +1,287 @@
+use crate::models::{Task, TaskStatus};
+use crate::storage::{repositories, Database};
+use anyhow::Result;
+use uuid::Uuid;
+
+/// Command names, as in `!task-<name>` and the `/task <name>` slash command.
+pub const COMMAND_NAMES: [&str; 6] = ["complete", "claim", "progress", "fail", "cancel", "status"];
+
+/// A task command sent by an agent or operator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskCommand {
+    Complete {
+        task_id: Uuid,
+        result: String,
+    },
+    /// Start working on a pending task.
+    Claim {
+        task_id: Uuid,
+    },
+    Progress {
+        task_id: Uuid,
+        percent: u8,
+        note: Option<String>,
+    },
+    Fail {
+        task_id: Uuid,
+        reason: String,
+    },
+    Cancel {
+        task_id: Uuid,
+        reason: Option<String>,
+    },
+    Status {
+        task_id: Uuid,
+    },
+}
+
+impl TaskCommand {
+    /// Parses a `!task-<name> <task id> [arguments]` message. Returns `None` for messages
+    /// that aren't task commands, and the usage text for malformed ones.
+    pub fn parse(content: &str) -> Option<Result<Self, String>> {
+        let rest = content.trim().strip_prefix("!task-")?;
+        let (name, args) = split_word(rest);
+        let (task_id, args) = split_word(args);
+        Some(Self::new(&name.to_ascii_lowercase(), task_id, args))
+    }
+
+    /// Builds a command from its name, task id and remaining arguments, which are parsed
+    /// as in the text form (e.g. `<percent> [note]` for `progress`).
+    pub fn new(name: &str, task_id: &str, args: &str) -> Result<Self, String> {
+        if !COMMAND_NAMES.contains(&name) {
+            return Err(format!(
+                "Unknown task command. Available: {}",
+                COMMAND_NAMES
+                    .iter()
+                    .map(|name| usage(name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        let task_id = Uuid::parse_str(task_id).map_err(|_| format!("Usage: {}", usage(name)))?;
+        let args = args.trim();
+        let text = (!args.is_empty()).then(|| args.to_string());
+
+        Ok(match name {
+            "complete" => TaskCommand::Complete {
+                task_id,
+                result: text.unwrap_or_else(|| "Completed via task command".to_string()),
+            },
+            "claim" => TaskCommand::Claim { task_id },
+            "progress" => {
+                let (percent, note) = split_word(args);
+                let percent = percent
+                    .trim_end_matches('%')
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|percent| *percent <= 100)
+                    .ok_or_else(|| format!("Usage: {}", usage(name)))?;
+                TaskCommand::Progress {
+                    task_id,
+                    percent,
+                    note: (!note.is_empty()).then(|| note.to_string()),
+                }
+            }
+            "fail" => TaskCommand::Fail {
+                task_id,
+                reason: text.unwrap_or_else(|| "Failed via task command".to_string()),
+            },
+            "cancel" => TaskCommand::Cancel {
+                task_id,
+                reason: text,
+            },
+            _ => TaskCommand::Status { task_id },
+        })
+    }
+
+    pub fn task_id(&self) -> Uuid {
+        match self {
+            TaskCommand::Complete { task_id, .. }
+            | TaskCommand::Claim { task_id }
+            | TaskCommand::Progress { task_id, .. }
+            | TaskCommand::Fail { task_id, .. }
+            | TaskCommand::Cancel { task_id, .. }
+            | TaskCommand::Status { task_id } => *task_id,
+        }
+    }
+}
+
+/// Result of [`execute`], for the transport to deliver.
+#[derive(Debug, Clone)]
+pub struct CommandOutcome {
+    /// Reply to whoever sent the command.
+    pub reply: String,
+    /// Entry for the team's coordination log, when the command changed the task.
+    pub log: Option<String>,
+    /// The task after the command, if it exists.
+    pub task: Option<Task>,
+    /// The command moved the task to a final status; the coordinator should follow up with
+    /// [`crate::coordinator::Coordinator::task_finished`].
+    pub finished: bool,
+}
+
+impl CommandOutcome {
+    fn reply(reply: String, task: Option<Task>) -> Self {
+        Self {
+            reply,
+            log: None,
+            task,
+            finished: false,
+        }
+    }
+
+    fn changed(reply: String, log: String, task: Task, finished: bool) -> Self {
+        Self {
+            reply,
+            log: Some(log),
+            task: Some(task),
+            finished,
+        }
+    }
+}
+
+/// Applies the command to the task. Commands that don't fit the task's current status
+/// (e.g. claiming a completed task) change nothing and explain why in the reply.
+pub async fn execute(db: &Database, command: &TaskCommand) -> Result<CommandOutcome> {
+    let task_repo = repositories::TaskRepository::new(db.db().clone());
+    let task_id = command.task_id();
+
+    let updated = match command {
+        TaskCommand::Status { .. } => {
+            let task = task_repo.get_by_id(task_id).await?;
+            let reply = match &task {
+                Some(task) => describe(task),
+                None => format!("Task {} not found.", task_id),
+            };
+            return Ok(CommandOutcome::reply(reply, task));
+        }
+        TaskCommand::Claim { .. } => task_repo.claim(task_id).await?.map(|task| {
+            CommandOutcome::changed(
+                format!("Task {} claimed.", task_id),
+                format!("Task `{}` claimed", task_id),
+                task,
+                false,
+            )
+        }),
+        TaskCommand::Progress { percent, note, .. } => task_repo
+            .update_progress(task_id, (*percent).into(), note.as_deref())
+            .await?
+            .map(|task| {
+                let note = note
+                    .as_deref()
+                    .map(|note| format!(": {}", note))
+                    .unwrap_or_default();
+                CommandOutcome::changed(
+                    format!("Task {} progress recorded ({}%).", task_id, percent),
+                    format!("Task `{}` is {}% done{}", task_id, percent, note),
+                    task,
+                    false,
+                )
+            }),
+        TaskCommand::Complete { result, .. } => task_repo
+            .finish_open(task_id, TaskStatus::Completed, result)
+            .await?
+            .map(|task| {
+                CommandOutcome::changed(
+                    format!("Task {} marked completed.", task_id),
+                    format!("Task `{}` completed: {}", task_id, result),
+                    task,
+                    true,
+                )
+            }),
+        TaskCommand::Fail { reason, .. } => task_repo
+            .finish_open(task_id, TaskStatus::Failed, reason)
+            .await?
+            .map(|task| {
+                CommandOutcome::changed(
+                    format!("Task {} marked failed.", task_id),
+                    format!("Task `{}` failed: {}", task_id, reason),
+                    task,
+                    true,
+                )
+            }),
+        TaskCommand::Cancel { reason, .. } => {
+            let reason = reason.as_deref().unwrap_or("Cancelled via task command");
+            task_repo
+                .finish_open(task_id, TaskStatus::Cancelled, reason)
+                .await?
+                .map(|task| {
+                    CommandOutcome::changed(
+                        format!("Task {} cancelled.", task_id),
+                        format!("Task `{}` cancelled: {}", task_id, reason),
+                        task,
+                        true,
+                    )
+                })
+        }
+    };
+
+    if let Some(outcome) = updated {
+        return Ok(outcome);
+    }
+    // Nothing changed: the task doesn't exist or is in the wrong state for the command
+    let task = task_repo.get_by_id(task_id).await?;
+    let reply = match &task {
+        Some(task) => format!(
+            "Task {} is {}; nothing changed.",
+            task_id,
+            task.status.as_str()
+        ),
+        None => format!("Task {} not found.", task_id),
+    };
+    Ok(CommandOutcome::reply(reply, task))
+}
+
+/// The reply to `status`.
+pub fn describe(task: &Task) -> String {
+    let mut lines = vec![
+        format!("**Task `{}`**: {}", task.id, task.status.as_str()),
+        task.description.clone(),
+    ];
+    lines.push(match task.assigned_to {
+        Some(agent_id) => format!("Assigned to: {}", agent_id),
+        None => "Assigned to: nobody yet".to_string(),
+    });
+    if let Some(progress) = task.progress {
+        let note = task
+            .progress_note
+            .as_deref()
+            .map(|note| format!(" ({})", note))
+            .unwrap_or_default();
+        lines.push(format!("Progress: {}%{}", progress, note));
+    }
+    if task.attempts > 0 {
+        let deadline = task
+            .deadline_at
+            .map(|deadline| format!(", due {}", deadline.format("%Y-%m-%d %H:%M UTC")))
+            .unwrap_or_default();
+        lines.push(format!(
+            "Attempt {} of {}{}",
+            task.attempts, task.max_attempts, deadline
+        ));
+    }
+    if let Some(result) = &task.result {
+        lines.push(format!("Result: {}", result));
+    }
+    lines.join("\n")
+}
+
+fn usage(name: &str) -> &'static str {
+    match name {
+        "complete" => "`!task-complete <task id> [result]`",
+        "claim" => "`!task-claim <task id>`",
+        "progress" => "`!task-progress <task id> <percent> [note]`",
+        "fail" => "`!task-fail <task id> [reason]`",
+        "cancel" => "`!task-cancel <task id> [reason]`",
+        _ => "`!task-status <task id>`",
+    }
+}
+
+/// The first whitespace-separated word and the trimmed rest.
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(end) => (&text[..end], text[end..].trim()),
+        None => (text, ""),
+    }
+}
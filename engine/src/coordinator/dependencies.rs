@@ -0,0 +1,42 @@
+use crate::models::Task;
+use crate::storage::{repositories, Database};
+use anyhow::Result;
+
+/// Results of the tasks `task` depends on, formatted to append to the message that hands
+/// it to an agent. `None` when it has no dependencies.
+pub async fn upstream_context(db: &Database, task: &Task) -> Result<Option<String>> {
+    if task.depends_on.is_empty() {
+        return Ok(None);
+    }
+
+    let task_repo = repositories::TaskRepository::new(db.db().clone());
+    let upstream = task_repo.get_by_ids(&task.depends_on).await?;
+
+    // Keep the order the dependencies were declared in
+    let lines: Vec<String> = task
+        .depends_on
+        .iter()
+        .filter_map(|id| upstream.iter().find(|upstream| upstream.id == *id))
+        .map(|upstream| {
+            format!(
+                "- `{}` {}\n  Result: {}",
+                upstream.id,
+                upstream.description,
+                upstream.result.as_deref().unwrap_or("(no result)")
+            )
+        })
+        .collect();
+
+    Ok(Some(format!(
+        "**Results from upstream tasks**\n{}",
+        lines.join("\n")
+    )))
+}
+
+/// `message` followed by the upstream context, if any.
+pub fn with_context(message: String, context: Option<&str>) -> String {
+    match context {
+        Some(context) => format!("{}\n\n{}", message, context),
+        None => message,
+    }
+}
@@ -1,20 +1,57 @@
-use crate::models::TaskStatus;
+use crate::coordinator::commands::TaskCommand;
+use crate::coordinator::outbox::DiscordOutbox;
+use crate::coordinator::transport::{short_id, CommandReceiver, CoordinationTransport};
+use crate::models::{Agent, DiscordChannels, Task, Team};
 use crate::storage::{repositories, Database};
-use anyhow::Result;
-use reqwest::Client;
-use serde_json::json;
+use anyhow::{Context as _, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use reqwest::{Client, Method, StatusCode};
+use serde_json::{json, Value};
 use serenity::{
+    all::{
+        Command, CommandOptionType, CreateCommand, CreateCommandOption, CreateInteractionResponse,
+        CreateInteractionResponseMessage, Interaction, Permissions, Ready, ResolvedOption,
+        ResolvedValue, RoleId, UserId,
+    },
     client::Client as SerenityClient,
     model::channel::Message,
     prelude::{Context, EventHandler, GatewayIntents},
 };
-use uuid::Uuid;
+use std::collections::HashSet;
+
+/// Name of the slash command mirroring the `!task-` commands.
+const TASK_SLASH_COMMAND: &str = "task";
+
+/// Channel types.
+const GUILD_TEXT: u8 = 0;
+const GUILD_CATEGORY: u8 = 4;
+const PUBLIC_THREAD: u8 = 11;
+/// Permission overwrite targets.
+const OVERWRITE_ROLE: u8 = 0;
+const OVERWRITE_MEMBER: u8 = 1;
+/// Discord's limits on thread and channel names.
+const MAX_THREAD_NAME_LEN: usize = 100;
+const MAX_CHANNEL_NAME_LEN: usize = 100;
+/// Minutes of inactivity before Discord hides a task thread; archiving it when the task
+/// finishes is what normally closes it.
+const THREAD_AUTO_ARCHIVE_MINUTES: u32 = 10080;
+
+#[derive(Debug, Clone)]
+pub struct DiscordConfig {
+    pub bot_token: String,
+    /// REST API base URL, e.g. `https://discord.com/api/v10`.
+    pub api_url: String,
+}
 
 #[derive(Clone)]
 pub struct DiscordClient {
     token: String,
-    http_client: Client,
+    api_url: String,
     db: Database,
+    http_client: Client,
+    outbox: DiscordOutbox,
 }
 
 #[derive(Debug, Clone)]
@@ -25,35 +62,30 @@ pub enum ChannelType {
 }
 
 impl DiscordClient {
-    pub async fn new(token: String, db: Database) -> Result<Self> {
+    pub async fn new(db: Database, config: DiscordConfig) -> Result<Self> {
+        let http_client = Client::new();
+        let outbox = DiscordOutbox::new(
+            db.clone(),
+            http_client.clone(),
+            config.bot_token.clone(),
+            config.api_url.clone(),
+        );
         Ok(Self {
-            token,
-            http_client: Client::new(),
+            token: config.bot_token,
+            api_url: config.api_url,
             db,
+            http_client,
+            outbox,
         })
     }
 
-    pub async fn start(&self) -> Result<()> {
-        // Start gateway for inbound message handling while keeping HTTP for outbound messages.
-        let intents = GatewayIntents::GUILD_MESSAGES
-            | GatewayIntents::DIRECT_MESSAGES
-            | GatewayIntents::MESSAGE_CONTENT;
-        let handler = DiscordEventHandler {
+    /// Handles the task commands the gateway delivers, running them through `receiver`.
+    pub fn command_handler(&self, receiver: CommandReceiver) -> DiscordEventHandler {
+        DiscordEventHandler {
             db: self.db.clone(),
-        };
-
-        let mut client = SerenityClient::builder(&self.token, intents)
-            .event_handler(handler)
-            .await?;
-
-        tokio::spawn(async move {
-            if let Err(error) = client.start().await {
-                tracing::error!("Discord gateway error: {}", error);
-            }
-        });
-
-        tracing::info!("Discord client initialized with gateway and HTTP API");
-        Ok(())
+            client: self.clone(),
+            receiver,
+        }
     }
 
     pub async fn send_message(&self, channel_id: u64, message: &str) -> Result<()> {
@@ -61,40 +93,17 @@ impl DiscordClient {
             .await
     }
 
+    /// Queues the message in the outbox, which delivers it in the background with
+    /// rate limiting and retries. Only fails if the message couldn't be queued.
     pub async fn send_message_with_embed(
         &self,
         channel_id: u64,
         message: &str,
         embed: Option<serde_json::Value>,
     ) -> Result<()> {
-        let url = format!(
-            "https://discord.com/api/v10/channels/{}/messages",
-            channel_id
-        );
-
-        let mut payload = json!({
-            "content": message
-        });
-
-        if let Some(embed_data) = embed {
-            payload["embeds"] = json!([embed_data]);
-        }
-
-        let response = self
-            .http_client
-            .post(&url)
-            .header("Authorization", format!("Bot {}", self.token))
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("Failed to send Discord message: {}", error_text);
-        }
-
-        Ok(())
+        self.outbox
+            .enqueue(channel_id, message, embed.as_ref())
+            .await
     }
 
     pub async fn send_to_channel_type(
@@ -131,61 +140,682 @@ impl DiscordClient {
         self.send_to_channel_type(channel_id, ChannelType::MasterOrders, message)
             .await
     }
+
+    /// Creates a public thread in the channel and returns its id. Unlike messages this
+    /// isn't queued, since the caller needs the id right away.
+    pub async fn create_thread(&self, channel_id: &str, name: &str) -> Result<String> {
+        let name: String = name.chars().take(MAX_THREAD_NAME_LEN).collect();
+        let body = json!({
+            "name": name,
+            "type": PUBLIC_THREAD,
+            "auto_archive_duration": THREAD_AUTO_ARCHIVE_MINUTES,
+        });
+        let thread = self
+            .api(
+                Method::POST,
+                &format!("/channels/{}/threads", channel_id),
+                Some(&body),
+            )
+            .await?
+            .with_context(|| format!("Discord channel {} not found", channel_id))?;
+        created_id(&thread)
+    }
+
+    /// Queues archiving the thread after the messages already queued for it.
+    pub async fn archive_thread(&self, thread_id: &str) -> Result<()> {
+        let thread_id_u64: u64 = thread_id
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid thread ID: {}", thread_id))?;
+        self.outbox.enqueue_archive(thread_id_u64).await
+    }
+
+    /// Creates a private category named after the team holding its three coordination
+    /// channels, visible only to the orchestrator's bot, the agents' bots and the team's
+    /// operator roles. Agents may post everywhere but the coordination log, which the
+    /// orchestrator writes. If a step fails, the channels created so far are deleted.
+    pub async fn provision_team_channels(
+        &self,
+        guild_id: &str,
+        team_name: &str,
+        agents: &[Agent],
+        operator_role_ids: &[String],
+    ) -> Result<ProvisionedChannels> {
+        let access = TeamAccess {
+            everyone_role_id: guild_id.to_string(),
+            orchestrator_id: bot_user_id(&self.token)
+                .context("the Discord bot token doesn't contain a user id")?,
+            agent_bot_ids: agents
+                .iter()
+                .filter_map(|agent| agent.discord_bot_token.as_deref())
+                .filter_map(bot_user_id)
+                .collect(),
+            operator_role_ids: operator_role_ids.to_vec(),
+        };
+
+        let category = json!({
+            "name": team_name.chars().take(MAX_CHANNEL_NAME_LEN).collect::<String>(),
+            "type": GUILD_CATEGORY,
+            "permission_overwrites": access.overwrites(true),
+        });
+        let category_id = self.create_channel(guild_id, &category).await?;
+
+        let mut created = vec![category_id.clone()];
+        let channels = self
+            .create_team_channels(guild_id, &category_id, &access, &mut created)
+            .await;
+        match channels {
+            Ok(channels) => Ok(ProvisionedChannels {
+                category_id,
+                channels,
+            }),
+            Err(e) => {
+                // Children first, so the category is empty when it goes
+                created.reverse();
+                if let Err(cleanup) = self.delete_channels(&created).await {
+                    tracing::warn!(guild_id, error = %cleanup, "failed to remove partly provisioned team channels");
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn create_team_channels(
+        &self,
+        guild_id: &str,
+        category_id: &str,
+        access: &TeamAccess,
+        created: &mut Vec<String>,
+    ) -> Result<DiscordChannels> {
+        let create = |name: &'static str, agents_post: bool| {
+            let body = json!({
+                "name": name,
+                "type": GUILD_TEXT,
+                "parent_id": category_id,
+                "permission_overwrites": access.overwrites(agents_post),
+            });
+            async move { self.create_channel(guild_id, &body).await }
+        };
+        let coordination_logs = create("coordination-logs", false).await?;
+        created.push(coordination_logs.clone());
+        let slave_communication = create("slave-communication", true).await?;
+        created.push(slave_communication.clone());
+        let master_orders = create("master-orders", true).await?;
+        created.push(master_orders.clone());
+
+        Ok(DiscordChannels {
+            coordination_logs,
+            slave_communication,
+            master_orders,
+        })
+    }
+
+    /// Lets an agent added to a team with provisioned channels see and use them.
+    pub async fn grant_team_access(&self, team: &Team, agent: &Agent) -> Result<()> {
+        let Some(category_id) = &team.discord_category_id else {
+            return Ok(());
+        };
+        let Some(agent_bot_id) = agent.discord_bot_token.as_deref().and_then(bot_user_id) else {
+            return Ok(());
+        };
+        let channels = &team.discord_channels;
+        for (channel_id, agents_post) in [
+            (category_id, true),
+            (&channels.coordination_logs, false),
+            (&channels.slave_communication, true),
+            (&channels.master_orders, true),
+        ] {
+            let (allow, deny) = agent_permissions(agents_post);
+            let body = json!({
+                "type": OVERWRITE_MEMBER,
+                "allow": allow.bits().to_string(),
+                "deny": deny.bits().to_string(),
+            });
+            self.api(
+                Method::PUT,
+                &format!("/channels/{}/permissions/{}", channel_id, agent_bot_id),
+                Some(&body),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the channels and category provisioned for the team. Channels that are
+    /// already gone are skipped.
+    pub async fn delete_team_channels(&self, team: &Team) -> Result<()> {
+        let Some(category_id) = &team.discord_category_id else {
+            return Ok(());
+        };
+        let channels = &team.discord_channels;
+        self.delete_channels(&[
+            channels.coordination_logs.clone(),
+            channels.slave_communication.clone(),
+            channels.master_orders.clone(),
+            category_id.clone(),
+        ])
+        .await
+    }
+
+    async fn create_channel(&self, guild_id: &str, body: &Value) -> Result<String> {
+        let channel = self
+            .api(
+                Method::POST,
+                &format!("/guilds/{}/channels", guild_id),
+                Some(body),
+            )
+            .await?
+            .with_context(|| format!("Discord guild {} not found", guild_id))?;
+        created_id(&channel)
+    }
+
+    /// Deletes every channel it can, in order, and returns the first failure.
+    async fn delete_channels(&self, channel_ids: &[String]) -> Result<()> {
+        let mut first_error = None;
+        let mut deleted = HashSet::new();
+        for channel_id in channel_ids {
+            if !deleted.insert(channel_id) {
+                continue;
+            }
+            let path = format!("/channels/{}", channel_id);
+            if let Err(e) = self.api(Method::DELETE, &path, None).await {
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Makes a Discord REST call and returns the response body, or `None` if the resource
+    /// doesn't exist.
+    async fn api(&self, method: Method, path: &str, body: Option<&Value>) -> Result<Option<Value>> {
+        let mut request = self
+            .http_client
+            .request(method, format!("{}{}", self.api_url, path))
+            .header("Authorization", format!("Bot {}", self.token));
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let text = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            anyhow::bail!("Discord returned {}: {}", status, text);
+        }
+        if text.is_empty() {
+            return Ok(Some(Value::Null));
+        }
+        Ok(Some(serde_json::from_str(&text)?))
+    }
+}
+
+/// Channels created for a team by [`DiscordClient::provision_team_channels`].
+#[derive(Debug, Clone)]
+pub struct ProvisionedChannels {
+    pub category_id: String,
+    pub channels: DiscordChannels,
+}
+
+/// Who may see a team's provisioned channels.
+struct TeamAccess {
+    /// The @everyone role shares the guild's id.
+    everyone_role_id: String,
+    orchestrator_id: UserId,
+    agent_bot_ids: Vec<UserId>,
+    operator_role_ids: Vec<String>,
+}
+
+impl TeamAccess {
+    fn overwrites(&self, agents_post: bool) -> Value {
+        let talk = Permissions::VIEW_CHANNEL
+            | Permissions::READ_MESSAGE_HISTORY
+            | Permissions::SEND_MESSAGES
+            | Permissions::SEND_MESSAGES_IN_THREADS;
+        let orchestrator = talk
+            | Permissions::EMBED_LINKS
+            | Permissions::ATTACH_FILES
+            | Permissions::CREATE_PUBLIC_THREADS
+            | Permissions::MANAGE_THREADS;
+        let (agent_allow, agent_deny) = agent_permissions(agents_post);
+
+        let overwrite = |id: String, kind: u8, allow: Permissions, deny: Permissions| {
+            json!({
+                "id": id,
+                "type": kind,
+                "allow": allow.bits().to_string(),
+                "deny": deny.bits().to_string(),
+            })
+        };
+        let mut overwrites = vec![
+            overwrite(
+                self.everyone_role_id.clone(),
+                OVERWRITE_ROLE,
+                Permissions::empty(),
+                Permissions::VIEW_CHANNEL,
+            ),
+            overwrite(
+                self.orchestrator_id.to_string(),
+                OVERWRITE_MEMBER,
+                orchestrator,
+                Permissions::empty(),
+            ),
+        ];
+        overwrites.extend(self.agent_bot_ids.iter().map(|bot_id| {
+            overwrite(
+                bot_id.to_string(),
+                OVERWRITE_MEMBER,
+                agent_allow,
+                agent_deny,
+            )
+        }));
+        overwrites.extend(
+            self.operator_role_ids.iter().map(|role_id| {
+                overwrite(role_id.clone(), OVERWRITE_ROLE, talk, Permissions::empty())
+            }),
+        );
+        Value::Array(overwrites)
+    }
 }
 
-struct DiscordEventHandler {
+/// What an agent's bot is allowed and denied in a team channel.
+fn agent_permissions(agents_post: bool) -> (Permissions, Permissions) {
+    let read = Permissions::VIEW_CHANNEL | Permissions::READ_MESSAGE_HISTORY;
+    let post = Permissions::SEND_MESSAGES
+        | Permissions::SEND_MESSAGES_IN_THREADS
+        | Permissions::EMBED_LINKS
+        | Permissions::ATTACH_FILES;
+    if agents_post {
+        (read | post, Permissions::empty())
+    } else {
+        (read, post)
+    }
+}
+
+fn created_id(created: &Value) -> Result<String> {
+    created["id"]
+        .as_str()
+        .map(str::to_string)
+        .context("Discord returned no id for the created channel")
+}
+
+#[async_trait]
+impl CoordinationTransport for DiscordClient {
+    /// Task commands are accepted as `!task-` messages and the `/task` slash command.
+    async fn start(&self, receiver: CommandReceiver) -> Result<()> {
+        self.outbox.clone().start();
+
+        // Start gateway for inbound message handling while keeping HTTP for outbound messages.
+        let intents = GatewayIntents::GUILD_MESSAGES
+            | GatewayIntents::DIRECT_MESSAGES
+            | GatewayIntents::MESSAGE_CONTENT;
+        let handler = self.command_handler(receiver);
+
+        let mut client = SerenityClient::builder(&self.token, intents)
+            .event_handler(handler)
+            .await?;
+
+        tokio::spawn(async move {
+            if let Err(error) = client.start().await {
+                tracing::error!("Discord gateway error: {}", error);
+            }
+        });
+
+        tracing::info!("Discord client initialized with gateway and HTTP API");
+        Ok(())
+    }
+
+    async fn send_order(&self, team: &Team, task: &Task, message: &str) -> Result<()> {
+        self.send_master_order(
+            task_channel(task, &team.discord_channels.master_orders),
+            message,
+        )
+        .await
+    }
+
+    async fn send_slave_message(&self, team: &Team, task: &Task, message: &str) -> Result<()> {
+        self.send_slave_message(
+            task_channel(task, &team.discord_channels.slave_communication),
+            message,
+        )
+        .await
+    }
+
+    async fn log(&self, team: &Team, task: Option<&Task>, message: &str) -> Result<()> {
+        let channel_id = &team.discord_channels.coordination_logs;
+        let channel_id = task.map_or(channel_id.as_str(), |task| task_channel(task, channel_id));
+        self.log_coordination(channel_id, message).await
+    }
+
+    /// Threads are opened in `master_orders`.
+    async fn open_thread(&self, team: &Team, task: &Task) -> Result<Option<String>> {
+        let name = format!("Task {}: {}", short_id(task.id), task.description);
+        let thread_id = self
+            .create_thread(&team.discord_channels.master_orders, &name)
+            .await?;
+        Ok(Some(thread_id))
+    }
+
+    async fn close_thread(&self, _team: &Team, task: &Task) -> Result<()> {
+        let Some(thread_id) = &task.thread_id else {
+            return Ok(());
+        };
+        let message = format!(
+            "**Task {}**\nTask ID: `{}`\n{}",
+            task.status.as_str(),
+            task.id,
+            task.result.as_deref().unwrap_or("No result.")
+        );
+        self.send_master_order(thread_id, &message).await?;
+        self.archive_thread(thread_id).await
+    }
+}
+
+/// Where a task's messages go: its thread if it has one, otherwise `channel_id`.
+pub fn task_channel<'a>(task: &'a Task, channel_id: &'a str) -> &'a str {
+    task.thread_id.as_deref().unwrap_or(channel_id)
+}
+
+pub struct DiscordEventHandler {
     db: Database,
+    client: DiscordClient,
+    receiver: CommandReceiver,
+}
+
+impl DiscordEventHandler {
+    /// Authorizes and runs a parsed command, returning the reply. Returns `None` when the
+    /// command came from outside the task's team channels, which is ignored rather than
+    /// answered.
+    pub async fn run(
+        &self,
+        command: Result<TaskCommand, String>,
+        sender: &CommandSender,
+    ) -> Option<String> {
+        let command = match command {
+            Ok(command) => command,
+            Err(usage) => return Some(usage),
+        };
+
+        match self.authorize(&command, sender).await {
+            Ok(Authorization::Allowed) => {}
+            Ok(Authorization::Denied(reason)) => {
+                tracing::warn!(
+                    user_id = %sender.user_id,
+                    channel_id = %sender.channel_id,
+                    task_id = %command.task_id(),
+                    "rejected task command: {}",
+                    reason
+                );
+                return Some(format!("Not allowed: {}.", reason));
+            }
+            Ok(Authorization::Ignored(reason)) => {
+                tracing::warn!(
+                    user_id = %sender.user_id,
+                    channel_id = %sender.channel_id,
+                    task_id = %command.task_id(),
+                    "ignored task command: {}",
+                    reason
+                );
+                return None;
+            }
+            Err(error) => {
+                tracing::error!("Failed authorizing {:?}: {}", command, error);
+                return Some("Failed to update task status.".to_string());
+            }
+        }
+        Some(self.execute(command, sender).await)
+    }
+
+    /// Commands must come from one of the team's channels or the task's thread. Anyone
+    /// there may ask for a task's status; changing a task takes the bot of the agent it is
+    /// assigned to or one of the team's operator roles.
+    async fn authorize(
+        &self,
+        command: &TaskCommand,
+        sender: &CommandSender,
+    ) -> Result<Authorization> {
+        let task_repo = repositories::TaskRepository::new(self.db.db().clone());
+        let Some(task) = task_repo.get_by_id(command.task_id()).await? else {
+            return Ok(Authorization::Ignored("unknown task".to_string()));
+        };
+        let team_repo = repositories::TeamRepository::new(self.db.db().clone());
+        let Some(team) = team_repo.get_by_id(task.team_id).await? else {
+            return Ok(Authorization::Ignored("task has no team".to_string()));
+        };
+
+        let channel_id = sender.channel_id.to_string();
+        let channels = [
+            &team.discord_channels.coordination_logs,
+            &team.discord_channels.slave_communication,
+            &team.discord_channels.master_orders,
+            &team.discord_channel_id,
+        ];
+        if !channels.iter().any(|channel| **channel == channel_id)
+            && task.thread_id.as_ref() != Some(&channel_id)
+        {
+            return Ok(Authorization::Ignored(
+                "not one of the team's channels".to_string(),
+            ));
+        }
+
+        if matches!(command, TaskCommand::Status { .. }) {
+            return Ok(Authorization::Allowed);
+        }
+        if sender.role_ids.iter().any(|role_id| {
+            team.settings
+                .operator_role_ids
+                .contains(&role_id.to_string())
+        }) {
+            return Ok(Authorization::Allowed);
+        }
+
+        let Some(agent_id) = task.assigned_to else {
+            return Ok(Authorization::Denied(
+                "only operators can act on unassigned tasks".to_string(),
+            ));
+        };
+        let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
+        let bot_user_id = agent_repo
+            .get_by_id(agent_id)
+            .await?
+            .and_then(|agent| agent.discord_bot_token)
+            .and_then(|token| bot_user_id(&token));
+        if bot_user_id == Some(sender.user_id) {
+            Ok(Authorization::Allowed)
+        } else {
+            Ok(Authorization::Denied(format!(
+                "task {} is assigned to another agent",
+                task.id
+            )))
+        }
+    }
+
+    /// Runs an authorized command, posts its log entry and returns the reply. The entry goes
+    /// to the task's thread, where there is one, unless the command was sent there already.
+    async fn execute(&self, command: TaskCommand, sender: &CommandSender) -> String {
+        let outcome = match self.receiver.execute(&command).await {
+            Ok(outcome) => outcome,
+            Err(error) => {
+                tracing::error!("Failed running {:?}: {}", command, error);
+                return "Failed to update task status.".to_string();
+            }
+        };
+
+        if let (Some(log), Some(task)) = (&outcome.log, &outcome.task) {
+            let sent_in_thread = task.thread_id == Some(sender.channel_id.to_string());
+            if !sent_in_thread {
+                if let Err(error) = self.log_to_team(task, log).await {
+                    tracing::warn!(task_id = %task.id, error = %error, "failed to log task command");
+                }
+            }
+        }
+        outcome.reply
+    }
+
+    async fn log_to_team(&self, task: &Task, message: &str) -> Result<()> {
+        let team_repo = repositories::TeamRepository::new(self.db.db().clone());
+        let Some(team) = team_repo.get_by_id(task.team_id).await? else {
+            return Ok(());
+        };
+        self.client
+            .log_coordination(
+                task_channel(task, &team.discord_channels.coordination_logs),
+                message,
+            )
+            .await
+    }
 }
 
 #[serenity::async_trait]
 impl EventHandler for DiscordEventHandler {
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        match Command::set_global_commands(&ctx.http, vec![task_slash_command()]).await {
+            Ok(_) => tracing::info!("Registered /{} for {}", TASK_SLASH_COMMAND, ready.user.name),
+            Err(error) => tracing::warn!("Failed registering slash commands: {}", error),
+        }
+    }
+
     async fn message(&self, ctx: Context, msg: Message) {
-        if msg.author.bot {
+        // Agents are bots too, so only our own messages are skipped
+        if msg.author.id == ctx.cache.current_user().id {
             return;
         }
 
-        if let Some((task_id, result)) = parse_task_complete(&msg.content) {
-            let repo = repositories::TaskRepository::new(self.db.db().clone());
-            let update = repo
-                .update_fields(task_id, Some(TaskStatus::Completed), Some(result.clone()))
-                .await;
-
-            match update {
-                Ok(_) => {
-                    let _ = msg
-                        .channel_id
-                        .say(&ctx.http, format!("Task {} marked completed.", task_id))
-                        .await;
-                }
-                Err(error) => {
-                    tracing::error!("Failed updating task {}: {}", task_id, error);
-                    let _ = msg
-                        .channel_id
-                        .say(&ctx.http, "Failed to update task status.")
-                        .await;
-                }
+        if let Some(command) = TaskCommand::parse(&msg.content) {
+            let sender = CommandSender {
+                user_id: msg.author.id,
+                channel_id: msg.channel_id.get(),
+                role_ids: msg
+                    .member
+                    .as_ref()
+                    .map(|member| member.roles.clone())
+                    .unwrap_or_default(),
+            };
+            if let Some(reply) = self.run(command, &sender).await {
+                let _ = msg.channel_id.say(&ctx.http, reply).await;
             }
         }
     }
-}
 
-fn parse_task_complete(content: &str) -> Option<(Uuid, String)> {
-    let trimmed = content.trim();
-    if !trimmed.starts_with("!task-complete") {
-        return None;
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let Interaction::Command(command) = interaction else {
+            return;
+        };
+        if command.data.name != TASK_SLASH_COMMAND {
+            return;
+        }
+
+        let sender = CommandSender {
+            user_id: command.user.id,
+            channel_id: command.channel_id.get(),
+            role_ids: command
+                .member
+                .as_ref()
+                .map(|member| member.roles.clone())
+                .unwrap_or_default(),
+        };
+        // Interactions must be answered, so ignored commands get a generic reply
+        let reply = self
+            .run(parse_slash_command(&command.data.options()), &sender)
+            .await
+            .unwrap_or_else(|| "This command can't be used here.".to_string());
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new().content(reply),
+        );
+        if let Err(error) = command.create_response(&ctx.http, response).await {
+            tracing::warn!("Failed responding to /{}: {}", TASK_SLASH_COMMAND, error);
+        }
     }
+}
+
+/// Who sent a task command, and where.
+pub struct CommandSender {
+    pub user_id: UserId,
+    pub channel_id: u64,
+    pub role_ids: Vec<RoleId>,
+}
 
-    let mut parts = trimmed.splitn(3, ' ');
-    let _ = parts.next();
-    let task_id = parts.next()?;
-    let result = parts.next().unwrap_or("").trim();
+enum Authorization {
+    Allowed,
+    /// Rejected with a reply explaining why.
+    Denied(String),
+    /// Rejected silently, e.g. commands from channels outside the team.
+    Ignored(String),
+}
 
-    let task_id = Uuid::parse_str(task_id).ok()?;
-    let result = if result.is_empty() {
-        "Completed via Discord".to_string()
-    } else {
-        result.to_string()
+/// The bot user a token belongs to: its first segment is the base64-encoded user id.
+fn bot_user_id(token: &str) -> Option<UserId> {
+    let encoded = token.split('.').next()?.trim_end_matches('=');
+    let decoded = STANDARD_NO_PAD
+        .decode(encoded)
+        .or_else(|_| URL_SAFE_NO_PAD.decode(encoded))
+        .ok()?;
+    let id: u64 = String::from_utf8(decoded).ok()?.parse().ok()?;
+    (id != 0).then(|| UserId::new(id))
+}
+
+/// `/task <command>` with the same subcommands and arguments as the `!task-` commands.
+fn task_slash_command() -> CreateCommand {
+    let task_id = || {
+        CreateCommandOption::new(CommandOptionType::String, "task_id", "ID of the task")
+            .required(true)
+    };
+    let text = |name: &str, description: &str| {
+        CreateCommandOption::new(CommandOptionType::String, name, description)
+    };
+    let subcommand = |name: &str, description: &str| {
+        CreateCommandOption::new(CommandOptionType::SubCommand, name, description)
+            .add_sub_option(task_id())
+    };
+
+    CreateCommand::new(TASK_SLASH_COMMAND)
+        .description("Report on or manage a task")
+        .add_option(
+            subcommand("complete", "Mark a task completed")
+                .add_sub_option(text("result", "What was done")),
+        )
+        .add_option(subcommand("claim", "Start working on a pending task"))
+        .add_option(
+            subcommand("progress", "Report progress on a task")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::Integer, "percent", "Percent done")
+                        .min_int_value(0)
+                        .max_int_value(100)
+                        .required(true),
+                )
+                .add_sub_option(text("note", "What is happening")),
+        )
+        .add_option(
+            subcommand("fail", "Mark a task failed")
+                .add_sub_option(text("reason", "Why it failed")),
+        )
+        .add_option(subcommand("cancel", "Cancel a task").add_sub_option(text("reason", "Why")))
+        .add_option(subcommand("status", "Show a task's status"))
+}
+
+fn parse_slash_command(options: &[ResolvedOption<'_>]) -> Result<TaskCommand, String> {
+    let Some((name, options)) = options.first().and_then(|option| match &option.value {
+        ResolvedValue::SubCommand(options) => Some((option.name, options)),
+        _ => None,
+    }) else {
+        return TaskCommand::new("", "", "");
     };
 
-    Some((task_id, result))
+    let mut task_id = "";
+    let mut percent = None;
+    let mut text = "";
+    for option in options {
+        match (option.name, &option.value) {
+            ("task_id", ResolvedValue::String(value)) => task_id = value,
+            ("percent", ResolvedValue::Integer(value)) => percent = Some(*value),
+            (_, ResolvedValue::String(value)) => text = value,
+            _ => {}
+        }
+    }
+
+    let args = match percent {
+        Some(percent) => format!("{} {}", percent, text),
+        None => text.to_string(),
+    };
+    TaskCommand::new(name, task_id, &args)
 }
@@ -0,0 +1,42 @@
+use crate::coordinator::Coordinator;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Periodically dispatches work that became ready without going through
+/// [`Coordinator::task_finished`]: tasks whose follow-up failed, and queued subtasks for
+/// slaves that came back up or had their limit raised.
+#[derive(Clone)]
+pub struct TaskDispatcher {
+    coordinator: Coordinator,
+    interval: Duration,
+}
+
+impl TaskDispatcher {
+    pub fn new(coordinator: Coordinator) -> Self {
+        Self {
+            coordinator,
+            interval: Duration::from_secs(15),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn start(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                ticker.tick().await;
+                match self.coordinator.dispatch_pending().await {
+                    Ok(0) => {}
+                    Ok(dispatched) => tracing::info!(dispatched, "dispatched pending tasks"),
+                    Err(e) => tracing::warn!(error = %e, "task dispatch failed"),
+                }
+            }
+        })
+    }
+}
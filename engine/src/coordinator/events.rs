@@ -0,0 +1,42 @@
+use crate::models::Task;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber; a subscriber that falls further behind skips ahead.
+const EVENT_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CoordinationEvent {
+    /// Every subtask of `task` reached a final status and their results were combined into
+    /// the task's, which is now completed or failed.
+    TaskAggregated { task: Task, subtasks: usize },
+}
+
+/// Fan-out of [`CoordinationEvent`]s to whoever subscribed; events published while nobody
+/// is listening are dropped.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<CoordinationEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: CoordinationEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<CoordinationEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
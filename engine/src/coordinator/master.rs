@@ -1,119 +1,396 @@
-use crate::coordinator::discord::DiscordClient;
+use crate::coordinator::planner::{self, PlannedSubtask};
+use crate::coordinator::routing::{self, RoutingCandidate, RoutingDecision};
+use crate::coordinator::transport::Transports;
+use crate::coordinator::{dependencies, log_undelivered};
 use crate::models::{Task, TaskStatus, Team};
 use crate::storage::{repositories, Database};
 use anyhow::Result;
+use reqwest::Client;
+use std::time::Duration;
 use uuid::Uuid;
 
 #[derive(Clone)]
 pub struct MasterCoordinator {
     db: Database,
-    discord_client: Option<DiscordClient>,
+    transports: Transports,
+    /// Shared by model-backed planners.
+    http: Client,
 }
 
 impl MasterCoordinator {
-    pub fn new(db: Database, discord_client: Option<DiscordClient>) -> Self {
-        Self { db, discord_client }
+    pub fn new(db: Database, transports: Transports) -> Self {
+        let http = Client::builder()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .unwrap_or_default();
+        Self {
+            db,
+            transports,
+            http,
+        }
     }
 
+    /// Splits the task with the team's planner and routes each subtask to the available
+    /// slave whose skills fit best (see [`routing::route_subtask`]); subtasks no slave can
+    /// take yet are queued. If the planner fails, the task is left undivided for the master.
+    /// Subtasks inherit the task's dependencies, so their messages carry the upstream results,
+    /// and its thread, so the whole conversation stays in one place.
     pub async fn delegate_task(&self, team: &Team, task: &Task) -> Result<Vec<Task>> {
-        // Simple task breakdown: split by sentences and assign to different slaves
-        let sentences: Vec<&str> = task
-            .description
-            .split('.')
-            .filter(|s| !s.trim().is_empty())
-            .collect();
-
-        // Send master order to Discord
-        if let Some(discord) = &self.discord_client {
-            let order_message = format!(
-                "**New Task Assigned**\nTask ID: `{}`\n{}\n\nReply with `!task-complete {}` and your result when finished.",
-                task.id,
-                task.description,
-                task.id
-            );
-            discord
-                .send_master_order(&team.discord_channels.master_orders, &order_message)
-                .await?;
-        }
+        let planned = self.plan_subtasks(team, task).await;
+        let thread_id = self.open_thread(team, task).await;
+        let task = &Task {
+            thread_id: thread_id.clone(),
+            ..task.clone()
+        };
+        log_undelivered(task.id, self.send_order(team, task).await);
 
         let task_repo = repositories::TaskRepository::new(self.db.db().clone());
         let mut subtasks = Vec::new();
 
-        // If we have slave agents, delegate subtasks
-        if !team.slave_ids.is_empty() && sentences.len() > 1 {
-            // Distribute sentences across slaves
-            for (idx, sentence) in sentences.iter().enumerate() {
-                let slave_idx = idx % team.slave_ids.len();
-                let slave_id = team.slave_ids[slave_idx];
+        // If we have slave agents, delegate subtasks; ones no slave can take yet are queued
+        // until `dispatch_queued` finds room
+        if !team.slave_ids.is_empty() && planned.len() > 1 {
+            let mut candidates = self.routing_candidates(team).await?;
+            for planned in &planned {
+                let decision = routing::route_subtask(planned, &candidates);
+                let routing_reason = match &decision {
+                    Some(decision) => decision.reason.clone(),
+                    None => routing::queued_reason(&candidates),
+                };
 
                 // Create subtask for slave
                 let subtask = Task {
                     id: Uuid::new_v4(),
                     team_id: team.id,
                     parent_task_id: Some(task.id),
-                    assigned_to: Some(slave_id),
+                    assigned_to: decision.map(|decision| decision.agent_id),
                     status: TaskStatus::Pending,
-                    description: format!("Subtask: {}", sentence.trim()),
+                    description: format!("Subtask: {}", planned.description),
                     result: None,
+                    routing_reason: Some(routing_reason),
+                    required_skills: planned.skills.clone(),
+                    depends_on: task.depends_on.clone(),
+                    timeout_secs: task.timeout_secs,
+                    max_attempts: task.max_attempts,
+                    attempts: 0,
+                    deadline_at: None,
+                    progress: None,
+                    progress_note: None,
+                    thread_id: thread_id.clone(),
                     created_at: chrono::Utc::now(),
                     updated_at: chrono::Utc::now(),
                 };
 
                 task_repo.create(&subtask).await?;
-                subtasks.push(subtask.clone());
-
-                // Notify slave via Discord
-                if let Some(discord) = &self.discord_client {
-                    let slave_message = format!(
-                        "**Subtask for Slave {}**\nTask ID: `{}`\n{}\n\nReply with `!task-complete {}` and your result when finished.",
-                        slave_id, subtask.id, subtask.description, subtask.id
-                    );
-                    discord
-                        .send_slave_message(
-                            &team.discord_channels.slave_communication,
-                            &slave_message,
-                        )
-                        .await?;
-                }
+                let subtask = match subtask.assigned_to {
+                    Some(slave_id) => {
+                        claim_slot(&mut candidates, slave_id);
+                        let subtask = task_repo
+                            .start_attempt(subtask.id, Some(slave_id))
+                            .await?
+                            .unwrap_or(subtask);
+                        log_undelivered(subtask.id, self.notify_slave(team, &subtask).await);
+                        subtask
+                    }
+                    None => subtask,
+                };
+                subtasks.push(subtask);
             }
         }
 
         Ok(subtasks)
     }
 
-    #[allow(dead_code)]
-    pub async fn aggregate_results(&self, task: &Task, team: &Team) -> Result<String> {
+    /// Assigns the team's queued subtasks, oldest first, to slaves that are running and have
+    /// spare capacity. Returns the subtasks that were assigned.
+    pub async fn dispatch_queued(&self, team: &Team) -> Result<Vec<Task>> {
+        let task_repo = repositories::TaskRepository::new(self.db.db().clone());
+        let queued = task_repo.list_queued(team.id).await?;
+        if queued.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut candidates = self.routing_candidates(team).await?;
+        let mut dispatched = Vec::new();
+        for task in queued {
+            let planned = PlannedSubtask {
+                description: task.description,
+                skills: task.required_skills,
+            };
+            let Some(decision) = routing::route_subtask(&planned, &candidates) else {
+                break;
+            };
+            // Someone else (another dispatch pass, a manual update) got to it first
+            let Some(subtask) = task_repo
+                .assign_queued(task.id, decision.agent_id, &decision.reason)
+                .await?
+            else {
+                continue;
+            };
+
+            claim_slot(&mut candidates, decision.agent_id);
+            let subtask = task_repo
+                .start_attempt(subtask.id, Some(decision.agent_id))
+                .await?
+                .unwrap_or(subtask);
+            log_undelivered(subtask.id, self.notify_slave(team, &subtask).await);
+            dispatched.push(subtask);
+        }
+
+        Ok(dispatched)
+    }
+
+    /// Opens a thread for a top-level task's orders, subtask messages and command replies,
+    /// where the team's transport has threads, and stores it on the task. Returns the task's
+    /// thread, which for a subtask is its parent's. Best-effort: without one, messages go to
+    /// the team channels.
+    pub async fn open_thread(&self, team: &Team, task: &Task) -> Option<String> {
+        if task.thread_id.is_some() || task.parent_task_id.is_some() {
+            return task.thread_id.clone();
+        }
+        let thread_id = match self.transports.for_team(team).open_thread(team, task).await {
+            Ok(thread_id) => thread_id?,
+            Err(e) => {
+                tracing::warn!(task_id = %task.id, error = %e, "failed to open task thread");
+                return None;
+            }
+        };
+
+        let task_repo = repositories::TaskRepository::new(self.db.db().clone());
+        if let Err(e) = task_repo.set_thread(task.id, &thread_id).await {
+            tracing::warn!(task_id = %task.id, error = %e, "failed to store task thread");
+        }
+        Some(thread_id)
+    }
+
+    /// Posts the outcome of a finished top-level task to its thread and closes it.
+    pub async fn close_thread(&self, team: &Team, task: &Task) -> Result<()> {
+        if task.thread_id.is_none() || task.parent_task_id.is_some() {
+            return Ok(());
+        }
+        self.transports
+            .for_team(team)
+            .close_thread(team, task)
+            .await
+    }
+
+    /// Sends the master order for a task over the team's transport.
+    async fn send_order(&self, team: &Team, task: &Task) -> Result<()> {
+        let order_message = format!(
+            "**New Task Assigned**\nTask ID: `{}`\n{}\n\nReply with `!task-complete {}` and your result when finished.",
+            task.id,
+            task.description,
+            task.id
+        );
+        let context = dependencies::upstream_context(&self.db, task).await?;
+        let order_message = dependencies::with_context(order_message, context.as_deref());
+        self.transports
+            .for_team(team)
+            .send_order(team, task, &order_message)
+            .await
+    }
+
+    pub async fn notify_slave(&self, team: &Team, subtask: &Task) -> Result<()> {
+        let Some(slave_id) = subtask.assigned_to else {
+            return Ok(());
+        };
+        let slave_message = format!(
+            "**Subtask for Slave {}**\nTask ID: `{}`\n{}\nRouted: {}\n\nReply with `!task-complete {}` and your result when finished.",
+            slave_id,
+            subtask.id,
+            subtask.description,
+            subtask.routing_reason.as_deref().unwrap_or_default(),
+            subtask.id
+        );
+        let context = dependencies::upstream_context(&self.db, subtask).await?;
+        let slave_message = dependencies::with_context(slave_message, context.as_deref());
+        self.transports
+            .for_team(team)
+            .send_slave_message(team, subtask, &slave_message)
+            .await
+    }
+
+    /// Picks who retries a timed-out task: another available slave that fits if there is
+    /// one, otherwise the same agent. Tasks held by the master are retried by the master.
+    /// Returns `None` for a subtask no slave can take yet, which should be queued.
+    pub async fn reroute(&self, team: &Team, task: &Task) -> Result<Option<RoutingDecision>> {
+        let previous = match task.assigned_to {
+            Some(previous) if !team.slave_ids.contains(&previous) => {
+                return Ok(Some(RoutingDecision {
+                    agent_id: previous,
+                    reason: "retry on the same agent".to_string(),
+                }));
+            }
+            previous => previous,
+        };
+
+        let mut candidates = self.routing_candidates(team).await?;
+        // The timed-out task no longer counts against its previous slave
+        if let Some(candidate) = candidates
+            .iter_mut()
+            .find(|candidate| Some(candidate.agent.id) == previous)
+        {
+            candidate.open_tasks = candidate.open_tasks.saturating_sub(1);
+        }
+
+        let planned = PlannedSubtask {
+            description: task.description.clone(),
+            skills: task.required_skills.clone(),
+        };
+        let others: Vec<RoutingCandidate> = candidates
+            .iter()
+            .filter(|candidate| Some(candidate.agent.id) != previous)
+            .cloned()
+            .collect();
+        let decision = routing::route_subtask(&planned, &others)
+            .or_else(|| routing::route_subtask(&planned, &candidates));
+
+        Ok(match (decision, previous) {
+            (Some(decision), _) => Some(RoutingDecision {
+                reason: format!("retry: {}", decision.reason),
+                ..decision
+            }),
+            // Only subtasks are picked up from the queue; anything else stays with its slave
+            (None, Some(previous)) if task.parent_task_id.is_none() => Some(RoutingDecision {
+                agent_id: previous,
+                reason: "retry on the same agent; no other slave available".to_string(),
+            }),
+            (None, _) => None,
+        })
+    }
+
+    /// Tells the master a task ran past its deadline and what happens next.
+    pub async fn report_timeout(&self, team: &Team, task: &Task, next_step: &str) -> Result<()> {
+        let message = format!(
+            "**Task Timed Out**\nTask ID: `{}`\n{}\nAttempt {} of {} ran past its deadline{}.\n{}",
+            task.id,
+            task.description,
+            task.attempts,
+            task.max_attempts,
+            task.assigned_to
+                .map(|agent_id| format!(" on agent {}", agent_id))
+                .unwrap_or_default(),
+            next_step
+        );
+        self.transports
+            .for_team(team)
+            .send_order(team, task, &message)
+            .await
+    }
+
+    /// The team's slaves with their open task counts, in team order.
+    async fn routing_candidates(&self, team: &Team) -> Result<Vec<RoutingCandidate>> {
+        let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
         let task_repo = repositories::TaskRepository::new(self.db.db().clone());
-        let subtasks = task_repo.get_by_parent_id(task.id).await?;
+        let open_tasks = task_repo.count_open_by_assignee(&team.slave_ids).await?;
 
-        let mut sections = Vec::new();
-        if let Some(result) = &task.result {
-            sections.push(format!("Main task: {}", result));
+        let mut candidates = Vec::with_capacity(team.slave_ids.len());
+        for slave_id in &team.slave_ids {
+            if let Some(agent) = agent_repo.get_by_id(*slave_id).await? {
+                candidates.push(RoutingCandidate {
+                    open_tasks: open_tasks.get(slave_id).copied().unwrap_or(0) as usize,
+                    agent,
+                });
+            }
         }
+        Ok(candidates)
+    }
 
-        for sub in subtasks {
-            if let Some(result) = sub.result {
-                sections.push(format!("{}: {}", sub.description, result));
+    async fn plan_subtasks(&self, team: &Team, task: &Task) -> Vec<PlannedSubtask> {
+        let planner = planner::planner_for(&team.settings.planner, &self.http);
+        match planner.plan(task).await {
+            Ok(planned) => planned,
+            Err(e) => {
+                tracing::warn!(
+                    task_id = %task.id,
+                    team_id = %team.id,
+                    error = %format!("{:#}", e),
+                    "task planner failed; leaving task undivided"
+                );
+                Vec::new()
             }
         }
+    }
 
-        let aggregated = if sections.is_empty() {
-            format!("No results yet for task: {}", task.description)
+    /// Called when a subtask of `parent` finishes. Once every subtask has reached a final
+    /// status, their results are combined into the parent's, which is marked `Completed`
+    /// (`Failed` if any subtask didn't complete), and the summary is posted to the
+    /// coordination log. Returns the finished parent, or `None` while subtasks are still
+    /// open or if the parent was already finished.
+    pub async fn aggregate_results(&self, team: &Team, parent: &Task) -> Result<Option<Task>> {
+        let task_repo = repositories::TaskRepository::new(self.db.db().clone());
+        let subtasks = task_repo.get_by_parent_id(parent.id).await?;
+        if subtasks.is_empty() || !subtasks.iter().all(|sub| sub.status.is_final()) {
+            return Ok(None);
+        }
+
+        let status = if subtasks
+            .iter()
+            .all(|sub| sub.status == TaskStatus::Completed)
+        {
+            TaskStatus::Completed
         } else {
-            sections.join("\n")
+            TaskStatus::Failed
+        };
+        let aggregated = summarize_results(parent, &subtasks);
+        let Some(parent) = task_repo
+            .finish_open(parent.id, status.clone(), &aggregated)
+            .await?
+        else {
+            return Ok(None);
         };
 
-        // Log aggregation to coordination channel
-        if let Some(discord) = &self.discord_client {
-            let log_message = format!(
-                "**Task Completed**\nTask: {}\nResult: {}",
-                task.description, aggregated
-            );
-            discord
-                .log_coordination(&team.discord_channels.coordination_logs, &log_message)
-                .await?;
+        // Log aggregation to coordination channel; the parent is already finished, so a
+        // failed post doesn't undo it
+        let heading = match status {
+            TaskStatus::Completed => "Task Completed",
+            _ => "Task Failed",
+        };
+        let log_message = format!(
+            "**{}**\nTask ID: `{}`\nTask: {}\nResult: {}",
+            heading, parent.id, parent.description, aggregated
+        );
+        if let Err(e) = self
+            .transports
+            .for_team(team)
+            .log(team, None, &log_message)
+            .await
+        {
+            tracing::warn!(task_id = %parent.id, error = %e, "failed to log aggregated task");
         }
 
-        Ok(aggregated)
+        Ok(Some(parent))
+    }
+}
+
+/// The task's own result (if any) followed by each subtask's, in creation order. Subtasks
+/// that didn't complete are listed with their status.
+pub fn summarize_results(task: &Task, subtasks: &[Task]) -> String {
+    let mut sections = Vec::new();
+    if let Some(result) = &task.result {
+        sections.push(format!("Main task: {}", result));
+    }
+
+    for sub in subtasks {
+        let result = sub.result.as_deref().unwrap_or("(no result)");
+        match sub.status {
+            TaskStatus::Completed => sections.push(format!("{}: {}", sub.description, result)),
+            ref status => sections.push(format!("{} [{:?}]: {}", sub.description, status, result)),
+        }
+    }
+
+    if sections.is_empty() {
+        format!("No results yet for task: {}", task.description)
+    } else {
+        sections.join("\n")
+    }
+}
+
+fn claim_slot(candidates: &mut [RoutingCandidate], agent_id: Uuid) {
+    if let Some(candidate) = candidates
+        .iter_mut()
+        .find(|candidate| candidate.agent.id == agent_id)
+    {
+        candidate.open_tasks += 1;
     }
 }
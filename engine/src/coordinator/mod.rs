@@ -1,39 +1,94 @@
+pub mod commands;
+pub mod dependencies;
 pub mod discord;
+pub mod dispatcher;
+pub mod events;
 pub mod master;
+pub mod outbox;
+pub mod planner;
+pub mod routing;
+pub mod slack;
 pub mod slave;
+pub mod sweeper;
+pub mod transport;
 
-use crate::storage::Database;
+use crate::models::{AgentRole, Task, TaskStatus, Team};
+use crate::storage::{repositories, Database};
 use anyhow::Result;
+use commands::{CommandOutcome, TaskCommand};
+use events::{CoordinationEvent, EventBus};
+use tokio::sync::{broadcast, mpsc};
+use transport::{CommandReceiver, CoordinationTransport, InProcessTransport, Transports};
+use uuid::Uuid;
+
+/// A notification that couldn't be sent (for Discord, queued in the outbox) is logged
+/// rather than failing the task operation that sent it.
+pub(crate) fn log_undelivered(task_id: Uuid, sent: Result<()>) {
+    if let Err(e) = sent {
+        tracing::warn!(task_id = %task_id, error = %e, "failed to send task notification");
+    }
+}
 
 #[derive(Clone)]
 pub struct Coordinator {
-    #[allow(unused)]
     db: Database,
-    discord_client: Option<discord::DiscordClient>,
+    transports: Transports,
     master_coordinator: master::MasterCoordinator,
     slave_coordinator: slave::SlaveCoordinator,
+    events: EventBus,
 }
 
 impl Coordinator {
-    pub async fn new(db: Database, discord_bot_token: Option<String>) -> Result<Self> {
-        let discord_client = if let Some(token) = discord_bot_token {
-            let client = discord::DiscordClient::new(token, db.clone()).await?;
+    pub async fn new(
+        db: Database,
+        discord_config: Option<discord::DiscordConfig>,
+        slack_config: Option<slack::SlackConfig>,
+    ) -> Result<Self> {
+        // Tasks finished by commands received over a transport, followed up below once the
+        // coordinator exists
+        let (finished_tx, mut finished_rx) = mpsc::unbounded_channel();
+        let receiver = CommandReceiver::new(db.clone(), finished_tx);
+        let discord_client = if let Some(config) = discord_config {
+            let client = discord::DiscordClient::new(db.clone(), config).await?;
             // Initialize the Discord client (start event handlers if needed)
-            client.start().await?;
+            client.start(receiver.clone()).await?;
             Some(client)
         } else {
             None
         };
+        let slack_client = match slack_config {
+            Some(config) => {
+                let client = slack::SlackClient::new(db.clone(), config);
+                client.start(receiver.clone()).await?;
+                Some(client)
+            }
+            None => None,
+        };
+        let in_process = InProcessTransport::new();
+        in_process.start(receiver).await?;
+        let transports = Transports::new(discord_client, slack_client, in_process);
 
-        let master_coordinator = master::MasterCoordinator::new(db.clone(), discord_client.clone());
-        let slave_coordinator = slave::SlaveCoordinator::new(db.clone(), discord_client.clone());
+        let master_coordinator = master::MasterCoordinator::new(db.clone(), transports.clone());
+        let slave_coordinator = slave::SlaveCoordinator::new(db.clone(), transports.clone());
 
-        Ok(Self {
+        let coordinator = Self {
             db,
-            discord_client,
+            transports,
             master_coordinator,
             slave_coordinator,
-        })
+            events: EventBus::new(),
+        };
+
+        let follow_up = coordinator.clone();
+        tokio::spawn(async move {
+            while let Some(task) = finished_rx.recv().await {
+                if let Err(e) = follow_up.task_finished(&task).await {
+                    tracing::warn!(task_id = %task.id, error = %e, "failed to follow up finished task");
+                }
+            }
+        });
+
+        Ok(coordinator)
     }
 
     pub fn master(&self) -> &master::MasterCoordinator {
@@ -44,10 +99,218 @@ impl Coordinator {
         &self.slave_coordinator
     }
 
-    pub async fn log_coordination(&self, channel_id: &str, message: &str) -> Result<()> {
-        if let Some(discord) = &self.discord_client {
-            discord.log_coordination(channel_id, message).await?;
+    pub fn subscribe(&self) -> broadcast::Receiver<CoordinationEvent> {
+        self.events.subscribe()
+    }
+
+    /// Set when the server has a Discord bot token; provisions team channels.
+    pub fn discord(&self) -> Option<&discord::DiscordClient> {
+        self.transports.discord()
+    }
+
+    /// Set when the server has a Slack bot token; receives Events API requests.
+    pub fn slack(&self) -> Option<&slack::SlackClient> {
+        self.transports.slack()
+    }
+
+    /// Messages of teams using the in-process transport, and commands sent on their behalf.
+    pub fn in_process(&self) -> &InProcessTransport {
+        self.transports.in_process()
+    }
+
+    /// Runs a task command received outside the team's transport, e.g. sent back by a
+    /// webhook receiver through the API. Its log entry goes out over the team's transport
+    /// and a task it finishes is followed up (best-effort) before returning.
+    pub async fn run_command(&self, team: &Team, command: &TaskCommand) -> Result<CommandOutcome> {
+        let outcome = commands::execute(&self.db, command).await?;
+        if let (Some(log), Some(task)) = (&outcome.log, &outcome.task) {
+            let transport = self.transports.for_team(team);
+            log_undelivered(task.id, transport.log(team, Some(task), log).await);
+        }
+        if let (true, Some(task)) = (outcome.finished, &outcome.task) {
+            if let Err(e) = self.task_finished(task).await {
+                tracing::warn!(task_id = %task.id, error = %e, "failed to follow up finished task");
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Hands a ready task to its agent as a new attempt: a master's task is split and
+    /// delegated (and marked in progress), a slave is sent the task. Returns the task as
+    /// stored afterwards.
+    pub async fn dispatch_task(&self, task: &Task) -> Result<Task> {
+        let Some(agent_id) = task.assigned_to else {
+            return Ok(task.clone());
+        };
+        let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
+        let agent = agent_repo
+            .get_by_id(agent_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Agent {} not found", agent_id))?;
+        let team_repo = repositories::TeamRepository::new(self.db.db().clone());
+        let team = team_repo
+            .get_by_id(task.team_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Team {} not found", task.team_id))?;
+
+        let task_repo = repositories::TaskRepository::new(self.db.db().clone());
+        let task = task_repo
+            .start_attempt(task.id, Some(agent_id))
+            .await?
+            .unwrap_or_else(|| task.clone());
+
+        match agent.role {
+            AgentRole::Master => {
+                self.master_coordinator.delegate_task(&team, &task).await?;
+                let updated = task_repo
+                    .update_fields(task.id, Some(TaskStatus::InProgress), None)
+                    .await?;
+                Ok(updated.unwrap_or(task))
+            }
+            // Subtasks keep the master's framing, e.g. when retried on another slave
+            AgentRole::Slave if task.parent_task_id.is_some() => {
+                log_undelivered(
+                    task.id,
+                    self.master_coordinator.notify_slave(&team, &task).await,
+                );
+                Ok(task)
+            }
+            AgentRole::Slave => {
+                let task = Task {
+                    thread_id: self.master_coordinator.open_thread(&team, &task).await,
+                    ..task
+                };
+                log_undelivered(
+                    task.id,
+                    self.slave_coordinator.notify_task(&team, &task).await,
+                );
+                Ok(task)
+            }
+        }
+    }
+
+    /// Follow-up once a task reaches a final status: on completion, dependents whose other
+    /// dependencies are also done are dispatched; on failure or cancellation, everything
+    /// downstream is cancelled. Either way queued subtasks get a chance at the freed slot.
+    /// If it was the last open subtask of its parent, the parent is aggregated and finished
+    /// in turn, and a [`CoordinationEvent::TaskAggregated`] is published. A finished top-level
+    /// task's thread gets the outcome and is archived.
+    pub async fn task_finished(&self, task: &Task) -> Result<()> {
+        let team_repo = repositories::TeamRepository::new(self.db.db().clone());
+        let task_repo = repositories::TaskRepository::new(self.db.db().clone());
+        let mut finished = Some(task.clone());
+
+        while let Some(task) = finished.take() {
+            if !task.status.is_final() {
+                break;
+            }
+            task_repo.end_attempt(task.id, &task.status).await?;
+            self.release_dependents(&task).await?;
+
+            let Some(team) = team_repo.get_by_id(task.team_id).await? else {
+                break;
+            };
+            self.master_coordinator.dispatch_queued(&team).await?;
+
+            let Some(parent_id) = task.parent_task_id else {
+                log_undelivered(
+                    task.id,
+                    self.master_coordinator.close_thread(&team, &task).await,
+                );
+                break;
+            };
+            let Some(parent) = task_repo.get_by_id(parent_id).await? else {
+                break;
+            };
+            finished = self
+                .master_coordinator
+                .aggregate_results(&team, &parent)
+                .await?;
+            if let Some(parent) = &finished {
+                let subtasks = task_repo.get_by_parent_id(parent.id).await?.len();
+                tracing::info!(task_id = %parent.id, status = ?parent.status, subtasks, "aggregated task results");
+                self.events.publish(CoordinationEvent::TaskAggregated {
+                    task: parent.clone(),
+                    subtasks,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn release_dependents(&self, task: &Task) -> Result<()> {
+        let task_repo = repositories::TaskRepository::new(self.db.db().clone());
+        match task.status {
+            TaskStatus::Completed => {
+                for ready in task_repo.unblock_ready(Some(task.id)).await? {
+                    self.dispatch_ready(&ready).await;
+                }
+            }
+            TaskStatus::Failed | TaskStatus::Cancelled | TaskStatus::TimedOut => {
+                let reason = format!("cancelled: upstream task {} did not complete", task.id);
+                let cancelled = task_repo.cancel_dependents(task.id, &reason).await?;
+                if !cancelled.is_empty() {
+                    tracing::info!(
+                        task_id = %task.id,
+                        cancelled = cancelled.len(),
+                        "cancelled tasks downstream of an unfinished task"
+                    );
+                }
+            }
+            _ => {}
         }
         Ok(())
     }
+
+    /// Catch-up for follow-ups [`Self::task_finished`] missed (it is best-effort): cancels
+    /// blocked tasks whose dependencies won't complete, dispatches those whose dependencies
+    /// are all done, then queued subtasks of every team. Returns how many tasks were
+    /// dispatched.
+    pub async fn dispatch_pending(&self) -> Result<usize> {
+        let task_repo = repositories::TaskRepository::new(self.db.db().clone());
+        let cancelled = task_repo
+            .cancel_unreachable("cancelled: an upstream task did not complete")
+            .await?;
+        if !cancelled.is_empty() {
+            tracing::info!(
+                cancelled = cancelled.len(),
+                "cancelled blocked tasks whose dependencies won't complete"
+            );
+        }
+
+        let ready = task_repo.unblock_ready(None).await?;
+        let mut dispatched = ready.len();
+        for task in &ready {
+            self.dispatch_ready(task).await;
+        }
+
+        let team_repo = repositories::TeamRepository::new(self.db.db().clone());
+        for team in team_repo.list_all().await? {
+            match self.master_coordinator.dispatch_queued(&team).await {
+                Ok(tasks) => dispatched += tasks.len(),
+                Err(e) => tracing::warn!(
+                    team_id = %team.id,
+                    error = %e,
+                    "failed to dispatch queued subtasks"
+                ),
+            }
+        }
+
+        Ok(dispatched)
+    }
+
+    /// The task is already `pending`, so a failed dispatch is only logged; it can be resent.
+    async fn dispatch_ready(&self, task: &Task) {
+        if let Err(e) = self.dispatch_task(task).await {
+            tracing::warn!(task_id = %task.id, error = %e, "failed to dispatch unblocked task");
+        }
+    }
+
+    pub async fn log_coordination(&self, team: &Team, message: &str) -> Result<()> {
+        self.transports
+            .for_team(team)
+            .log(team, None, message)
+            .await
+    }
 }
@@ -0,0 +1,405 @@
+use crate::models::{OutboxAction, OutboxMessage};
+use crate::storage::{repositories, Database};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderMap;
+use reqwest::multipart::{Form, Part};
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+/// Discord's limit on message content, in characters.
+pub const MAX_MESSAGE_CHARS: usize = 2000;
+/// Longer messages are sent as a text attachment instead of this many chunks or more.
+const MAX_CHUNKS: usize = 5;
+/// Failed attempts (not counting rate-limit waits) before a message is given up on.
+const MAX_ATTEMPTS: i32 = 8;
+const MAX_BACKOFF_SECS: i64 = 600;
+/// Messages delivered per pass; the rest wait for the next one.
+const BATCH_SIZE: i64 = 50;
+/// How long a claimed message is hidden from other senders while it is delivered.
+const LEASE_SECS: i64 = 60;
+/// Upper bound on the wait between passes when nothing wakes the sender.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const SENT_RETENTION_DAYS: i64 = 7;
+
+/// Delivers queued Discord messages in the background. Sends are throttled per channel
+/// (Discord's per-route buckets) and globally according to the rate-limit headers and
+/// `429` responses, failures are retried with exponential backoff, and messages over
+/// [`MAX_MESSAGE_CHARS`] are split into chunks or sent as an attachment.
+#[derive(Clone)]
+pub struct DiscordOutbox {
+    db: Database,
+    http_client: Client,
+    token: String,
+    api_url: String,
+    wake: Arc<Notify>,
+}
+
+/// When each route, and Discord as a whole, may be called again.
+#[derive(Default)]
+struct RateLimits {
+    routes: HashMap<String, DateTime<Utc>>,
+    global: Option<DateTime<Utc>>,
+}
+
+impl RateLimits {
+    fn blocked_until(&self, route: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        [self.global, self.routes.get(route).copied()]
+            .into_iter()
+            .flatten()
+            .filter(|until| *until > now)
+            .max()
+    }
+}
+
+enum Delivery {
+    Sent,
+    /// Rate limited until the given time; not a failure.
+    Deferred(DateTime<Utc>),
+    Retry(String),
+    Failed(String),
+}
+
+#[derive(Deserialize)]
+struct RateLimitBody {
+    retry_after: f64,
+    #[serde(default)]
+    global: bool,
+}
+
+impl DiscordOutbox {
+    pub fn new(db: Database, http_client: Client, token: String, api_url: String) -> Self {
+        Self {
+            db,
+            http_client,
+            token,
+            api_url,
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Queues a message; it is delivered by the task started with [`Self::start`].
+    pub async fn enqueue(
+        &self,
+        channel_id: u64,
+        content: &str,
+        embed: Option<&serde_json::Value>,
+    ) -> Result<()> {
+        let outbox_repo = repositories::DiscordOutboxRepository::new(self.db.db().clone());
+        outbox_repo
+            .enqueue(
+                &channel_id.to_string(),
+                OutboxAction::Message,
+                content,
+                embed,
+            )
+            .await?;
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// Queues archiving a thread, done once the messages queued for it before are sent.
+    pub async fn enqueue_archive(&self, thread_id: u64) -> Result<()> {
+        let outbox_repo = repositories::DiscordOutboxRepository::new(self.db.db().clone());
+        outbox_repo
+            .enqueue(
+                &thread_id.to_string(),
+                OutboxAction::ArchiveThread,
+                "",
+                None,
+            )
+            .await?;
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    pub fn start(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut limits = RateLimits::default();
+            let mut last_prune = Utc::now();
+
+            loop {
+                match self.deliver_due(&mut limits).await {
+                    // A full batch means more may be due right away
+                    Ok(delivered) if delivered as i64 >= BATCH_SIZE => continue,
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!(error = %e, "Discord outbox pass failed"),
+                }
+
+                if Utc::now() - last_prune > chrono::Duration::hours(1) {
+                    last_prune = Utc::now();
+                    let outbox_repo =
+                        repositories::DiscordOutboxRepository::new(self.db.db().clone());
+                    let cutoff = last_prune - chrono::Duration::days(SENT_RETENTION_DAYS);
+                    if let Err(e) = outbox_repo.prune_sent(cutoff).await {
+                        tracing::warn!(error = %e, "failed to prune Discord outbox");
+                    }
+                }
+
+                let _ = tokio::time::timeout(POLL_INTERVAL, self.wake.notified()).await;
+            }
+        })
+    }
+
+    /// Delivers the messages that are due and returns how many were claimed.
+    async fn deliver_due(&self, limits: &mut RateLimits) -> Result<usize> {
+        let outbox_repo = repositories::DiscordOutboxRepository::new(self.db.db().clone());
+        let now = Utc::now();
+        let messages = outbox_repo
+            .claim_due(now, now + chrono::Duration::seconds(LEASE_SECS), BATCH_SIZE)
+            .await?;
+
+        for message in &messages {
+            let delivery = match limits.blocked_until(&message.channel_id, Utc::now()) {
+                Some(until) => Delivery::Deferred(until),
+                None => self.deliver(message, limits).await,
+            };
+
+            match delivery {
+                Delivery::Sent => outbox_repo.mark_sent(message.id).await?,
+                Delivery::Deferred(until) => {
+                    outbox_repo.reschedule(message.id, until, None).await?
+                }
+                Delivery::Retry(error) if message.attempts + 1 < MAX_ATTEMPTS => {
+                    let backoff = (2_i64 << message.attempts.min(16)).min(MAX_BACKOFF_SECS);
+                    let at = Utc::now() + chrono::Duration::seconds(backoff);
+                    outbox_repo
+                        .reschedule(message.id, at, Some(error.as_str()))
+                        .await?;
+                }
+                Delivery::Retry(error) | Delivery::Failed(error) => {
+                    tracing::warn!(
+                        message_id = %message.id,
+                        channel_id = %message.channel_id,
+                        error = %error,
+                        "giving up on Discord message"
+                    );
+                    outbox_repo.mark_failed(message.id, &error).await?;
+                }
+            }
+        }
+
+        Ok(messages.len())
+    }
+
+    async fn deliver(&self, message: &OutboxMessage, limits: &mut RateLimits) -> Delivery {
+        match message.action {
+            OutboxAction::Message => self.deliver_message(message, limits).await,
+            OutboxAction::ArchiveThread => {
+                let request = self
+                    .http_client
+                    .patch(format!("{}/channels/{}", self.api_url, message.channel_id))
+                    .json(&json!({ "archived": true }));
+                self.send(request, &message.channel_id, limits)
+                    .await
+                    .unwrap_or(Delivery::Sent)
+            }
+        }
+    }
+
+    /// Sends the message's remaining chunks, recording progress after each one.
+    async fn deliver_message(&self, message: &OutboxMessage, limits: &mut RateLimits) -> Delivery {
+        let outbox_repo = repositories::DiscordOutboxRepository::new(self.db.db().clone());
+        let chunks = split_message(&message.content);
+        let url = format!("{}/channels/{}/messages", self.api_url, message.channel_id);
+
+        for (index, chunk) in chunks
+            .iter()
+            .enumerate()
+            .skip(message.chunks_sent.max(0) as usize)
+        {
+            // The embed goes with the first chunk
+            let embed = message.embed.as_ref().filter(|_| index == 0);
+            let request = self.http_client.post(&url);
+            let request = match chunk {
+                Chunk::Text(content) => {
+                    let mut payload = json!({ "content": content });
+                    if let Some(embed) = embed {
+                        payload["embeds"] = json!([embed]);
+                    }
+                    request.json(&payload)
+                }
+                Chunk::Attachment { preview, file } => {
+                    let mut payload = json!({
+                        "content": preview,
+                        "attachments": [{ "id": 0, "filename": "message.txt" }],
+                    });
+                    if let Some(embed) = embed {
+                        payload["embeds"] = json!([embed]);
+                    }
+                    let file = match Part::text(file.clone())
+                        .file_name("message.txt")
+                        .mime_str("text/plain")
+                    {
+                        Ok(file) => file,
+                        Err(e) => return Delivery::Failed(e.to_string()),
+                    };
+                    request.multipart(
+                        Form::new()
+                            .text("payload_json", payload.to_string())
+                            .part("files[0]", file),
+                    )
+                }
+            };
+
+            if let Some(delivery) = self.send(request, &message.channel_id, limits).await {
+                return delivery;
+            }
+
+            if index + 1 < chunks.len() {
+                if let Err(e) = outbox_repo
+                    .set_chunks_sent(message.id, index as i32 + 1)
+                    .await
+                {
+                    tracing::warn!(message_id = %message.id, error = %e, "failed to record sent chunk");
+                }
+                // Later chunks share the route, so wait out an exhausted bucket here
+                if let Some(until) = limits.blocked_until(&message.channel_id, Utc::now()) {
+                    return Delivery::Deferred(until);
+                }
+            }
+        }
+
+        Delivery::Sent
+    }
+
+    /// Makes one request on `route`, updating the rate limits from the response. Returns
+    /// `None` on success, otherwise how to handle the outbox entry.
+    async fn send(
+        &self,
+        request: RequestBuilder,
+        route: &str,
+        limits: &mut RateLimits,
+    ) -> Option<Delivery> {
+        let response = match request
+            .header("Authorization", format!("Bot {}", self.token))
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => return Some(Delivery::Retry(e.to_string())),
+        };
+        let status = response.status();
+        let headers = response.headers().clone();
+        update_limits(limits, route, &headers);
+
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let body = response.json::<RateLimitBody>().await.ok();
+            let retry_after = body
+                .as_ref()
+                .map(|body| body.retry_after)
+                .or_else(|| header_secs(&headers, "retry-after"))
+                .unwrap_or(1.0);
+            let until = Utc::now() + seconds(retry_after);
+            if body.is_some_and(|body| body.global) {
+                limits.global = Some(until);
+            } else {
+                limits.routes.insert(route.to_string(), until);
+            }
+            return Some(Delivery::Deferred(until));
+        }
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            let error = format!("Discord returned {}: {}", status, error_text);
+            return Some(if status.is_server_error() {
+                Delivery::Retry(error)
+            } else {
+                Delivery::Failed(error)
+            });
+        }
+        None
+    }
+}
+
+/// A piece of an outbox message as sent to Discord.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Chunk {
+    Text(String),
+    Attachment { preview: String, file: String },
+}
+
+/// Splits content into messages of at most [`MAX_MESSAGE_CHARS`], preferring line breaks.
+/// Content that would take [`MAX_CHUNKS`] or more is sent as one attachment with a preview.
+fn split_message(content: &str) -> Vec<Chunk> {
+    let chunks = split_chunks(content, MAX_MESSAGE_CHARS);
+    if chunks.len() < MAX_CHUNKS {
+        return chunks.into_iter().map(Chunk::Text).collect();
+    }
+
+    let note = "\n… (full message attached)";
+    let preview_chars = MAX_MESSAGE_CHARS - note.chars().count();
+    let preview = split_chunks(content, preview_chars)
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+    vec![Chunk::Attachment {
+        preview: format!("{}{}", preview.trim_end(), note),
+        file: content.to_string(),
+    }]
+}
+
+fn split_chunks(content: &str, limit: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+
+    for line in content.split_inclusive('\n') {
+        let line_chars = line.chars().count();
+        if current_chars + line_chars > limit && !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+        if line_chars <= limit {
+            current.push_str(line);
+            current_chars += line_chars;
+            continue;
+        }
+        // A single line over the limit is split wherever it has to be
+        for ch in line.chars() {
+            if current_chars == limit {
+                chunks.push(std::mem::take(&mut current));
+                current_chars = 0;
+            }
+            current.push(ch);
+            current_chars += 1;
+        }
+    }
+
+    if !current.is_empty() || chunks.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Blocks the route until its bucket resets once the last request used it up.
+fn update_limits(limits: &mut RateLimits, route: &str, headers: &HeaderMap) {
+    let remaining = headers
+        .get("x-ratelimit-remaining")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u32>().ok());
+    if remaining != Some(0) {
+        return;
+    }
+    if let Some(reset_after) = header_secs(headers, "x-ratelimit-reset-after") {
+        limits
+            .routes
+            .insert(route.to_string(), Utc::now() + seconds(reset_after));
+    }
+}
+
+fn header_secs(headers: &HeaderMap, name: &str) -> Option<f64> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+fn seconds(secs: f64) -> chrono::Duration {
+    chrono::Duration::milliseconds((secs.max(0.0) * 1000.0).ceil() as i64)
+}
@@ -0,0 +1,291 @@
+use crate::models::{ModelPlannerConfig, PlannerConfig, Task};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+const MODEL_SYSTEM_PROMPT: &str = "You split a task into independent subtasks that can be \
+worked on in parallel by different agents. Reply with only a JSON array with one object per \
+subtask: {\"description\": a self-contained instruction, \"skills\": short lowercase tags \
+for the skills it needs}. Reply with a single-element array if the task should not be split.";
+
+/// A unit of work the master hands to a slave.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedSubtask {
+    pub description: String,
+    /// Skills the subtask needs, used to route it to a slave; may be empty.
+    pub skills: Vec<String>,
+}
+
+/// Decomposes a task into subtasks. Returning fewer than two means the task isn't split
+/// and the master keeps it.
+#[async_trait]
+pub trait TaskPlanner: Send + Sync {
+    async fn plan(&self, task: &Task) -> Result<Vec<PlannedSubtask>>;
+}
+
+/// The planner a team is configured with.
+pub fn planner_for(config: &PlannerConfig, http: &Client) -> Box<dyn TaskPlanner> {
+    match config {
+        PlannerConfig::None => Box::new(NoSplitPlanner),
+        PlannerConfig::Lines => Box::new(LinePlanner),
+        PlannerConfig::Json => Box::new(JsonPlanner),
+        PlannerConfig::Model(config) => Box::new(ModelPlanner::new(http.clone(), config.clone())),
+    }
+}
+
+pub struct NoSplitPlanner;
+
+#[async_trait]
+impl TaskPlanner for NoSplitPlanner {
+    async fn plan(&self, _task: &Task) -> Result<Vec<PlannedSubtask>> {
+        Ok(Vec::new())
+    }
+}
+
+/// One subtask per non-empty line. Bullets (`-`, `*`, `•`), numbering (`1.`, `2)`) and
+/// checkboxes (`[ ]`) are stripped, so pasted lists work as-is.
+pub struct LinePlanner;
+
+#[async_trait]
+impl TaskPlanner for LinePlanner {
+    async fn plan(&self, task: &Task) -> Result<Vec<PlannedSubtask>> {
+        Ok(task
+            .description
+            .lines()
+            .map(strip_list_marker)
+            .filter(|line| !line.is_empty())
+            .map(|line| PlannedSubtask {
+                description: line.to_string(),
+                skills: Vec::new(),
+            })
+            .collect())
+    }
+}
+
+/// The description itself is the plan: a JSON array of strings or
+/// `{"description": ..., "skills": [...]}` objects, or an object with such an array under
+/// `subtasks`.
+pub struct JsonPlanner;
+
+#[async_trait]
+impl TaskPlanner for JsonPlanner {
+    async fn plan(&self, task: &Task) -> Result<Vec<PlannedSubtask>> {
+        parse_subtask_list(&task.description).context("task description is not a JSON subtask list")
+    }
+}
+
+/// Asks an OpenAI-compatible chat completions endpoint to decompose the task.
+pub struct ModelPlanner {
+    http: Client,
+    config: ModelPlannerConfig,
+}
+
+impl ModelPlanner {
+    pub fn new(http: Client, config: ModelPlannerConfig) -> Self {
+        Self { http, config }
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatCompletion {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    content: Option<String>,
+}
+
+#[async_trait]
+impl TaskPlanner for ModelPlanner {
+    async fn plan(&self, task: &Task) -> Result<Vec<PlannedSubtask>> {
+        let url = format!(
+            "{}/chat/completions",
+            self.config.endpoint.trim_end_matches('/')
+        );
+        let body = json!({
+            "model": self.config.model,
+            "temperature": 0,
+            "messages": [
+                { "role": "system", "content": MODEL_SYSTEM_PROMPT },
+                { "role": "user", "content": task.description },
+            ],
+        });
+
+        let mut request = self.http.post(&url).json(&body);
+        if let Some(api_key) = &self.config.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("planner request to {} failed", url))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!("planner endpoint returned {}: {}", status, error_text);
+        }
+
+        let completion: ChatCompletion = response
+            .json()
+            .await
+            .context("planner endpoint returned an invalid chat completion")?;
+        let content = completion
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .ok_or_else(|| anyhow::anyhow!("planner endpoint returned no message"))?;
+
+        let mut subtasks = parse_subtask_list(&content)
+            .with_context(|| format!("planner model returned an invalid plan: {}", content))?;
+        if let Some(max_subtasks) = self.config.max_subtasks {
+            subtasks.truncate(max_subtasks);
+        }
+        Ok(subtasks)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SubtaskItem {
+    Text(String),
+    Object {
+        description: String,
+        #[serde(default)]
+        skills: Vec<String>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SubtaskList {
+    Items(Vec<SubtaskItem>),
+    Wrapped { subtasks: Vec<SubtaskItem> },
+}
+
+/// Parses a JSON subtask list, tolerating Markdown code fences and text around the JSON
+/// (models add both).
+fn parse_subtask_list(text: &str) -> Result<Vec<PlannedSubtask>> {
+    let text = text.trim();
+    let list: SubtaskList = match serde_json::from_str(text) {
+        Ok(list) => list,
+        Err(err) => {
+            let start = text.find(['[', '{']);
+            let end = text.rfind([']', '}']);
+            match (start, end) {
+                (Some(start), Some(end)) if start < end => {
+                    serde_json::from_str(&text[start..=end])?
+                }
+                _ => return Err(err.into()),
+            }
+        }
+    };
+
+    let items = match list {
+        SubtaskList::Items(items) | SubtaskList::Wrapped { subtasks: items } => items,
+    };
+    Ok(items
+        .into_iter()
+        .map(|item| match item {
+            SubtaskItem::Text(description) => (description, Vec::new()),
+            SubtaskItem::Object {
+                description,
+                skills,
+            } => (description, skills),
+        })
+        .filter(|(description, _)| !description.trim().is_empty())
+        .map(|(description, skills)| PlannedSubtask {
+            description: description.trim().to_string(),
+            skills,
+        })
+        .collect())
+}
+
+/// Markers only count when followed by whitespace, so `-v` or `1.2.0` are left alone.
+fn strip_list_marker(line: &str) -> &str {
+    fn marker_rest(rest: Option<&str>) -> Option<&str> {
+        rest.filter(|rest| rest.starts_with(char::is_whitespace))
+            .map(str::trim_start)
+    }
+
+    let line = line.trim();
+    let line = marker_rest(line.strip_prefix(['-', '*', '•', '+'])).unwrap_or(line);
+
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let line = match marker_rest(line[digits..].strip_prefix(['.', ')'])) {
+        Some(rest) if digits > 0 => rest,
+        _ => line,
+    };
+
+    line.strip_prefix("[ ]")
+        .or_else(|| line.strip_prefix("[x]"))
+        .or_else(|| line.strip_prefix("[X]"))
+        .map(str::trim_start)
+        .unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subtask(description: &str, skills: &[&str]) -> PlannedSubtask {
+        PlannedSubtask {
+            description: description.to_string(),
+            skills: skills.iter().map(|skill| skill.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn strip_list_marker_removes_bullets_numbers_and_checkboxes() {
+        assert_eq!(strip_list_marker("- write docs"), "write docs");
+        assert_eq!(strip_list_marker("  * write docs  "), "write docs");
+        assert_eq!(strip_list_marker("• write docs"), "write docs");
+        assert_eq!(strip_list_marker("12. write docs"), "write docs");
+        assert_eq!(strip_list_marker("3) write docs"), "write docs");
+        assert_eq!(strip_list_marker("- [ ] write docs"), "write docs");
+        assert_eq!(strip_list_marker("1. [x] write docs"), "write docs");
+    }
+
+    #[test]
+    fn strip_list_marker_keeps_markers_without_a_space() {
+        assert_eq!(strip_list_marker("-v flag"), "-v flag");
+        assert_eq!(strip_list_marker("1.2.0 release"), "1.2.0 release");
+        assert_eq!(strip_list_marker("2)x"), "2)x");
+    }
+
+    #[test]
+    fn parse_subtask_list_accepts_strings_and_objects() {
+        let subtasks = parse_subtask_list(
+            r#"["  write docs ", {"description": "ship", "skills": ["ops"]}, ""]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            subtasks,
+            [subtask("write docs", &[]), subtask("ship", &["ops"])]
+        );
+    }
+
+    #[test]
+    fn parse_subtask_list_unwraps_objects_and_code_fences() {
+        let fenced = "Here is the plan:\n```json\n{\"subtasks\": [{\"description\": \"test\"}, \"deploy\"]}\n```\nGood luck!";
+
+        let subtasks = parse_subtask_list(fenced).unwrap();
+
+        assert_eq!(subtasks, [subtask("test", &[]), subtask("deploy", &[])]);
+    }
+
+    #[test]
+    fn parse_subtask_list_rejects_text_without_a_list() {
+        assert!(parse_subtask_list("I can't split this task.").is_err());
+        assert!(parse_subtask_list("{\"plan\": \"none\"}").is_err());
+    }
+}
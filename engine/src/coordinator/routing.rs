@@ -0,0 +1,340 @@
+use crate::coordinator::planner::PlannedSubtask;
+use crate::models::{Agent, AgentStatus};
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+/// Score for a skill the planner (or a `#tag` in the description) asked for explicitly.
+const TAGGED_SKILL_SCORE: u32 = 3;
+/// Score for a skill mentioned in the subtask description.
+const MENTIONED_SKILL_SCORE: u32 = 2;
+/// Score for each word of the agent's responsibility found in the description.
+const RESPONSIBILITY_SCORE: u32 = 1;
+
+/// Responsibility words shorter than this ("and", "the", "of") are ignored.
+const MIN_KEYWORD_LEN: usize = 4;
+
+/// Open tasks an agent may hold when it doesn't set `max_concurrent_tasks`.
+pub const DEFAULT_MAX_CONCURRENT_TASKS: usize = 3;
+
+/// A slave that may take the subtask, with its current workload.
+#[derive(Debug, Clone)]
+pub struct RoutingCandidate {
+    pub agent: Agent,
+    /// Pending and in-progress tasks already assigned to the agent.
+    pub open_tasks: usize,
+}
+
+impl RoutingCandidate {
+    pub fn max_concurrent_tasks(&self) -> usize {
+        self.agent
+            .max_concurrent_tasks
+            .map(|limit| limit.max(0) as usize)
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_TASKS)
+    }
+
+    /// Running and below its concurrency limit.
+    pub fn is_available(&self) -> bool {
+        self.agent.status == AgentStatus::Running && self.open_tasks < self.max_concurrent_tasks()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutingDecision {
+    pub agent_id: Uuid,
+    /// Human-readable rationale, stored on the subtask.
+    pub reason: String,
+}
+
+/// Picks the available candidate whose skills and responsibility best match the subtask.
+/// Ties, and subtasks nobody matches, go to the least-loaded one (then the earliest listed).
+/// Returns `None` when no candidate is available, in which case the subtask should be
+/// queued.
+pub fn route_subtask(
+    subtask: &PlannedSubtask,
+    candidates: &[RoutingCandidate],
+) -> Option<RoutingDecision> {
+    let terms = SubtaskTerms::new(subtask);
+    let (candidate, score) = candidates
+        .iter()
+        .filter(|candidate| candidate.is_available())
+        .map(|candidate| (candidate, score_candidate(&terms, &candidate.agent)))
+        .min_by_key(|(candidate, score)| (std::cmp::Reverse(score.total), candidate.open_tasks))?;
+
+    let load = match candidate.open_tasks {
+        1 => "1 open task".to_string(),
+        count => format!("{} open tasks", count),
+    };
+    let reason = if score.total == 0 {
+        format!("no skill or responsibility match; least loaded ({})", load)
+    } else {
+        let mut matched = Vec::new();
+        if !score.skills.is_empty() {
+            matched.push(format!("skills {}", join(&score.skills)));
+        }
+        if !score.keywords.is_empty() {
+            matched.push(format!("responsibility {}", join(&score.keywords)));
+        }
+        format!(
+            "matched {} (score {}, {})",
+            matched.join(" and "),
+            score.total,
+            load
+        )
+    };
+
+    Some(RoutingDecision {
+        agent_id: candidate.agent.id,
+        reason,
+    })
+}
+
+/// Why [`route_subtask`] found nobody, stored on the queued subtask.
+pub fn queued_reason(candidates: &[RoutingCandidate]) -> String {
+    if candidates.is_empty() {
+        return "queued: team has no slaves".to_string();
+    }
+    let not_running = candidates
+        .iter()
+        .filter(|candidate| candidate.agent.status != AgentStatus::Running)
+        .count();
+    let at_limit = candidates.len() - not_running;
+    format!(
+        "queued: no slave available ({} not running, {} at their task limit)",
+        not_running, at_limit
+    )
+}
+
+#[derive(Debug, Default)]
+struct Score {
+    total: u32,
+    skills: BTreeSet<String>,
+    keywords: BTreeSet<String>,
+}
+
+struct SubtaskTerms {
+    /// Requested by the planner or tagged with `#skill` in the description.
+    tags: BTreeSet<String>,
+    /// The description's words, normalized and space-separated with a leading and trailing
+    /// space, so multi-word skills can be matched with `contains`.
+    text: String,
+    words: BTreeSet<String>,
+}
+
+impl SubtaskTerms {
+    fn new(subtask: &PlannedSubtask) -> Self {
+        let mut tags: BTreeSet<String> = subtask
+            .skills
+            .iter()
+            .map(|skill| normalize(skill))
+            .filter(|skill| !skill.is_empty())
+            .collect();
+        tags.extend(
+            subtask
+                .description
+                .split_whitespace()
+                .filter_map(|word| word.strip_prefix('#'))
+                .map(normalize)
+                .filter(|tag| !tag.is_empty()),
+        );
+
+        let words: Vec<String> = normalize(&subtask.description)
+            .split(' ')
+            .filter(|word| !word.is_empty())
+            .map(str::to_string)
+            .collect();
+        Self {
+            tags,
+            text: format!(" {} ", words.join(" ")),
+            words: words.into_iter().collect(),
+        }
+    }
+}
+
+fn score_candidate(terms: &SubtaskTerms, agent: &Agent) -> Score {
+    let mut score = Score::default();
+
+    for skill in &agent.skills {
+        let skill = normalize(skill);
+        if skill.is_empty() {
+            continue;
+        }
+        if terms.tags.contains(&skill) {
+            score.total += TAGGED_SKILL_SCORE;
+        } else if terms.text.contains(&format!(" {} ", skill)) {
+            score.total += MENTIONED_SKILL_SCORE;
+        } else {
+            continue;
+        }
+        score.skills.insert(skill);
+    }
+
+    if let Some(responsibility) = &agent.responsibility {
+        for keyword in normalize(responsibility).split(' ') {
+            if keyword.len() >= MIN_KEYWORD_LEN
+                && terms.words.contains(keyword)
+                && score.keywords.insert(keyword.to_string())
+            {
+                score.total += RESPONSIBILITY_SCORE;
+            }
+        }
+    }
+
+    score
+}
+
+/// Lowercases and turns punctuation into single spaces, so "Web-Scraping" matches
+/// "web scraping" and "Rust," matches "rust".
+fn normalize(value: &str) -> String {
+    value
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric() && c != '+' && c != '#')
+        .map(|word| word.trim_start_matches('#'))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn join(values: &BTreeSet<String>) -> String {
+    values.iter().cloned().collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AgentRole, AgentRuntime, ModelProvider};
+
+    fn candidate(
+        skills: &[&str],
+        responsibility: Option<&str>,
+        open_tasks: usize,
+    ) -> RoutingCandidate {
+        RoutingCandidate {
+            agent: Agent {
+                status: AgentStatus::Running,
+                skills: skills.iter().map(|skill| skill.to_string()).collect(),
+                responsibility: responsibility.map(str::to_string),
+                ..Agent::new(
+                    "slave",
+                    AgentRole::Slave,
+                    AgentRuntime::OpenClaw,
+                    ModelProvider::Anthropic,
+                )
+            },
+            open_tasks,
+        }
+    }
+
+    fn subtask(description: &str, skills: &[&str]) -> PlannedSubtask {
+        PlannedSubtask {
+            description: description.to_string(),
+            skills: skills.iter().map(|skill| skill.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn tagged_skills_outscore_mentioned_ones() {
+        let mentioned = candidate(&["rust"], None, 0);
+        let tagged = candidate(&["Web-Scraping"], None, 0);
+        let candidates = [mentioned, tagged.clone()];
+
+        let decision = route_subtask(
+            &subtask("Port the rust crawler", &["web scraping"]),
+            &candidates,
+        )
+        .unwrap();
+
+        assert_eq!(decision.agent_id, tagged.agent.id);
+        assert_eq!(
+            decision.reason,
+            "matched skills web scraping (score 3, 0 open tasks)"
+        );
+    }
+
+    #[test]
+    fn hash_tags_in_the_description_count_as_requested_skills() {
+        let candidates = [candidate(&["sql"], None, 0)];
+
+        let decision = route_subtask(&subtask("Fix the report #SQL", &[]), &candidates).unwrap();
+
+        assert_eq!(
+            decision.reason,
+            "matched skills sql (score 3, 0 open tasks)"
+        );
+    }
+
+    #[test]
+    fn responsibility_words_add_to_the_score() {
+        let generalist = candidate(&[], Some("Billing and invoices"), 0);
+        let specialist = candidate(&["stripe"], Some("billing"), 2);
+        let candidates = [generalist, specialist.clone()];
+
+        let decision = route_subtask(
+            &subtask("Reconcile stripe billing for March", &[]),
+            &candidates,
+        )
+        .unwrap();
+
+        assert_eq!(decision.agent_id, specialist.agent.id);
+        assert_eq!(
+            decision.reason,
+            "matched skills stripe and responsibility billing (score 3, 2 open tasks)"
+        );
+    }
+
+    #[test]
+    fn short_responsibility_words_are_ignored() {
+        let candidates = [candidate(&[], Some("the api"), 1)];
+
+        let decision = route_subtask(&subtask("Document the api", &[]), &candidates).unwrap();
+
+        assert_eq!(
+            decision.reason,
+            "no skill or responsibility match; least loaded (1 open task)"
+        );
+    }
+
+    #[test]
+    fn ties_go_to_the_least_loaded_then_the_first_listed() {
+        let busy = candidate(&["rust"], None, 2);
+        let idle = candidate(&["rust"], None, 0);
+        let also_idle = candidate(&["rust"], None, 0);
+        let candidates = [busy, idle.clone(), also_idle];
+
+        let decision = route_subtask(&subtask("Write rust code", &[]), &candidates).unwrap();
+
+        assert_eq!(decision.agent_id, idle.agent.id);
+    }
+
+    #[test]
+    fn unavailable_candidates_are_skipped() {
+        let mut stopped = candidate(&["rust"], None, 0);
+        stopped.agent.status = AgentStatus::Stopped;
+        let mut full = candidate(&["rust"], None, 1);
+        full.agent.max_concurrent_tasks = Some(1);
+        let unmatched = candidate(&["design"], None, 2);
+        let candidates = [stopped, full, unmatched.clone()];
+
+        let decision = route_subtask(&subtask("Write rust code", &[]), &candidates).unwrap();
+
+        assert_eq!(decision.agent_id, unmatched.agent.id);
+        assert_eq!(
+            decision.reason,
+            "no skill or responsibility match; least loaded (2 open tasks)"
+        );
+    }
+
+    #[test]
+    fn nobody_available_is_explained_by_queued_reason() {
+        let mut stopped = candidate(&[], None, 0);
+        stopped.agent.status = AgentStatus::Stopped;
+        let full = candidate(&[], None, DEFAULT_MAX_CONCURRENT_TASKS);
+        let candidates = [stopped, full];
+
+        assert_eq!(route_subtask(&subtask("Anything", &[]), &candidates), None);
+        assert_eq!(
+            queued_reason(&candidates),
+            "queued: no slave available (1 not running, 1 at their task limit)"
+        );
+        assert_eq!(queued_reason(&[]), "queued: team has no slaves");
+    }
+}
@@ -0,0 +1,509 @@
+use crate::coordinator::commands::TaskCommand;
+use crate::coordinator::transport::{short_id, CommandReceiver, CoordinationTransport};
+use crate::models::{SlackChannels, Task, Team};
+use crate::storage::{repositories, Database};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::{SinkExt, StreamExt};
+use hmac::{Hmac, Mac};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+/// Slack rejects requests signed longer ago than this, so captured ones can't be replayed.
+const MAX_SIGNATURE_AGE_SECS: i64 = 300;
+/// Attempts at a Web API call that keeps being rate limited.
+const MAX_CALL_ATTEMPTS: u32 = 3;
+/// Wait before reconnecting a dropped Socket Mode connection.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct SlackConfig {
+    /// Bot token (`xoxb-`) used for Web API calls.
+    pub bot_token: String,
+    /// App-level token (`xapp-`); when set, events are received over Socket Mode.
+    pub app_token: Option<String>,
+    /// Verifies events Slack POSTs to the API server, when Socket Mode isn't used.
+    pub signing_secret: Option<String>,
+    /// Web API base URL, e.g. `https://slack.com/api`.
+    pub api_url: String,
+}
+
+/// Coordinates teams over Slack: messages are posted with `chat.postMessage`, and
+/// `!task-` commands are received from the Events API, over Socket Mode or through
+/// [`Self::handle_callback`].
+#[derive(Clone)]
+pub struct SlackClient {
+    db: Database,
+    http: Client,
+    config: SlackConfig,
+    /// Our own bot user, whose messages are skipped.
+    bot_user_id: Arc<OnceLock<String>>,
+    receiver: Arc<OnceLock<CommandReceiver>>,
+}
+
+/// An Events API request body, also the payload of Socket Mode `events_api` envelopes.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum SlackCallback {
+    UrlVerification {
+        challenge: String,
+    },
+    EventCallback {
+        event: SlackEvent,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct SlackEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    /// Set for edits, joins and other messages that aren't plain posts.
+    subtype: Option<String>,
+    user: Option<String>,
+    channel: Option<String>,
+    text: Option<String>,
+    ts: Option<String>,
+    thread_ts: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SocketEnvelope {
+    #[serde(rename = "type")]
+    kind: String,
+    envelope_id: Option<String>,
+    payload: Option<Value>,
+}
+
+enum Authorization {
+    Allowed,
+    /// Rejected with a reply explaining why.
+    Denied(String),
+    /// Rejected silently, e.g. commands from channels outside the team.
+    Ignored(String),
+}
+
+impl SlackClient {
+    pub fn new(db: Database, config: SlackConfig) -> Self {
+        Self {
+            db,
+            http: Client::new(),
+            config,
+            bot_user_id: Arc::new(OnceLock::new()),
+            receiver: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// Posts a message, in a thread when `thread_ts` is given, and returns its timestamp.
+    pub async fn post_message(
+        &self,
+        channel: &str,
+        text: &str,
+        thread_ts: Option<&str>,
+    ) -> Result<String> {
+        let mut body = json!({ "channel": channel, "text": to_mrkdwn(text) });
+        if let Some(thread_ts) = thread_ts {
+            body["thread_ts"] = json!(thread_ts);
+        }
+        let response = self
+            .call("chat.postMessage", &self.config.bot_token, &body)
+            .await?;
+        response["ts"]
+            .as_str()
+            .map(str::to_string)
+            .context("Slack chat.postMessage returned no ts")
+    }
+
+    /// Checks the `X-Slack-Signature` of an Events API request. Always fails without a
+    /// signing secret.
+    pub fn verify_signature(&self, timestamp: &str, body: &[u8], signature: &str) -> bool {
+        let Some(secret) = &self.config.signing_secret else {
+            return false;
+        };
+        let Ok(sent_at) = timestamp.parse::<i64>() else {
+            return false;
+        };
+        if (Utc::now().timestamp() - sent_at).abs() > MAX_SIGNATURE_AGE_SECS {
+            return false;
+        }
+        let Some(signature) = signature
+            .strip_prefix("v0=")
+            .and_then(|hex_digest| hex::decode(hex_digest).ok())
+        else {
+            return false;
+        };
+
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key");
+        mac.update(format!("v0:{}:", timestamp).as_bytes());
+        mac.update(body);
+        mac.verify_slice(&signature).is_ok()
+    }
+
+    /// Handles an Events API request body. Returns the challenge to echo back for URL
+    /// verification; events are handled in the background, since Slack expects an answer
+    /// within three seconds.
+    pub fn handle_callback(&self, body: &[u8]) -> Result<Option<String>> {
+        match serde_json::from_slice(body).context("invalid Slack event payload")? {
+            SlackCallback::UrlVerification { challenge } => Ok(Some(challenge)),
+            SlackCallback::EventCallback { event } => {
+                let client = self.clone();
+                tokio::spawn(async move { client.handle_event(event).await });
+                Ok(None)
+            }
+            SlackCallback::Other => Ok(None),
+        }
+    }
+
+    /// Calls a Web API method, waiting out rate limits. Slack reports most errors as
+    /// `"ok": false` in a 200 response.
+    async fn call(&self, method: &str, token: &str, body: &Value) -> Result<Value> {
+        let url = format!("{}/{}", self.config.api_url.trim_end_matches('/'), method);
+        let mut attempt = 1;
+        loop {
+            let response = self
+                .http
+                .post(&url)
+                .bearer_auth(token)
+                .json(body)
+                .send()
+                .await
+                .with_context(|| format!("Slack {} request failed", method))?;
+
+            let status = response.status();
+            if status == StatusCode::TOO_MANY_REQUESTS && attempt < MAX_CALL_ATTEMPTS {
+                let retry_after = response
+                    .headers()
+                    .get("retry-after")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(1);
+                tokio::time::sleep(Duration::from_secs(retry_after)).await;
+                attempt += 1;
+                continue;
+            }
+            if !status.is_success() {
+                let error_text = response.text().await.unwrap_or_default();
+                anyhow::bail!("Slack {} returned {}: {}", method, status, error_text);
+            }
+
+            let response: Value = response
+                .json()
+                .await
+                .with_context(|| format!("Slack {} returned invalid JSON", method))?;
+            if response["ok"].as_bool() != Some(true) {
+                anyhow::bail!(
+                    "Slack {} failed: {}",
+                    method,
+                    response["error"].as_str().unwrap_or("unknown error")
+                );
+            }
+            return Ok(response);
+        }
+    }
+
+    /// Receives events over Socket Mode until the process exits, reconnecting whenever the
+    /// connection drops or Slack asks for a refresh.
+    async fn run_socket_mode(self, app_token: String) {
+        loop {
+            if let Err(e) = self.socket_session(&app_token).await {
+                tracing::warn!(error = %e, "Slack Socket Mode connection failed");
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    async fn socket_session(&self, app_token: &str) -> Result<()> {
+        let response = self
+            .call("apps.connections.open", app_token, &json!({}))
+            .await?;
+        let url = response["url"]
+            .as_str()
+            .context("Slack apps.connections.open returned no url")?;
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await?;
+        tracing::info!("Slack Socket Mode connected");
+
+        while let Some(frame) = socket.next().await {
+            let text = match frame? {
+                WsMessage::Text(text) => text,
+                WsMessage::Close(_) => break,
+                _ => continue,
+            };
+            let envelope: SocketEnvelope = match serde_json::from_str(&text) {
+                Ok(envelope) => envelope,
+                Err(e) => {
+                    tracing::warn!(error = %e, "ignoring malformed Socket Mode message");
+                    continue;
+                }
+            };
+            // Slack redelivers envelopes that aren't acknowledged
+            if let Some(envelope_id) = &envelope.envelope_id {
+                let ack = json!({ "envelope_id": envelope_id }).to_string();
+                socket.send(WsMessage::Text(ack)).await?;
+            }
+            match (envelope.kind.as_str(), envelope.payload) {
+                ("events_api", Some(payload)) => {
+                    if let Err(e) = self.handle_callback(payload.to_string().as_bytes()) {
+                        tracing::warn!(error = %e, "ignoring Socket Mode event");
+                    }
+                }
+                ("disconnect", _) => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_event(&self, event: SlackEvent) {
+        if event.kind != "message" || event.subtype.is_some() {
+            return;
+        }
+        let (Some(user), Some(channel), Some(text), Some(ts)) =
+            (&event.user, &event.channel, &event.text, &event.ts)
+        else {
+            return;
+        };
+        if self.bot_user_id.get() == Some(user) {
+            return;
+        }
+        let Some(command) = TaskCommand::parse(&unescape(text)) else {
+            return;
+        };
+
+        let reply = match command {
+            Ok(command) => self.run(command, user, channel, &event).await,
+            Err(usage) => Some(usage),
+        };
+        if let Some(reply) = reply {
+            let thread_ts = event.thread_ts.as_deref().unwrap_or(ts);
+            if let Err(e) = self.post_message(channel, &reply, Some(thread_ts)).await {
+                tracing::warn!(channel = %channel, error = %e, "failed to reply to Slack command");
+            }
+        }
+    }
+
+    /// Authorizes and runs a command, returning the reply, or `None` for commands from
+    /// outside the task's team channels, which are ignored.
+    async fn run(
+        &self,
+        command: TaskCommand,
+        user: &str,
+        channel: &str,
+        event: &SlackEvent,
+    ) -> Option<String> {
+        match self.authorize(&command, user, channel).await {
+            Ok(Authorization::Allowed) => {}
+            Ok(Authorization::Denied(reason)) => {
+                tracing::warn!(user, channel, task_id = %command.task_id(), "rejected Slack task command: {}", reason);
+                return Some(format!("Not allowed: {}.", reason));
+            }
+            Ok(Authorization::Ignored(reason)) => {
+                tracing::warn!(user, channel, task_id = %command.task_id(), "ignored Slack task command: {}", reason);
+                return None;
+            }
+            Err(e) => {
+                tracing::error!("Failed authorizing {:?}: {}", command, e);
+                return Some("Failed to update task status.".to_string());
+            }
+        }
+
+        let Some(receiver) = self.receiver.get() else {
+            return Some("Task commands aren't being received yet.".to_string());
+        };
+        let outcome = match receiver.execute(&command).await {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                tracing::error!("Failed running {:?}: {}", command, e);
+                return Some("Failed to update task status.".to_string());
+            }
+        };
+
+        // The reply already shows the update when the command was sent in the task's thread
+        if let (Some(log), Some(task)) = (&outcome.log, &outcome.task) {
+            let sent_in_thread = task.thread_id.is_some() && task.thread_id == event.thread_ts;
+            if !sent_in_thread {
+                if let Err(e) = self.log_to_team(task, log).await {
+                    tracing::warn!(task_id = %task.id, error = %e, "failed to log task command");
+                }
+            }
+        }
+        Some(outcome.reply)
+    }
+
+    /// Commands must come from one of the team's Slack channels. Anyone there may ask for a
+    /// task's status; changing a task takes one of the team's Slack operators. Agents aren't
+    /// mapped to Slack users, so an assigned agent's bot has to be listed as an operator.
+    async fn authorize(
+        &self,
+        command: &TaskCommand,
+        user: &str,
+        channel: &str,
+    ) -> Result<Authorization> {
+        let task_repo = repositories::TaskRepository::new(self.db.db().clone());
+        let Some(task) = task_repo.get_by_id(command.task_id()).await? else {
+            return Ok(Authorization::Ignored("unknown task".to_string()));
+        };
+        let team_repo = repositories::TeamRepository::new(self.db.db().clone());
+        let Some(team) = team_repo.get_by_id(task.team_id).await? else {
+            return Ok(Authorization::Ignored("task has no team".to_string()));
+        };
+
+        let in_team_channel = team.slack_channels.as_ref().is_some_and(|channels| {
+            [
+                &channels.coordination_logs,
+                &channels.slave_communication,
+                &channels.master_orders,
+            ]
+            .iter()
+            .any(|team_channel| team_channel.as_str() == channel)
+        });
+        if !in_team_channel {
+            return Ok(Authorization::Ignored(
+                "not one of the team's Slack channels".to_string(),
+            ));
+        }
+
+        let operators = &team.settings.slack_operator_user_ids;
+        if matches!(command, TaskCommand::Status { .. })
+            || operators.iter().any(|operator| operator == user)
+        {
+            Ok(Authorization::Allowed)
+        } else if operators.is_empty() {
+            Ok(Authorization::Denied(
+                "the team has no Slack operators, so tasks can't be changed from Slack".to_string(),
+            ))
+        } else {
+            Ok(Authorization::Denied(
+                "only the team's Slack operators can change tasks".to_string(),
+            ))
+        }
+    }
+
+    async fn log_to_team(&self, task: &Task, message: &str) -> Result<()> {
+        let team_repo = repositories::TeamRepository::new(self.db.db().clone());
+        let Some(team) = team_repo.get_by_id(task.team_id).await? else {
+            return Ok(());
+        };
+        self.log(&team, Some(task), message).await
+    }
+}
+
+/// Where a message for the task goes: its thread in `master_orders` if it has one,
+/// otherwise `channel`.
+fn destination<'a>(
+    channels: &'a SlackChannels,
+    task: Option<&'a Task>,
+    channel: &'a str,
+) -> (&'a str, Option<&'a str>) {
+    match task.and_then(|task| task.thread_id.as_deref()) {
+        Some(thread_ts) => (&channels.master_orders, Some(thread_ts)),
+        None => (channel, None),
+    }
+}
+
+fn slack_channels(team: &Team) -> Result<&SlackChannels> {
+    team.slack_channels
+        .as_ref()
+        .with_context(|| format!("team {} has no Slack channels", team.id))
+}
+
+#[async_trait]
+impl CoordinationTransport for SlackClient {
+    /// Commands come over Socket Mode when an app token is configured; otherwise Slack must
+    /// be set up to POST events to the API server.
+    async fn start(&self, receiver: CommandReceiver) -> Result<()> {
+        let _ = self.receiver.set(receiver);
+
+        match self
+            .call("auth.test", &self.config.bot_token, &json!({}))
+            .await
+        {
+            Ok(response) => {
+                if let Some(user_id) = response["user_id"].as_str() {
+                    let _ = self.bot_user_id.set(user_id.to_string());
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "Slack auth.test failed"),
+        }
+
+        if let Some(app_token) = self.config.app_token.clone() {
+            tokio::spawn(self.clone().run_socket_mode(app_token));
+        }
+        tracing::info!("Slack client initialized");
+        Ok(())
+    }
+
+    async fn send_order(&self, team: &Team, task: &Task, message: &str) -> Result<()> {
+        let channels = slack_channels(team)?;
+        let (channel, thread_ts) = destination(channels, Some(task), &channels.master_orders);
+        self.post_message(channel, &format!("👑 [MASTER] {}", message), thread_ts)
+            .await?;
+        Ok(())
+    }
+
+    async fn send_slave_message(&self, team: &Team, task: &Task, message: &str) -> Result<()> {
+        let channels = slack_channels(team)?;
+        let (channel, thread_ts) = destination(channels, Some(task), &channels.slave_communication);
+        self.post_message(channel, &format!("🤖 [SLAVE] {}", message), thread_ts)
+            .await?;
+        Ok(())
+    }
+
+    async fn log(&self, team: &Team, task: Option<&Task>, message: &str) -> Result<()> {
+        let channels = slack_channels(team)?;
+        let (channel, thread_ts) = destination(channels, task, &channels.coordination_logs);
+        self.post_message(channel, &format!("📊 [COORD] {}", message), thread_ts)
+            .await?;
+        Ok(())
+    }
+
+    /// The thread hangs off a message introducing the task in `master_orders`.
+    async fn open_thread(&self, team: &Team, task: &Task) -> Result<Option<String>> {
+        let channels = slack_channels(team)?;
+        let message = format!("*Task {}*\n{}", short_id(task.id), task.description);
+        let thread_ts = self
+            .post_message(&channels.master_orders, &message, None)
+            .await?;
+        Ok(Some(thread_ts))
+    }
+
+    /// Slack threads can't be archived, so this only posts the outcome.
+    async fn close_thread(&self, team: &Team, task: &Task) -> Result<()> {
+        let channels = slack_channels(team)?;
+        let Some(thread_ts) = &task.thread_id else {
+            return Ok(());
+        };
+        let message = format!(
+            "**Task {}**\nTask ID: `{}`\n{}",
+            task.status.as_str(),
+            task.id,
+            task.result.as_deref().unwrap_or("No result.")
+        );
+        self.post_message(&channels.master_orders, &message, Some(thread_ts))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Slack's mrkdwn: `*bold*` rather than Markdown's `**bold**`, and `&`, `<` and `>`
+/// escaped.
+fn to_mrkdwn(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace("**", "*")
+}
+
+/// Message text as typed, undoing Slack's escaping.
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
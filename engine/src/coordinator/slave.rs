@@ -1,4 +1,5 @@
-use crate::coordinator::discord::DiscordClient;
+use crate::coordinator::dependencies;
+use crate::coordinator::transport::Transports;
 use crate::models::{Task, TaskStatus, Team};
 use crate::storage::{repositories, Database};
 use anyhow::Result;
@@ -7,46 +8,59 @@ use uuid::Uuid;
 #[derive(Clone)]
 pub struct SlaveCoordinator {
     db: Database,
-    discord_client: Option<DiscordClient>,
+    transports: Transports,
 }
 
 impl SlaveCoordinator {
-    pub fn new(db: Database, discord_client: Option<DiscordClient>) -> Self {
-        Self { db, discord_client }
+    pub fn new(db: Database, transports: Transports) -> Self {
+        Self { db, transports }
     }
 
     pub async fn execute_task(&self, _task: &Task) -> Result<String> {
         // Task execution is handled by the OpenClaw agent instance
         // This coordinator just tracks the task status
-        // The actual execution happens when the OpenClaw agent receives the task via Discord
+        // The actual execution happens when the OpenClaw agent receives the task over the
+        // team's transport
 
         // Simulate task execution result
         Ok("Task executed by OpenClaw agent".to_string())
     }
 
+    /// Sends a task assigned directly to a slave (rather than delegated by the master) over
+    /// the team's transport, with the results of any upstream tasks.
+    pub async fn notify_task(&self, team: &Team, task: &Task) -> Result<()> {
+        let Some(slave_id) = task.assigned_to else {
+            return Ok(());
+        };
+        let message = format!(
+            "**Task for Slave {}**\nTask ID: `{}`\n{}\n\nReply with `!task-complete {}` and your result when finished.",
+            slave_id, task.id, task.description, task.id
+        );
+        let context = dependencies::upstream_context(&self.db, task).await?;
+        let message = dependencies::with_context(message, context.as_deref());
+        self.transports
+            .for_team(team)
+            .send_slave_message(team, task, &message)
+            .await
+    }
+
     pub async fn report_result(&self, task_id: Uuid, result: &str, team: &Team) -> Result<()> {
         let task_repo = repositories::TaskRepository::new(self.db.db().clone());
-        task_repo
+        let task = task_repo
             .update_fields(
                 task_id,
                 Some(TaskStatus::Completed),
                 Some(result.to_string()),
             )
-            .await?;
-
-        // Send result to slave communication channel
-        if let Some(discord) = &self.discord_client {
-            let message = format!("**Task {} Completed**\nResult: {}", task_id, result);
-            discord
-                .send_slave_message(&team.discord_channels.slave_communication, &message)
-                .await?;
-
-            // Also log to coordination channel
-            let log_message = format!("Slave reported completion for task {}: {}", task_id, result);
-            discord
-                .log_coordination(&team.discord_channels.coordination_logs, &log_message)
-                .await?;
-        }
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Task {} not found", task_id))?;
+
+        // Send result to the slaves, and log it for the team
+        let transport = self.transports.for_team(team);
+        let message = format!("**Task {} Completed**\nResult: {}", task_id, result);
+        transport.send_slave_message(team, &task, &message).await?;
+        let log_message = format!("Slave reported completion for task {}: {}", task_id, result);
+        transport.log(team, Some(&task), &log_message).await?;
 
         tracing::info!("Task {} result reported: {}", task_id, result);
         Ok(())
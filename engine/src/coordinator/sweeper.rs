@@ -0,0 +1,143 @@
+use crate::coordinator::{log_undelivered, Coordinator};
+use crate::models::{Task, TaskStatus};
+use crate::storage::{repositories, Database};
+use anyhow::Result;
+use chrono::Utc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Outcome of a single sweep, mostly for logging.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SweepSummary {
+    pub timed_out: usize,
+    pub retried: usize,
+    pub queued: usize,
+    pub given_up: usize,
+}
+
+/// Periodically times out tasks whose current attempt ran past its deadline. Tasks with
+/// attempts left are retried, on another slave when one fits (see
+/// [`crate::coordinator::master::MasterCoordinator::reroute`]); the rest are marked
+/// `timed_out`, which cancels their dependents. The master is told either way.
+#[derive(Clone)]
+pub struct TaskSweeper {
+    db: Database,
+    coordinator: Coordinator,
+    interval: Duration,
+}
+
+impl TaskSweeper {
+    pub fn new(db: Database, coordinator: Coordinator) -> Self {
+        Self {
+            db,
+            coordinator,
+            interval: Duration::from_secs(30),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn start(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                ticker.tick().await;
+                match self.sweep_once().await {
+                    Ok(summary) if summary.timed_out > 0 => {
+                        tracing::info!(?summary, "timed out overdue tasks");
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!(error = %e, "task timeout sweep failed"),
+                }
+            }
+        })
+    }
+
+    pub async fn sweep_once(&self) -> Result<SweepSummary> {
+        let task_repo = repositories::TaskRepository::new(self.db.db().clone());
+        let mut summary = SweepSummary::default();
+
+        for task in task_repo.claim_overdue(Utc::now()).await? {
+            summary.timed_out += 1;
+            if let Err(e) = self.time_out(&task, &mut summary).await {
+                tracing::warn!(task_id = %task.id, error = %e, "failed to handle timed-out task");
+            }
+        }
+
+        Ok(summary)
+    }
+
+    async fn time_out(&self, task: &Task, summary: &mut SweepSummary) -> Result<()> {
+        let task_repo = repositories::TaskRepository::new(self.db.db().clone());
+        let team_repo = repositories::TeamRepository::new(self.db.db().clone());
+        task_repo
+            .end_attempt(task.id, &TaskStatus::TimedOut)
+            .await?;
+        let Some(team) = team_repo.get_by_id(task.team_id).await? else {
+            return Ok(());
+        };
+        let master = self.coordinator.master();
+
+        if task.attempts >= task.max_attempts {
+            let result = match task.attempts {
+                1 => "timed out".to_string(),
+                attempts => format!("timed out after {} attempts", attempts),
+            };
+            if let Some(finished) = task_repo
+                .finish_open(task.id, TaskStatus::TimedOut, &result)
+                .await?
+            {
+                summary.given_up += 1;
+                log_undelivered(
+                    task.id,
+                    master
+                        .report_timeout(&team, task, "No attempts left; giving up.")
+                        .await,
+                );
+                self.coordinator.task_finished(&finished).await?;
+            }
+            return Ok(());
+        }
+
+        match master.reroute(&team, task).await? {
+            Some(decision) => {
+                let Some(retry) = task_repo
+                    .reassign_for_retry(task.id, Some(decision.agent_id), &decision.reason)
+                    .await?
+                else {
+                    return Ok(());
+                };
+                summary.retried += 1;
+                let next_step = format!("Retrying on agent {}.", decision.agent_id);
+                log_undelivered(
+                    task.id,
+                    master.report_timeout(&team, task, &next_step).await,
+                );
+                self.coordinator.dispatch_task(&retry).await?;
+            }
+            None => {
+                let reason = "queued: retry waiting for an available slave";
+                if task_repo
+                    .reassign_for_retry(task.id, None, reason)
+                    .await?
+                    .is_some()
+                {
+                    summary.queued += 1;
+                    log_undelivered(
+                        task.id,
+                        master
+                            .report_timeout(&team, task, "Queued until a slave is available.")
+                            .await,
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
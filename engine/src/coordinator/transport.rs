@@ -0,0 +1,316 @@
+use crate::coordinator::commands::{self, CommandOutcome, TaskCommand};
+use crate::coordinator::discord::DiscordClient;
+use crate::coordinator::slack::SlackClient;
+use crate::models::{Task, Team, TransportConfig, WebhookTransportConfig};
+use crate::storage::Database;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::{broadcast, mpsc};
+use uuid::Uuid;
+
+/// Messages the in-process transport keeps; older ones are dropped.
+const RETAINED_MESSAGES: usize = 1000;
+
+/// Delivers a team's coordination messages and receives the task commands agents send
+/// back. Orders go to the master, slave messages to the slaves and log entries to whoever
+/// follows the team; transports with threads keep each top-level task in its own.
+#[async_trait]
+pub trait CoordinationTransport: Send + Sync {
+    /// Starts receiving task commands, which are run through `receiver`.
+    async fn start(&self, receiver: CommandReceiver) -> Result<()>;
+
+    async fn send_order(&self, team: &Team, task: &Task, message: &str) -> Result<()>;
+
+    async fn send_slave_message(&self, team: &Team, task: &Task, message: &str) -> Result<()>;
+
+    /// Posts to the team's coordination log, or the task's thread when given one that has it.
+    async fn log(&self, team: &Team, task: Option<&Task>, message: &str) -> Result<()>;
+
+    /// Opens a thread for a top-level task and returns its id, to be stored on the task.
+    /// Transports without threads return `None`.
+    async fn open_thread(&self, _team: &Team, _task: &Task) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Posts a finished task's outcome to its thread and closes it.
+    async fn close_thread(&self, _team: &Team, _task: &Task) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs task commands received by a transport. Tasks they finish are handed to the
+/// coordinator, which follows up with [`crate::coordinator::Coordinator::task_finished`].
+#[derive(Clone)]
+pub struct CommandReceiver {
+    db: Database,
+    finished: mpsc::UnboundedSender<Task>,
+}
+
+impl CommandReceiver {
+    pub fn new(db: Database, finished: mpsc::UnboundedSender<Task>) -> Self {
+        Self { db, finished }
+    }
+
+    pub async fn execute(&self, command: &TaskCommand) -> Result<CommandOutcome> {
+        let outcome = commands::execute(&self.db, command).await?;
+        if outcome.finished {
+            if let Some(task) = &outcome.task {
+                let _ = self.finished.send(task.clone());
+            }
+        }
+        Ok(outcome)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKind {
+    Order,
+    SlaveMessage,
+    Log,
+}
+
+/// A message as delivered by the in-process and webhook transports.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveredMessage {
+    pub kind: MessageKind,
+    pub team_id: Uuid,
+    pub task_id: Option<Uuid>,
+    pub message: String,
+    pub sent_at: DateTime<Utc>,
+}
+
+impl DeliveredMessage {
+    fn new(kind: MessageKind, team: &Team, task: Option<&Task>, message: &str) -> Self {
+        Self {
+            kind,
+            team_id: team.id,
+            task_id: task.map(|task| task.id),
+            message: message.to_string(),
+            sent_at: Utc::now(),
+        }
+    }
+}
+
+/// The first segment of a task id, enough to tell task threads apart.
+pub(crate) fn short_id(id: Uuid) -> String {
+    id.to_string().chars().take(8).collect()
+}
+
+/// Resolves the transport each team is configured with.
+#[derive(Clone)]
+pub struct Transports {
+    discord: Option<DiscordClient>,
+    slack: Option<SlackClient>,
+    in_process: InProcessTransport,
+    http: Client,
+}
+
+impl Transports {
+    pub fn new(
+        discord: Option<DiscordClient>,
+        slack: Option<SlackClient>,
+        in_process: InProcessTransport,
+    ) -> Self {
+        Self {
+            discord,
+            slack,
+            in_process,
+            http: Client::new(),
+        }
+    }
+
+    /// Teams on Discord or Slack use the in-process transport while the server has no bot
+    /// token for it, so their messages can still be followed.
+    pub fn for_team(&self, team: &Team) -> Box<dyn CoordinationTransport> {
+        match &team.settings.transport {
+            TransportConfig::Discord => match &self.discord {
+                Some(discord) => Box::new(discord.clone()),
+                None => Box::new(self.in_process.clone()),
+            },
+            TransportConfig::Slack => match &self.slack {
+                Some(slack) => Box::new(slack.clone()),
+                None => Box::new(self.in_process.clone()),
+            },
+            TransportConfig::InProcess => Box::new(self.in_process.clone()),
+            TransportConfig::Webhook(config) => {
+                Box::new(WebhookTransport::new(self.http.clone(), config.clone()))
+            }
+        }
+    }
+
+    pub fn discord(&self) -> Option<&DiscordClient> {
+        self.discord.as_ref()
+    }
+
+    pub fn slack(&self) -> Option<&SlackClient> {
+        self.slack.as_ref()
+    }
+
+    pub fn in_process(&self) -> &InProcessTransport {
+        &self.in_process
+    }
+}
+
+/// Keeps messages in memory and publishes them to subscribers, for teams coordinated from
+/// within the server and for tests. Commands are sent with [`Self::send_command`].
+#[derive(Clone)]
+pub struct InProcessTransport {
+    messages: Arc<Mutex<VecDeque<DeliveredMessage>>>,
+    sender: broadcast::Sender<DeliveredMessage>,
+    receiver: Arc<OnceLock<CommandReceiver>>,
+}
+
+impl InProcessTransport {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(RETAINED_MESSAGES);
+        Self {
+            messages: Arc::new(Mutex::new(VecDeque::new())),
+            sender,
+            receiver: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// The retained messages, oldest first.
+    pub fn messages(&self) -> Vec<DeliveredMessage> {
+        let messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+        messages.iter().cloned().collect()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DeliveredMessage> {
+        self.sender.subscribe()
+    }
+
+    /// Runs a task command as if an agent had sent it.
+    pub async fn send_command(&self, command: &TaskCommand) -> Result<CommandOutcome> {
+        let receiver = self
+            .receiver
+            .get()
+            .context("in-process transport has not been started")?;
+        receiver.execute(command).await
+    }
+
+    fn deliver(&self, message: DeliveredMessage) {
+        tracing::debug!(
+            team_id = %message.team_id,
+            kind = ?message.kind,
+            "coordination message: {}",
+            message.message
+        );
+        {
+            let mut messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+            if messages.len() == RETAINED_MESSAGES {
+                messages.pop_front();
+            }
+            messages.push_back(message.clone());
+        }
+        let _ = self.sender.send(message);
+    }
+}
+
+impl Default for InProcessTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl CoordinationTransport for InProcessTransport {
+    async fn start(&self, receiver: CommandReceiver) -> Result<()> {
+        let _ = self.receiver.set(receiver);
+        Ok(())
+    }
+
+    async fn send_order(&self, team: &Team, task: &Task, message: &str) -> Result<()> {
+        self.deliver(DeliveredMessage::new(
+            MessageKind::Order,
+            team,
+            Some(task),
+            message,
+        ));
+        Ok(())
+    }
+
+    async fn send_slave_message(&self, team: &Team, task: &Task, message: &str) -> Result<()> {
+        self.deliver(DeliveredMessage::new(
+            MessageKind::SlaveMessage,
+            team,
+            Some(task),
+            message,
+        ));
+        Ok(())
+    }
+
+    async fn log(&self, team: &Team, task: Option<&Task>, message: &str) -> Result<()> {
+        self.deliver(DeliveredMessage::new(MessageKind::Log, team, task, message));
+        Ok(())
+    }
+}
+
+/// POSTs each message as JSON to the team's URL. Agents send commands back through
+/// `POST /api/teams/:id/commands`.
+pub struct WebhookTransport {
+    http: Client,
+    config: WebhookTransportConfig,
+}
+
+impl WebhookTransport {
+    pub fn new(http: Client, config: WebhookTransportConfig) -> Self {
+        Self { http, config }
+    }
+
+    async fn post(&self, message: DeliveredMessage) -> Result<()> {
+        let mut request = self.http.post(&self.config.url).json(&message);
+        if let Some(secret) = &self.config.secret {
+            request = request.bearer_auth(secret);
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("webhook request to {} failed", self.config.url))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!("webhook returned {}: {}", status, error_text);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CoordinationTransport for WebhookTransport {
+    /// Commands arrive through the API rather than a connection of the transport's own.
+    async fn start(&self, _receiver: CommandReceiver) -> Result<()> {
+        Ok(())
+    }
+
+    async fn send_order(&self, team: &Team, task: &Task, message: &str) -> Result<()> {
+        self.post(DeliveredMessage::new(
+            MessageKind::Order,
+            team,
+            Some(task),
+            message,
+        ))
+        .await
+    }
+
+    async fn send_slave_message(&self, team: &Team, task: &Task, message: &str) -> Result<()> {
+        self.post(DeliveredMessage::new(
+            MessageKind::SlaveMessage,
+            team,
+            Some(task),
+            message,
+        ))
+        .await
+    }
+
+    async fn log(&self, team: &Team, task: Option<&Task>, message: &str) -> Result<()> {
+        self.post(DeliveredMessage::new(MessageKind::Log, team, task, message))
+            .await
+    }
+}
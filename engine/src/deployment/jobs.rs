@@ -0,0 +1,366 @@
+use crate::deployment::manager::DeploymentManager;
+use crate::models::{
+    Agent, Deployment, DeploymentJob, DeploymentJobKind, DeploymentJobStatus, DeploymentStatus,
+    VpsProvider,
+};
+use crate::storage::{repositories, Database};
+use anyhow::Result;
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// Worker count and retry policy for [`DeploymentJobQueue`].
+#[derive(Debug, Clone, Copy)]
+pub struct DeploymentJobOptions {
+    pub workers: usize,
+    /// How often idle workers check for due jobs (enqueueing also wakes them).
+    pub poll_interval: Duration,
+    /// Attempts per job, including the first.
+    pub max_attempts: i32,
+    /// Delay before the first retry; doubles on every subsequent one.
+    pub retry_backoff: Duration,
+}
+
+impl Default for DeploymentJobOptions {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            poll_interval: Duration::from_secs(5),
+            max_attempts: 3,
+            retry_backoff: Duration::from_secs(15),
+        }
+    }
+}
+
+/// Persisted queue of deployments, drained by background workers so callers
+/// don't block on provider provisioning.
+#[derive(Clone)]
+pub struct DeploymentJobQueue {
+    db: Database,
+    manager: DeploymentManager,
+    options: DeploymentJobOptions,
+    wake: Arc<Notify>,
+}
+
+impl DeploymentJobQueue {
+    pub fn new(db: Database, manager: DeploymentManager) -> Self {
+        Self {
+            db,
+            manager,
+            options: DeploymentJobOptions::default(),
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn with_options(mut self, options: DeploymentJobOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub async fn enqueue_deploy(
+        &self,
+        agent_id: Uuid,
+        provider: VpsProvider,
+        region: Option<String>,
+        railway_api_key: Option<String>,
+    ) -> Result<DeploymentJob> {
+        let job = self.new_job(
+            DeploymentJobKind::Deploy,
+            vec![agent_id],
+            provider,
+            region,
+            railway_api_key,
+        );
+        self.enqueue(job).await
+    }
+
+    pub async fn enqueue_deploy_multi(
+        &self,
+        agent_ids: Vec<Uuid>,
+        provider: VpsProvider,
+        region: Option<String>,
+        railway_api_key: Option<String>,
+    ) -> Result<DeploymentJob> {
+        if agent_ids.is_empty() {
+            anyhow::bail!("At least one agent required for multi-agent deploy");
+        }
+
+        let job = self.new_job(
+            DeploymentJobKind::DeployMulti,
+            agent_ids,
+            provider,
+            region,
+            railway_api_key,
+        );
+        self.enqueue(job).await
+    }
+
+    /// Queues pushing `agent`'s unsaved settings to its deployment; they are saved once the
+    /// push succeeds.
+    pub async fn enqueue_update_settings(
+        &self,
+        agent: Agent,
+        deployment: &Deployment,
+        railway_api_key: Option<String>,
+    ) -> Result<DeploymentJob> {
+        if agent.deployment_id != Some(deployment.id) {
+            anyhow::bail!(
+                "Agent {} is not part of deployment {}",
+                agent.id,
+                deployment.id
+            );
+        }
+
+        let mut job = self.new_job(
+            DeploymentJobKind::UpdateSettings,
+            vec![agent.id],
+            deployment.provider.clone(),
+            deployment.region.clone(),
+            railway_api_key,
+        );
+        job.deployment_id = Some(deployment.id);
+        job.agent_settings = Some(agent);
+        self.enqueue(job).await
+    }
+
+    /// Queues re-applying `revision` of `deployment`; see [`DeploymentManager::rollback`].
+    pub async fn enqueue_rollback(
+        &self,
+        deployment: &Deployment,
+        revision: i32,
+        railway_api_key: Option<String>,
+    ) -> Result<DeploymentJob> {
+        let agent_ids = deployment
+            .agent_ids
+            .clone()
+            .unwrap_or_else(|| vec![deployment.agent_id]);
+        let mut job = self.new_job(
+            DeploymentJobKind::Rollback,
+            agent_ids,
+            deployment.provider.clone(),
+            deployment.region.clone(),
+            railway_api_key,
+        );
+        job.deployment_id = Some(deployment.id);
+        job.revision = Some(revision);
+        self.enqueue(job).await
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Option<DeploymentJob>> {
+        repositories::DeploymentJobRepository::new(self.db.db().clone())
+            .get_by_id(id)
+            .await
+    }
+
+    /// Requeues jobs interrupted by a previous shutdown and spawns the workers.
+    ///
+    /// Assumes a single orchestrator process owns the queue: anything still `running`
+    /// at startup is treated as abandoned.
+    pub async fn start(&self) -> Result<Vec<JoinHandle<()>>> {
+        let repo = repositories::DeploymentJobRepository::new(self.db.db().clone());
+        let resumed = repo.requeue_running().await?;
+        if resumed > 0 {
+            tracing::info!(jobs = resumed, "resuming interrupted deployment jobs");
+        }
+
+        Ok((0..self.options.workers.max(1))
+            .map(|worker| {
+                let queue = self.clone();
+                tokio::spawn(async move { queue.run_worker(worker).await })
+            })
+            .collect())
+    }
+
+    fn new_job(
+        &self,
+        kind: DeploymentJobKind,
+        agent_ids: Vec<Uuid>,
+        provider: VpsProvider,
+        region: Option<String>,
+        railway_api_key: Option<String>,
+    ) -> DeploymentJob {
+        let now = Utc::now();
+        DeploymentJob {
+            id: Uuid::new_v4(),
+            kind,
+            agent_ids,
+            provider,
+            region,
+            railway_api_key,
+            agent_settings: None,
+            revision: None,
+            status: DeploymentJobStatus::Queued,
+            attempts: 0,
+            max_attempts: self.options.max_attempts.max(1),
+            scheduled_at: now,
+            deployment_id: None,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    async fn enqueue(&self, job: DeploymentJob) -> Result<DeploymentJob> {
+        repositories::DeploymentJobRepository::new(self.db.db().clone())
+            .create(&job)
+            .await?;
+        self.wake.notify_one();
+
+        Ok(job)
+    }
+
+    async fn run_worker(self, worker: usize) {
+        let repo = repositories::DeploymentJobRepository::new(self.db.db().clone());
+
+        loop {
+            match repo.claim_next().await {
+                Ok(Some(job)) => {
+                    self.process(&repo, job).await;
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!(worker, error = %e, "failed to claim deployment job");
+                }
+            }
+
+            tokio::select! {
+                _ = self.wake.notified() => {}
+                _ = tokio::time::sleep(self.options.poll_interval) => {}
+            }
+        }
+    }
+
+    async fn process(&self, repo: &repositories::DeploymentJobRepository, job: DeploymentJob) {
+        tracing::info!(
+            job_id = %job.id,
+            attempt = job.attempts,
+            max_attempts = job.max_attempts,
+            "running deployment job"
+        );
+
+        let outcome = match self.execute(repo, &job).await {
+            Ok(deployment) => {
+                tracing::info!(job_id = %job.id, deployment_id = %deployment.id, "deployment job succeeded");
+                repo.mark_succeeded(job.id, deployment.id).await
+            }
+            Err(e) if job.attempts < job.max_attempts => {
+                let backoff =
+                    self.options.retry_backoff * 2u32.pow(job.attempts.saturating_sub(1) as u32);
+                tracing::warn!(job_id = %job.id, error = %e, retry_in = ?backoff, "deployment job failed, retrying");
+                repo.reschedule(
+                    job.id,
+                    &format!("{:#}", e),
+                    Utc::now() + chrono::Duration::from_std(backoff).unwrap_or_default(),
+                )
+                .await
+            }
+            Err(e) => {
+                tracing::error!(job_id = %job.id, error = %e, "deployment job failed");
+                repo.mark_failed(job.id, &format!("{:#}", e)).await
+            }
+        };
+
+        if let Err(e) = outcome {
+            tracing::error!(job_id = %job.id, error = %e, "failed to record deployment job outcome");
+        }
+    }
+
+    async fn execute(
+        &self,
+        repo: &repositories::DeploymentJobRepository,
+        job: &DeploymentJob,
+    ) -> Result<Deployment> {
+        let deployment_repo = repositories::DeploymentRepository::new(self.db.db().clone());
+
+        if matches!(job.kind, DeploymentJobKind::UpdateSettings) {
+            let agent = job
+                .agent_settings
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("Settings job {} has no agent settings", job.id))?;
+            let deployment_id = job
+                .deployment_id
+                .ok_or_else(|| anyhow::anyhow!("Settings job {} has no deployment", job.id))?;
+            self.manager
+                .update_agent_settings(agent, job.railway_api_key.clone())
+                .await?;
+            return deployment_repo
+                .get_by_id(deployment_id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Deployment {} not found", deployment_id));
+        }
+
+        if matches!(job.kind, DeploymentJobKind::Rollback) {
+            let revision = job
+                .revision
+                .ok_or_else(|| anyhow::anyhow!("Rollback job {} has no revision", job.id))?;
+            let deployment_id = job
+                .deployment_id
+                .ok_or_else(|| anyhow::anyhow!("Rollback job {} has no deployment", job.id))?;
+            self.manager
+                .rollback(deployment_id, revision, job.railway_api_key.clone())
+                .await?;
+            return deployment_repo
+                .get_by_id(deployment_id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Deployment {} not found", deployment_id));
+        }
+
+        // A previous attempt (or a process that died mid-job) may have left a deployment behind:
+        // keep waiting on it if the provider accepted it, otherwise tear it down and start over.
+        if let Some(deployment_id) = job.deployment_id {
+            if let Some(previous) = deployment_repo.get_by_id(deployment_id).await? {
+                let in_flight = previous.provider_id.is_some()
+                    && matches!(
+                        previous.status,
+                        DeploymentStatus::Creating | DeploymentStatus::Running
+                    );
+                if in_flight {
+                    return self
+                        .manager
+                        .wait_until_running(&previous, job.railway_api_key.clone())
+                        .await;
+                }
+                self.manager
+                    .abandon_deployment(&previous, job.railway_api_key.clone())
+                    .await?;
+            }
+        }
+
+        let agents = self.load_agents(&job.agent_ids).await?;
+        let deployment = self
+            .manager
+            .create_deployment(
+                &agents,
+                job.provider.clone(),
+                job.region.clone(),
+                matches!(job.kind, DeploymentJobKind::DeployMulti),
+            )
+            .await?;
+        repo.update_deployment_id(job.id, deployment.id).await?;
+
+        let deployment = self
+            .manager
+            .provision(&deployment, &agents, job.railway_api_key.clone())
+            .await?;
+        self.manager
+            .wait_until_running(&deployment, job.railway_api_key.clone())
+            .await
+    }
+
+    async fn load_agents(&self, agent_ids: &[Uuid]) -> Result<Vec<Agent>> {
+        let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
+        let mut agents = Vec::with_capacity(agent_ids.len());
+        for id in agent_ids {
+            let agent = agent_repo
+                .get_by_id(*id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Agent {} not found", id))?;
+            agents.push(agent);
+        }
+        Ok(agents)
+    }
+}
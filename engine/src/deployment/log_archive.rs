@@ -0,0 +1,171 @@
+use crate::adapters::trait_def::DeploymentId;
+use crate::adapters::{LogStreamOptions, VpsAdapters, VpsProvider};
+use crate::models::{Deployment, DeploymentStatus};
+use crate::storage::{repositories, Database};
+use anyhow::Result;
+use chrono::Utc;
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Lines fetched on a deployment's first archive pass; later passes resume from the newest
+/// archived timestamp.
+const INITIAL_TAIL: usize = 1000;
+
+/// How long archived logs are kept.
+#[derive(Debug, Clone, Copy)]
+pub struct LogRetention {
+    pub days: u32,
+    /// Warnings and errors are kept longer, for postmortems.
+    pub error_days: u32,
+}
+
+impl Default for LogRetention {
+    fn default() -> Self {
+        Self {
+            days: 14,
+            error_days: 90,
+        }
+    }
+}
+
+/// Outcome of a single archive pass, mostly for logging.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ArchiveSummary {
+    pub deployments: usize,
+    pub archived: u64,
+    pub pruned: u64,
+    pub errors: usize,
+}
+
+/// Periodically copies provider logs into `deployment_logs` and prunes them according to
+/// [`LogRetention`], so logs stay searchable after a deployment is destroyed.
+#[derive(Clone)]
+pub struct LogArchiver {
+    db: Database,
+    vps_adapters: VpsAdapters,
+    interval: Duration,
+    retention: LogRetention,
+}
+
+impl LogArchiver {
+    pub fn new(db: Database, vps_adapters: VpsAdapters) -> Self {
+        Self {
+            db,
+            vps_adapters,
+            interval: Duration::from_secs(300),
+            retention: LogRetention::default(),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_retention(mut self, retention: LogRetention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn start(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                ticker.tick().await;
+                match self.archive_once().await {
+                    Ok(summary) if summary.archived > 0 || summary.errors > 0 => {
+                        tracing::info!(?summary, "archived deployment logs");
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!(error = %e, "deployment log archiving failed"),
+                }
+            }
+        })
+    }
+
+    /// Archives every provisioned deployment whose provider is configured, then prunes.
+    pub async fn archive_once(&self) -> Result<ArchiveSummary> {
+        let deployment_repo = repositories::DeploymentRepository::new(self.db.db().clone());
+        let mut summary = ArchiveSummary::default();
+
+        for deployment in deployment_repo.list_all().await? {
+            if matches!(
+                deployment.status,
+                DeploymentStatus::Destroyed | DeploymentStatus::Lost
+            ) || deployment.provider_id.is_none()
+            {
+                continue;
+            }
+            // Railway keys are supplied per request, so those are only archived on teardown
+            let Some(vps_provider) = self.vps_adapters.get_provider(deployment.provider.clone())
+            else {
+                continue;
+            };
+
+            summary.deployments += 1;
+            match archive_deployment(&self.db, vps_provider.as_ref(), &deployment).await {
+                Ok(archived) => summary.archived += archived,
+                Err(e) => {
+                    summary.errors += 1;
+                    tracing::warn!(
+                        deployment_id = %deployment.id,
+                        error = %e,
+                        "failed to archive deployment logs"
+                    );
+                }
+            }
+        }
+
+        summary.pruned = self.prune().await?;
+        Ok(summary)
+    }
+
+    pub async fn prune(&self) -> Result<u64> {
+        let now = Utc::now();
+        let log_repo = repositories::DeploymentLogRepository::new(self.db.db().clone());
+        log_repo
+            .prune(
+                now - chrono::Duration::days(self.retention.days.into()),
+                now - chrono::Duration::days(self.retention.error_days.into()),
+            )
+            .await
+    }
+}
+
+/// Copies the deployment's new log lines from the provider into the archive and returns
+/// how many were added. Called before anything that deletes the provider's logs, such as
+/// destroying the deployment.
+pub async fn archive_deployment(
+    db: &Database,
+    vps_provider: &dyn VpsProvider,
+    deployment: &Deployment,
+) -> Result<u64> {
+    let Some(provider_id) = deployment.provider_id.clone() else {
+        return Ok(0);
+    };
+    let log_repo = repositories::DeploymentLogRepository::new(db.db().clone());
+    let since = log_repo.latest_timestamp(deployment.id).await?;
+
+    let options = LogStreamOptions {
+        follow: false,
+        since,
+        tail: since.is_none().then_some(INITIAL_TAIL),
+    };
+    let deployment_id = DeploymentId {
+        id: deployment.id,
+        provider_id,
+    };
+    let mut stream = vps_provider.stream_logs(&deployment_id, options).await?;
+
+    let mut entries = Vec::new();
+    while let Some(entry) = stream.next().await {
+        entries.push(entry?);
+    }
+
+    log_repo
+        .insert_entries(deployment, &entries, Utc::now())
+        .await
+}
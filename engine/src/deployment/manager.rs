@@ -1,19 +1,44 @@
+use crate::adapters::trait_def::{AgentConfig, DeploymentId};
 use crate::adapters::VpsAdapters;
+use crate::deployment::log_archive;
 use crate::models::{
-    Agent, AgentStatus, Deployment, DeploymentStatus, VpsProvider as ModelVpsProvider,
+    Agent, AgentStatus, Deployment, DeploymentRevision, DeploymentStatus,
+    VpsProvider as ModelVpsProvider,
 };
 use crate::runtime::RuntimeRegistry;
 use crate::storage::{repositories, Database};
 use anyhow::Result;
 use chrono::Utc;
+use claws_runtime_core::RuntimePlan;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
+/// How long `deploy_agent` waits for a provider to report `Running`.
+#[derive(Debug, Clone, Copy)]
+pub struct DeploymentPolling {
+    pub interval: Duration,
+    pub max_attempts: u32,
+}
+
+impl Default for DeploymentPolling {
+    fn default() -> Self {
+        // 30 attempts with 2s delay = 60s timeout
+        Self {
+            interval: Duration::from_secs(2),
+            max_attempts: 30,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct DeploymentManager {
     db: Database,
     pub vps_adapters: VpsAdapters,
     runtime_registry: RuntimeRegistry,
+    polling: DeploymentPolling,
 }
 
 impl DeploymentManager {
@@ -22,9 +47,17 @@ impl DeploymentManager {
             db,
             vps_adapters,
             runtime_registry: RuntimeRegistry::new(),
+            polling: DeploymentPolling::default(),
         })
     }
 
+    pub fn with_polling(mut self, polling: DeploymentPolling) -> Self {
+        self.polling = polling;
+        self
+    }
+
+    /// Deploys a single agent and blocks until it is running. The API goes through
+    /// [`DeploymentJobQueue`](crate::deployment::jobs::DeploymentJobQueue) instead.
     pub async fn deploy_agent(
         &self,
         agent: Agent,
@@ -32,15 +65,49 @@ impl DeploymentManager {
         region: Option<String>,
         railway_api_key: Option<String>,
     ) -> Result<Deployment> {
-        // Get the appropriate VPS provider adapter
-        let vps_provider = self.resolve_provider(provider.clone(), railway_api_key)?;
+        let agents = std::slice::from_ref(&agent);
+        let deployment = self
+            .create_deployment(agents, provider, region, false)
+            .await?;
+        self.provision(&deployment, agents, railway_api_key.clone())
+            .await?;
+        self.wait_until_running(&deployment, railway_api_key).await
+    }
+
+    /// Deploy multiple OpenClaw agents on a single VPS. Coordination (Discord channels) is unchanged per agent.
+    pub async fn deploy_agents_multi(
+        &self,
+        agents: Vec<Agent>,
+        provider: ModelVpsProvider,
+        region: Option<String>,
+        railway_api_key: Option<String>,
+    ) -> Result<Deployment> {
+        let deployment = self
+            .create_deployment(&agents, provider, region, true)
+            .await?;
+        self.provision(&deployment, &agents, railway_api_key.clone())
+            .await?;
+        self.wait_until_running(&deployment, railway_api_key).await
+    }
+
+    /// Records a pending deployment for `agents` and marks them as deploying.
+    pub async fn create_deployment(
+        &self,
+        agents: &[Agent],
+        provider: ModelVpsProvider,
+        region: Option<String>,
+        multi: bool,
+    ) -> Result<Deployment> {
+        if agents.is_empty() {
+            anyhow::bail!("At least one agent required to deploy");
+        }
+        self.check_capabilities(agents, provider.clone(), region.as_deref(), multi)?;
 
-        // Create deployment record
         let deployment = Deployment {
             id: Uuid::new_v4(),
-            agent_id: agent.id,
-            agent_ids: None,
-            provider: provider.clone(),
+            agent_id: agents[0].id,
+            agent_ids: multi.then(|| agents.iter().map(|a| a.id).collect()),
+            provider,
             region,
             status: DeploymentStatus::Pending,
             provider_id: None,
@@ -51,34 +118,96 @@ impl DeploymentManager {
             updated_at: Utc::now(),
         };
 
-        // Save to database
         let deployment_repo = repositories::DeploymentRepository::new(self.db.db().clone());
         deployment_repo.create(&deployment).await?;
 
-        // Update agent status
         let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
-        agent_repo
-            .update_status(agent.id, AgentStatus::Deploying)
-            .await?;
+        for agent in agents {
+            agent_repo
+                .update_status(agent.id, AgentStatus::Deploying)
+                .await?;
+        }
 
-        let (_runtime_kind, runtime_plan) = self
-            .runtime_registry
-            .build_plan(std::slice::from_ref(&agent))?;
+        Ok(deployment)
+    }
 
-        // Deploy to VPS with runtime configuration
-        let agent_config = crate::adapters::trait_def::AgentConfig {
-            agent: agent.clone(),
-            agents: None,
-            region: deployment.region.clone(),
-            runtime: agent.runtime,
-            runtime_init_script: runtime_plan.init_script,
-            runtime_env: runtime_plan.env,
-            runtime_services: runtime_plan.services,
-        };
+    /// Fails if `provider` isn't configured or can't host `agents` as requested.
+    pub fn check_capabilities(
+        &self,
+        agents: &[Agent],
+        provider: ModelVpsProvider,
+        region: Option<&str>,
+        multi: bool,
+    ) -> Result<()> {
+        let capabilities = self
+            .vps_adapters
+            .capabilities(provider.clone())
+            .ok_or_else(|| anyhow::anyhow!("VPS provider {:?} not configured", provider))?;
+
+        if let Some(agent) = agents
+            .iter()
+            .find(|agent| !capabilities.supports_runtime(agent.runtime))
+        {
+            anyhow::bail!(
+                "VPS provider {:?} does not support the {:?} runtime",
+                provider,
+                agent.runtime
+            );
+        }
+        if multi && agents.len() > 1 && !capabilities.multi_agent {
+            anyhow::bail!(
+                "VPS provider {:?} does not support multi-agent deployments",
+                provider
+            );
+        }
+        if let Some(region) = region.filter(|region| !capabilities.supports_region(region)) {
+            anyhow::bail!(
+                "VPS provider {:?} does not support region {}",
+                provider,
+                region
+            );
+        }
+        Ok(())
+    }
+
+    /// Builds the runtime plan and hands the deployment to its provider, recording the
+    /// provider ID. Marks the deployment failed if the provider rejects it.
+    pub async fn provision(
+        &self,
+        deployment: &Deployment,
+        agents: &[Agent],
+        railway_api_key: Option<String>,
+    ) -> Result<Deployment> {
+        let result = self
+            .provision_inner(deployment, agents, railway_api_key)
+            .await;
+        if result.is_err() {
+            self.mark_failed(deployment).await?;
+        }
+        result
+    }
+
+    async fn provision_inner(
+        &self,
+        deployment: &Deployment,
+        agents: &[Agent],
+        railway_api_key: Option<String>,
+    ) -> Result<Deployment> {
+        let vps_provider = self.resolve_provider(deployment.provider.clone(), railway_api_key)?;
+        let (_runtime_kind, runtime_plan) = self.runtime_registry.build_plan(agents)?;
 
+        // Deploy to VPS with runtime configuration
+        let mut agent_config = agent_config(deployment, agents, &runtime_plan);
+        if agent_config.volume_id.is_none() && vps_provider.capabilities().volumes {
+            agent_config.volume_id = Some(
+                self.attach_volume(deployment, vps_provider.as_ref(), &agent_config)
+                    .await?,
+            );
+        }
         let deploy_result = vps_provider.deploy_agent(agent_config).await?;
 
         // Persist provider_id so destroy can target the correct VPS
+        let deployment_repo = repositories::DeploymentRepository::new(self.db.db().clone());
         deployment_repo
             .update_provider_id(deployment.id, deploy_result.provider_id.clone())
             .await?;
@@ -86,12 +215,79 @@ impl DeploymentManager {
             .update_status(deployment.id, DeploymentStatus::Creating)
             .await?;
 
-        // Poll deployment status until ready
-        let mut attempts = 0;
-        let max_attempts = 30; // 30 attempts with 2s delay = 60s timeout
+        self.record_revision(deployment, agents, &runtime_plan, None)
+            .await?;
+
+        Ok(Deployment {
+            provider_id: Some(deploy_result.provider_id),
+            status: DeploymentStatus::Creating,
+            ..deployment.clone()
+        })
+    }
+
+    /// Gives the deployment the volume left behind by the agent's previous deployment on the
+    /// same provider and region, or a new one. Recorded before deploying, so a failed attempt
+    /// hands the volume on to its retry.
+    async fn attach_volume(
+        &self,
+        deployment: &Deployment,
+        vps_provider: &dyn crate::adapters::VpsProvider,
+        config: &AgentConfig,
+    ) -> Result<String> {
+        let deployment_repo = repositories::DeploymentRepository::new(self.db.db().clone());
+        let reusable = deployment_repo
+            .find_reusable_volume(
+                deployment.agent_id,
+                &deployment.provider,
+                deployment.region.as_deref(),
+            )
+            .await?;
+
+        let volume_id = match reusable {
+            Some((previous_deployment_id, volume_id)) => {
+                deployment_repo
+                    .update_volume_id(previous_deployment_id, None)
+                    .await?;
+                volume_id
+            }
+            None => vps_provider.create_volume(config).await?,
+        };
+
+        deployment_repo
+            .update_volume_id(deployment.id, Some(volume_id.clone()))
+            .await?;
+        Ok(volume_id)
+    }
 
-        while attempts < max_attempts {
-            tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+    /// Polls the provider until the deployment is running, then links its agents to it.
+    /// Fails (and marks the deployment failed) if the provider reports failure or polling times out.
+    pub async fn wait_until_running(
+        &self,
+        deployment: &Deployment,
+        railway_api_key: Option<String>,
+    ) -> Result<Deployment> {
+        let vps_provider = self.resolve_provider(deployment.provider.clone(), railway_api_key)?;
+        let deployment_repo = repositories::DeploymentRepository::new(self.db.db().clone());
+        let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
+        let label = if deployment.agent_ids.is_some() {
+            "Multi-agent deployment"
+        } else {
+            "Deployment"
+        };
+
+        let provider_id = deployment_repo
+            .get_by_id(deployment.id)
+            .await?
+            .and_then(|current| current.provider_id)
+            .ok_or_else(|| anyhow::anyhow!("{} has not been provisioned", label))?;
+        let deploy_result = crate::adapters::trait_def::DeploymentId {
+            id: deployment.id,
+            provider_id,
+        };
+
+        // Poll deployment status until ready
+        for _ in 0..self.polling.max_attempts {
+            tokio::time::sleep(self.polling.interval).await;
 
             let status = vps_provider.get_status(&deploy_result).await?;
 
@@ -106,149 +302,339 @@ impl DeploymentManager {
                     .await?;
 
                 // Update agent status and link to deployment
-                agent_repo
-                    .update_status(agent.id, AgentStatus::Running)
-                    .await?;
-                agent_repo
-                    .update_deployment_id(agent.id, Some(deployment.id))
-                    .await?;
-                break;
+                for id in deployment_agent_ids(deployment) {
+                    agent_repo.update_status(id, AgentStatus::Running).await?;
+                    agent_repo
+                        .update_deployment_id(id, Some(deployment.id))
+                        .await?;
+                }
+
+                return deployment_repo
+                    .get_by_id(deployment.id)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("Deployment not found"));
             } else if matches!(status.status, DeploymentStatus::Failed) {
-                deployment_repo
-                    .update_status(deployment.id, DeploymentStatus::Failed)
-                    .await?;
-                agent_repo
-                    .update_status(agent.id, AgentStatus::Error)
-                    .await?;
-                anyhow::bail!("Deployment failed");
+                self.mark_failed(deployment).await?;
+                anyhow::bail!("{} failed", label);
             }
-
-            attempts += 1;
         }
 
-        if attempts >= max_attempts {
-            deployment_repo
-                .update_status(deployment.id, DeploymentStatus::Failed)
-                .await?;
-            agent_repo
-                .update_status(agent.id, AgentStatus::Error)
-                .await?;
-            anyhow::bail!("Deployment timeout");
-        }
-
-        Ok(deployment)
+        self.mark_failed(deployment).await?;
+        anyhow::bail!("{} timeout", label)
     }
 
-    /// Deploy multiple OpenClaw agents on a single VPS. Coordination (Discord channels) is unchanged per agent.
-    pub async fn deploy_agents_multi(
+    /// Best-effort teardown of a deployment left behind by an earlier, unsuccessful attempt.
+    pub async fn abandon_deployment(
         &self,
-        agents: Vec<Agent>,
-        provider: ModelVpsProvider,
-        region: Option<String>,
+        deployment: &Deployment,
         railway_api_key: Option<String>,
-    ) -> Result<Deployment> {
-        if agents.is_empty() {
-            anyhow::bail!("At least one agent required for multi-agent deploy");
+    ) -> Result<()> {
+        if let Some(provider_id) = &deployment.provider_id {
+            let destroyed =
+                match self.resolve_provider(deployment.provider.clone(), railway_api_key) {
+                    Ok(vps_provider) => {
+                        self.archive_logs(vps_provider.as_ref(), deployment).await;
+                        vps_provider
+                            .destroy_agent(&crate::adapters::trait_def::DeploymentId {
+                                id: deployment.id,
+                                provider_id: provider_id.clone(),
+                            })
+                            .await
+                    }
+                    Err(e) => Err(e),
+                };
+            if let Err(e) = destroyed {
+                tracing::warn!(
+                    deployment_id = %deployment.id,
+                    error = %e,
+                    "failed to destroy abandoned deployment"
+                );
+            }
         }
 
-        let vps_provider = self.resolve_provider(provider.clone(), railway_api_key)?;
-
-        let agent_ids: Vec<Uuid> = agents.iter().map(|a| a.id).collect();
-        let deployment = Deployment {
-            id: Uuid::new_v4(),
-            agent_id: agents[0].id,
-            agent_ids: Some(agent_ids.clone()),
-            provider: provider.clone(),
-            region: region.clone(),
-            status: DeploymentStatus::Pending,
-            provider_id: None,
-            endpoint: None,
-            gateway_url: None,
-            volume_id: None,
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
-        };
+        let deployment_repo = repositories::DeploymentRepository::new(self.db.db().clone());
+        deployment_repo
+            .update_status(deployment.id, DeploymentStatus::Failed)
+            .await
+    }
 
+    async fn mark_failed(&self, deployment: &Deployment) -> Result<()> {
         let deployment_repo = repositories::DeploymentRepository::new(self.db.db().clone());
-        deployment_repo.create(&deployment).await?;
+        deployment_repo
+            .update_status(deployment.id, DeploymentStatus::Failed)
+            .await?;
 
         let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
-        for id in &agent_ids {
-            agent_repo
-                .update_status(*id, AgentStatus::Deploying)
-                .await?;
+        for id in deployment_agent_ids(deployment) {
+            agent_repo.update_status(id, AgentStatus::Error).await?;
         }
+        Ok(())
+    }
 
-        let (_runtime_kind, runtime_plan) = self.runtime_registry.build_plan(&agents)?;
+    /// Renders the runtime plan for `agents` without deploying, e.g. to validate a config change.
+    pub fn build_plan(&self, agents: &[Agent]) -> Result<RuntimePlan> {
+        Ok(self.runtime_registry.build_plan(agents)?.1)
+    }
 
-        let agent_config = crate::adapters::trait_def::AgentConfig {
-            agent: agents[0].clone(),
-            agents: Some(agents.clone()),
-            region: deployment.region.clone(),
-            runtime: agents[0].runtime,
-            runtime_init_script: runtime_plan.init_script,
-            runtime_env: runtime_plan.env,
-            runtime_services: runtime_plan.services,
-        };
+    /// Re-renders the runtime plan from the agents' current settings and, if it differs
+    /// from the latest revision, pushes it to the provider and records a new revision.
+    /// Returns `None` when nothing changed.
+    pub async fn apply_runtime_changes(
+        &self,
+        deployment_id: Uuid,
+        railway_api_key: Option<String>,
+    ) -> Result<Option<DeploymentRevision>> {
+        let deployment = self.get_deployment(deployment_id).await?;
+        let agents = self.load_agents(&deployment).await?;
+        self.apply_agents(&deployment, &agents, railway_api_key)
+            .await
+    }
 
-        let deploy_result = vps_provider.deploy_agent(agent_config).await?;
+    /// Saves the agent's user-editable settings, first pushing the runtime plan they produce
+    /// to its deployment (if any). When the push fails nothing is saved, so the stored
+    /// settings keep matching what is deployed.
+    pub async fn update_agent_settings(
+        &self,
+        agent: &Agent,
+        railway_api_key: Option<String>,
+    ) -> Result<Option<DeploymentRevision>> {
+        let revision = match agent.deployment_id {
+            Some(deployment_id) => {
+                let deployment = self.get_deployment(deployment_id).await?;
+                let agents = self.agents_with(&deployment, agent).await?;
+                self.apply_agents(&deployment, &agents, railway_api_key)
+                    .await
+                    .map_err(|e| e.context("settings were not saved"))?
+            }
+            None => None,
+        };
 
-        deployment_repo
-            .update_provider_id(deployment.id, deploy_result.provider_id.clone())
+        repositories::AgentRepository::new(self.db.db().clone())
+            .update_settings(agent)
             .await?;
-        deployment_repo
-            .update_status(deployment.id, DeploymentStatus::Creating)
+        Ok(revision)
+    }
+
+    /// Every agent the runtime plan for `agent` is built from: those sharing its deployment,
+    /// with `agent`'s (possibly unsaved) settings in place of its stored ones.
+    pub async fn plan_agents(&self, agent: &Agent) -> Result<Vec<Agent>> {
+        match agent.deployment_id {
+            Some(deployment_id) => {
+                let deployment = self.get_deployment(deployment_id).await?;
+                self.agents_with(&deployment, agent).await
+            }
+            None => Ok(vec![agent.clone()]),
+        }
+    }
+
+    async fn agents_with(&self, deployment: &Deployment, agent: &Agent) -> Result<Vec<Agent>> {
+        let mut agents = self.load_agents(deployment).await?;
+        for loaded in &mut agents {
+            if loaded.id == agent.id {
+                *loaded = agent.clone();
+            }
+        }
+        Ok(agents)
+    }
+
+    /// Renders the plan for `agents` and, if it differs from the latest revision, pushes it
+    /// and records a new revision.
+    async fn apply_agents(
+        &self,
+        deployment: &Deployment,
+        agents: &[Agent],
+        railway_api_key: Option<String>,
+    ) -> Result<Option<DeploymentRevision>> {
+        let (_runtime_kind, plan) = self.runtime_registry.build_plan(agents)?;
+
+        let revision_repo = repositories::DeploymentRevisionRepository::new(self.db.db().clone());
+        let latest = match revision_repo.get_latest(deployment.id).await? {
+            Some(latest) => latest,
+            None => self.seed_revision(deployment).await?,
+        };
+        let init_script_hash = hash_init_script(&plan.init_script);
+        let services_unchanged =
+            serde_json::to_value(&latest.services)? == serde_json::to_value(&plan.services)?;
+        if latest.env == plan.env
+            && latest.init_script_hash == init_script_hash
+            && services_unchanged
+        {
+            return Ok(None);
+        }
+
+        let update = PlanUpdate::between(Some(&latest), &plan.env, &init_script_hash);
+        self.apply_plan(deployment, agents, &plan, update, railway_api_key)
             .await?;
+        self.record_revision(deployment, agents, &plan, None)
+            .await
+            .map(Some)
+    }
 
-        let max_attempts = 30;
-        let mut attempts = 0;
+    /// Records the plan of the agents' stored settings as the first revision of a deployment
+    /// made before revisions existed, so a change to it is compared against what it runs
+    /// rather than redeploying it from scratch.
+    async fn seed_revision(&self, deployment: &Deployment) -> Result<DeploymentRevision> {
+        let agents = self.load_agents(deployment).await?;
+        let (_runtime_kind, plan) = self.runtime_registry.build_plan(&agents)?;
+        self.record_revision(deployment, &agents, &plan, None).await
+    }
 
-        while attempts < max_attempts {
-            tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+    /// Re-applies an earlier revision: pushes its rendered plan, via `update_config` when the
+    /// init script is unchanged or a full redeploy otherwise, then restores each agent's
+    /// `runtime_config` from it. The rollback itself is recorded as a new revision.
+    pub async fn rollback(
+        &self,
+        deployment_id: Uuid,
+        revision: i32,
+        railway_api_key: Option<String>,
+    ) -> Result<DeploymentRevision> {
+        let deployment = self.get_deployment(deployment_id).await?;
+        let revision_repo = repositories::DeploymentRevisionRepository::new(self.db.db().clone());
+        let target = revision_repo
+            .get(deployment.id, revision)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Revision {} not found", revision))?;
+        let current = revision_repo.get_latest(deployment.id).await?;
 
-            let status = vps_provider.get_status(&deploy_result).await?;
+        let mut agents = self.load_agents(&deployment).await?;
+        for agent in &mut agents {
+            if let Some(runtime_config) = target.agent_configs.get(&agent.id) {
+                agent.runtime_config = runtime_config.clone();
+            }
+        }
 
-            if matches!(status.status, DeploymentStatus::Running) {
-                deployment_repo
-                    .update_status_details(
-                        deployment.id,
-                        DeploymentStatus::Running,
-                        status.endpoint,
-                        status.gateway_url,
-                    )
-                    .await?;
+        let plan = RuntimePlan {
+            env: target.env.clone(),
+            init_script: target.init_script.clone(),
+            services: target.services.clone(),
+        };
+        let update = PlanUpdate::between(current.as_ref(), &target.env, &target.init_script_hash);
+        self.apply_plan(&deployment, &agents, &plan, update, railway_api_key)
+            .await?;
 
-                for id in &agent_ids {
-                    agent_repo.update_status(*id, AgentStatus::Running).await?;
-                    agent_repo
-                        .update_deployment_id(*id, Some(deployment.id))
-                        .await?;
-                }
-                break;
-            } else if matches!(status.status, DeploymentStatus::Failed) {
-                deployment_repo
-                    .update_status(deployment.id, DeploymentStatus::Failed)
+        // Saved only once pushed, so a failed rollback leaves the stored settings matching
+        // what is still running
+        let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
+        for agent in &agents {
+            if target.agent_configs.contains_key(&agent.id) {
+                agent_repo
+                    .update_runtime_config(agent.id, agent.runtime_config.clone())
                     .await?;
-                for id in &agent_ids {
-                    agent_repo.update_status(*id, AgentStatus::Error).await?;
-                }
-                anyhow::bail!("Multi-agent deployment failed");
             }
-
-            attempts += 1;
         }
 
-        if attempts >= max_attempts {
-            deployment_repo
-                .update_status(deployment.id, DeploymentStatus::Failed)
+        self.record_revision(&deployment, &agents, &plan, Some(target.revision))
+            .await
+    }
+
+    /// Pushes `plan` to a provisioned deployment. When that restarts or replaces the agent,
+    /// the deployment is left `Creating` for the reconciler to pick up once it is running.
+    async fn apply_plan(
+        &self,
+        deployment: &Deployment,
+        agents: &[Agent],
+        plan: &RuntimePlan,
+        update: PlanUpdate,
+        railway_api_key: Option<String>,
+    ) -> Result<()> {
+        let (vps_provider, current) = self.provider_for(deployment, railway_api_key)?;
+        let config = agent_config(deployment, agents, plan);
+
+        if update != PlanUpdate::Redeploy {
+            vps_provider.update_config(&current, config).await?;
+            let mut restarted = vps_provider.update_config_restarts();
+            if update == PlanUpdate::Restart && !restarted {
+                vps_provider.restart_agent(&current).await?;
+                restarted = true;
+            }
+            if restarted {
+                self.set_lifecycle_status(
+                    deployment,
+                    DeploymentStatus::Creating,
+                    AgentStatus::Deploying,
+                )
                 .await?;
-            for id in &agent_ids {
-                agent_repo.update_status(*id, AgentStatus::Error).await?;
             }
-            anyhow::bail!("Multi-agent deployment timeout");
+            return Ok(());
         }
 
-        Ok(deployment)
+        // The old resource goes first: a volume attaches to one machine at a time, and some
+        // providers name the resource after the agent. Without a replacement the deployment
+        // has nothing running, so it is marked failed.
+        self.archive_logs(vps_provider.as_ref(), deployment).await;
+        vps_provider.destroy_agent(&current).await?;
+        let replacement = match vps_provider.deploy_agent(config).await {
+            Ok(replacement) => replacement,
+            Err(e) => {
+                self.mark_failed(deployment).await?;
+                return Err(e.context("the previous deployment was destroyed"));
+            }
+        };
+
+        let deployment_repo = repositories::DeploymentRepository::new(self.db.db().clone());
+        deployment_repo
+            .update_provider_id(deployment.id, replacement.provider_id)
+            .await?;
+        deployment_repo
+            .update_status_details(deployment.id, DeploymentStatus::Creating, None, None)
+            .await?;
+
+        let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
+        for id in deployment_agent_ids(deployment) {
+            agent_repo.update_status(id, AgentStatus::Deploying).await?;
+        }
+        Ok(())
+    }
+
+    async fn record_revision(
+        &self,
+        deployment: &Deployment,
+        agents: &[Agent],
+        plan: &RuntimePlan,
+        restored_from: Option<i32>,
+    ) -> Result<DeploymentRevision> {
+        let mut revision = DeploymentRevision {
+            id: Uuid::new_v4(),
+            deployment_id: deployment.id,
+            revision: 0,
+            runtime: agents[0].runtime,
+            provider: deployment.provider.clone(),
+            region: deployment.region.clone(),
+            env: plan.env.clone(),
+            init_script: plan.init_script.clone(),
+            init_script_hash: hash_init_script(&plan.init_script),
+            services: plan.services.clone(),
+            agent_configs: agents
+                .iter()
+                .map(|agent| (agent.id, agent.runtime_config.clone()))
+                .collect(),
+            restored_from,
+            created_at: Utc::now(),
+        };
+
+        let revision_repo = repositories::DeploymentRevisionRepository::new(self.db.db().clone());
+        revision.revision = revision_repo.create(&revision).await?;
+        Ok(revision)
+    }
+
+    async fn get_deployment(&self, deployment_id: Uuid) -> Result<Deployment> {
+        repositories::DeploymentRepository::new(self.db.db().clone())
+            .get_by_id(deployment_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Deployment not found"))
+    }
+
+    async fn load_agents(&self, deployment: &Deployment) -> Result<Vec<Agent>> {
+        let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
+        let mut agents = Vec::new();
+        for id in deployment_agent_ids(deployment) {
+            let agent = agent_repo
+                .get_by_id(id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Agent {} not found", id))?;
+            agents.push(agent);
+        }
+        Ok(agents)
     }
 
     pub async fn get_agent_status(&self, agent_id: Uuid) -> Result<AgentStatus> {
@@ -287,15 +673,21 @@ impl DeploymentManager {
         }
     }
 
-    pub async fn destroy_agent(&self, agent_id: Uuid) -> Result<()> {
+    /// Tears down the agent's deployment. Its volume is kept for the next deploy unless
+    /// `delete_data` is set, in which case it (and any volume left by earlier deployments
+    /// of the agent) is deleted too.
+    pub async fn destroy_agent(&self, agent_id: Uuid, delete_data: bool) -> Result<()> {
         let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
-        let _agent = agent_repo
+        let agent = agent_repo
             .get_by_id(agent_id)
             .await?
             .ok_or_else(|| anyhow::anyhow!("Agent not found"))?;
 
         let deployment_repo = repositories::DeploymentRepository::new(self.db.db().clone());
-        let deployment = deployment_repo.get_by_agent_id(agent_id).await?;
+        let deployment = match agent.deployment_id {
+            Some(deployment_id) => deployment_repo.get_by_id(deployment_id).await?,
+            None => deployment_repo.get_by_agent_id(agent_id).await?,
+        };
 
         if let Some(deployment) = deployment {
             // Get VPS provider adapter
@@ -310,29 +702,226 @@ impl DeploymentManager {
                 id: deployment.id,
                 provider_id: deployment
                     .provider_id
+                    .clone()
                     .unwrap_or_else(|| format!("{:?}-{}", deployment.provider, deployment.id)),
             };
 
+            self.archive_logs(vps_provider.as_ref(), &deployment).await;
             vps_provider.destroy_agent(&deployment_id_struct).await?;
 
             deployment_repo
-                .update_status(deployment.id, DeploymentStatus::Stopped)
+                .update_status(deployment.id, DeploymentStatus::Destroyed)
                 .await?;
 
-            // Mark all agents on this VPS as stopped and unlink deployment
+            if delete_data {
+                while let Some((owner_id, volume_id)) = deployment_repo
+                    .find_reusable_volume(
+                        deployment.agent_id,
+                        &deployment.provider,
+                        deployment.region.as_deref(),
+                    )
+                    .await?
+                {
+                    vps_provider.delete_volume(&volume_id).await?;
+                    deployment_repo.update_volume_id(owner_id, None).await?;
+                }
+            }
+
+            // Mark all agents on this VPS as destroyed and unlink deployment
             let agent_ids: Vec<Uuid> = deployment
                 .agent_ids
                 .unwrap_or_else(|| vec![deployment.agent_id]);
             for aid in agent_ids {
-                agent_repo.update_status(aid, AgentStatus::Stopped).await?;
+                agent_repo
+                    .update_status(aid, AgentStatus::Destroyed)
+                    .await?;
                 agent_repo.update_deployment_id(aid, None).await?;
             }
         } else {
             agent_repo
-                .update_status(agent_id, AgentStatus::Stopped)
+                .update_status(agent_id, AgentStatus::Destroyed)
                 .await?;
         }
 
         Ok(())
     }
+
+    /// Pauses the agent's deployment, keeping the provider resource so it can be started
+    /// again. Every agent sharing the deployment is stopped with it.
+    pub async fn stop_agent(
+        &self,
+        agent_id: Uuid,
+        railway_api_key: Option<String>,
+    ) -> Result<Deployment> {
+        let deployment = self.agent_deployment(agent_id).await?;
+        let (vps_provider, provider_deployment_id) =
+            self.provider_for(&deployment, railway_api_key)?;
+
+        vps_provider.stop_agent(&provider_deployment_id).await?;
+        self.set_lifecycle_status(&deployment, DeploymentStatus::Stopped, AgentStatus::Stopped)
+            .await
+    }
+
+    /// Resumes a stopped deployment. It is left `Creating` until the reconciler sees it running.
+    pub async fn start_agent(
+        &self,
+        agent_id: Uuid,
+        railway_api_key: Option<String>,
+    ) -> Result<Deployment> {
+        let deployment = self.agent_deployment(agent_id).await?;
+        let (vps_provider, provider_deployment_id) =
+            self.provider_for(&deployment, railway_api_key)?;
+
+        vps_provider.start_agent(&provider_deployment_id).await?;
+        self.set_lifecycle_status(
+            &deployment,
+            DeploymentStatus::Creating,
+            AgentStatus::Deploying,
+        )
+        .await
+    }
+
+    /// Restarts the deployment in place. It is left `Creating` until the reconciler sees it running.
+    pub async fn restart_agent(
+        &self,
+        agent_id: Uuid,
+        railway_api_key: Option<String>,
+    ) -> Result<Deployment> {
+        let deployment = self.agent_deployment(agent_id).await?;
+        let (vps_provider, provider_deployment_id) =
+            self.provider_for(&deployment, railway_api_key)?;
+
+        vps_provider.restart_agent(&provider_deployment_id).await?;
+        self.set_lifecycle_status(
+            &deployment,
+            DeploymentStatus::Creating,
+            AgentStatus::Deploying,
+        )
+        .await
+    }
+
+    /// The deployment the agent is currently linked to.
+    async fn agent_deployment(&self, agent_id: Uuid) -> Result<Deployment> {
+        let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
+        let agent = agent_repo
+            .get_by_id(agent_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Agent not found"))?;
+        let deployment_id = agent
+            .deployment_id
+            .ok_or_else(|| anyhow::anyhow!("Agent {} is not deployed", agent_id))?;
+
+        self.get_deployment(deployment_id).await
+    }
+
+    fn provider_for(
+        &self,
+        deployment: &Deployment,
+        railway_api_key: Option<String>,
+    ) -> Result<(Arc<dyn crate::adapters::VpsProvider>, DeploymentId)> {
+        let vps_provider = self.resolve_provider(deployment.provider.clone(), railway_api_key)?;
+        let provider_id = deployment
+            .provider_id
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Deployment has not been provisioned"))?;
+
+        Ok((
+            vps_provider,
+            DeploymentId {
+                id: deployment.id,
+                provider_id,
+            },
+        ))
+    }
+
+    /// Best-effort copy of the deployment's logs before the provider deletes them.
+    async fn archive_logs(
+        &self,
+        vps_provider: &dyn crate::adapters::VpsProvider,
+        deployment: &Deployment,
+    ) {
+        if let Err(e) = log_archive::archive_deployment(&self.db, vps_provider, deployment).await {
+            tracing::warn!(
+                deployment_id = %deployment.id,
+                error = %e,
+                "failed to archive logs before teardown"
+            );
+        }
+    }
+
+    async fn set_lifecycle_status(
+        &self,
+        deployment: &Deployment,
+        deployment_status: DeploymentStatus,
+        agent_status: AgentStatus,
+    ) -> Result<Deployment> {
+        let deployment_repo = repositories::DeploymentRepository::new(self.db.db().clone());
+        deployment_repo
+            .update_status(deployment.id, deployment_status.clone())
+            .await?;
+
+        let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
+        for id in deployment_agent_ids(deployment) {
+            agent_repo.update_status(id, agent_status.clone()).await?;
+        }
+
+        Ok(Deployment {
+            status: deployment_status,
+            ..deployment.clone()
+        })
+    }
+}
+
+/// How a new plan reaches a provisioned deployment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlanUpdate {
+    /// Only the services changed; pushed with `update_config`.
+    InPlace,
+    /// The env changed, which the agent only reads when it starts.
+    Restart,
+    /// The init script changed, so the provider resource is replaced.
+    Redeploy,
+}
+
+impl PlanUpdate {
+    fn between(
+        current: Option<&DeploymentRevision>,
+        env: &BTreeMap<String, String>,
+        init_script_hash: &str,
+    ) -> Self {
+        match current {
+            Some(current) if current.init_script_hash == init_script_hash => {
+                if &current.env == env {
+                    Self::InPlace
+                } else {
+                    Self::Restart
+                }
+            }
+            _ => Self::Redeploy,
+        }
+    }
+}
+
+fn deployment_agent_ids(deployment: &Deployment) -> Vec<Uuid> {
+    deployment
+        .agent_ids
+        .clone()
+        .unwrap_or_else(|| vec![deployment.agent_id])
+}
+
+fn agent_config(deployment: &Deployment, agents: &[Agent], plan: &RuntimePlan) -> AgentConfig {
+    AgentConfig {
+        agent: agents[0].clone(),
+        agents: deployment.agent_ids.as_ref().map(|_| agents.to_vec()),
+        region: deployment.region.clone(),
+        runtime: agents[0].runtime,
+        runtime_init_script: plan.init_script.clone(),
+        runtime_env: plan.env.clone(),
+        runtime_services: plan.services.clone(),
+        volume_id: deployment.volume_id.clone(),
+    }
+}
+
+fn hash_init_script(init_script: &str) -> String {
+    hex::encode(Sha256::digest(init_script.as_bytes()))
 }
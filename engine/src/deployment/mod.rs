@@ -1 +1,4 @@
+pub mod jobs;
+pub mod log_archive;
 pub mod manager;
+pub mod reconciler;
@@ -0,0 +1,208 @@
+use crate::adapters::trait_def::{DeploymentId, ResourceNotFound};
+use crate::adapters::VpsAdapters;
+use crate::models::{AgentStatus, Deployment, DeploymentStatus};
+use crate::storage::{repositories, Database};
+use anyhow::Result;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Outcome of a single reconciliation pass, mostly for logging.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReconcileSummary {
+    pub checked: usize,
+    pub updated: usize,
+    pub lost: usize,
+    pub errors: usize,
+}
+
+/// Periodically compares live deployments against their provider and writes back
+/// status, endpoint and gateway drift, so the database reflects machines that crashed,
+/// stopped or were deleted after the initial deploy.
+#[derive(Clone)]
+pub struct DeploymentReconciler {
+    db: Database,
+    vps_adapters: VpsAdapters,
+    interval: Duration,
+}
+
+impl DeploymentReconciler {
+    pub fn new(db: Database, vps_adapters: VpsAdapters) -> Self {
+        Self {
+            db,
+            vps_adapters,
+            interval: Duration::from_secs(60),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn start(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                ticker.tick().await;
+                match self.reconcile_once().await {
+                    Ok(summary) if summary.updated > 0 || summary.errors > 0 => {
+                        tracing::info!(?summary, "reconciled deployments");
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!(error = %e, "deployment reconciliation failed"),
+                }
+            }
+        })
+    }
+
+    /// Checks every deployment that is not destroyed or already flagged as lost.
+    /// Stopped ones are included, since a paused machine can still be deleted or started
+    /// out-of-band.
+    pub async fn reconcile_once(&self) -> Result<ReconcileSummary> {
+        let deployment_repo = repositories::DeploymentRepository::new(self.db.db().clone());
+        let mut summary = ReconcileSummary::default();
+
+        for deployment in deployment_repo.list_all().await? {
+            if !self.should_reconcile(&deployment).await? {
+                continue;
+            }
+
+            summary.checked += 1;
+            match self.reconcile_deployment(&deployment).await {
+                Ok(Some(DeploymentStatus::Lost)) => {
+                    summary.updated += 1;
+                    summary.lost += 1;
+                }
+                Ok(Some(_)) => summary.updated += 1,
+                Ok(None) => {}
+                Err(e) => {
+                    summary.errors += 1;
+                    tracing::warn!(
+                        deployment_id = %deployment.id,
+                        error = %e,
+                        "failed to reconcile deployment"
+                    );
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+
+    async fn should_reconcile(&self, deployment: &Deployment) -> Result<bool> {
+        match deployment.status {
+            DeploymentStatus::Destroyed | DeploymentStatus::Lost => Ok(false),
+            DeploymentStatus::Running | DeploymentStatus::Stopped | DeploymentStatus::Failed => {
+                Ok(true)
+            }
+            // Until agents are linked, the deployment job that created it is still polling
+            DeploymentStatus::Pending | DeploymentStatus::Creating => {
+                let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
+                Ok(agent_repo
+                    .get_by_id(deployment.agent_id)
+                    .await?
+                    .is_some_and(|agent| agent.deployment_id == Some(deployment.id)))
+            }
+        }
+    }
+
+    /// Returns the new status if anything was written.
+    async fn reconcile_deployment(
+        &self,
+        deployment: &Deployment,
+    ) -> Result<Option<DeploymentStatus>> {
+        let Some(provider_id) = deployment.provider_id.clone() else {
+            return Ok(None);
+        };
+        // Railway keys are supplied per request and not kept around, so those can't be checked
+        let Some(vps_provider) = self.vps_adapters.get_provider(deployment.provider.clone()) else {
+            tracing::debug!(
+                deployment_id = %deployment.id,
+                provider = ?deployment.provider,
+                "skipping reconciliation; provider not configured"
+            );
+            return Ok(None);
+        };
+
+        let deployment_repo = repositories::DeploymentRepository::new(self.db.db().clone());
+        let deployment_id = DeploymentId {
+            id: deployment.id,
+            provider_id,
+        };
+
+        let observed = match vps_provider.get_status(&deployment_id).await {
+            Ok(observed) => observed,
+            Err(e) if e.downcast_ref::<ResourceNotFound>().is_some() => {
+                tracing::warn!(
+                    deployment_id = %deployment.id,
+                    provider_id = %deployment_id.provider_id,
+                    "provider resource vanished; marking deployment lost"
+                );
+                deployment_repo
+                    .update_status(deployment.id, DeploymentStatus::Lost)
+                    .await?;
+                self.sync_agents(deployment, AgentStatus::Error).await?;
+                return Ok(Some(DeploymentStatus::Lost));
+            }
+            Err(e) => return Err(e),
+        };
+
+        let drifted = observed.status != deployment.status
+            || observed.endpoint != deployment.endpoint
+            || observed.gateway_url != deployment.gateway_url;
+        if !drifted {
+            return Ok(None);
+        }
+
+        tracing::info!(
+            deployment_id = %deployment.id,
+            from = ?deployment.status,
+            to = ?observed.status,
+            "deployment drifted from provider state"
+        );
+        deployment_repo
+            .update_status_details(
+                deployment.id,
+                observed.status.clone(),
+                observed.endpoint,
+                observed.gateway_url,
+            )
+            .await?;
+        self.sync_agents(deployment, agent_status_for(&observed.status))
+            .await?;
+
+        Ok(Some(observed.status))
+    }
+
+    /// Only touches agents still linked to this deployment, so an old deployment left
+    /// behind by a retry can't overwrite the status of the agent's current one.
+    async fn sync_agents(&self, deployment: &Deployment, status: AgentStatus) -> Result<()> {
+        let agent_repo = repositories::AgentRepository::new(self.db.db().clone());
+        let agent_ids = deployment
+            .agent_ids
+            .clone()
+            .unwrap_or_else(|| vec![deployment.agent_id]);
+
+        for id in agent_ids {
+            let Some(agent) = agent_repo.get_by_id(id).await? else {
+                continue;
+            };
+            if agent.deployment_id == Some(deployment.id) && agent.status != status {
+                agent_repo.update_status(id, status.clone()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn agent_status_for(status: &DeploymentStatus) -> AgentStatus {
+    match status {
+        DeploymentStatus::Pending | DeploymentStatus::Creating => AgentStatus::Deploying,
+        DeploymentStatus::Running => AgentStatus::Running,
+        DeploymentStatus::Stopped => AgentStatus::Stopped,
+        DeploymentStatus::Destroyed => AgentStatus::Destroyed,
+        DeploymentStatus::Failed | DeploymentStatus::Lost => AgentStatus::Error,
+    }
+}
@@ -1,5 +1,8 @@
+use crate::adapters::LogLevel;
 use chrono::{DateTime, Utc};
+use claws_runtime_core::RuntimeServicePort;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,10 +26,47 @@ pub struct Agent {
     pub runtime_config: Option<serde_json::Value>,
     pub responsibility: Option<String>, // What the agent does (e.g., "Delegates, connects dots, ships")
     pub emoji: Option<String>,          // Emoji representing the agent's role (e.g., "🧰")
+    /// Open tasks the master may assign at once; `None` uses the default limit.
+    pub max_concurrent_tasks: Option<i32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl Agent {
+    /// A pending agent with a fresh id and none of the optional settings.
+    pub fn new(
+        name: &str,
+        role: AgentRole,
+        runtime: AgentRuntime,
+        model_provider: ModelProvider,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            role,
+            status: AgentStatus::Pending,
+            runtime,
+            deployment_id: None,
+            team_id: None,
+            discord_bot_token: None,
+            discord_channel_id: None,
+            discord_channels: None,
+            model_provider,
+            model_api_key: None,
+            model_endpoint: None,
+            personality: None,
+            skills: Vec::new(),
+            workspace_dir: None,
+            runtime_config: None,
+            responsibility: None,
+            emoji: None,
+            max_concurrent_tasks: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum AgentRole {
@@ -34,13 +74,16 @@ pub enum AgentRole {
     Slave,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum AgentStatus {
     Pending,
     Deploying,
     Running,
+    /// Paused on the provider; can be started again.
     Stopped,
+    /// Torn down; the agent has no deployment anymore.
+    Destroyed,
     Error,
 }
 
@@ -72,7 +115,7 @@ pub struct Deployment {
     pub provider: VpsProvider,
     pub region: Option<String>,
     pub status: DeploymentStatus,
-    /// Provider-specific ID (e.g. flyio-{machine_id}) for get_status/destroy.
+    /// Provider-specific ID (e.g. flyio-{app}/{machine_id}) for get_status/destroy.
     pub provider_id: Option<String>,
     pub endpoint: Option<String>,
     pub gateway_url: Option<String>,
@@ -87,15 +130,133 @@ pub enum VpsProvider {
     Railway,
     FlyIo,
     Aws,
+    Docker,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum DeploymentStatus {
     Pending,
     Creating,
     Running,
+    /// Paused on the provider; can be started again.
     Stopped,
+    /// Torn down on request.
+    Destroyed,
+    Failed,
+    /// The provider no longer has the resource (deleted out-of-band or crashed away).
+    Lost,
+}
+
+/// Immutable snapshot of the runtime plan applied to a deployment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentRevision {
+    pub id: Uuid,
+    pub deployment_id: Uuid,
+    /// Sequential per deployment, starting at 1.
+    pub revision: i32,
+    pub runtime: AgentRuntime,
+    pub provider: VpsProvider,
+    pub region: Option<String>,
+    /// Rendered runtime env (contains secrets).
+    pub env: BTreeMap<String, String>,
+    pub init_script: String,
+    /// SHA-256 of `init_script`, hex encoded.
+    pub init_script_hash: String,
+    pub services: Vec<RuntimeServicePort>,
+    /// Each agent's `runtime_config` at the time, restored on rollback.
+    pub agent_configs: BTreeMap<Uuid, Option<serde_json::Value>>,
+    /// Set when this revision was created by rolling back to an earlier one.
+    pub restored_from: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A Discord message waiting in the outbox, see [`crate::coordinator::outbox`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxMessage {
+    pub id: Uuid,
+    pub channel_id: String,
+    pub action: OutboxAction,
+    pub content: String,
+    pub embed: Option<serde_json::Value>,
+    /// Chunks of an over-long message already delivered.
+    pub chunks_sent: i32,
+    /// Failed delivery attempts so far; rate-limit waits don't count.
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxAction {
+    /// Post `content` to the channel.
+    Message,
+    /// Archive the thread `channel_id`, after the messages queued before it.
+    ArchiveThread,
+}
+
+/// A provider log line archived to the database, so it survives the deployment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentLog {
+    pub id: Uuid,
+    pub deployment_id: Uuid,
+    /// The deployment's primary agent; multi-agent deployments share one log.
+    pub agent_id: Uuid,
+    pub provider: VpsProvider,
+    /// The provider's timestamp, or when the line was archived if it didn't report one.
+    pub timestamp: DateTime<Utc>,
+    pub level: Option<LogLevel>,
+    pub source: String,
+    pub message: String,
+    pub archived_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentJob {
+    pub id: Uuid,
+    pub kind: DeploymentJobKind,
+    /// Agents to deploy; a single entry for `Deploy` jobs.
+    pub agent_ids: Vec<Uuid>,
+    pub provider: VpsProvider,
+    pub region: Option<String>,
+    /// The caller's key for Railway jobs; cleared once the job succeeds or fails.
+    #[serde(skip_serializing)]
+    pub railway_api_key: Option<String>,
+    /// The agent with its unsaved settings, for `UpdateSettings` jobs. Holds secrets, so it is
+    /// cleared along with `railway_api_key`.
+    #[serde(skip_serializing)]
+    pub agent_settings: Option<Agent>,
+    /// Revision to go back to, for `Rollback` jobs.
+    pub revision: Option<i32>,
+    pub status: DeploymentJobStatus,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    /// Earliest time a worker may pick the job up (pushed back between retries).
+    pub scheduled_at: DateTime<Utc>,
+    /// Deployment created by the latest attempt, if any.
+    pub deployment_id: Option<Uuid>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentJobKind {
+    Deploy,
+    DeployMulti,
+    /// Pushes an agent's changed settings to its deployment, then saves them.
+    UpdateSettings,
+    /// Re-applies an earlier revision of a deployment.
+    Rollback,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeploymentJobStatus {
+    Queued,
+    Running,
+    Succeeded,
     Failed,
 }
 
@@ -107,10 +268,138 @@ pub struct Team {
     pub slave_ids: Vec<Uuid>,
     pub discord_channel_id: String, // Main coordination channel (deprecated, use discord_channels)
     pub discord_channels: DiscordChannels,
+    /// Set when the bot provisioned the team's Discord channels under this category; they
+    /// are deleted along with the team.
+    pub discord_category_id: Option<String>,
+    /// Required when the team coordinates over Slack.
+    pub slack_channels: Option<SlackChannels>,
+    pub settings: TeamSettings,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TeamSettings {
+    /// How the master splits incoming tasks into subtasks for slaves.
+    pub planner: PlannerConfig,
+    /// Default deadline for each attempt at a task, when the task doesn't set one.
+    pub task_timeout_secs: Option<u32>,
+    /// Default number of attempts before a timed-out task is given up on.
+    pub max_task_attempts: Option<u32>,
+    /// Discord roles whose members may run task commands on any of the team's tasks, in
+    /// addition to the bot of the agent each task is assigned to.
+    pub operator_role_ids: Vec<String>,
+    /// Slack users (people or agent bots) who may change the team's tasks from Slack. When
+    /// empty, tasks can only be changed through the other transports or the API.
+    pub slack_operator_user_ids: Vec<String>,
+    /// Where orders, slave messages and coordination logs are delivered.
+    pub transport: TransportConfig,
+}
+
+/// Placeholder [`TeamSettings::redacted`] puts in place of credentials.
+pub const REDACTED: &str = "***";
+
+impl TeamSettings {
+    /// Copy safe to return from the API, without credentials.
+    pub fn redacted(&self) -> Self {
+        let planner = match &self.planner {
+            PlannerConfig::Model(model) => PlannerConfig::Model(ModelPlannerConfig {
+                api_key: model.api_key.as_ref().map(|_| REDACTED.to_string()),
+                ..model.clone()
+            }),
+            planner => planner.clone(),
+        };
+        let transport = match &self.transport {
+            TransportConfig::Webhook(webhook) => TransportConfig::Webhook(WebhookTransportConfig {
+                secret: webhook.secret.as_ref().map(|_| REDACTED.to_string()),
+                ..webhook.clone()
+            }),
+            transport => transport.clone(),
+        };
+        Self {
+            planner,
+            transport,
+            ..self.clone()
+        }
+    }
+
+    /// Takes credentials left out or sent back as [`REDACTED`] from `stored`, so settings
+    /// read from the API can be sent back as they are. A credential is only kept for the
+    /// same endpoint or URL, so a new one never receives the old credential. An empty value
+    /// clears it.
+    pub fn keep_credentials(&mut self, stored: &TeamSettings) {
+        if let (PlannerConfig::Model(model), PlannerConfig::Model(stored)) =
+            (&mut self.planner, &stored.planner)
+        {
+            let same_endpoint = model.endpoint == stored.endpoint;
+            keep_credential(&mut model.api_key, &stored.api_key, same_endpoint);
+        }
+        if let (TransportConfig::Webhook(webhook), TransportConfig::Webhook(stored)) =
+            (&mut self.transport, &stored.transport)
+        {
+            let same_url = webhook.url == stored.url;
+            keep_credential(&mut webhook.secret, &stored.secret, same_url);
+        }
+    }
+}
+
+fn keep_credential(value: &mut Option<String>, stored: &Option<String>, same_target: bool) {
+    match value.as_deref() {
+        None | Some(REDACTED) if same_target => *value = stored.clone(),
+        Some("") => *value = None,
+        _ => {}
+    }
+}
+
+/// Task decomposition strategy; see [`crate::coordinator::planner`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "lowercase")]
+pub enum PlannerConfig {
+    /// The master keeps the whole task.
+    None,
+    /// One subtask per non-empty line, with list bullets and numbering stripped.
+    #[default]
+    Lines,
+    /// The description is a JSON list of subtasks.
+    Json,
+    /// An OpenAI-compatible chat completions endpoint decomposes the task.
+    Model(ModelPlannerConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelPlannerConfig {
+    /// Base URL, e.g. `https://api.openai.com/v1`; `/chat/completions` is appended.
+    pub endpoint: String,
+    pub model: String,
+    pub api_key: Option<String>,
+    /// Upper bound on subtasks; extra ones returned by the model are dropped.
+    #[serde(default)]
+    pub max_subtasks: Option<usize>,
+}
+
+/// How a team coordinates; see [`crate::coordinator::transport`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TransportConfig {
+    /// The team's Discord channels, with a thread per top-level task.
+    #[default]
+    Discord,
+    /// The team's Slack channels, with a thread per top-level task.
+    Slack,
+    /// Kept in memory on the server, for coordinating without an external service.
+    InProcess,
+    /// POSTed as JSON to a URL; commands come back through the API.
+    Webhook(WebhookTransportConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookTransportConfig {
+    pub url: String,
+    /// Sent as a bearer token with each request, so the receiver can verify it.
+    pub secret: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiscordChannels {
     pub coordination_logs: String, // Channel for coordination logs and status updates
@@ -118,6 +407,14 @@ pub struct DiscordChannels {
     pub master_orders: String,     // Channel for master orders and task delegation
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlackChannels {
+    pub coordination_logs: String,
+    pub slave_communication: String,
+    /// Task threads are started here.
+    pub master_orders: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     pub id: Uuid,
@@ -128,15 +425,85 @@ pub struct Task {
     pub status: TaskStatus,
     pub description: String,
     pub result: Option<String>,
+    /// Why the master routed this subtask to `assigned_to`, e.g. which skills matched, or
+    /// why it is still queued.
+    pub routing_reason: Option<String>,
+    /// Skills the planner asked for, kept so queued subtasks can be routed later.
+    pub required_skills: Vec<String>,
+    /// Tasks that must complete before this one is dispatched; their results are passed
+    /// along with it.
+    pub depends_on: Vec<Uuid>,
+    /// How long an agent has to finish an attempt; `None` means no deadline.
+    pub timeout_secs: Option<i32>,
+    /// Attempts allowed before a timed-out task stays timed out.
+    pub max_attempts: i32,
+    /// Times the task has been dispatched to an agent.
+    pub attempts: i32,
+    /// When the current attempt times out.
+    pub deadline_at: Option<DateTime<Utc>>,
+    /// Percent done, as last reported by the agent.
+    pub progress: Option<i32>,
+    pub progress_note: Option<String>,
+    /// Thread for the task's conversation on the team's transport (a Discord thread id, or
+    /// the Slack timestamp of the thread's first message); subtasks share their parent's.
+    pub thread_id: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum TaskStatus {
+    /// Waiting for tasks in `depends_on` to complete.
+    Blocked,
     Pending,
     InProgress,
     Completed,
     Failed,
+    /// An upstream task failed or was cancelled.
+    Cancelled,
+    /// Every attempt ran past its deadline.
+    #[serde(rename = "timed_out")]
+    TimedOut,
+}
+
+impl TaskStatus {
+    /// Name as stored in the database and shown to agents, e.g. `in_progress`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::Blocked => "blocked",
+            TaskStatus::Pending => "pending",
+            TaskStatus::InProgress => "in_progress",
+            TaskStatus::Completed => "completed",
+            TaskStatus::Failed => "failed",
+            TaskStatus::Cancelled => "cancelled",
+            TaskStatus::TimedOut => "timed_out",
+        }
+    }
+
+    /// Completed, failed, cancelled or timed out; the task won't change again.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            TaskStatus::Completed
+                | TaskStatus::Failed
+                | TaskStatus::Cancelled
+                | TaskStatus::TimedOut
+        )
+    }
+}
+
+/// One dispatch of a task to an agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskAttempt {
+    pub id: Uuid,
+    pub task_id: Uuid,
+    /// 1 for the first dispatch.
+    pub attempt: i32,
+    pub agent_id: Option<Uuid>,
+    pub started_at: DateTime<Utc>,
+    pub deadline_at: Option<DateTime<Utc>>,
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Status the task ended the attempt with; `None` while it is running.
+    pub outcome: Option<TaskStatus>,
 }
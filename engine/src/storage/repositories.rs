@@ -1,11 +1,17 @@
+use crate::adapters::{LogEntry, LogLevel};
 use crate::models::{
-    Agent, AgentRole, AgentRuntime, AgentStatus, Deployment, DeploymentStatus, DiscordChannels,
-    ModelProvider, Task, TaskStatus, Team, VpsProvider,
+    Agent, AgentRole, AgentRuntime, AgentStatus, Deployment, DeploymentJob, DeploymentJobKind,
+    DeploymentJobStatus, DeploymentLog, DeploymentRevision, DeploymentStatus, DiscordChannels,
+    ModelProvider, OutboxAction, OutboxMessage, SlackChannels, Task, TaskAttempt, TaskStatus, Team,
+    TeamSettings, VpsProvider,
 };
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use claws_runtime_core::RuntimeServicePort;
+use sha2::{Digest, Sha256};
 use sqlx::types::Json;
 use sqlx::{FromRow, PgPool, Postgres, Transaction};
+use std::collections::{BTreeMap, HashMap};
 use uuid::Uuid;
 
 #[derive(FromRow)]
@@ -29,6 +35,7 @@ struct AgentRow {
     runtime_config: Option<Json<serde_json::Value>>,
     responsibility: Option<String>,
     emoji: Option<String>,
+    max_concurrent_tasks: Option<i32>,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 }
@@ -57,6 +64,7 @@ impl TryFrom<AgentRow> for Agent {
             runtime_config: row.runtime_config.map(|value| value.0),
             responsibility: row.responsibility,
             emoji: row.emoji,
+            max_concurrent_tasks: row.max_concurrent_tasks,
             created_at: row.created_at,
             updated_at: row.updated_at,
         })
@@ -100,6 +108,121 @@ impl TryFrom<DeploymentRow> for Deployment {
     }
 }
 
+#[derive(FromRow)]
+struct DeploymentJobRow {
+    id: Uuid,
+    kind: String,
+    agent_ids: Vec<Uuid>,
+    provider: String,
+    region: Option<String>,
+    railway_api_key: Option<String>,
+    agent_settings: Option<Json<Agent>>,
+    revision: Option<i32>,
+    status: String,
+    attempts: i32,
+    max_attempts: i32,
+    scheduled_at: DateTime<Utc>,
+    deployment_id: Option<Uuid>,
+    last_error: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl TryFrom<DeploymentJobRow> for DeploymentJob {
+    type Error = anyhow::Error;
+
+    fn try_from(row: DeploymentJobRow) -> Result<Self> {
+        Ok(DeploymentJob {
+            id: row.id,
+            kind: parse_deployment_job_kind(&row.kind)?,
+            agent_ids: row.agent_ids,
+            provider: parse_vps_provider(&row.provider)?,
+            region: row.region,
+            railway_api_key: row.railway_api_key,
+            agent_settings: row.agent_settings.map(|settings| settings.0),
+            revision: row.revision,
+            status: parse_deployment_job_status(&row.status)?,
+            attempts: row.attempts,
+            max_attempts: row.max_attempts,
+            scheduled_at: row.scheduled_at,
+            deployment_id: row.deployment_id,
+            last_error: row.last_error,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct DeploymentRevisionRow {
+    id: Uuid,
+    deployment_id: Uuid,
+    revision: i32,
+    runtime: String,
+    provider: String,
+    region: Option<String>,
+    env: Json<BTreeMap<String, String>>,
+    init_script: String,
+    init_script_hash: String,
+    services: Json<Vec<RuntimeServicePort>>,
+    agent_configs: Json<BTreeMap<Uuid, Option<serde_json::Value>>>,
+    restored_from: Option<i32>,
+    created_at: DateTime<Utc>,
+}
+
+impl TryFrom<DeploymentRevisionRow> for DeploymentRevision {
+    type Error = anyhow::Error;
+
+    fn try_from(row: DeploymentRevisionRow) -> Result<Self> {
+        Ok(DeploymentRevision {
+            id: row.id,
+            deployment_id: row.deployment_id,
+            revision: row.revision,
+            runtime: parse_agent_runtime(&row.runtime)?,
+            provider: parse_vps_provider(&row.provider)?,
+            region: row.region,
+            env: row.env.0,
+            init_script: row.init_script,
+            init_script_hash: row.init_script_hash,
+            services: row.services.0,
+            agent_configs: row.agent_configs.0,
+            restored_from: row.restored_from,
+            created_at: row.created_at,
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct DeploymentLogRow {
+    id: Uuid,
+    deployment_id: Uuid,
+    agent_id: Uuid,
+    provider: String,
+    logged_at: DateTime<Utc>,
+    level: Option<String>,
+    source: String,
+    message: String,
+    archived_at: DateTime<Utc>,
+}
+
+impl TryFrom<DeploymentLogRow> for DeploymentLog {
+    type Error = anyhow::Error;
+
+    fn try_from(row: DeploymentLogRow) -> Result<Self> {
+        Ok(DeploymentLog {
+            id: row.id,
+            deployment_id: row.deployment_id,
+            agent_id: row.agent_id,
+            provider: parse_vps_provider(&row.provider)?,
+            timestamp: row.logged_at,
+            level: row.level.as_deref().and_then(LogLevel::parse),
+            source: row.source,
+            message: row.message,
+            archived_at: row.archived_at,
+        })
+    }
+}
+
 #[derive(FromRow)]
 struct TeamRow {
     id: Uuid,
@@ -108,6 +231,9 @@ struct TeamRow {
     slave_ids: Vec<Uuid>,
     discord_channel_id: String,
     discord_channels: Json<DiscordChannels>,
+    discord_category_id: Option<String>,
+    slack_channels: Option<Json<SlackChannels>>,
+    settings: Json<TeamSettings>,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 }
@@ -121,6 +247,9 @@ impl From<TeamRow> for Team {
             slave_ids: row.slave_ids,
             discord_channel_id: row.discord_channel_id,
             discord_channels: row.discord_channels.0,
+            discord_category_id: row.discord_category_id,
+            slack_channels: row.slack_channels.map(|value| value.0),
+            settings: row.settings.0,
             created_at: row.created_at,
             updated_at: row.updated_at,
         }
@@ -136,6 +265,16 @@ struct TaskRow {
     status: String,
     description: String,
     result: Option<String>,
+    routing_reason: Option<String>,
+    required_skills: Vec<String>,
+    depends_on: Vec<Uuid>,
+    timeout_secs: Option<i32>,
+    max_attempts: i32,
+    attempts: i32,
+    deadline_at: Option<DateTime<Utc>>,
+    progress: Option<i32>,
+    progress_note: Option<String>,
+    thread_id: Option<String>,
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 }
@@ -152,12 +291,80 @@ impl TryFrom<TaskRow> for Task {
             status: parse_task_status(&row.status)?,
             description: row.description,
             result: row.result,
+            routing_reason: row.routing_reason,
+            required_skills: row.required_skills,
+            depends_on: row.depends_on,
+            timeout_secs: row.timeout_secs,
+            max_attempts: row.max_attempts,
+            attempts: row.attempts,
+            deadline_at: row.deadline_at,
+            progress: row.progress,
+            progress_note: row.progress_note,
+            thread_id: row.thread_id,
             created_at: row.created_at,
             updated_at: row.updated_at,
         })
     }
 }
 
+#[derive(FromRow)]
+struct TaskAttemptRow {
+    id: Uuid,
+    task_id: Uuid,
+    attempt: i32,
+    agent_id: Option<Uuid>,
+    started_at: DateTime<Utc>,
+    deadline_at: Option<DateTime<Utc>>,
+    ended_at: Option<DateTime<Utc>>,
+    outcome: Option<String>,
+}
+
+impl TryFrom<TaskAttemptRow> for TaskAttempt {
+    type Error = anyhow::Error;
+
+    fn try_from(row: TaskAttemptRow) -> Result<Self> {
+        Ok(TaskAttempt {
+            id: row.id,
+            task_id: row.task_id,
+            attempt: row.attempt,
+            agent_id: row.agent_id,
+            started_at: row.started_at,
+            deadline_at: row.deadline_at,
+            ended_at: row.ended_at,
+            outcome: row.outcome.as_deref().map(parse_task_status).transpose()?,
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct OutboxRow {
+    id: Uuid,
+    channel_id: String,
+    action: String,
+    content: String,
+    embed: Option<Json<serde_json::Value>>,
+    chunks_sent: i32,
+    attempts: i32,
+    created_at: DateTime<Utc>,
+}
+
+impl TryFrom<OutboxRow> for OutboxMessage {
+    type Error = anyhow::Error;
+
+    fn try_from(row: OutboxRow) -> Result<Self> {
+        Ok(OutboxMessage {
+            id: row.id,
+            channel_id: row.channel_id,
+            action: parse_outbox_action(&row.action)?,
+            content: row.content,
+            embed: row.embed.map(|embed| embed.0),
+            chunks_sent: row.chunks_sent,
+            attempts: row.attempts,
+            created_at: row.created_at,
+        })
+    }
+}
+
 pub struct AgentRepository {
     db: PgPool,
 }
@@ -174,13 +381,13 @@ impl AgentRepository {
                 id, name, role, status, runtime, deployment_id, team_id, discord_bot_token,
                 discord_channel_id, discord_channels, model_provider, model_api_key,
                 model_endpoint, personality, skills, workspace_dir, runtime_config, responsibility, emoji,
-                created_at, updated_at
+                max_concurrent_tasks, created_at, updated_at
             )
             VALUES (
                 $1, $2, $3, $4, $5, $6, $7,
                 $8, $9, $10, $11, $12,
                 $13, $14, $15, $16, $17, $18,
-                $19, $20, $21
+                $19, $20, $21, $22
             )
             "#,
         )
@@ -203,6 +410,7 @@ impl AgentRepository {
         .bind(agent.runtime_config.clone().map(Json))
         .bind(&agent.responsibility)
         .bind(&agent.emoji)
+        .bind(agent.max_concurrent_tasks)
         .bind(agent.created_at)
         .bind(agent.updated_at)
         .execute(&self.db)
@@ -218,7 +426,7 @@ impl AgentRepository {
             SELECT id, name, role, status, deployment_id, team_id, discord_bot_token,
                    discord_channel_id, discord_channels, model_provider, model_api_key,
                    model_endpoint, personality, skills, workspace_dir, runtime_config, responsibility, emoji,
-                   runtime,
+                   runtime, max_concurrent_tasks,
                    created_at, updated_at
             FROM agents
             WHERE id = $1
@@ -347,6 +555,42 @@ impl AgentRepository {
         Ok(())
     }
 
+    /// Persists the user-editable settings that feed the runtime plan.
+    pub async fn update_settings(&self, agent: &Agent) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE agents
+            SET discord_channel_id = $2,
+                discord_channels = $3,
+                model_provider = $4,
+                model_api_key = $5,
+                model_endpoint = $6,
+                personality = $7,
+                skills = $8,
+                runtime_config = $9,
+                max_concurrent_tasks = $10,
+                updated_at = $11
+            WHERE id = $1
+            "#,
+        )
+        .bind(agent.id)
+        .bind(&agent.discord_channel_id)
+        .bind(agent.discord_channels.as_ref().map(Json))
+        .bind(model_provider_to_str(&agent.model_provider))
+        .bind(&agent.model_api_key)
+        .bind(&agent.model_endpoint)
+        .bind(&agent.personality)
+        .bind(&agent.skills)
+        .bind(agent.runtime_config.as_ref().map(Json))
+        .bind(agent.max_concurrent_tasks)
+        .bind(Utc::now())
+        .execute(&self.db)
+        .await
+        .context("failed to update agent settings")?;
+
+        Ok(())
+    }
+
     pub async fn update_runtime_config(
         &self,
         id: Uuid,
@@ -424,7 +668,7 @@ impl AgentRepository {
             SELECT id, name, role, status, deployment_id, team_id, discord_bot_token,
                    discord_channel_id, discord_channels, model_provider, model_api_key,
                    model_endpoint, personality, skills, workspace_dir, runtime_config, responsibility, emoji,
-                   runtime,
+                   runtime, max_concurrent_tasks,
                    created_at, updated_at
             FROM agents
             ORDER BY created_at DESC
@@ -545,6 +789,56 @@ impl DeploymentRepository {
         Ok(())
     }
 
+    pub async fn update_volume_id(&self, id: Uuid, volume_id: Option<String>) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE deployments
+            SET volume_id = $2,
+                updated_at = $3
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(volume_id)
+        .bind(Utc::now())
+        .execute(&self.db)
+        .await
+        .context("failed to update deployment volume_id")?;
+
+        Ok(())
+    }
+
+    /// The most recent volume left behind by a torn-down deployment of `agent_id` on the
+    /// same provider and region, as `(deployment_id, volume_id)`.
+    pub async fn find_reusable_volume(
+        &self,
+        agent_id: Uuid,
+        provider: &VpsProvider,
+        region: Option<&str>,
+    ) -> Result<Option<(Uuid, String)>> {
+        let row: Option<(Uuid, String)> = sqlx::query_as(
+            r#"
+            SELECT id, volume_id
+            FROM deployments
+            WHERE agent_id = $1
+              AND provider = $2
+              AND region IS NOT DISTINCT FROM $3
+              AND volume_id IS NOT NULL
+              AND status IN ('destroyed', 'failed', 'lost')
+            ORDER BY updated_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(agent_id)
+        .bind(vps_provider_to_str(provider))
+        .bind(region)
+        .fetch_optional(&self.db)
+        .await
+        .context("failed to look up reusable volume")?;
+
+        Ok(row)
+    }
+
     pub async fn get_by_agent_id(&self, agent_id: Uuid) -> Result<Option<Deployment>> {
         let row: Option<DeploymentRow> = sqlx::query_as(
             r#"
@@ -594,44 +888,60 @@ impl DeploymentRepository {
     }
 }
 
-pub struct TeamRepository {
+pub struct DeploymentJobRepository {
     db: PgPool,
 }
 
-impl TeamRepository {
+impl DeploymentJobRepository {
     pub fn new(db: PgPool) -> Self {
         Self { db }
     }
 
-    pub async fn create(&self, team: &Team) -> Result<()> {
+    pub async fn create(&self, job: &DeploymentJob) -> Result<()> {
         sqlx::query(
             r#"
-            INSERT INTO teams (
-                id, name, master_id, slave_ids, discord_channel_id, discord_channels, created_at, updated_at
+            INSERT INTO deployment_jobs (
+                id, kind, agent_ids, provider, region, railway_api_key, agent_settings, revision,
+                status, attempts, max_attempts, scheduled_at, deployment_id, last_error,
+                created_at, updated_at
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8,
+                $9, $10, $11, $12, $13, $14,
+                $15, $16
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             "#,
         )
-        .bind(team.id)
-        .bind(&team.name)
-        .bind(team.master_id)
-        .bind(&team.slave_ids)
-        .bind(&team.discord_channel_id)
-        .bind(Json(team.discord_channels.clone()))
-        .bind(team.created_at)
-        .bind(team.updated_at)
+        .bind(job.id)
+        .bind(deployment_job_kind_to_str(&job.kind))
+        .bind(&job.agent_ids)
+        .bind(vps_provider_to_str(&job.provider))
+        .bind(&job.region)
+        .bind(&job.railway_api_key)
+        .bind(job.agent_settings.as_ref().map(Json))
+        .bind(job.revision)
+        .bind(deployment_job_status_to_str(&job.status))
+        .bind(job.attempts)
+        .bind(job.max_attempts)
+        .bind(job.scheduled_at)
+        .bind(job.deployment_id)
+        .bind(&job.last_error)
+        .bind(job.created_at)
+        .bind(job.updated_at)
         .execute(&self.db)
         .await
-        .context("failed to create team")?;
+        .context("failed to create deployment job")?;
 
         Ok(())
     }
 
-    pub async fn get_by_id(&self, id: Uuid) -> Result<Option<Team>> {
-        let row: Option<TeamRow> = sqlx::query_as(
+    pub async fn get_by_id(&self, id: Uuid) -> Result<Option<DeploymentJob>> {
+        let row: Option<DeploymentJobRow> = sqlx::query_as(
             r#"
-            SELECT id, name, master_id, slave_ids, discord_channel_id, discord_channels, created_at, updated_at
-            FROM teams
+            SELECT id, kind, agent_ids, provider, region, railway_api_key, agent_settings, revision,
+                   status, attempts, max_attempts, scheduled_at, deployment_id, last_error,
+                   created_at, updated_at
+            FROM deployment_jobs
             WHERE id = $1
             "#,
         )
@@ -639,227 +949,1431 @@ impl TeamRepository {
         .fetch_optional(&self.db)
         .await?;
 
-        Ok(row.map(Team::from))
+        row.map(DeploymentJob::try_from).transpose()
     }
 
-    pub async fn list_all(&self) -> Result<Vec<Team>> {
-        let rows: Vec<TeamRow> = sqlx::query_as(
+    /// Atomically moves the oldest due job to `running` and counts the attempt.
+    /// `SKIP LOCKED` lets several workers poll without handing out the same job.
+    pub async fn claim_next(&self) -> Result<Option<DeploymentJob>> {
+        let row: Option<DeploymentJobRow> = sqlx::query_as(
             r#"
-            SELECT id, name, master_id, slave_ids, discord_channel_id, discord_channels, created_at, updated_at
-            FROM teams
-            ORDER BY created_at DESC
+            UPDATE deployment_jobs
+            SET status = 'running',
+                attempts = attempts + 1,
+                updated_at = $1
+            WHERE id = (
+                SELECT id
+                FROM deployment_jobs
+                WHERE status = 'queued' AND scheduled_at <= $1
+                ORDER BY scheduled_at, created_at
+                FOR UPDATE SKIP LOCKED
+                LIMIT 1
+            )
+            RETURNING id, kind, agent_ids, provider, region, railway_api_key, agent_settings,
+                      revision, status, attempts, max_attempts, scheduled_at, deployment_id,
+                      last_error, created_at, updated_at
             "#,
         )
-        .fetch_all(&self.db)
-        .await?;
+        .bind(Utc::now())
+        .fetch_optional(&self.db)
+        .await
+        .context("failed to claim deployment job")?;
 
-        Ok(rows.into_iter().map(Team::from).collect())
+        row.map(DeploymentJob::try_from).transpose()
     }
 
-    pub async fn update_members(
-        &self,
-        id: Uuid,
-        master_id: Uuid,
-        slave_ids: Vec<Uuid>,
-    ) -> Result<()> {
+    pub async fn update_deployment_id(&self, id: Uuid, deployment_id: Uuid) -> Result<()> {
         sqlx::query(
             r#"
-            UPDATE teams
-            SET master_id = $2,
-                slave_ids = $3,
-                updated_at = $4
+            UPDATE deployment_jobs
+            SET deployment_id = $2,
+                updated_at = $3
             WHERE id = $1
             "#,
         )
         .bind(id)
-        .bind(master_id)
-        .bind(slave_ids)
+        .bind(deployment_id)
         .bind(Utc::now())
         .execute(&self.db)
         .await
-        .context("failed to update team members")?;
+        .context("failed to update deployment job deployment_id")?;
 
         Ok(())
     }
 
-    pub async fn create_tx(&self, tx: &mut Transaction<'_, Postgres>, team: &Team) -> Result<()> {
+    /// Finishing a job drops the caller's Railway key and the agent settings it carried,
+    /// which are only kept for retries.
+    pub async fn mark_succeeded(&self, id: Uuid, deployment_id: Uuid) -> Result<()> {
         sqlx::query(
             r#"
-            INSERT INTO teams (
-                id, name, master_id, slave_ids, discord_channel_id, discord_channels, created_at, updated_at
-            )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            UPDATE deployment_jobs
+            SET status = 'succeeded',
+                deployment_id = $2,
+                railway_api_key = NULL,
+                agent_settings = NULL,
+                last_error = NULL,
+                updated_at = $3
+            WHERE id = $1
             "#,
         )
-        .bind(team.id)
-        .bind(&team.name)
-        .bind(team.master_id)
-        .bind(&team.slave_ids)
-        .bind(&team.discord_channel_id)
-        .bind(Json(team.discord_channels.clone()))
-        .bind(team.created_at)
-        .bind(team.updated_at)
-        .execute(tx.as_mut())
+        .bind(id)
+        .bind(deployment_id)
+        .bind(Utc::now())
+        .execute(&self.db)
         .await
-        .context("failed to create team")?;
+        .context("failed to mark deployment job succeeded")?;
 
         Ok(())
     }
 
-    pub async fn update_members_tx(
+    /// Puts the job back in the queue to be retried at `scheduled_at`.
+    pub async fn reschedule(
         &self,
-        tx: &mut Transaction<'_, Postgres>,
         id: Uuid,
-        master_id: Uuid,
-        slave_ids: Vec<Uuid>,
+        error: &str,
+        scheduled_at: DateTime<Utc>,
     ) -> Result<()> {
         sqlx::query(
             r#"
-            UPDATE teams
-            SET master_id = $2,
-                slave_ids = $3,
+            UPDATE deployment_jobs
+            SET status = 'queued',
+                last_error = $2,
+                scheduled_at = $3,
                 updated_at = $4
             WHERE id = $1
             "#,
         )
         .bind(id)
-        .bind(master_id)
-        .bind(slave_ids)
+        .bind(error)
+        .bind(scheduled_at)
         .bind(Utc::now())
-        .execute(tx.as_mut())
+        .execute(&self.db)
         .await
-        .context("failed to update team members")?;
+        .context("failed to reschedule deployment job")?;
 
         Ok(())
     }
-}
-
-pub struct TaskRepository {
-    db: PgPool,
-}
-
-impl TaskRepository {
-    pub fn new(db: PgPool) -> Self {
-        Self { db }
-    }
 
-    pub async fn create(&self, task: &Task) -> Result<()> {
+    pub async fn mark_failed(&self, id: Uuid, error: &str) -> Result<()> {
         sqlx::query(
             r#"
-            INSERT INTO tasks (
-                id, team_id, parent_task_id, assigned_to, status, description, result, created_at, updated_at
-            )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            UPDATE deployment_jobs
+            SET status = 'failed',
+                railway_api_key = NULL,
+                agent_settings = NULL,
+                last_error = $2,
+                updated_at = $3
+            WHERE id = $1
             "#,
         )
-        .bind(task.id)
-        .bind(task.team_id)
-        .bind(task.parent_task_id)
-        .bind(task.assigned_to)
-        .bind(task_status_to_str(&task.status))
-        .bind(&task.description)
-        .bind(&task.result)
-        .bind(task.created_at)
-        .bind(task.updated_at)
+        .bind(id)
+        .bind(error)
+        .bind(Utc::now())
         .execute(&self.db)
         .await
-        .context("failed to create task")?;
+        .context("failed to mark deployment job failed")?;
 
         Ok(())
     }
 
-    pub async fn get_by_id(&self, id: Uuid) -> Result<Option<Task>> {
-        let row: Option<TaskRow> = sqlx::query_as(
+    /// Whether a job of `kind` for the agent is still queued or running.
+    pub async fn has_open_job(&self, agent_id: Uuid, kind: &DeploymentJobKind) -> Result<bool> {
+        let open: bool = sqlx::query_scalar(
             r#"
-            SELECT id, team_id, parent_task_id, assigned_to, status, description, result, created_at, updated_at
-            FROM tasks
-            WHERE id = $1
+            SELECT EXISTS (
+                SELECT 1
+                FROM deployment_jobs
+                WHERE agent_ids @> ARRAY[$1]::uuid[]
+                  AND kind = $2
+                  AND status IN ('queued', 'running')
+            )
             "#,
         )
-        .bind(id)
-        .fetch_optional(&self.db)
-        .await?;
+        .bind(agent_id)
+        .bind(deployment_job_kind_to_str(kind))
+        .fetch_one(&self.db)
+        .await
+        .context("failed to check for open deployment jobs")?;
 
-        row.map(Task::try_from).transpose()
+        Ok(open)
     }
 
-    pub async fn update_status(&self, id: Uuid, status: TaskStatus) -> Result<()> {
-        sqlx::query(
+    /// Returns jobs left `running` by a previous process to the queue. Returns how many were requeued.
+    pub async fn requeue_running(&self) -> Result<u64> {
+        let result = sqlx::query(
             r#"
-            UPDATE tasks
-            SET status = $2,
-                updated_at = $3
+            UPDATE deployment_jobs
+            SET status = 'queued',
+                scheduled_at = $1,
+                updated_at = $1
+            WHERE status = 'running'
+            "#,
+        )
+        .bind(Utc::now())
+        .execute(&self.db)
+        .await
+        .context("failed to requeue running deployment jobs")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+pub struct DeploymentRevisionRepository {
+    db: PgPool,
+}
+
+impl DeploymentRevisionRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    /// Inserts the revision with the next number for its deployment and returns that number;
+    /// `revision.revision` is ignored. Concurrent inserts fail on the unique constraint
+    /// rather than producing duplicates.
+    pub async fn create(&self, revision: &DeploymentRevision) -> Result<i32> {
+        let number: i32 = sqlx::query_scalar(
+            r#"
+            INSERT INTO deployment_revisions (
+                id, deployment_id, revision, runtime, provider, region, env, init_script,
+                init_script_hash, services, agent_configs, restored_from, created_at
+            )
+            SELECT $1, $2, COALESCE(MAX(revision), 0) + 1, $3, $4, $5, $6, $7,
+                   $8, $9, $10, $11, $12
+            FROM deployment_revisions
+            WHERE deployment_id = $2
+            RETURNING revision
+            "#,
+        )
+        .bind(revision.id)
+        .bind(revision.deployment_id)
+        .bind(agent_runtime_to_str(&revision.runtime))
+        .bind(vps_provider_to_str(&revision.provider))
+        .bind(&revision.region)
+        .bind(Json(&revision.env))
+        .bind(&revision.init_script)
+        .bind(&revision.init_script_hash)
+        .bind(Json(&revision.services))
+        .bind(Json(&revision.agent_configs))
+        .bind(revision.restored_from)
+        .bind(revision.created_at)
+        .fetch_one(&self.db)
+        .await
+        .context("failed to create deployment revision")?;
+
+        Ok(number)
+    }
+
+    pub async fn get(
+        &self,
+        deployment_id: Uuid,
+        revision: i32,
+    ) -> Result<Option<DeploymentRevision>> {
+        let row: Option<DeploymentRevisionRow> = sqlx::query_as(
+            r#"
+            SELECT id, deployment_id, revision, runtime, provider, region, env, init_script,
+                   init_script_hash, services, agent_configs, restored_from, created_at
+            FROM deployment_revisions
+            WHERE deployment_id = $1 AND revision = $2
+            "#,
+        )
+        .bind(deployment_id)
+        .bind(revision)
+        .fetch_optional(&self.db)
+        .await?;
+
+        row.map(DeploymentRevision::try_from).transpose()
+    }
+
+    pub async fn get_latest(&self, deployment_id: Uuid) -> Result<Option<DeploymentRevision>> {
+        let row: Option<DeploymentRevisionRow> = sqlx::query_as(
+            r#"
+            SELECT id, deployment_id, revision, runtime, provider, region, env, init_script,
+                   init_script_hash, services, agent_configs, restored_from, created_at
+            FROM deployment_revisions
+            WHERE deployment_id = $1
+            ORDER BY revision DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(deployment_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        row.map(DeploymentRevision::try_from).transpose()
+    }
+
+    /// Newest first.
+    pub async fn list_by_deployment(&self, deployment_id: Uuid) -> Result<Vec<DeploymentRevision>> {
+        let rows: Vec<DeploymentRevisionRow> = sqlx::query_as(
+            r#"
+            SELECT id, deployment_id, revision, runtime, provider, region, env, init_script,
+                   init_script_hash, services, agent_configs, restored_from, created_at
+            FROM deployment_revisions
+            WHERE deployment_id = $1
+            ORDER BY revision DESC
+            "#,
+        )
+        .bind(deployment_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        rows.into_iter().map(DeploymentRevision::try_from).collect()
+    }
+}
+
+/// Filters for [`DeploymentLogRepository::search`]; unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct LogSearch {
+    /// Matches the deployment's primary agent or any agent sharing it.
+    pub agent_id: Option<Uuid>,
+    pub deployment_id: Option<Uuid>,
+    /// Lines at this level or more severe; lines without a level are excluded.
+    pub min_level: Option<LogLevel>,
+    /// Case-insensitive substring of the message.
+    pub text: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: i64,
+}
+
+pub struct DeploymentLogRepository {
+    db: PgPool,
+}
+
+impl DeploymentLogRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    /// Archives `entries` for the deployment and returns how many were new. Lines already
+    /// archived (same timestamp, source and message, and the same occurrence of it within the
+    /// batch) are skipped, so overlapping batches can be inserted safely while lines a
+    /// deployment legitimately repeats are kept.
+    pub async fn insert_entries(
+        &self,
+        deployment: &Deployment,
+        entries: &[LogEntry],
+        archived_at: DateTime<Utc>,
+    ) -> Result<u64> {
+        let mut tx = self.db.begin().await?;
+        let mut inserted = 0;
+        let mut occurrences: HashMap<String, u32> = HashMap::new();
+        for entry in entries {
+            let line = log_fingerprint(entry);
+            let occurrence = occurrences.entry(line.clone()).or_default();
+            let fingerprint = match *occurrence {
+                0 => line,
+                n => format!("{}:{}", line, n),
+            };
+            *occurrence += 1;
+
+            let result = sqlx::query(
+                r#"
+                INSERT INTO deployment_logs (
+                    id, deployment_id, agent_id, provider, logged_at, level, source, message,
+                    fingerprint, archived_at
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                ON CONFLICT (deployment_id, fingerprint) DO NOTHING
+                "#,
+            )
+            .bind(Uuid::new_v4())
+            .bind(deployment.id)
+            .bind(deployment.agent_id)
+            .bind(vps_provider_to_str(&deployment.provider))
+            .bind(entry.timestamp.unwrap_or(archived_at))
+            .bind(entry.level.map(|level| level.as_str()))
+            .bind(&entry.source)
+            .bind(&entry.message)
+            .bind(fingerprint)
+            .bind(archived_at)
+            .execute(&mut *tx)
+            .await
+            .context("failed to archive deployment log")?;
+            inserted += result.rows_affected();
+        }
+        tx.commit().await?;
+
+        Ok(inserted)
+    }
+
+    /// Provider timestamp of the newest archived line, to resume archiving from.
+    pub async fn latest_timestamp(&self, deployment_id: Uuid) -> Result<Option<DateTime<Utc>>> {
+        let latest = sqlx::query_scalar(
+            r#"
+            SELECT MAX(logged_at)
+            FROM deployment_logs
+            WHERE deployment_id = $1
+            "#,
+        )
+        .bind(deployment_id)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(latest)
+    }
+
+    /// Newest first.
+    pub async fn search(&self, search: &LogSearch) -> Result<Vec<DeploymentLog>> {
+        let levels: Option<Vec<&str>> = search.min_level.map(|min_level| {
+            LogLevel::ALL
+                .iter()
+                .filter(|level| **level >= min_level)
+                .map(LogLevel::as_str)
+                .collect()
+        });
+        let pattern = search
+            .text
+            .as_deref()
+            .map(|text| format!("%{}%", escape_like(text)));
+
+        let rows: Vec<DeploymentLogRow> = sqlx::query_as(
+            r#"
+            SELECT id, deployment_id, agent_id, provider, logged_at, level, source, message,
+                   archived_at
+            FROM deployment_logs
+            WHERE ($1::uuid IS NULL
+                   OR agent_id = $1
+                   OR deployment_id IN (SELECT id FROM deployments WHERE $1 = ANY(agent_ids)))
+              AND ($2::uuid IS NULL OR deployment_id = $2)
+              AND ($3::text[] IS NULL OR level = ANY($3))
+              AND ($4::text IS NULL OR message ILIKE $4)
+              AND ($5::timestamptz IS NULL OR logged_at >= $5)
+              AND ($6::timestamptz IS NULL OR logged_at < $6)
+            ORDER BY logged_at DESC
+            LIMIT $7
+            "#,
+        )
+        .bind(search.agent_id)
+        .bind(search.deployment_id)
+        .bind(levels)
+        .bind(pattern)
+        .bind(search.since)
+        .bind(search.until)
+        .bind(search.limit)
+        .fetch_all(&self.db)
+        .await?;
+
+        rows.into_iter().map(DeploymentLog::try_from).collect()
+    }
+
+    /// Deletes lines older than `cutoff`, except warnings and errors, which are kept until
+    /// `error_cutoff`. Returns how many were deleted.
+    pub async fn prune(&self, cutoff: DateTime<Utc>, error_cutoff: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM deployment_logs
+            WHERE logged_at < $2
+               OR (logged_at < $1 AND (level IS NULL OR level NOT IN ('warn', 'error')))
+            "#,
+        )
+        .bind(cutoff)
+        .bind(error_cutoff)
+        .execute(&self.db)
+        .await
+        .context("failed to prune deployment logs")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+fn log_fingerprint(entry: &LogEntry) -> String {
+    let mut hasher = Sha256::new();
+    if let Some(timestamp) = entry.timestamp {
+        hasher.update(timestamp.to_rfc3339().as_bytes());
+    }
+    hasher.update([0]);
+    hasher.update(entry.source.as_bytes());
+    hasher.update([0]);
+    hasher.update(entry.message.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+pub struct TeamRepository {
+    db: PgPool,
+}
+
+impl TeamRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, team: &Team) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO teams (
+                id, name, master_id, slave_ids, discord_channel_id, discord_channels,
+                discord_category_id, slack_channels, settings, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+        )
+        .bind(team.id)
+        .bind(&team.name)
+        .bind(team.master_id)
+        .bind(&team.slave_ids)
+        .bind(&team.discord_channel_id)
+        .bind(Json(team.discord_channels.clone()))
+        .bind(&team.discord_category_id)
+        .bind(team.slack_channels.clone().map(Json))
+        .bind(Json(&team.settings))
+        .bind(team.created_at)
+        .bind(team.updated_at)
+        .execute(&self.db)
+        .await
+        .context("failed to create team")?;
+
+        Ok(())
+    }
+
+    pub async fn get_by_id(&self, id: Uuid) -> Result<Option<Team>> {
+        let row: Option<TeamRow> = sqlx::query_as(
+            r#"
+            SELECT id, name, master_id, slave_ids, discord_channel_id, discord_channels,
+                   discord_category_id, slack_channels, settings, created_at, updated_at
+            FROM teams
             WHERE id = $1
             "#,
         )
         .bind(id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(row.map(Team::from))
+    }
+
+    pub async fn list_all(&self) -> Result<Vec<Team>> {
+        let rows: Vec<TeamRow> = sqlx::query_as(
+            r#"
+            SELECT id, name, master_id, slave_ids, discord_channel_id, discord_channels,
+                   discord_category_id, slack_channels, settings, created_at, updated_at
+            FROM teams
+            ORDER BY created_at DESC
+            "#,
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows.into_iter().map(Team::from).collect())
+    }
+
+    pub async fn update_members(
+        &self,
+        id: Uuid,
+        master_id: Uuid,
+        slave_ids: Vec<Uuid>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE teams
+            SET master_id = $2,
+                slave_ids = $3,
+                updated_at = $4
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(master_id)
+        .bind(slave_ids)
+        .bind(Utc::now())
+        .execute(&self.db)
+        .await
+        .context("failed to update team members")?;
+
+        Ok(())
+    }
+
+    pub async fn update_details(
+        &self,
+        id: Uuid,
+        name: &str,
+        slack_channels: Option<&SlackChannels>,
+        settings: &TeamSettings,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE teams
+            SET name = $2,
+                slack_channels = $3,
+                settings = $4,
+                updated_at = $5
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(name)
+        .bind(slack_channels.map(Json))
+        .bind(Json(settings))
+        .bind(Utc::now())
+        .execute(&self.db)
+        .await
+        .context("failed to update team")?;
+
+        Ok(())
+    }
+
+    pub async fn create_tx(&self, tx: &mut Transaction<'_, Postgres>, team: &Team) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO teams (
+                id, name, master_id, slave_ids, discord_channel_id, discord_channels,
+                discord_category_id, slack_channels, settings, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+        )
+        .bind(team.id)
+        .bind(&team.name)
+        .bind(team.master_id)
+        .bind(&team.slave_ids)
+        .bind(&team.discord_channel_id)
+        .bind(Json(team.discord_channels.clone()))
+        .bind(&team.discord_category_id)
+        .bind(team.slack_channels.clone().map(Json))
+        .bind(Json(&team.settings))
+        .bind(team.created_at)
+        .bind(team.updated_at)
+        .execute(tx.as_mut())
+        .await
+        .context("failed to create team")?;
+
+        Ok(())
+    }
+
+    pub async fn update_members_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: Uuid,
+        master_id: Uuid,
+        slave_ids: Vec<Uuid>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE teams
+            SET master_id = $2,
+                slave_ids = $3,
+                updated_at = $4
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(master_id)
+        .bind(slave_ids)
+        .bind(Utc::now())
+        .execute(tx.as_mut())
+        .await
+        .context("failed to update team members")?;
+
+        Ok(())
+    }
+
+    /// Deletes the team along with its tasks and their attempts.
+    pub async fn delete_tx(&self, tx: &mut Transaction<'_, Postgres>, id: Uuid) -> Result<()> {
+        sqlx::query(
+            r#"
+            DELETE FROM task_attempts
+            WHERE task_id IN (SELECT id FROM tasks WHERE team_id = $1)
+            "#,
+        )
+        .bind(id)
+        .execute(tx.as_mut())
+        .await
+        .context("failed to delete team task attempts")?;
+
+        sqlx::query("DELETE FROM tasks WHERE team_id = $1")
+            .bind(id)
+            .execute(tx.as_mut())
+            .await
+            .context("failed to delete team tasks")?;
+
+        sqlx::query("DELETE FROM teams WHERE id = $1")
+            .bind(id)
+            .execute(tx.as_mut())
+            .await
+            .context("failed to delete team")?;
+
+        Ok(())
+    }
+}
+
+pub struct TaskRepository {
+    db: PgPool,
+}
+
+impl TaskRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, task: &Task) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO tasks (
+                id, team_id, parent_task_id, assigned_to, status, description, result,
+                routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                attempts, deadline_at, progress, progress_note, thread_id,
+                created_at, updated_at
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18,
+                $19
+            )
+            "#,
+        )
+        .bind(task.id)
+        .bind(task.team_id)
+        .bind(task.parent_task_id)
+        .bind(task.assigned_to)
+        .bind(task_status_to_str(&task.status))
+        .bind(&task.description)
+        .bind(&task.result)
+        .bind(&task.routing_reason)
+        .bind(&task.required_skills)
+        .bind(&task.depends_on)
+        .bind(task.timeout_secs)
+        .bind(task.max_attempts)
+        .bind(task.attempts)
+        .bind(task.deadline_at)
+        .bind(task.progress)
+        .bind(&task.progress_note)
+        .bind(&task.thread_id)
+        .bind(task.created_at)
+        .bind(task.updated_at)
+        .execute(&self.db)
+        .await
+        .context("failed to create task")?;
+
+        Ok(())
+    }
+
+    pub async fn get_by_id(&self, id: Uuid) -> Result<Option<Task>> {
+        let row: Option<TaskRow> = sqlx::query_as(
+            r#"
+            SELECT id, team_id, parent_task_id, assigned_to, status, description, result,
+                   routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                   attempts, deadline_at, progress, progress_note, thread_id,
+                   created_at, updated_at
+            FROM tasks
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        row.map(Task::try_from).transpose()
+    }
+
+    pub async fn update_status(&self, id: Uuid, status: TaskStatus) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE tasks
+            SET status = $2,
+                updated_at = $3
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(task_status_to_str(&status))
+        .bind(Utc::now())
+        .execute(&self.db)
+        .await
+        .context("failed to update task status")?;
+
+        Ok(())
+    }
+
+    pub async fn update_fields(
+        &self,
+        id: Uuid,
+        status: Option<TaskStatus>,
+        result: Option<String>,
+    ) -> Result<Option<Task>> {
+        let status_value = status.map(|value| task_status_to_str(&value).to_string());
+
+        let row: Option<TaskRow> = sqlx::query_as(
+            r#"
+            UPDATE tasks
+            SET status = COALESCE($2, status),
+                result = COALESCE($3, result),
+                updated_at = $4
+            WHERE id = $1
+            RETURNING id, team_id, parent_task_id, assigned_to, status, description, result,
+                      routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                      attempts, deadline_at, progress, progress_note, thread_id,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(id)
+        .bind(status_value)
+        .bind(result)
+        .bind(Utc::now())
+        .fetch_optional(&self.db)
+        .await?;
+
+        row.map(Task::try_from).transpose()
+    }
+
+    pub async fn get_by_agent_id(&self, agent_id: Uuid) -> Result<Vec<Task>> {
+        let rows: Vec<TaskRow> = sqlx::query_as(
+            r#"
+            SELECT id, team_id, parent_task_id, assigned_to, status, description, result,
+                   routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                   attempts, deadline_at, progress, progress_note, thread_id,
+                   created_at, updated_at
+            FROM tasks
+            WHERE assigned_to = $1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(agent_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        rows.into_iter().map(Task::try_from).collect()
+    }
+
+    pub async fn get_by_parent_id(&self, parent_id: Uuid) -> Result<Vec<Task>> {
+        let rows: Vec<TaskRow> = sqlx::query_as(
+            r#"
+            SELECT id, team_id, parent_task_id, assigned_to, status, description, result,
+                   routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                   attempts, deadline_at, progress, progress_note, thread_id,
+                   created_at, updated_at
+            FROM tasks
+            WHERE parent_task_id = $1
+            ORDER BY created_at ASC
+            "#,
+        )
+        .bind(parent_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        rows.into_iter().map(Task::try_from).collect()
+    }
+
+    /// Unassigned pending subtasks of the team, oldest first.
+    pub async fn list_queued(&self, team_id: Uuid) -> Result<Vec<Task>> {
+        let rows: Vec<TaskRow> = sqlx::query_as(
+            r#"
+            SELECT id, team_id, parent_task_id, assigned_to, status, description, result,
+                   routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                   attempts, deadline_at, progress, progress_note, thread_id,
+                   created_at, updated_at
+            FROM tasks
+            WHERE team_id = $1
+              AND assigned_to IS NULL
+              AND status = 'pending'
+              AND parent_task_id IS NOT NULL
+            ORDER BY created_at ASC
+            "#,
+        )
+        .bind(team_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        rows.into_iter().map(Task::try_from).collect()
+    }
+
+    /// Assigns a queued subtask. Returns `None` if it was assigned, or changed state, in the
+    /// meantime.
+    pub async fn assign_queued(
+        &self,
+        id: Uuid,
+        agent_id: Uuid,
+        routing_reason: &str,
+    ) -> Result<Option<Task>> {
+        let row: Option<TaskRow> = sqlx::query_as(
+            r#"
+            UPDATE tasks
+            SET assigned_to = $2,
+                routing_reason = $3,
+                updated_at = $4
+            WHERE id = $1 AND assigned_to IS NULL AND status = 'pending'
+            RETURNING id, team_id, parent_task_id, assigned_to, status, description, result,
+                      routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                      attempts, deadline_at, progress, progress_note, thread_id,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(id)
+        .bind(agent_id)
+        .bind(routing_reason)
+        .bind(Utc::now())
+        .fetch_optional(&self.db)
+        .await?;
+
+        row.map(Task::try_from).transpose()
+    }
+
+    /// Found tasks in no particular order; missing IDs are skipped.
+    pub async fn get_by_ids(&self, ids: &[Uuid]) -> Result<Vec<Task>> {
+        let rows: Vec<TaskRow> = sqlx::query_as(
+            r#"
+            SELECT id, team_id, parent_task_id, assigned_to, status, description, result,
+                   routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                   attempts, deadline_at, progress, progress_note, thread_id,
+                   created_at, updated_at
+            FROM tasks
+            WHERE id = ANY($1)
+            "#,
+        )
+        .bind(ids)
+        .fetch_all(&self.db)
+        .await?;
+
+        rows.into_iter().map(Task::try_from).collect()
+    }
+
+    /// Tasks that list `id` in `depends_on`, oldest first.
+    pub async fn get_dependents(&self, id: Uuid) -> Result<Vec<Task>> {
+        let rows: Vec<TaskRow> = sqlx::query_as(
+            r#"
+            SELECT id, team_id, parent_task_id, assigned_to, status, description, result,
+                   routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                   attempts, deadline_at, progress, progress_note, thread_id,
+                   created_at, updated_at
+            FROM tasks
+            WHERE $1 = ANY(depends_on)
+            ORDER BY created_at ASC
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&self.db)
+        .await?;
+
+        rows.into_iter().map(Task::try_from).collect()
+    }
+
+    /// Moves blocked tasks whose dependencies have all completed to `pending` and returns
+    /// them. Limited to dependents of `upstream_id` when given. Each task is only returned
+    /// by one caller, so concurrent callers don't dispatch it twice.
+    pub async fn unblock_ready(&self, upstream_id: Option<Uuid>) -> Result<Vec<Task>> {
+        let rows: Vec<TaskRow> = sqlx::query_as(
+            r#"
+            UPDATE tasks t
+            SET status = 'pending',
+                updated_at = $2
+            WHERE t.status = 'blocked'
+              AND ($1::uuid IS NULL OR $1 = ANY(t.depends_on))
+              AND NOT EXISTS (
+                  SELECT 1 FROM tasks d
+                  WHERE d.id = ANY(t.depends_on) AND d.status <> 'completed'
+              )
+            RETURNING t.id, t.team_id, t.parent_task_id, t.assigned_to, t.status, t.description,
+                      t.result, t.routing_reason, t.required_skills, t.depends_on, t.timeout_secs,
+                      t.max_attempts, t.attempts, t.deadline_at, t.progress, t.progress_note,
+                      t.thread_id, t.created_at, t.updated_at
+            "#,
+        )
+        .bind(upstream_id)
+        .bind(Utc::now())
+        .fetch_all(&self.db)
+        .await?;
+
+        rows.into_iter().map(Task::try_from).collect()
+    }
+
+    /// Sets the final status and result of a task that is still open. Returns `None` if it was
+    /// already finished, so only one caller gets to act on the transition.
+    pub async fn finish_open(
+        &self,
+        id: Uuid,
+        status: TaskStatus,
+        result: &str,
+    ) -> Result<Option<Task>> {
+        let row: Option<TaskRow> = sqlx::query_as(
+            r#"
+            UPDATE tasks
+            SET status = $2,
+                result = $3,
+                updated_at = $4
+            WHERE id = $1 AND status IN ('blocked', 'pending', 'in_progress')
+            RETURNING id, team_id, parent_task_id, assigned_to, status, description, result,
+                      routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                      attempts, deadline_at, progress, progress_note, thread_id,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(id)
         .bind(task_status_to_str(&status))
+        .bind(result)
         .bind(Utc::now())
-        .execute(&self.db)
+        .fetch_optional(&self.db)
         .await
-        .context("failed to update task status")?;
+        .context("failed to finish task")?;
+
+        row.map(Task::try_from).transpose()
+    }
 
+    pub async fn set_thread(&self, id: Uuid, thread_id: &str) -> Result<()> {
+        sqlx::query("UPDATE tasks SET thread_id = $2, updated_at = $3 WHERE id = $1")
+            .bind(id)
+            .bind(thread_id)
+            .bind(Utc::now())
+            .execute(&self.db)
+            .await
+            .context("failed to store task thread")?;
         Ok(())
     }
 
-    pub async fn update_fields(
+    /// Moves a pending task to `in_progress`. Returns `None` if it isn't pending.
+    pub async fn claim(&self, id: Uuid) -> Result<Option<Task>> {
+        let row: Option<TaskRow> = sqlx::query_as(
+            r#"
+            UPDATE tasks
+            SET status = 'in_progress',
+                updated_at = $2
+            WHERE id = $1 AND status = 'pending'
+            RETURNING id, team_id, parent_task_id, assigned_to, status, description, result,
+                      routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                      attempts, deadline_at, progress, progress_note, thread_id,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(id)
+        .bind(Utc::now())
+        .fetch_optional(&self.db)
+        .await
+        .context("failed to claim task")?;
+
+        row.map(Task::try_from).transpose()
+    }
+
+    /// Records progress on an open task, moving it to `in_progress` if it was pending.
+    /// Returns `None` if it isn't open.
+    pub async fn update_progress(
         &self,
         id: Uuid,
-        status: Option<TaskStatus>,
-        result: Option<String>,
+        progress: i32,
+        note: Option<&str>,
     ) -> Result<Option<Task>> {
-        let status_value = status.map(|value| task_status_to_str(&value).to_string());
-
         let row: Option<TaskRow> = sqlx::query_as(
             r#"
             UPDATE tasks
-            SET status = COALESCE($2, status),
-                result = COALESCE($3, result),
+            SET status = 'in_progress',
+                progress = $2,
+                progress_note = $3,
                 updated_at = $4
-            WHERE id = $1
-            RETURNING id, team_id, parent_task_id, assigned_to, status, description, result, created_at, updated_at
+            WHERE id = $1 AND status IN ('pending', 'in_progress')
+            RETURNING id, team_id, parent_task_id, assigned_to, status, description, result,
+                      routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                      attempts, deadline_at, progress, progress_note, thread_id,
+                      created_at, updated_at
             "#,
         )
         .bind(id)
-        .bind(status_value)
-        .bind(result)
+        .bind(progress)
+        .bind(note)
         .bind(Utc::now())
         .fetch_optional(&self.db)
-        .await?;
+        .await
+        .context("failed to update task progress")?;
 
         row.map(Task::try_from).transpose()
     }
 
-    pub async fn get_by_agent_id(&self, agent_id: Uuid) -> Result<Vec<Task>> {
+    /// Cancels every unfinished task that depends on `id`, directly or transitively, and
+    /// returns them.
+    pub async fn cancel_dependents(&self, id: Uuid, reason: &str) -> Result<Vec<Task>> {
         let rows: Vec<TaskRow> = sqlx::query_as(
             r#"
-            SELECT id, team_id, parent_task_id, assigned_to, status, description, result, created_at, updated_at
-            FROM tasks
-            WHERE assigned_to = $1
-            ORDER BY created_at DESC
+            WITH RECURSIVE dependents AS (
+                SELECT id FROM tasks WHERE $1 = ANY(depends_on)
+                UNION
+                SELECT t.id FROM tasks t JOIN dependents d ON d.id = ANY(t.depends_on)
+            )
+            UPDATE tasks
+            SET status = 'cancelled',
+                result = COALESCE(result, $2),
+                updated_at = $3
+            WHERE id IN (SELECT id FROM dependents)
+              AND status IN ('blocked', 'pending', 'in_progress')
+            RETURNING id, team_id, parent_task_id, assigned_to, status, description, result,
+                      routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                      attempts, deadline_at, progress, progress_note, thread_id,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(id)
+        .bind(reason)
+        .bind(Utc::now())
+        .fetch_all(&self.db)
+        .await
+        .context("failed to cancel dependent tasks")?;
+
+        rows.into_iter().map(Task::try_from).collect()
+    }
+
+    /// Cancels blocked tasks with a dependency that failed, was cancelled or timed out, along
+    /// with everything downstream of them, and returns them. Catches tasks created while
+    /// their dependency was finishing, which [`Self::cancel_dependents`] ran too early for.
+    pub async fn cancel_unreachable(&self, reason: &str) -> Result<Vec<Task>> {
+        let rows: Vec<TaskRow> = sqlx::query_as(
+            r#"
+            WITH RECURSIVE unreachable AS (
+                SELECT t.id FROM tasks t
+                WHERE t.status = 'blocked'
+                  AND EXISTS (
+                      SELECT 1 FROM tasks d
+                      WHERE d.id = ANY(t.depends_on)
+                        AND d.status IN ('failed', 'cancelled', 'timed_out')
+                  )
+                UNION
+                SELECT t.id FROM tasks t JOIN unreachable u ON u.id = ANY(t.depends_on)
+            )
+            UPDATE tasks
+            SET status = 'cancelled',
+                result = COALESCE(result, $1),
+                updated_at = $2
+            WHERE id IN (SELECT id FROM unreachable)
+              AND status IN ('blocked', 'pending', 'in_progress')
+            RETURNING id, team_id, parent_task_id, assigned_to, status, description, result,
+                      routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                      attempts, deadline_at, progress, progress_note, thread_id,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(reason)
+        .bind(Utc::now())
+        .fetch_all(&self.db)
+        .await
+        .context("failed to cancel unreachable tasks")?;
+
+        rows.into_iter().map(Task::try_from).collect()
+    }
+
+    /// Records a new attempt at the task by `agent_id` and starts its deadline from the
+    /// task's `timeout_secs`. Returns the updated task, or `None` if it doesn't exist.
+    pub async fn start_attempt(&self, id: Uuid, agent_id: Option<Uuid>) -> Result<Option<Task>> {
+        let now = Utc::now();
+        let mut tx = self.db.begin().await?;
+
+        let row: Option<TaskRow> = sqlx::query_as(
+            r#"
+            UPDATE tasks
+            SET attempts = attempts + 1,
+                deadline_at = $2 + make_interval(secs => timeout_secs),
+                updated_at = $2
+            WHERE id = $1
+            RETURNING id, team_id, parent_task_id, assigned_to, status, description, result,
+                      routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                      attempts, deadline_at, progress, progress_note, thread_id,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(id)
+        .bind(now)
+        .fetch_optional(&mut *tx)
+        .await
+        .context("failed to start task attempt")?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        // A redispatch without a timeout in between ends the previous attempt
+        sqlx::query(
+            r#"
+            UPDATE task_attempts
+            SET ended_at = $2
+            WHERE task_id = $1 AND ended_at IS NULL
+            "#,
+        )
+        .bind(id)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO task_attempts (id, task_id, attempt, agent_id, started_at, deadline_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (task_id, attempt) DO NOTHING
             "#,
         )
+        .bind(Uuid::new_v4())
+        .bind(id)
+        .bind(row.attempts)
         .bind(agent_id)
+        .bind(now)
+        .bind(row.deadline_at)
+        .execute(&mut *tx)
+        .await
+        .context("failed to record task attempt")?;
+
+        tx.commit().await?;
+        Task::try_from(row).map(Some)
+    }
+
+    /// Closes the task's running attempt with `outcome` and clears its deadline.
+    pub async fn end_attempt(&self, id: Uuid, outcome: &TaskStatus) -> Result<()> {
+        let now = Utc::now();
+        let mut tx = self.db.begin().await?;
+        sqlx::query(
+            r#"
+            UPDATE task_attempts
+            SET ended_at = $2,
+                outcome = $3
+            WHERE task_id = $1 AND ended_at IS NULL
+            "#,
+        )
+        .bind(id)
+        .bind(now)
+        .bind(task_status_to_str(outcome))
+        .execute(&mut *tx)
+        .await
+        .context("failed to end task attempt")?;
+
+        sqlx::query("UPDATE tasks SET deadline_at = NULL WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Every attempt at the task, oldest first.
+    pub async fn list_attempts(&self, id: Uuid) -> Result<Vec<TaskAttempt>> {
+        let rows: Vec<TaskAttemptRow> = sqlx::query_as(
+            r#"
+            SELECT id, task_id, attempt, agent_id, started_at, deadline_at, ended_at, outcome
+            FROM task_attempts
+            WHERE task_id = $1
+            ORDER BY attempt ASC
+            "#,
+        )
+        .bind(id)
         .fetch_all(&self.db)
         .await?;
 
-        rows.into_iter().map(Task::try_from).collect()
+        rows.into_iter().map(TaskAttempt::try_from).collect()
     }
 
-    pub async fn get_by_parent_id(&self, parent_id: Uuid) -> Result<Vec<Task>> {
+    /// Open tasks whose deadline has passed, with the deadline cleared so each is only
+    /// returned to one caller. Tasks split into subtasks are skipped; they finish when their
+    /// subtasks do.
+    pub async fn claim_overdue(&self, now: DateTime<Utc>) -> Result<Vec<Task>> {
         let rows: Vec<TaskRow> = sqlx::query_as(
             r#"
-            SELECT id, team_id, parent_task_id, assigned_to, status, description, result, created_at, updated_at
+            UPDATE tasks t
+            SET deadline_at = NULL,
+                updated_at = $1
+            WHERE t.deadline_at <= $1
+              AND t.status IN ('pending', 'in_progress')
+              AND NOT EXISTS (SELECT 1 FROM tasks s WHERE s.parent_task_id = t.id)
+            RETURNING t.id, t.team_id, t.parent_task_id, t.assigned_to, t.status, t.description,
+                      t.result, t.routing_reason, t.required_skills, t.depends_on, t.timeout_secs,
+                      t.max_attempts, t.attempts, t.deadline_at, t.progress, t.progress_note,
+                      t.thread_id, t.created_at, t.updated_at
+            "#,
+        )
+        .bind(now)
+        .fetch_all(&self.db)
+        .await
+        .context("failed to claim overdue tasks")?;
+
+        rows.into_iter().map(Task::try_from).collect()
+    }
+
+    /// Puts an open task back to `pending` for another attempt, assigned to `agent_id` (or
+    /// queued when `None`). Returns `None` if it finished in the meantime.
+    pub async fn reassign_for_retry(
+        &self,
+        id: Uuid,
+        agent_id: Option<Uuid>,
+        routing_reason: &str,
+    ) -> Result<Option<Task>> {
+        let row: Option<TaskRow> = sqlx::query_as(
+            r#"
+            UPDATE tasks
+            SET status = 'pending',
+                assigned_to = $2,
+                routing_reason = $3,
+                updated_at = $4
+            WHERE id = $1 AND status IN ('pending', 'in_progress')
+            RETURNING id, team_id, parent_task_id, assigned_to, status, description, result,
+                      routing_reason, required_skills, depends_on, timeout_secs, max_attempts,
+                      attempts, deadline_at, progress, progress_note, thread_id,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(id)
+        .bind(agent_id)
+        .bind(routing_reason)
+        .bind(Utc::now())
+        .fetch_optional(&self.db)
+        .await?;
+
+        row.map(Task::try_from).transpose()
+    }
+
+    /// Pending and in-progress tasks per agent; agents without any are omitted.
+    pub async fn count_open_by_assignee(&self, agent_ids: &[Uuid]) -> Result<HashMap<Uuid, i64>> {
+        let rows: Vec<(Uuid, i64)> = sqlx::query_as(
+            r#"
+            SELECT assigned_to, COUNT(*)
             FROM tasks
-            WHERE parent_task_id = $1
-            ORDER BY created_at ASC
+            WHERE assigned_to = ANY($1) AND status IN ('pending', 'in_progress')
+            GROUP BY assigned_to
             "#,
         )
-        .bind(parent_id)
+        .bind(agent_ids)
         .fetch_all(&self.db)
         .await?;
 
-        rows.into_iter().map(Task::try_from).collect()
+        Ok(rows.into_iter().collect())
+    }
+}
+
+pub struct DiscordOutboxRepository {
+    db: PgPool,
+}
+
+impl DiscordOutboxRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+
+    /// Queues a message (or other action) for delivery as soon as possible and returns its
+    /// id.
+    pub async fn enqueue(
+        &self,
+        channel_id: &str,
+        action: OutboxAction,
+        content: &str,
+        embed: Option<&serde_json::Value>,
+    ) -> Result<Uuid> {
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+        sqlx::query(
+            r#"
+            INSERT INTO discord_outbox (
+                id, channel_id, action, content, embed, next_attempt_at, created_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $6)
+            "#,
+        )
+        .bind(id)
+        .bind(channel_id)
+        .bind(outbox_action_to_str(&action))
+        .bind(content)
+        .bind(embed.map(Json))
+        .bind(now)
+        .execute(&self.db)
+        .await
+        .context("failed to queue Discord message")?;
+
+        Ok(id)
+    }
+
+    /// Pending messages due by `now`, oldest first. Each is leased until `lease_until` so
+    /// a concurrent sender skips it; delivering it should reschedule or finish it.
+    pub async fn claim_due(
+        &self,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<OutboxMessage>> {
+        let rows: Vec<OutboxRow> = sqlx::query_as(
+            r#"
+            UPDATE discord_outbox
+            SET next_attempt_at = $2
+            WHERE id IN (
+                SELECT id FROM discord_outbox
+                WHERE status = 'pending' AND next_attempt_at <= $1
+                ORDER BY created_at ASC
+                LIMIT $3
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, channel_id, action, content, embed, chunks_sent, attempts, created_at
+            "#,
+        )
+        .bind(now)
+        .bind(lease_until)
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await
+        .context("failed to claim Discord messages")?;
+
+        let mut messages = rows
+            .into_iter()
+            .map(OutboxMessage::try_from)
+            .collect::<Result<Vec<_>>>()?;
+        messages.sort_by_key(|message| message.created_at);
+        Ok(messages)
+    }
+
+    pub async fn set_chunks_sent(&self, id: Uuid, chunks_sent: i32) -> Result<()> {
+        sqlx::query("UPDATE discord_outbox SET chunks_sent = $2 WHERE id = $1")
+            .bind(id)
+            .bind(chunks_sent)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn mark_sent(&self, id: Uuid) -> Result<()> {
+        sqlx::query("UPDATE discord_outbox SET status = 'sent', sent_at = $2 WHERE id = $1")
+            .bind(id)
+            .bind(Utc::now())
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Tries again at `at`. `error` counts as a failed attempt; `None` is a rate-limit wait.
+    pub async fn reschedule(&self, id: Uuid, at: DateTime<Utc>, error: Option<&str>) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE discord_outbox
+            SET next_attempt_at = $2,
+                attempts = attempts + CASE WHEN $3::text IS NULL THEN 0 ELSE 1 END,
+                last_error = COALESCE($3, last_error)
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(at)
+        .bind(error)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Gives up on the message.
+    pub async fn mark_failed(&self, id: Uuid, error: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE discord_outbox
+            SET status = 'failed',
+                attempts = attempts + 1,
+                last_error = $2
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(error)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes delivered messages sent before `cutoff`; failed ones are kept for inspection.
+    pub async fn prune_sent(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let result =
+            sqlx::query("DELETE FROM discord_outbox WHERE status = 'sent' AND sent_at < $1")
+                .bind(cutoff)
+                .execute(&self.db)
+                .await?;
+        Ok(result.rows_affected())
     }
 }
 
@@ -877,6 +2391,7 @@ fn parse_agent_status(value: &str) -> Result<AgentStatus> {
         "deploying" => Ok(AgentStatus::Deploying),
         "running" => Ok(AgentStatus::Running),
         "stopped" => Ok(AgentStatus::Stopped),
+        "destroyed" => Ok(AgentStatus::Destroyed),
         "error" => Ok(AgentStatus::Error),
         _ => anyhow::bail!("invalid agent status: {}", value),
     }
@@ -907,6 +2422,7 @@ fn parse_vps_provider(value: &str) -> Result<VpsProvider> {
         "railway" => Ok(VpsProvider::Railway),
         "flyio" => Ok(VpsProvider::FlyIo),
         "aws" => Ok(VpsProvider::Aws),
+        "docker" => Ok(VpsProvider::Docker),
         _ => anyhow::bail!("invalid vps provider: {}", value),
     }
 }
@@ -917,17 +2433,42 @@ fn parse_deployment_status(value: &str) -> Result<DeploymentStatus> {
         "creating" => Ok(DeploymentStatus::Creating),
         "running" => Ok(DeploymentStatus::Running),
         "stopped" => Ok(DeploymentStatus::Stopped),
+        "destroyed" => Ok(DeploymentStatus::Destroyed),
         "failed" => Ok(DeploymentStatus::Failed),
+        "lost" => Ok(DeploymentStatus::Lost),
         _ => anyhow::bail!("invalid deployment status: {}", value),
     }
 }
 
+fn parse_deployment_job_kind(value: &str) -> Result<DeploymentJobKind> {
+    match value {
+        "deploy" => Ok(DeploymentJobKind::Deploy),
+        "deploy_multi" => Ok(DeploymentJobKind::DeployMulti),
+        "update_settings" => Ok(DeploymentJobKind::UpdateSettings),
+        "rollback" => Ok(DeploymentJobKind::Rollback),
+        _ => anyhow::bail!("invalid deployment job kind: {}", value),
+    }
+}
+
+fn parse_deployment_job_status(value: &str) -> Result<DeploymentJobStatus> {
+    match value {
+        "queued" => Ok(DeploymentJobStatus::Queued),
+        "running" => Ok(DeploymentJobStatus::Running),
+        "succeeded" => Ok(DeploymentJobStatus::Succeeded),
+        "failed" => Ok(DeploymentJobStatus::Failed),
+        _ => anyhow::bail!("invalid deployment job status: {}", value),
+    }
+}
+
 fn parse_task_status(value: &str) -> Result<TaskStatus> {
     match value {
+        "blocked" => Ok(TaskStatus::Blocked),
         "pending" => Ok(TaskStatus::Pending),
         "in_progress" => Ok(TaskStatus::InProgress),
         "completed" => Ok(TaskStatus::Completed),
         "failed" => Ok(TaskStatus::Failed),
+        "cancelled" => Ok(TaskStatus::Cancelled),
+        "timed_out" => Ok(TaskStatus::TimedOut),
         _ => anyhow::bail!("invalid task status: {}", value),
     }
 }
@@ -945,6 +2486,7 @@ fn agent_status_to_str(status: &AgentStatus) -> &'static str {
         AgentStatus::Deploying => "deploying",
         AgentStatus::Running => "running",
         AgentStatus::Stopped => "stopped",
+        AgentStatus::Destroyed => "destroyed",
         AgentStatus::Error => "error",
     }
 }
@@ -972,6 +2514,7 @@ fn vps_provider_to_str(provider: &VpsProvider) -> &'static str {
         VpsProvider::Railway => "railway",
         VpsProvider::FlyIo => "flyio",
         VpsProvider::Aws => "aws",
+        VpsProvider::Docker => "docker",
     }
 }
 
@@ -981,15 +2524,45 @@ fn deployment_status_to_str(status: &DeploymentStatus) -> &'static str {
         DeploymentStatus::Creating => "creating",
         DeploymentStatus::Running => "running",
         DeploymentStatus::Stopped => "stopped",
+        DeploymentStatus::Destroyed => "destroyed",
         DeploymentStatus::Failed => "failed",
+        DeploymentStatus::Lost => "lost",
     }
 }
 
-fn task_status_to_str(status: &TaskStatus) -> &'static str {
+fn deployment_job_kind_to_str(kind: &DeploymentJobKind) -> &'static str {
+    match kind {
+        DeploymentJobKind::Deploy => "deploy",
+        DeploymentJobKind::DeployMulti => "deploy_multi",
+        DeploymentJobKind::UpdateSettings => "update_settings",
+        DeploymentJobKind::Rollback => "rollback",
+    }
+}
+
+fn deployment_job_status_to_str(status: &DeploymentJobStatus) -> &'static str {
     match status {
-        TaskStatus::Pending => "pending",
-        TaskStatus::InProgress => "in_progress",
-        TaskStatus::Completed => "completed",
-        TaskStatus::Failed => "failed",
+        DeploymentJobStatus::Queued => "queued",
+        DeploymentJobStatus::Running => "running",
+        DeploymentJobStatus::Succeeded => "succeeded",
+        DeploymentJobStatus::Failed => "failed",
+    }
+}
+
+fn parse_outbox_action(value: &str) -> Result<OutboxAction> {
+    match value {
+        "message" => Ok(OutboxAction::Message),
+        "archive_thread" => Ok(OutboxAction::ArchiveThread),
+        _ => anyhow::bail!("invalid outbox action: {}", value),
+    }
+}
+
+fn outbox_action_to_str(action: &OutboxAction) -> &'static str {
+    match action {
+        OutboxAction::Message => "message",
+        OutboxAction::ArchiveThread => "archive_thread",
     }
 }
+
+fn task_status_to_str(status: &TaskStatus) -> &'static str {
+    status.as_str()
+}
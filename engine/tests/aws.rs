@@ -0,0 +1,433 @@
+//! `AwsAdapter` against a stub EC2 Query and CloudWatch Logs API, served on one endpoint as
+//! `aws_endpoint_url` expects.
+
+mod common;
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use base64::Engine;
+use chrono::{TimeZone, Utc};
+use engine::adapters::aws::AwsAdapter;
+use engine::adapters::logs::LogStreamOptions;
+use engine::adapters::trait_def::{AgentConfig, DeploymentId, ResourceNotFound, VpsProvider as _};
+use engine::models::{AgentRuntime, DeploymentStatus};
+use futures::TryStreamExt;
+use reqwest::Url;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+#[derive(Default)]
+struct AwsApi {
+    /// Instance states by instance ID.
+    instances: Mutex<BTreeMap<String, String>>,
+    /// EC2 requests received, as their decoded form parameters.
+    ec2_calls: Mutex<Vec<HashMap<String, String>>>,
+    /// CloudWatch `GetLogEvents` request bodies received.
+    log_queries: Mutex<Vec<Value>>,
+    /// The CloudWatch log stream, as `(timestamp_ms, message)`; `None` until it is created.
+    log_events: Mutex<Option<Vec<(i64, String)>>>,
+    /// Console output returned by `GetConsoleOutput`.
+    console: Mutex<String>,
+    /// Answers EC2 actions with this status and error code instead of running them.
+    errors: Mutex<HashMap<String, (StatusCode, String)>>,
+}
+
+type Stub = Arc<AwsApi>;
+
+fn ec2_error(status: StatusCode, code: &str) -> Response {
+    let xml = format!(
+        "<Response><Errors><Error><Code>{}</Code><Message>stubbed {}</Message></Error></Errors></Response>",
+        code, code
+    );
+    (status, xml).into_response()
+}
+
+fn instance_xml(instance_id: &str, state: &str) -> String {
+    format!(
+        "<DescribeInstancesResponse><reservationSet><item><instancesSet><item>\
+         <instanceId>{}</instanceId><instanceState><code>0</code><name>{}</name></instanceState>\
+         <ipAddress>203.0.113.7</ipAddress>\
+         </item></instancesSet></item></reservationSet></DescribeInstancesResponse>",
+        instance_id, state
+    )
+}
+
+async fn aws(State(stub): State<Stub>, headers: HeaderMap, body: Bytes) -> Response {
+    let signed = headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("AWS4-HMAC-SHA256 Credential=AKIDTEST/"));
+    if !signed {
+        return ec2_error(StatusCode::FORBIDDEN, "AuthFailure");
+    }
+
+    match headers
+        .get("x-amz-target")
+        .and_then(|value| value.to_str().ok())
+    {
+        Some("Logs_20140328.GetLogEvents") => log_events(&stub, &body),
+        Some(_) => StatusCode::BAD_REQUEST.into_response(),
+        None => ec2(&stub, &body),
+    }
+}
+
+fn log_events(stub: &AwsApi, body: &[u8]) -> Response {
+    let query: Value = serde_json::from_slice(body).unwrap();
+    stub.log_queries.lock().unwrap().push(query.clone());
+
+    let Some(events) = stub.log_events.lock().unwrap().clone() else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "__type": "com.amazonaws.logs#ResourceNotFoundException",
+                "message": "The specified log stream does not exist."
+            })),
+        )
+            .into_response();
+    };
+
+    // Tokens are the index of the next event to return
+    let start = query["nextToken"]
+        .as_str()
+        .and_then(|token| token.strip_prefix("f/"))
+        .and_then(|index| index.parse().ok())
+        .unwrap_or(0);
+    let limit = query["limit"].as_u64().unwrap_or(100) as usize;
+    let page: Vec<Value> = events
+        .iter()
+        .skip(start)
+        .take(limit)
+        .map(|(timestamp, message)| json!({ "timestamp": timestamp, "message": message }))
+        .collect();
+    Json(json!({
+        "events": page,
+        "nextForwardToken": format!("f/{}", start + page.len()),
+    }))
+    .into_response()
+}
+
+fn ec2(stub: &AwsApi, body: &[u8]) -> Response {
+    let url = Url::parse(&format!(
+        "http://stub/?{}",
+        std::str::from_utf8(body).unwrap()
+    ))
+    .unwrap();
+    let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+    stub.ec2_calls.lock().unwrap().push(params.clone());
+
+    let action = params["Action"].as_str();
+    if let Some((status, code)) = stub.errors.lock().unwrap().get(action) {
+        return ec2_error(*status, code);
+    }
+
+    let instance_id = params
+        .get("InstanceId.1")
+        .or_else(|| params.get("InstanceId"))
+        .cloned()
+        .unwrap_or_default();
+    let mut instances = stub.instances.lock().unwrap();
+    if action != "RunInstances" && !instances.contains_key(&instance_id) {
+        return ec2_error(StatusCode::BAD_REQUEST, "InvalidInstanceID.NotFound");
+    }
+
+    let xml = match action {
+        "RunInstances" => {
+            let instance_id = format!("i-{}", instances.len() + 1);
+            instances.insert(instance_id.clone(), "pending".to_string());
+            format!(
+                "<RunInstancesResponse><instancesSet><item><instanceId>{}</instanceId>\
+                 </item></instancesSet></RunInstancesResponse>",
+                instance_id
+            )
+        }
+        "DescribeInstances" => instance_xml(&instance_id, &instances[&instance_id]),
+        "StopInstances" => {
+            instances.insert(instance_id, "stopped".to_string());
+            "<StopInstancesResponse/>".to_string()
+        }
+        "StartInstances" => {
+            instances.insert(instance_id, "running".to_string());
+            "<StartInstancesResponse/>".to_string()
+        }
+        "TerminateInstances" => {
+            instances.remove(&instance_id);
+            "<TerminateInstancesResponse/>".to_string()
+        }
+        "GetConsoleOutput" => format!(
+            "<GetConsoleOutputResponse><instanceId>{}</instanceId><output>{}</output>\
+             </GetConsoleOutputResponse>",
+            instance_id,
+            base64::engine::general_purpose::STANDARD.encode(&*stub.console.lock().unwrap())
+        ),
+        _ => format!("<{}Response/>", action),
+    };
+    xml.into_response()
+}
+
+/// A stub with one running instance, `i-running`, and an adapter pointed at it; logs are
+/// shipped to CloudWatch when `instance_profile` is given.
+async fn aws_api(instance_profile: Option<&str>) -> (Stub, AwsAdapter) {
+    let stub = Stub::default();
+    stub.instances
+        .lock()
+        .unwrap()
+        .insert("i-running".to_string(), "running".to_string());
+    let url = common::serve(Router::new().route("/", post(aws)).with_state(stub.clone())).await;
+
+    let mut config = common::config();
+    config.aws_access_key_id = Some("AKIDTEST".to_string());
+    config.aws_secret_access_key = Some("secret".to_string());
+    config.aws_region = "us-east-1".to_string();
+    config.aws_endpoint_url = Some(url);
+    config.aws_ami_id = Some("ami-test".to_string());
+    config.aws_log_group = "/clawguild/test".to_string();
+    config.aws_instance_profile = instance_profile.map(str::to_string);
+    (stub, AwsAdapter::new(&config).unwrap())
+}
+
+fn agent_config() -> AgentConfig {
+    AgentConfig {
+        agent: common::agent("scout"),
+        agents: None,
+        region: None,
+        runtime: AgentRuntime::OpenClaw,
+        runtime_init_script: "start-agent".to_string(),
+        runtime_env: BTreeMap::from([("AGENT_NAME".to_string(), "scout".to_string())]),
+        runtime_services: Vec::new(),
+        volume_id: None,
+    }
+}
+
+fn deployment(instance_id: &str) -> DeploymentId {
+    DeploymentId {
+        id: Uuid::new_v4(),
+        provider_id: format!("aws-us-east-1/{}", instance_id),
+    }
+}
+
+fn user_data(params: &HashMap<String, String>, key: &str) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD
+        .decode(&params[key])
+        .unwrap();
+    String::from_utf8(encoded).unwrap()
+}
+
+fn actions(stub: &AwsApi) -> Vec<String> {
+    stub.ec2_calls
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|params| params["Action"].clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn instances_with_a_profile_ship_their_logs_to_cloudwatch() {
+    let (stub, aws) = aws_api(Some("clawguild-agent")).await;
+
+    let deployed = aws.deploy_agent(agent_config()).await.unwrap();
+
+    assert_eq!(deployed.provider_id, "aws-us-east-1/i-2");
+    let calls = stub.ec2_calls.lock().unwrap();
+    let run = &calls[0];
+    assert_eq!(run["Action"], "RunInstances");
+    assert_eq!(run["ImageId"], "ami-test");
+    assert_eq!(run["IamInstanceProfile.Name"], "clawguild-agent");
+    let script = user_data(run, "UserData");
+    assert!(script.contains("amazon-cloudwatch-agent-ctl"));
+    assert!(script.contains("\"log_group_name\":\"/clawguild/test\""));
+    assert!(script.contains("export AGENT_NAME='scout'\nstart-agent"));
+}
+
+#[tokio::test]
+async fn instances_without_a_profile_skip_log_shipping() {
+    let (stub, aws) = aws_api(None).await;
+
+    aws.deploy_agent(agent_config()).await.unwrap();
+
+    let calls = stub.ec2_calls.lock().unwrap();
+    assert!(!calls[0].contains_key("IamInstanceProfile.Name"));
+    let script = user_data(&calls[0], "UserData");
+    assert!(!script.contains("amazon-cloudwatch-agent"));
+    assert!(script.contains("start-agent"));
+}
+
+#[tokio::test]
+async fn logs_are_read_from_the_instance_log_stream() {
+    let (stub, aws) = aws_api(Some("clawguild-agent")).await;
+    let start = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+    *stub.log_events.lock().unwrap() = Some(
+        (0..5)
+            .map(|n| (start.timestamp_millis() + n, format!("line {}\n", n)))
+            .collect(),
+    );
+
+    let lines = aws
+        .get_logs(&deployment("i-running"), Some(2))
+        .await
+        .unwrap();
+    assert_eq!(lines, ["line 0", "line 1"]);
+
+    let entries: Vec<_> = aws
+        .stream_logs(
+            &deployment("i-running"),
+            LogStreamOptions {
+                follow: false,
+                since: Some(start),
+                tail: None,
+            },
+        )
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries[0].source, "i-running");
+    assert_eq!(entries[0].timestamp, Some(start));
+
+    let queries = stub.log_queries.lock().unwrap();
+    assert_eq!(queries[0]["logGroupName"], "/clawguild/test");
+    assert_eq!(queries[0]["logStreamName"], "i-running");
+    assert_eq!(queries[1]["startTime"], start.timestamp_millis());
+    assert_eq!(queries[1]["startFromHead"], true);
+}
+
+#[tokio::test]
+async fn logs_fall_back_to_the_console_until_the_stream_exists() {
+    let (stub, aws) = aws_api(Some("clawguild-agent")).await;
+    *stub.console.lock().unwrap() = "booting\n\ncloud-init done\n".to_string();
+
+    let lines = aws.get_logs(&deployment("i-running"), None).await.unwrap();
+
+    assert_eq!(lines, ["booting", "cloud-init done"]);
+    assert_eq!(stub.log_queries.lock().unwrap().len(), 1);
+    let console = &stub.ec2_calls.lock().unwrap()[0];
+    assert_eq!(console["Action"], "GetConsoleOutput");
+    assert_eq!(console["InstanceId"], "i-running");
+}
+
+#[tokio::test]
+async fn console_logs_keep_the_requested_tail() {
+    let (stub, aws) = aws_api(None).await;
+    *stub.console.lock().unwrap() = "one\ntwo\nthree\n".to_string();
+
+    let entries: Vec<_> = aws
+        .stream_logs(
+            &deployment("i-running"),
+            LogStreamOptions {
+                follow: false,
+                since: None,
+                tail: Some(2),
+            },
+        )
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    let messages: Vec<_> = entries.iter().map(|entry| entry.message.as_str()).collect();
+    assert_eq!(messages, ["two", "three"]);
+    assert!(entries.iter().all(|entry| entry.timestamp.is_none()));
+    assert!(stub.log_queries.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn update_config_replaces_user_data_while_the_instance_is_stopped() {
+    let (stub, aws) = aws_api(None).await;
+    let mut config = agent_config();
+    config.runtime_init_script = "start-agent --new".to_string();
+
+    aws.update_config(&deployment("i-running"), config)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        actions(&stub),
+        [
+            "StopInstances",
+            "DescribeInstances",
+            "ModifyInstanceAttribute",
+            "StartInstances"
+        ]
+    );
+    let calls = stub.ec2_calls.lock().unwrap();
+    assert!(user_data(&calls[2], "UserData.Value").contains("start-agent --new"));
+    assert_eq!(stub.instances.lock().unwrap()["i-running"], "running");
+}
+
+#[tokio::test]
+async fn status_follows_the_instance_state() {
+    let (stub, aws) = aws_api(None).await;
+
+    let status = aws.get_status(&deployment("i-running")).await.unwrap();
+    assert_eq!(status.status, DeploymentStatus::Running);
+    assert_eq!(status.endpoint.as_deref(), Some("http://203.0.113.7"));
+
+    aws.stop_agent(&deployment("i-running")).await.unwrap();
+    let status = aws.get_status(&deployment("i-running")).await.unwrap();
+    assert_eq!(status.status, DeploymentStatus::Stopped);
+    assert_eq!(
+        actions(&stub),
+        ["DescribeInstances", "StopInstances", "DescribeInstances"]
+    );
+}
+
+#[tokio::test]
+async fn unknown_instances_are_reported_as_missing() {
+    let (_stub, aws) = aws_api(None).await;
+    let missing = deployment("i-gone");
+
+    let error = aws.get_status(&missing).await.unwrap_err();
+    assert!(
+        error.downcast_ref::<ResourceNotFound>().is_some(),
+        "{:#}",
+        error
+    );
+    let error = aws.start_agent(&missing).await.unwrap_err();
+    assert!(
+        error.downcast_ref::<ResourceNotFound>().is_some(),
+        "{:#}",
+        error
+    );
+    let error = aws.get_logs(&missing, None).await.unwrap_err();
+    assert!(
+        error.downcast_ref::<ResourceNotFound>().is_some(),
+        "{:#}",
+        error
+    );
+
+    // Already gone is as good as destroyed
+    aws.destroy_agent(&missing).await.unwrap();
+}
+
+#[tokio::test]
+async fn other_ec2_errors_are_surfaced() {
+    let (stub, aws) = aws_api(None).await;
+    stub.errors.lock().unwrap().insert(
+        "StopInstances".to_string(),
+        (StatusCode::FORBIDDEN, "UnauthorizedOperation".to_string()),
+    );
+
+    let error = aws.stop_agent(&deployment("i-running")).await.unwrap_err();
+
+    assert!(error.downcast_ref::<ResourceNotFound>().is_none());
+    let message = error.to_string();
+    assert!(message.contains("StopInstances"), "{}", message);
+    assert!(message.contains("403"), "{}", message);
+    assert!(message.contains("UnauthorizedOperation"), "{}", message);
+
+    let error = aws
+        .update_config(&deployment("i-running"), agent_config())
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("UnauthorizedOperation"));
+    assert_eq!(stub.instances.lock().unwrap()["i-running"], "running");
+}
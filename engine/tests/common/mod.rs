@@ -0,0 +1,176 @@
+//! Setup shared by the integration tests.
+//!
+//! Tests that need Postgres call [`test_db`] and are skipped unless `TEST_DATABASE_URL` points
+//! at a scratch database. Each test runs the migrations in a schema of its own, so tests can
+//! run in parallel against the same database.
+
+#![allow(dead_code)]
+
+use chrono::Utc;
+use engine::models::{
+    Agent, AgentRole, AgentRuntime, DiscordChannels, ModelProvider, Task, TaskStatus, Team,
+    TeamSettings,
+};
+use engine::storage::repositories::{AgentRepository, TaskRepository, TeamRepository};
+use engine::Config;
+use engine::Database;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// A migrated database in a fresh schema, or `None` (and the test should return early) when
+/// `TEST_DATABASE_URL` isn't set.
+pub async fn test_db() -> Option<Database> {
+    let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+        eprintln!("TEST_DATABASE_URL is not set; skipping");
+        return None;
+    };
+
+    let schema = format!("test_{}", Uuid::new_v4().simple());
+    let admin = PgPool::connect(&url)
+        .await
+        .expect("failed to connect to TEST_DATABASE_URL");
+    sqlx::query(&format!("CREATE SCHEMA {}", schema))
+        .execute(&admin)
+        .await
+        .expect("failed to create test schema");
+    admin.close().await;
+
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let url = format!("{}{}options=-c%20search_path%3D{}", url, separator, schema);
+    let db = Database::new(&url)
+        .await
+        .expect("failed to connect to test schema");
+    db.run_migrations().await.expect("failed to run migrations");
+    Some(db)
+}
+
+/// An OpenClaw slave with only the fields deployments need.
+pub fn agent(name: &str) -> Agent {
+    Agent {
+        model_api_key: Some("sk-test".to_string()),
+        ..Agent::new(
+            name,
+            AgentRole::Slave,
+            AgentRuntime::OpenClaw,
+            ModelProvider::Anthropic,
+        )
+    }
+}
+
+pub async fn insert_agent(db: &Database, name: &str) -> Agent {
+    let agent = agent(name);
+    AgentRepository::new(db.db())
+        .create(&agent)
+        .await
+        .expect("failed to insert agent");
+    agent
+}
+
+pub async fn get_agent(db: &Database, id: Uuid) -> Agent {
+    AgentRepository::new(db.db())
+        .get_by_id(id)
+        .await
+        .expect("failed to load agent")
+        .expect("agent not found")
+}
+
+/// A team with default settings and placeholder Discord channels.
+pub fn team(master_id: Uuid, slave_ids: Vec<Uuid>) -> Team {
+    Team {
+        id: Uuid::new_v4(),
+        name: "test team".to_string(),
+        master_id,
+        slave_ids,
+        discord_channel_id: "logs".to_string(),
+        discord_channels: DiscordChannels {
+            coordination_logs: "logs".to_string(),
+            slave_communication: "slaves".to_string(),
+            master_orders: "orders".to_string(),
+        },
+        discord_category_id: None,
+        slack_channels: None,
+        settings: TeamSettings::default(),
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    }
+}
+
+pub async fn insert_team(db: &Database, team: Team) -> Team {
+    TeamRepository::new(db.db())
+        .create(&team)
+        .await
+        .expect("failed to insert team");
+    team
+}
+
+/// A top-level task of `team_id` with no assignee.
+pub fn task(team_id: Uuid, status: TaskStatus, depends_on: Vec<Uuid>) -> Task {
+    Task {
+        id: Uuid::new_v4(),
+        team_id,
+        parent_task_id: None,
+        assigned_to: None,
+        status,
+        description: "test task".to_string(),
+        result: None,
+        routing_reason: None,
+        required_skills: Vec::new(),
+        depends_on,
+        timeout_secs: None,
+        max_attempts: 1,
+        attempts: 0,
+        deadline_at: None,
+        progress: None,
+        progress_note: None,
+        thread_id: None,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    }
+}
+
+pub async fn insert_task(db: &Database, task: Task) -> Task {
+    TaskRepository::new(db.db())
+        .create(&task)
+        .await
+        .expect("failed to insert task");
+    task
+}
+
+pub async fn get_task(db: &Database, id: Uuid) -> Task {
+    TaskRepository::new(db.db())
+        .get_by_id(id)
+        .await
+        .expect("failed to load task")
+        .expect("task not found")
+}
+
+/// Serves `router` on a free local port and returns its base URL.
+pub async fn serve(router: axum::Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("failed to bind stub server");
+    let address = listener.local_addr().expect("stub server has no address");
+    tokio::spawn(async move {
+        axum::serve(listener, router)
+            .await
+            .expect("stub server failed");
+    });
+    format!("http://{}", address)
+}
+
+/// Server config from the environment with every provider credential cleared, for tests to
+/// fill in what they point at stub servers.
+pub fn config() -> Config {
+    Config {
+        railway_api_key: None,
+        fly_api_token: None,
+        fly_api_url: None,
+        aws_access_key_id: None,
+        aws_secret_access_key: None,
+        aws_session_token: None,
+        aws_endpoint_url: None,
+        aws_instance_profile: None,
+        docker_host: None,
+        ..Config::load().expect("failed to load config")
+    }
+}
@@ -0,0 +1,970 @@
+//! Deploy, multi-deploy, destroy, rollback and timeout paths of `DeploymentManager`, its
+//! capability checks, job queue and log archiving against `MockVpsProvider`. Needs `TEST_DATABASE_URL`; see `common`.
+
+mod common;
+
+use engine::adapters::mock::{MockCall, MockScript, MockVpsProvider};
+use engine::adapters::trait_def::{DeploymentId, ResourceNotFound, VpsProvider as _};
+use engine::adapters::{LogStreamOptions, ProviderCapabilities, VpsAdapters};
+use engine::deployment::jobs::{DeploymentJobOptions, DeploymentJobQueue};
+use engine::deployment::log_archive::archive_deployment;
+use engine::deployment::manager::{DeploymentManager, DeploymentPolling};
+use engine::models::{
+    AgentRuntime, AgentStatus, Deployment, DeploymentJob, DeploymentJobKind, DeploymentJobStatus,
+    DeploymentRevision, DeploymentStatus, VpsProvider,
+};
+use engine::storage::repositories::{
+    AgentRepository, DeploymentJobRepository, DeploymentLogRepository, DeploymentRepository,
+    DeploymentRevisionRepository, LogSearch,
+};
+use engine::Database;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+const POLLING: DeploymentPolling = DeploymentPolling {
+    interval: Duration::from_millis(1),
+    max_attempts: 5,
+};
+
+async fn manager(db: &Database, mock: &MockVpsProvider) -> DeploymentManager {
+    let adapters =
+        VpsAdapters::default().with_provider(VpsProvider::Docker, Arc::new(mock.clone()));
+    DeploymentManager::new(db.clone(), adapters)
+        .await
+        .unwrap()
+        .with_polling(POLLING)
+}
+
+async fn get_deployment(db: &Database, id: Uuid) -> Deployment {
+    DeploymentRepository::new(db.db())
+        .get_by_id(id)
+        .await
+        .unwrap()
+        .expect("deployment not found")
+}
+
+/// A started queue with a single attempt per job.
+async fn job_queue(db: &Database, manager: DeploymentManager) -> DeploymentJobQueue {
+    let queue = DeploymentJobQueue::new(db.clone(), manager).with_options(DeploymentJobOptions {
+        workers: 1,
+        poll_interval: Duration::from_millis(10),
+        max_attempts: 1,
+        retry_backoff: Duration::from_millis(1),
+    });
+    queue.start().await.unwrap();
+    queue
+}
+
+async fn wait_for_job(queue: &DeploymentJobQueue, id: Uuid) -> DeploymentJob {
+    for _ in 0..500 {
+        let job = queue.get(id).await.unwrap().expect("job not found");
+        if matches!(
+            job.status,
+            DeploymentJobStatus::Succeeded | DeploymentJobStatus::Failed
+        ) {
+            return job;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("job {} did not finish", id);
+}
+
+fn status_calls(mock: &MockVpsProvider) -> usize {
+    mock.calls()
+        .iter()
+        .filter(|call| matches!(call, MockCall::GetStatus { .. }))
+        .count()
+}
+
+#[tokio::test]
+async fn deploy_polls_until_running_and_links_the_agent() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new()
+        .with_default_script(MockScript::running().endpoint("http://agent.test"));
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+
+    let deployment = manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+
+    assert_eq!(deployment.status, DeploymentStatus::Running);
+    assert_eq!(deployment.endpoint.as_deref(), Some("http://agent.test"));
+    let provider_id = deployment.provider_id.clone().unwrap();
+    assert_eq!(mock.provider_ids(), vec![provider_id.clone()]);
+    // Pending, Creating, Running
+    assert_eq!(status_calls(&mock), 3);
+    assert_eq!(mock.volumes(), vec![deployment.volume_id.clone().unwrap()]);
+
+    let agent = common::get_agent(&db, agent.id).await;
+    assert_eq!(agent.status, AgentStatus::Running);
+    assert_eq!(agent.deployment_id, Some(deployment.id));
+}
+
+#[tokio::test]
+async fn multi_deploy_puts_every_agent_on_one_machine() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let manager = manager(&db, &mock).await;
+    let scout = common::insert_agent(&db, "scout").await;
+    let builder = common::insert_agent(&db, "builder").await;
+
+    let deployment = manager
+        .deploy_agents_multi(
+            vec![scout.clone(), builder.clone()],
+            VpsProvider::Docker,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(deployment.status, DeploymentStatus::Running);
+    assert_eq!(deployment.agent_ids, Some(vec![scout.id, builder.id]));
+    let provider_ids = mock.provider_ids();
+    assert_eq!(provider_ids.len(), 1);
+    let config = mock.config(&provider_ids[0]).unwrap();
+    let hosted: Vec<Uuid> = config.agents.unwrap().iter().map(|a| a.id).collect();
+    assert_eq!(hosted, vec![scout.id, builder.id]);
+
+    for id in [scout.id, builder.id] {
+        let agent = common::get_agent(&db, id).await;
+        assert_eq!(agent.status, AgentStatus::Running);
+        assert_eq!(agent.deployment_id, Some(deployment.id));
+    }
+}
+
+#[tokio::test]
+async fn deploy_fails_when_the_provider_reports_failure() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new().with_default_script(MockScript::failing());
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+
+    let error = manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.to_string(), "Deployment failed");
+    assert_eq!(status_calls(&mock), 3);
+    assert_eq!(
+        common::get_agent(&db, agent.id).await.status,
+        AgentStatus::Error
+    );
+}
+
+#[tokio::test]
+async fn deploy_times_out_after_the_configured_attempts() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new().with_default_script(MockScript::never_ready());
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+
+    let deployment = manager
+        .create_deployment(
+            std::slice::from_ref(&agent),
+            VpsProvider::Docker,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+    manager
+        .provision(&deployment, std::slice::from_ref(&agent), None)
+        .await
+        .unwrap();
+    let error = manager
+        .wait_until_running(&deployment, None)
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.to_string(), "Deployment timeout");
+    assert_eq!(status_calls(&mock), POLLING.max_attempts as usize);
+    assert_eq!(
+        get_deployment(&db, deployment.id).await.status,
+        DeploymentStatus::Failed
+    );
+    assert_eq!(
+        common::get_agent(&db, agent.id).await.status,
+        AgentStatus::Error
+    );
+}
+
+/// A single-agent provider that only runs ZeroClaw, in `iad`.
+fn limited_capabilities() -> ProviderCapabilities {
+    ProviderCapabilities {
+        runtimes: vec![AgentRuntime::ZeroClaw],
+        multi_agent: false,
+        volumes: false,
+        exposed_ports: false,
+        regions: vec!["iad".to_string()],
+        log_streaming: false,
+    }
+}
+
+#[tokio::test]
+async fn deployments_the_provider_cannot_host_are_rejected_before_they_are_recorded() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new().with_capabilities(limited_capabilities());
+    let manager = manager(&db, &mock).await;
+    let openclaw = common::insert_agent(&db, "scout").await;
+    let mut zeroclaw = common::agent("builder");
+    zeroclaw.runtime = AgentRuntime::ZeroClaw;
+    AgentRepository::new(db.db())
+        .create(&zeroclaw)
+        .await
+        .unwrap();
+
+    let unsupported_runtime = manager
+        .deploy_agent(openclaw.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap_err();
+    assert!(unsupported_runtime
+        .to_string()
+        .contains("does not support the OpenClaw runtime"));
+    let multi_agent = manager
+        .deploy_agents_multi(
+            vec![zeroclaw.clone(), zeroclaw.clone()],
+            VpsProvider::Docker,
+            None,
+            None,
+        )
+        .await
+        .unwrap_err();
+    assert!(multi_agent
+        .to_string()
+        .contains("does not support multi-agent deployments"));
+    let unknown_region = manager
+        .deploy_agent(
+            zeroclaw.clone(),
+            VpsProvider::Docker,
+            Some("mars".to_string()),
+            None,
+        )
+        .await
+        .unwrap_err();
+    assert!(unknown_region
+        .to_string()
+        .contains("does not support region mars"));
+
+    assert!(DeploymentRepository::new(db.db())
+        .list_all()
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(mock.calls().is_empty());
+    for id in [openclaw.id, zeroclaw.id] {
+        assert_eq!(
+            common::get_agent(&db, id).await.status,
+            AgentStatus::Pending
+        );
+    }
+
+    // What it can host goes through
+    manager
+        .deploy_agent(zeroclaw, VpsProvider::Docker, Some("iad".to_string()), None)
+        .await
+        .unwrap();
+}
+
+#[test]
+fn configured_providers_are_listed_with_their_capabilities() {
+    let mock = MockVpsProvider::new().with_capabilities(limited_capabilities());
+    let adapters = VpsAdapters::default().with_provider(VpsProvider::Docker, Arc::new(mock));
+
+    let available = adapters.available();
+
+    // Railway keys may come with each request, so it is always listed
+    assert_eq!(available.len(), 2);
+    assert!(matches!(available[0].0, VpsProvider::Railway));
+    assert!(matches!(available[1].0, VpsProvider::Docker));
+    let docker = &available[1].1;
+    assert_eq!(docker.runtimes, [AgentRuntime::ZeroClaw]);
+    assert!(!docker.multi_agent);
+    assert_eq!(docker.regions, ["iad"]);
+}
+
+#[tokio::test]
+async fn destroy_tears_down_the_machine_and_keeps_the_volume() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    let deployment = manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+    let provider_id = deployment.provider_id.clone().unwrap();
+
+    manager.destroy_agent(agent.id, false).await.unwrap();
+
+    assert!(mock.is_destroyed(&provider_id));
+    assert_eq!(mock.volumes().len(), 1);
+    assert_eq!(
+        get_deployment(&db, deployment.id).await.status,
+        DeploymentStatus::Destroyed
+    );
+    let agent = common::get_agent(&db, agent.id).await;
+    assert_eq!(agent.status, AgentStatus::Destroyed);
+    assert_eq!(agent.deployment_id, None);
+}
+
+#[tokio::test]
+async fn redeploying_after_a_destroy_reuses_the_kept_volume() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    let first = manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+    manager.destroy_agent(agent.id, false).await.unwrap();
+
+    let agent = common::get_agent(&db, agent.id).await;
+    let second = manager
+        .deploy_agent(agent, VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+
+    assert_ne!(second.id, first.id);
+    assert!(first.volume_id.is_some());
+    assert_eq!(second.volume_id, first.volume_id);
+    let created_volumes = mock
+        .calls()
+        .iter()
+        .filter(|call| matches!(call, MockCall::CreateVolume { .. }))
+        .count();
+    assert_eq!(created_volumes, 1);
+    let config = mock.config(second.provider_id.as_ref().unwrap()).unwrap();
+    assert_eq!(config.volume_id, first.volume_id);
+    // The new deployment owns it now, so destroying the old one can't take it along
+    assert_eq!(get_deployment(&db, first.id).await.volume_id, None);
+}
+
+#[tokio::test]
+async fn destroy_with_delete_data_removes_the_volume() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+
+    manager.destroy_agent(agent.id, true).await.unwrap();
+
+    assert!(mock.volumes().is_empty());
+}
+
+#[tokio::test]
+async fn destroying_one_agent_of_a_multi_deploy_destroys_them_all() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let manager = manager(&db, &mock).await;
+    let scout = common::insert_agent(&db, "scout").await;
+    let builder = common::insert_agent(&db, "builder").await;
+    manager
+        .deploy_agents_multi(
+            vec![scout.clone(), builder.clone()],
+            VpsProvider::Docker,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+    manager.destroy_agent(builder.id, false).await.unwrap();
+
+    for id in [scout.id, builder.id] {
+        let agent = common::get_agent(&db, id).await;
+        assert_eq!(agent.status, AgentStatus::Destroyed);
+        assert_eq!(agent.deployment_id, None);
+    }
+}
+
+#[tokio::test]
+async fn env_changes_are_pushed_and_the_agent_restarted() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    let deployment = manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+    let provider_id = deployment.provider_id.clone().unwrap();
+
+    let unchanged = manager
+        .apply_runtime_changes(deployment.id, None)
+        .await
+        .unwrap();
+    assert!(unchanged.is_none());
+
+    let mut agent = common::get_agent(&db, agent.id).await;
+    agent.model_api_key = Some("sk-rotated".to_string());
+    AgentRepository::new(db.db())
+        .update_settings(&agent)
+        .await
+        .unwrap();
+    let calls_before = mock.calls().len();
+
+    let revision = manager
+        .apply_runtime_changes(deployment.id, None)
+        .await
+        .unwrap()
+        .expect("env change should record a revision");
+
+    assert_eq!(revision.env["OPENCLAW_API_KEY"], "sk-rotated");
+    assert_eq!(
+        mock.calls()[calls_before..],
+        [
+            MockCall::UpdateConfig {
+                provider_id: provider_id.clone()
+            },
+            MockCall::Restart {
+                provider_id: provider_id.clone()
+            },
+        ]
+    );
+    assert_eq!(
+        mock.config(&provider_id).unwrap().runtime_env["OPENCLAW_API_KEY"],
+        "sk-rotated"
+    );
+    assert_eq!(
+        get_deployment(&db, deployment.id).await.status,
+        DeploymentStatus::Creating
+    );
+}
+
+#[tokio::test]
+async fn settings_are_not_saved_when_the_push_fails() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock =
+        MockVpsProvider::new().with_default_script(MockScript::running().fail_update("rejected"));
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    let deployment = manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+
+    let mut agent = common::get_agent(&db, agent.id).await;
+    agent.model_api_key = Some("sk-rotated".to_string());
+    let error = manager
+        .update_agent_settings(&agent, None)
+        .await
+        .unwrap_err();
+
+    assert!(format!("{:#}", error).contains("rejected"));
+    let stored = common::get_agent(&db, agent.id).await;
+    assert_eq!(stored.model_api_key.as_deref(), Some("sk-test"));
+    let revisions = DeploymentRevisionRepository::new(db.db())
+        .list_by_deployment(deployment.id)
+        .await
+        .unwrap();
+    assert_eq!(revisions.len(), 1);
+}
+
+#[tokio::test]
+async fn settings_are_saved_once_pushed() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+
+    let mut agent = common::get_agent(&db, agent.id).await;
+    agent.model_api_key = Some("sk-rotated".to_string());
+    let revision = manager.update_agent_settings(&agent, None).await.unwrap();
+
+    assert_eq!(revision.unwrap().revision, 2);
+    let stored = common::get_agent(&db, agent.id).await;
+    assert_eq!(stored.model_api_key.as_deref(), Some("sk-rotated"));
+}
+
+#[tokio::test]
+async fn settings_pushed_to_a_vanished_deployment_report_it_missing() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    let deployment = manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+    // Removed behind the orchestrator's back
+    let id = DeploymentId {
+        id: deployment.id,
+        provider_id: deployment.provider_id.clone().unwrap(),
+    };
+    mock.destroy_agent(&id).await.unwrap();
+
+    let mut agent = common::get_agent(&db, agent.id).await;
+    agent.model_api_key = Some("sk-rotated".to_string());
+    let error = manager
+        .update_agent_settings(&agent, None)
+        .await
+        .unwrap_err();
+
+    assert!(
+        error.downcast_ref::<ResourceNotFound>().is_some(),
+        "{:#}",
+        error
+    );
+    let unknown = DeploymentId {
+        id: Uuid::new_v4(),
+        provider_id: "mock-unknown".to_string(),
+    };
+    let error = mock.get_logs(&unknown, None).await.unwrap_err();
+    assert!(error.downcast_ref::<ResourceNotFound>().is_some());
+    let error = mock
+        .stream_logs(&unknown, LogStreamOptions::default())
+        .await
+        .err()
+        .unwrap();
+    assert!(error.downcast_ref::<ResourceNotFound>().is_some());
+}
+
+#[tokio::test]
+async fn settings_jobs_push_then_save_the_settings() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    let deployment = manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+    let queue = job_queue(&db, manager).await;
+
+    let mut agent = common::get_agent(&db, agent.id).await;
+    agent.model_api_key = Some("sk-rotated".to_string());
+    let job = queue
+        .enqueue_update_settings(agent.clone(), &deployment, None)
+        .await
+        .unwrap();
+    assert_eq!(job.kind, DeploymentJobKind::UpdateSettings);
+    assert!(DeploymentJobRepository::new(db.db())
+        .has_open_job(agent.id, &DeploymentJobKind::UpdateSettings)
+        .await
+        .unwrap());
+
+    let job = wait_for_job(&queue, job.id).await;
+    assert_eq!(job.status, DeploymentJobStatus::Succeeded);
+    assert_eq!(job.deployment_id, Some(deployment.id));
+    assert!(job.agent_settings.is_none());
+    let stored = common::get_agent(&db, agent.id).await;
+    assert_eq!(stored.model_api_key.as_deref(), Some("sk-rotated"));
+    let revisions = DeploymentRevisionRepository::new(db.db())
+        .list_by_deployment(deployment.id)
+        .await
+        .unwrap();
+    assert_eq!(revisions.len(), 2);
+    assert!(!DeploymentJobRepository::new(db.db())
+        .has_open_job(agent.id, &DeploymentJobKind::UpdateSettings)
+        .await
+        .unwrap());
+}
+
+#[tokio::test]
+async fn failed_settings_jobs_leave_the_settings_unsaved() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock =
+        MockVpsProvider::new().with_default_script(MockScript::running().fail_update("rejected"));
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    let deployment = manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+    let queue = job_queue(&db, manager).await;
+
+    let mut agent = common::get_agent(&db, agent.id).await;
+    agent.model_api_key = Some("sk-rotated".to_string());
+    let job = queue
+        .enqueue_update_settings(agent.clone(), &deployment, None)
+        .await
+        .unwrap();
+
+    let job = wait_for_job(&queue, job.id).await;
+    assert_eq!(job.status, DeploymentJobStatus::Failed);
+    assert!(job.last_error.unwrap().contains("rejected"));
+    assert!(job.agent_settings.is_none());
+    let stored = common::get_agent(&db, agent.id).await;
+    assert_eq!(stored.model_api_key.as_deref(), Some("sk-test"));
+}
+
+#[tokio::test]
+async fn finished_jobs_drop_the_railway_key() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let queue = job_queue(&db, manager(&db, &mock).await).await;
+    let agent = common::insert_agent(&db, "scout").await;
+
+    let job = queue
+        .enqueue_deploy(
+            agent.id,
+            VpsProvider::Docker,
+            None,
+            Some("railway-key".to_string()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        queue.get(job.id).await.unwrap().unwrap().railway_api_key,
+        Some("railway-key".to_string())
+    );
+
+    let job = wait_for_job(&queue, job.id).await;
+    assert_eq!(job.status, DeploymentJobStatus::Succeeded);
+    assert_eq!(job.railway_api_key, None);
+}
+
+#[tokio::test]
+async fn failed_jobs_drop_the_railway_key() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new().with_default_script(MockScript::failing());
+    let queue = job_queue(&db, manager(&db, &mock).await).await;
+    let agent = common::insert_agent(&db, "scout").await;
+
+    let job = queue
+        .enqueue_deploy(
+            agent.id,
+            VpsProvider::Docker,
+            None,
+            Some("railway-key".to_string()),
+        )
+        .await
+        .unwrap();
+
+    let job = wait_for_job(&queue, job.id).await;
+    assert_eq!(job.status, DeploymentJobStatus::Failed);
+    assert_eq!(job.railway_api_key, None);
+}
+
+#[tokio::test]
+async fn a_failed_redeploy_marks_the_deployment_failed() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    let deployment = manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+    let provider_id = deployment.provider_id.clone().unwrap();
+
+    // A revision with another init script makes the current plan a redeploy
+    let revision_repo = DeploymentRevisionRepository::new(db.db());
+    let latest = revision_repo
+        .get_latest(deployment.id)
+        .await
+        .unwrap()
+        .unwrap();
+    revision_repo
+        .create(&DeploymentRevision {
+            id: Uuid::new_v4(),
+            init_script_hash: "outdated".to_string(),
+            ..latest
+        })
+        .await
+        .unwrap();
+    mock.push_script(MockScript::running().fail_deploy("no capacity"));
+
+    let error = manager
+        .apply_runtime_changes(deployment.id, None)
+        .await
+        .unwrap_err();
+
+    assert!(format!("{:#}", error).contains("no capacity"));
+    assert!(mock.is_destroyed(&provider_id));
+    assert_eq!(
+        get_deployment(&db, deployment.id).await.status,
+        DeploymentStatus::Failed
+    );
+    assert_eq!(
+        common::get_agent(&db, agent.id).await.status,
+        AgentStatus::Error
+    );
+}
+
+#[tokio::test]
+async fn deployments_without_revisions_are_updated_in_place() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    let deployment = manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+    let provider_id = deployment.provider_id.clone().unwrap();
+    // As if deployed before revisions were recorded
+    sqlx::query("DELETE FROM deployment_revisions WHERE deployment_id = $1")
+        .bind(deployment.id)
+        .execute(&db.db())
+        .await
+        .unwrap();
+    let calls_before = mock.calls().len();
+
+    let mut agent = common::get_agent(&db, agent.id).await;
+    agent.model_api_key = Some("sk-rotated".to_string());
+    let revision = manager
+        .update_agent_settings(&agent, None)
+        .await
+        .unwrap()
+        .expect("env change should record a revision");
+
+    assert_eq!(
+        mock.calls()[calls_before..],
+        [
+            MockCall::UpdateConfig {
+                provider_id: provider_id.clone()
+            },
+            MockCall::Restart { provider_id },
+        ]
+    );
+    // The seeded revision of what was running, then the change
+    assert_eq!(revision.revision, 2);
+    let revisions = DeploymentRevisionRepository::new(db.db())
+        .list_by_deployment(deployment.id)
+        .await
+        .unwrap();
+    let seeded = revisions.iter().find(|r| r.revision == 1).unwrap();
+    assert_eq!(seeded.env["OPENCLAW_API_KEY"], "sk-test");
+}
+
+/// Records a revision made from the latest one by `change`, with the agent's stored
+/// `runtime_config` set to match, as if the deployment had been changed to it.
+async fn change_revision(
+    db: &Database,
+    deployment: &Deployment,
+    change: impl FnOnce(&mut DeploymentRevision),
+) -> DeploymentRevision {
+    let revision_repo = DeploymentRevisionRepository::new(db.db());
+    let mut revision = revision_repo
+        .get_latest(deployment.id)
+        .await
+        .unwrap()
+        .unwrap();
+    revision.id = Uuid::new_v4();
+    revision.agent_configs = [(deployment.agent_id, Some(json!({ "tools": ["browser"] })))]
+        .into_iter()
+        .collect();
+    change(&mut revision);
+    revision.revision = revision_repo.create(&revision).await.unwrap();
+    AgentRepository::new(db.db())
+        .update_runtime_config(deployment.agent_id, Some(json!({ "tools": ["browser"] })))
+        .await
+        .unwrap();
+    revision
+}
+
+#[tokio::test]
+async fn rollback_jobs_update_in_place_when_the_init_script_is_unchanged() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    let deployment = manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+    let provider_id = deployment.provider_id.clone().unwrap();
+    change_revision(&db, &deployment, |revision| {
+        revision
+            .env
+            .insert("OPENCLAW_API_KEY".to_string(), "sk-rotated".to_string());
+    })
+    .await;
+    let queue = job_queue(&db, manager).await;
+    let calls_before = mock.calls().len();
+
+    let job = queue.enqueue_rollback(&deployment, 1, None).await.unwrap();
+    assert_eq!(job.kind, DeploymentJobKind::Rollback);
+    assert_eq!(job.revision, Some(1));
+
+    let job = wait_for_job(&queue, job.id).await;
+    assert_eq!(job.status, DeploymentJobStatus::Succeeded);
+    assert_eq!(job.deployment_id, Some(deployment.id));
+    assert_eq!(
+        mock.calls()[calls_before..],
+        [
+            MockCall::UpdateConfig {
+                provider_id: provider_id.clone()
+            },
+            MockCall::Restart {
+                provider_id: provider_id.clone()
+            },
+        ]
+    );
+    assert_eq!(
+        mock.config(&provider_id).unwrap().runtime_env["OPENCLAW_API_KEY"],
+        "sk-test"
+    );
+    let latest = DeploymentRevisionRepository::new(db.db())
+        .get_latest(deployment.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(latest.revision, 3);
+    assert_eq!(latest.restored_from, Some(1));
+    assert_eq!(latest.env["OPENCLAW_API_KEY"], "sk-test");
+    assert_eq!(common::get_agent(&db, agent.id).await.runtime_config, None);
+}
+
+#[tokio::test]
+async fn rollbacks_to_another_init_script_redeploy() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    let deployment = manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+    let provider_id = deployment.provider_id.clone().unwrap();
+    change_revision(&db, &deployment, |revision| {
+        revision.init_script_hash = "newer".to_string();
+    })
+    .await;
+
+    let revision = manager.rollback(deployment.id, 1, None).await.unwrap();
+
+    assert_eq!(revision.revision, 3);
+    assert_eq!(revision.restored_from, Some(1));
+    assert!(mock.is_destroyed(&provider_id));
+    let replacement = get_deployment(&db, deployment.id)
+        .await
+        .provider_id
+        .unwrap();
+    assert_ne!(replacement, provider_id);
+    assert!(!mock.is_destroyed(&replacement));
+    assert_eq!(common::get_agent(&db, agent.id).await.runtime_config, None);
+}
+
+#[tokio::test]
+async fn failed_rollbacks_keep_the_stored_settings() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new();
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    let deployment = manager
+        .deploy_agent(agent.clone(), VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+    change_revision(&db, &deployment, |revision| {
+        revision.init_script_hash = "newer".to_string();
+    })
+    .await;
+    mock.push_script(MockScript::running().fail_deploy("no capacity"));
+
+    let error = manager.rollback(deployment.id, 1, None).await.unwrap_err();
+
+    assert!(format!("{:#}", error).contains("no capacity"));
+    assert_eq!(
+        common::get_agent(&db, agent.id).await.runtime_config,
+        Some(json!({ "tools": ["browser"] }))
+    );
+    let latest = DeploymentRevisionRepository::new(db.db())
+        .get_latest(deployment.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(latest.revision, 2);
+}
+
+#[tokio::test]
+async fn archiving_keeps_repeated_lines_and_skips_rearchived_ones() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let mock = MockVpsProvider::new()
+        .with_default_script(MockScript::running().logs(["starting", "tick", "tick"]));
+    let manager = manager(&db, &mock).await;
+    let agent = common::insert_agent(&db, "scout").await;
+    let deployment = manager
+        .deploy_agent(agent, VpsProvider::Docker, None, None)
+        .await
+        .unwrap();
+    let provider_id = deployment.provider_id.clone().unwrap();
+
+    // Mock lines have no timestamps, so every pass re-reads the whole output
+    assert_eq!(
+        archive_deployment(&db, &mock, &deployment).await.unwrap(),
+        3
+    );
+    assert_eq!(
+        archive_deployment(&db, &mock, &deployment).await.unwrap(),
+        0
+    );
+    mock.push_logs(&provider_id, ["tick"]);
+    assert_eq!(
+        archive_deployment(&db, &mock, &deployment).await.unwrap(),
+        1
+    );
+
+    let logs = DeploymentLogRepository::new(db.db())
+        .search(&LogSearch {
+            deployment_id: Some(deployment.id),
+            text: Some("tick".to_string()),
+            limit: 10,
+            ..LogSearch::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(logs.len(), 3);
+}
@@ -0,0 +1,236 @@
+//! Who may run `!task-` commands over Discord, and where. Commands are handed to the
+//! gateway's handler directly. Needs `TEST_DATABASE_URL`; see `common`.
+
+mod common;
+
+use engine::coordinator::commands::TaskCommand;
+use engine::coordinator::discord::{
+    CommandSender, DiscordClient, DiscordConfig, DiscordEventHandler,
+};
+use engine::coordinator::transport::CommandReceiver;
+use engine::models::{Agent, Task, TaskStatus, Team};
+use engine::storage::repositories::AgentRepository;
+use engine::Database;
+use serenity::all::{RoleId, UserId};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+const LOGS: u64 = 100;
+const OPERATOR_ROLE: u64 = 900;
+/// The user id encoded in [`BOT_TOKEN`].
+const BOT_USER: u64 = 4242;
+const BOT_TOKEN: &str = "NDI0Mg.GxYz.signature";
+
+async fn handler(db: &Database) -> DiscordEventHandler {
+    let discord = DiscordClient::new(
+        db.clone(),
+        DiscordConfig {
+            bot_token: "test-token".to_string(),
+            api_url: "http://127.0.0.1:9".to_string(),
+        },
+    )
+    .await
+    .unwrap();
+    let (finished, _finished_rx) = mpsc::unbounded_channel();
+    discord.command_handler(CommandReceiver::new(db.clone(), finished))
+}
+
+/// A team coordinating in channels 100, 200 and 300, with one operator role.
+async fn discord_team(db: &Database) -> Team {
+    let mut team = common::team(Uuid::new_v4(), Vec::new());
+    team.discord_channel_id = LOGS.to_string();
+    team.discord_channels.coordination_logs = LOGS.to_string();
+    team.discord_channels.slave_communication = "200".to_string();
+    team.discord_channels.master_orders = "300".to_string();
+    team.settings.operator_role_ids = vec![OPERATOR_ROLE.to_string()];
+    common::insert_team(db, team).await
+}
+
+/// A slave whose Discord bot is [`BOT_USER`].
+async fn insert_slave(db: &Database) -> Agent {
+    let mut agent = common::agent("slave");
+    agent.discord_bot_token = Some(BOT_TOKEN.to_string());
+    AgentRepository::new(db.db())
+        .create(&agent)
+        .await
+        .expect("failed to insert slave");
+    agent
+}
+
+async fn insert_task(db: &Database, team: &Team, assigned_to: Option<&Agent>) -> Task {
+    let mut task = common::task(team.id, TaskStatus::Pending, vec![]);
+    task.assigned_to = assigned_to.map(|agent| agent.id);
+    common::insert_task(db, task).await
+}
+
+fn sender(user_id: u64, channel_id: u64, role_ids: &[u64]) -> CommandSender {
+    CommandSender {
+        user_id: UserId::new(user_id),
+        channel_id,
+        role_ids: role_ids
+            .iter()
+            .map(|role_id| RoleId::new(*role_id))
+            .collect(),
+    }
+}
+
+fn claim(task: &Task) -> Result<TaskCommand, String> {
+    Ok(TaskCommand::Claim { task_id: task.id })
+}
+
+/// Messages queued for `channel_id` in the outbox.
+async fn queued(db: &Database, channel_id: u64) -> Vec<String> {
+    sqlx::query_scalar(
+        "SELECT content FROM discord_outbox WHERE channel_id = $1 ORDER BY created_at",
+    )
+    .bind(channel_id.to_string())
+    .fetch_all(&db.db())
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn the_assigned_agent_can_change_its_task() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let handler = handler(&db).await;
+    let team = discord_team(&db).await;
+    let slave = insert_slave(&db).await;
+    let task = insert_task(&db, &team, Some(&slave)).await;
+
+    let reply = handler.run(claim(&task), &sender(BOT_USER, 200, &[])).await;
+
+    assert_eq!(reply, Some(format!("Task {} claimed.", task.id)));
+    assert_eq!(
+        common::get_task(&db, task.id).await.status,
+        TaskStatus::InProgress
+    );
+    assert_eq!(
+        queued(&db, LOGS).await,
+        [format!("📊 [COORD] Task `{}` claimed", task.id)]
+    );
+}
+
+#[tokio::test]
+async fn other_users_are_turned_away_but_may_ask_for_status() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let handler = handler(&db).await;
+    let team = discord_team(&db).await;
+    let slave = insert_slave(&db).await;
+    let task = insert_task(&db, &team, Some(&slave)).await;
+
+    let reply = handler.run(claim(&task), &sender(7, LOGS, &[555])).await;
+
+    assert_eq!(
+        reply,
+        Some(format!(
+            "Not allowed: task {} is assigned to another agent.",
+            task.id
+        ))
+    );
+    assert_eq!(
+        common::get_task(&db, task.id).await.status,
+        TaskStatus::Pending
+    );
+    assert!(queued(&db, LOGS).await.is_empty());
+
+    let status = handler
+        .run(
+            Ok(TaskCommand::Status { task_id: task.id }),
+            &sender(7, LOGS, &[]),
+        )
+        .await
+        .unwrap();
+    assert!(status.contains("pending"), "{}", status);
+}
+
+#[tokio::test]
+async fn operators_can_change_any_task() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let handler = handler(&db).await;
+    let team = discord_team(&db).await;
+    let slave = insert_slave(&db).await;
+    let assigned = insert_task(&db, &team, Some(&slave)).await;
+    let unassigned = insert_task(&db, &team, None).await;
+
+    // Not even the agents' bots may act on unassigned tasks
+    let reply = handler
+        .run(claim(&unassigned), &sender(BOT_USER, 300, &[]))
+        .await;
+    assert_eq!(
+        reply.as_deref(),
+        Some("Not allowed: only operators can act on unassigned tasks.")
+    );
+
+    let operator = sender(8, 300, &[555, OPERATOR_ROLE]);
+    let reply = handler.run(claim(&unassigned), &operator).await;
+    assert_eq!(reply, Some(format!("Task {} claimed.", unassigned.id)));
+    let reply = handler
+        .run(
+            Ok(TaskCommand::Cancel {
+                task_id: assigned.id,
+                reason: None,
+            }),
+            &operator,
+        )
+        .await;
+    assert_eq!(reply, Some(format!("Task {} cancelled.", assigned.id)));
+    assert_eq!(
+        common::get_task(&db, assigned.id).await.status,
+        TaskStatus::Cancelled
+    );
+}
+
+#[tokio::test]
+async fn commands_outside_the_team_channels_are_ignored() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let handler = handler(&db).await;
+    let team = discord_team(&db).await;
+    let slave = insert_slave(&db).await;
+    let mut task = common::task(team.id, TaskStatus::Pending, vec![]);
+    task.assigned_to = Some(slave.id);
+    task.thread_id = Some("777".to_string());
+    let task = common::insert_task(&db, task).await;
+    let operator = [OPERATOR_ROLE];
+
+    assert_eq!(
+        handler.run(claim(&task), &sender(8, 555, &operator)).await,
+        None
+    );
+    let unknown = Ok(TaskCommand::Claim {
+        task_id: Uuid::new_v4(),
+    });
+    assert_eq!(
+        handler.run(unknown, &sender(8, LOGS, &operator)).await,
+        None
+    );
+    assert_eq!(
+        common::get_task(&db, task.id).await.status,
+        TaskStatus::Pending
+    );
+
+    // The task's thread counts as a team channel, and the log stays out of it
+    let reply = handler.run(claim(&task), &sender(BOT_USER, 777, &[])).await;
+    assert_eq!(reply, Some(format!("Task {} claimed.", task.id)));
+    assert!(queued(&db, 777).await.is_empty());
+}
+
+#[tokio::test]
+async fn malformed_commands_are_answered_with_their_usage() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let handler = handler(&db).await;
+    let command = TaskCommand::parse("!task-claim nope").unwrap();
+
+    let reply = handler.run(command, &sender(7, 555, &[])).await;
+
+    assert_eq!(reply.as_deref(), Some("Usage: `!task-claim <task id>`"));
+}
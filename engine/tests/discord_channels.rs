@@ -0,0 +1,319 @@
+//! Provisioning a team's private Discord channels against a stub REST API, and removing
+//! them again. Needs `TEST_DATABASE_URL`; see `common`.
+
+mod common;
+
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, post, put};
+use axum::{Json, Router};
+use engine::coordinator::discord::{DiscordClient, DiscordConfig};
+use engine::models::{Agent, DiscordChannels, Team};
+use engine::Database;
+use serde_json::{json, Value};
+use serenity::all::Permissions;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+const GUILD: &str = "500";
+/// Encodes the orchestrator's user id, 1000.
+const ORCHESTRATOR_TOKEN: &str = "MTAwMA.GxYz.signature";
+/// Encodes an agent bot's user id, 4242.
+const AGENT_TOKEN: &str = "NDI0Mg.GxYz.signature";
+
+#[derive(Default)]
+struct DiscordApi {
+    /// Every call, as `(method, path, body)`.
+    calls: Mutex<Vec<(Method, String, Value)>>,
+    /// Fails the channel creation with this (1-based) index.
+    fail_create: Mutex<Option<usize>>,
+    /// Channels answered with 404.
+    missing: Mutex<HashSet<String>>,
+}
+
+type Stub = Arc<DiscordApi>;
+
+fn authorized(headers: &HeaderMap) -> bool {
+    headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        == Some(&format!("Bot {}", ORCHESTRATOR_TOKEN))
+}
+
+async fn create_channel(
+    State(stub): State<Stub>,
+    Path(guild_id): Path<String>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let mut calls = stub.calls.lock().unwrap();
+    calls.push((
+        Method::POST,
+        format!("/guilds/{}/channels", guild_id),
+        body.clone(),
+    ));
+    let created = calls
+        .iter()
+        .filter(|(method, _, _)| method == Method::POST)
+        .count();
+    if *stub.fail_create.lock().unwrap() == Some(created) {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "boom").into_response();
+    }
+    Json(json!({ "id": format!("{}", 600 + created), "name": body["name"] })).into_response()
+}
+
+async fn set_permission(
+    State(stub): State<Stub>,
+    Path((channel_id, target_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    stub.calls.lock().unwrap().push((
+        Method::PUT,
+        format!("/channels/{}/permissions/{}", channel_id, target_id),
+        body,
+    ));
+    StatusCode::NO_CONTENT.into_response()
+}
+
+async fn delete_channel(
+    State(stub): State<Stub>,
+    Path(channel_id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    stub.calls.lock().unwrap().push((
+        Method::DELETE,
+        format!("/channels/{}", channel_id),
+        Value::Null,
+    ));
+    if stub.missing.lock().unwrap().contains(&channel_id) {
+        return (StatusCode::NOT_FOUND, Json(json!({ "code": 10003 }))).into_response();
+    }
+    Json(json!({ "id": channel_id })).into_response()
+}
+
+async fn discord(db: &Database) -> (Stub, DiscordClient) {
+    let stub = Stub::default();
+    let router = Router::new()
+        .route("/guilds/:guild_id/channels", post(create_channel))
+        .route(
+            "/channels/:channel_id/permissions/:target_id",
+            put(set_permission),
+        )
+        .route("/channels/:channel_id", delete(delete_channel))
+        .with_state(stub.clone());
+    let api_url = common::serve(router).await;
+    let client = DiscordClient::new(
+        db.clone(),
+        DiscordConfig {
+            bot_token: ORCHESTRATOR_TOKEN.to_string(),
+            api_url,
+        },
+    )
+    .await
+    .unwrap();
+    (stub, client)
+}
+
+fn agent_with_bot() -> Agent {
+    let mut agent = common::agent("slave");
+    agent.discord_bot_token = Some(AGENT_TOKEN.to_string());
+    agent
+}
+
+/// A team whose channels were provisioned as 601 (category) and 602–604.
+fn provisioned_team() -> Team {
+    let mut team = common::team(Uuid::new_v4(), Vec::new());
+    team.discord_category_id = Some("601".to_string());
+    team.discord_channels = DiscordChannels {
+        coordination_logs: "602".to_string(),
+        slave_communication: "603".to_string(),
+        master_orders: "604".to_string(),
+    };
+    team.discord_channel_id = "602".to_string();
+    team
+}
+
+fn calls(stub: &Stub) -> Vec<(Method, String, Value)> {
+    stub.calls.lock().unwrap().clone()
+}
+
+/// The `(allow, deny)` an overwrite list gives `id`.
+fn overwrite(overwrites: &Value, id: &str) -> (Permissions, Permissions) {
+    let overwrite = overwrites
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|overwrite| overwrite["id"] == id)
+        .unwrap_or_else(|| panic!("no overwrite for {}", id));
+    let bits = |field: &str| {
+        Permissions::from_bits_truncate(overwrite[field].as_str().unwrap().parse().unwrap())
+    };
+    (bits("allow"), bits("deny"))
+}
+
+#[tokio::test]
+async fn team_channels_are_created_private_to_the_team() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, discord) = discord(&db).await;
+
+    let provisioned = discord
+        .provision_team_channels(GUILD, "Research", &[agent_with_bot()], &["900".to_string()])
+        .await
+        .unwrap();
+
+    assert_eq!(provisioned.category_id, "601");
+    assert_eq!(provisioned.channels.coordination_logs, "602");
+    assert_eq!(provisioned.channels.slave_communication, "603");
+    assert_eq!(provisioned.channels.master_orders, "604");
+
+    let calls = calls(&stub);
+    let names: Vec<_> = calls
+        .iter()
+        .map(|(_, _, body)| body["name"].clone())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "Research",
+            "coordination-logs",
+            "slave-communication",
+            "master-orders"
+        ]
+    );
+    assert!(calls
+        .iter()
+        .all(|(_, path, _)| path == "/guilds/500/channels"));
+    assert_eq!(calls[0].2["type"], 4);
+    for (_, _, body) in &calls[1..] {
+        assert_eq!(body["type"], 0);
+        assert_eq!(body["parent_id"], "601");
+    }
+
+    let category = &calls[0].2["permission_overwrites"];
+    // Hidden from @everyone, visible to the orchestrator, the agent and operators
+    assert!(overwrite(category, GUILD)
+        .1
+        .contains(Permissions::VIEW_CHANNEL));
+    assert!(overwrite(category, "1000")
+        .0
+        .contains(Permissions::VIEW_CHANNEL | Permissions::MANAGE_THREADS));
+    assert!(overwrite(category, "4242")
+        .0
+        .contains(Permissions::SEND_MESSAGES));
+    assert!(overwrite(category, "900")
+        .0
+        .contains(Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES));
+
+    // Agents read the coordination log but only the orchestrator writes it
+    let (allow, deny) = overwrite(&calls[1].2["permission_overwrites"], "4242");
+    assert!(allow.contains(Permissions::VIEW_CHANNEL));
+    assert!(deny.contains(Permissions::SEND_MESSAGES));
+    let (allow, _) = overwrite(&calls[2].2["permission_overwrites"], "4242");
+    assert!(allow.contains(Permissions::SEND_MESSAGES));
+}
+
+#[tokio::test]
+async fn failed_provisioning_removes_what_was_created() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, discord) = discord(&db).await;
+    *stub.fail_create.lock().unwrap() = Some(3);
+
+    let error = discord
+        .provision_team_channels(GUILD, "Research", &[], &[])
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("500"), "{:#}", error);
+    let deleted: Vec<_> = calls(&stub)
+        .into_iter()
+        .filter(|(method, _, _)| method == Method::DELETE)
+        .map(|(_, path, _)| path)
+        .collect();
+    // The channel first, so the category is empty when it goes
+    assert_eq!(deleted, ["/channels/602", "/channels/601"]);
+}
+
+#[tokio::test]
+async fn agents_joining_later_are_let_into_the_channels() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, discord) = discord(&db).await;
+    let team = provisioned_team();
+
+    discord
+        .grant_team_access(&team, &agent_with_bot())
+        .await
+        .unwrap();
+    // Agents without a bot have nothing to be granted
+    discord
+        .grant_team_access(&team, &common::agent("botless"))
+        .await
+        .unwrap();
+
+    let calls = calls(&stub);
+    let paths: Vec<_> = calls.iter().map(|(_, path, _)| path.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "/channels/601/permissions/4242",
+            "/channels/602/permissions/4242",
+            "/channels/603/permissions/4242",
+            "/channels/604/permissions/4242",
+        ]
+    );
+    let denied = |body: &Value| {
+        Permissions::from_bits_truncate(body["deny"].as_str().unwrap().parse().unwrap())
+    };
+    assert!(denied(&calls[1].2).contains(Permissions::SEND_MESSAGES));
+    assert!(denied(&calls[2].2).is_empty());
+    assert_eq!(calls[0].2["type"], 1);
+}
+
+#[tokio::test]
+async fn removing_a_team_deletes_its_channels_even_if_some_are_gone() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, discord) = discord(&db).await;
+    stub.missing.lock().unwrap().insert("603".to_string());
+
+    discord
+        .delete_team_channels(&provisioned_team())
+        .await
+        .unwrap();
+
+    let deleted: Vec<_> = calls(&stub).into_iter().map(|(_, path, _)| path).collect();
+    assert_eq!(
+        deleted,
+        [
+            "/channels/602",
+            "/channels/603",
+            "/channels/604",
+            "/channels/601"
+        ]
+    );
+
+    // Teams using channels they brought along keep them
+    discord
+        .delete_team_channels(&common::team(Uuid::new_v4(), Vec::new()))
+        .await
+        .unwrap();
+    assert_eq!(stub.calls.lock().unwrap().len(), 4);
+}
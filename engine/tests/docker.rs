@@ -0,0 +1,330 @@
+//! `DockerAdapter` against a stub Docker Engine API served over `http://`.
+
+mod common;
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use claws_runtime_core::RuntimeServicePort;
+use engine::adapters::docker::DockerAdapter;
+use engine::adapters::logs::LogStreamOptions;
+use engine::adapters::trait_def::{AgentConfig, DeploymentId, ResourceNotFound, VpsProvider as _};
+use engine::models::{AgentRuntime, DeploymentStatus};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+const IMAGE: &str = "debian:bookworm-slim";
+
+struct Container {
+    status: String,
+    exit_code: i64,
+    /// The body it was created with.
+    config: Value,
+}
+
+#[derive(Default)]
+struct DockerApi {
+    images: Mutex<HashSet<String>>,
+    /// Reported in the pull's progress stream, after a 200.
+    pull_error: Mutex<Option<String>>,
+    containers: Mutex<BTreeMap<String, Container>>,
+    /// Requests received, as `"{method} {path}"`.
+    calls: Mutex<Vec<String>>,
+}
+
+type Stub = Arc<DockerApi>;
+
+impl DockerApi {
+    fn record(&self, call: String) {
+        self.calls.lock().unwrap().push(call);
+    }
+}
+
+async fn inspect_image(State(stub): State<Stub>, Path(name): Path<String>) -> Response {
+    stub.record(format!("GET /images/{}/json", name));
+    if stub.images.lock().unwrap().contains(&name) {
+        Json(json!({ "Id": "sha256:abc" })).into_response()
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "message": "No such image" })),
+        )
+            .into_response()
+    }
+}
+
+async fn pull_image(
+    State(stub): State<Stub>,
+    Query(query): Query<HashMap<String, String>>,
+) -> String {
+    let image = format!("{}:{}", query["fromImage"], query["tag"]);
+    stub.record(format!("POST /images/create {}", image));
+    let mut progress = vec![json!({ "status": format!("Pulling from {}", image) })];
+    match stub.pull_error.lock().unwrap().clone() {
+        Some(error) => {
+            progress.push(json!({ "errorDetail": { "message": error }, "error": error }))
+        }
+        None => {
+            stub.images.lock().unwrap().insert(image);
+            progress.push(json!({ "status": "Download complete" }));
+        }
+    }
+    progress
+        .iter()
+        .map(|line| format!("{}\r\n", line))
+        .collect()
+}
+
+async fn create_container(
+    State(stub): State<Stub>,
+    Query(query): Query<HashMap<String, String>>,
+    Json(config): Json<Value>,
+) -> Response {
+    let name = query["name"].clone();
+    stub.record(format!("POST /containers/create {}", name));
+    stub.containers.lock().unwrap().insert(
+        name.clone(),
+        Container {
+            status: "created".to_string(),
+            exit_code: 0,
+            config,
+        },
+    );
+    (StatusCode::CREATED, Json(json!({ "Id": name }))).into_response()
+}
+
+async fn remove_container(State(stub): State<Stub>, Path(name): Path<String>) -> StatusCode {
+    stub.record(format!("DELETE /containers/{}", name));
+    match stub.containers.lock().unwrap().remove(&name) {
+        Some(_) => StatusCode::NO_CONTENT,
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+/// `GET /containers/{name}/json` with every host port bound to 49153.
+async fn inspect_container(
+    State(stub): State<Stub>,
+    Path((name, action)): Path<(String, String)>,
+) -> Response {
+    let containers = stub.containers.lock().unwrap();
+    let Some(container) = containers.get(&name).filter(|_| action == "json") else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "message": "No such container" })),
+        )
+            .into_response();
+    };
+    let ports: serde_json::Map<String, Value> = container.config["ExposedPorts"]
+        .as_object()
+        .map(|exposed| {
+            exposed
+                .keys()
+                .map(|port| {
+                    let binding = json!([{ "HostIp": "127.0.0.1", "HostPort": "49153" }]);
+                    (port.clone(), binding)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Json(json!({
+        "State": { "Status": container.status, "ExitCode": container.exit_code },
+        "NetworkSettings": { "Ports": ports },
+    }))
+    .into_response()
+}
+
+async fn container_action(
+    State(stub): State<Stub>,
+    Path((name, action)): Path<(String, String)>,
+) -> StatusCode {
+    stub.record(format!("POST /containers/{}/{}", name, action));
+    let mut containers = stub.containers.lock().unwrap();
+    let Some(container) = containers.get_mut(&name) else {
+        return StatusCode::NOT_FOUND;
+    };
+    container.status = match action.as_str() {
+        "stop" => "exited".to_string(),
+        _ => "running".to_string(),
+    };
+    StatusCode::NO_CONTENT
+}
+
+async fn docker() -> (Stub, DockerAdapter) {
+    let stub = Stub::default();
+    let router = Router::new()
+        .route("/images/:name/json", get(inspect_image))
+        .route("/images/create", post(pull_image))
+        .route("/containers/create", post(create_container))
+        .route("/containers/:name", delete(remove_container))
+        .route(
+            "/containers/:name/:action",
+            get(inspect_container).post(container_action),
+        )
+        .with_state(stub.clone());
+    let url = common::serve(router).await;
+    (stub, DockerAdapter::from_host(&url).unwrap())
+}
+
+fn agent_config() -> AgentConfig {
+    AgentConfig {
+        agent: common::agent("scout"),
+        agents: None,
+        region: None,
+        runtime: AgentRuntime::OpenClaw,
+        runtime_init_script: "start-agent".to_string(),
+        runtime_env: BTreeMap::from([("AGENT_NAME".to_string(), "scout".to_string())]),
+        runtime_services: vec![RuntimeServicePort {
+            port: 443,
+            handlers: vec!["http".to_string()],
+            internal_port: 8080,
+        }],
+        volume_id: Some("clawguild-scout-data".to_string()),
+    }
+}
+
+fn deployment(container_name: &str) -> DeploymentId {
+    DeploymentId {
+        id: Uuid::new_v4(),
+        provider_id: format!("docker-{}", container_name),
+    }
+}
+
+fn calls(stub: &Stub) -> Vec<String> {
+    stub.calls.lock().unwrap().clone()
+}
+
+#[tokio::test]
+async fn deploy_creates_a_container_with_its_ports_and_volume() {
+    let (stub, docker) = docker().await;
+    let config = agent_config();
+    let name = format!("clawguild-{}", config.agent.id);
+
+    let deployment = docker.deploy_agent(config.clone()).await.unwrap();
+
+    assert_eq!(deployment.provider_id, format!("docker-{}", name));
+    let containers = stub.containers.lock().unwrap();
+    let container = &containers[&name];
+    assert_eq!(container.status, "running");
+    let created = &container.config;
+    assert_eq!(created["Image"], IMAGE);
+    assert_eq!(created["Cmd"], json!(["/bin/bash", "-c", "start-agent"]));
+    assert_eq!(created["Env"], json!(["AGENT_NAME=scout"]));
+    assert_eq!(
+        created["Labels"]["clawguild.agent_id"],
+        config.agent.id.to_string()
+    );
+    assert_eq!(created["ExposedPorts"], json!({ "8080/tcp": {} }));
+    // Only reachable from the host, on a port Docker picks
+    assert_eq!(
+        created["HostConfig"]["PortBindings"],
+        json!({ "8080/tcp": [{ "HostIp": "127.0.0.1", "HostPort": "" }] })
+    );
+    assert_eq!(
+        created["HostConfig"]["Mounts"],
+        json!([{
+            "Type": "volume",
+            "Source": "clawguild-scout-data",
+            "Target": "/root/.openclaw",
+        }])
+    );
+}
+
+#[tokio::test]
+async fn the_image_is_only_pulled_when_the_host_lacks_it() {
+    let (stub, docker) = docker().await;
+
+    docker.deploy_agent(agent_config()).await.unwrap();
+    docker.deploy_agent(agent_config()).await.unwrap();
+
+    let inspected = format!("GET /images/{}/json", IMAGE);
+    let pull = format!("POST /images/create {}", IMAGE);
+    let image_calls: Vec<_> = calls(&stub)
+        .into_iter()
+        .filter(|call| call.contains("/images/"))
+        .collect();
+    assert_eq!(image_calls, [inspected.clone(), pull, inspected]);
+}
+
+#[tokio::test]
+async fn pull_errors_in_the_progress_stream_fail_the_deploy() {
+    let (stub, docker) = docker().await;
+    *stub.pull_error.lock().unwrap() = Some("toomanyrequests: rate limit exceeded".to_string());
+
+    let error = docker.deploy_agent(agent_config()).await.unwrap_err();
+
+    assert!(
+        format!("{:#}", error).contains("toomanyrequests"),
+        "{:#}",
+        error
+    );
+    assert!(stub.containers.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn status_follows_the_container_state() {
+    let (stub, docker) = docker().await;
+    let deployment = docker.deploy_agent(agent_config()).await.unwrap();
+    let name = deployment.provider_id.strip_prefix("docker-").unwrap();
+
+    let cases = [
+        ("running", 0, DeploymentStatus::Running),
+        ("created", 0, DeploymentStatus::Creating),
+        ("restarting", 0, DeploymentStatus::Creating),
+        ("paused", 0, DeploymentStatus::Stopped),
+        ("exited", 0, DeploymentStatus::Stopped),
+        ("exited", 137, DeploymentStatus::Failed),
+        ("dead", 1, DeploymentStatus::Failed),
+    ];
+    for (state, exit_code, expected) in cases {
+        {
+            let mut containers = stub.containers.lock().unwrap();
+            let container = containers.get_mut(name).unwrap();
+            container.status = state.to_string();
+            container.exit_code = exit_code;
+        }
+        let status = docker.get_status(&deployment).await.unwrap();
+        assert_eq!(status.status, expected, "{} ({})", state, exit_code);
+        assert_eq!(status.endpoint.as_deref(), Some("http://127.0.0.1:49153"));
+    }
+}
+
+#[tokio::test]
+async fn missing_containers_are_reported_as_missing() {
+    let (_stub, docker) = docker().await;
+    let missing = deployment("clawguild-gone");
+
+    let error = docker.get_status(&missing).await.unwrap_err();
+    assert!(
+        error.downcast_ref::<ResourceNotFound>().is_some(),
+        "{:#}",
+        error
+    );
+    for error in [
+        docker.stop_agent(&missing).await.unwrap_err(),
+        docker.start_agent(&missing).await.unwrap_err(),
+        docker.restart_agent(&missing).await.unwrap_err(),
+    ] {
+        assert!(
+            error.downcast_ref::<ResourceNotFound>().is_some(),
+            "{:#}",
+            error
+        );
+    }
+    let error = docker
+        .stream_logs(&missing, LogStreamOptions::default())
+        .await
+        .err()
+        .unwrap();
+    assert!(
+        error.downcast_ref::<ResourceNotFound>().is_some(),
+        "{:#}",
+        error
+    );
+
+    // Already gone is as good as destroyed
+    docker.destroy_agent(&missing).await.unwrap();
+}
@@ -0,0 +1,392 @@
+//! `FlyIoAdapter` against a stub Machines API, mostly for deployments recorded before provider
+//! IDs named the app (`flyio-{machine_id}`). Tests touching deployments need
+//! `TEST_DATABASE_URL`; see `common`.
+
+mod common;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{TimeZone, Utc};
+use engine::adapters::flyio::FlyIoAdapter;
+use engine::adapters::logs::LogStreamOptions;
+use engine::adapters::trait_def::{DeploymentId, VpsProvider as _};
+use engine::adapters::VpsAdapters;
+use engine::deployment::reconciler::DeploymentReconciler;
+use engine::models::{AgentStatus, Deployment, DeploymentStatus, VpsProvider};
+use engine::storage::repositories::{AgentRepository, DeploymentRepository};
+use engine::Database;
+use futures::TryStreamExt;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+#[derive(Default)]
+struct FlyApi {
+    /// Machine states by app and machine ID.
+    apps: Mutex<BTreeMap<String, BTreeMap<String, String>>>,
+    /// Machine lifecycle actions and deletes received, as `"{method} {path}"`.
+    calls: Mutex<Vec<String>>,
+    /// Answers machine deletes with this instead of deleting the machine.
+    delete_error: Mutex<Option<StatusCode>>,
+}
+
+type Stub = Arc<FlyApi>;
+
+async fn list_apps(State(stub): State<Stub>) -> Json<Value> {
+    let apps: Vec<Value> = stub
+        .apps
+        .lock()
+        .unwrap()
+        .keys()
+        .map(|name| json!({ "name": name }))
+        .collect();
+    Json(json!({ "total_apps": apps.len(), "apps": apps }))
+}
+
+async fn get_app(State(stub): State<Stub>, Path(app): Path<String>) -> StatusCode {
+    if stub.apps.lock().unwrap().contains_key(&app) {
+        StatusCode::OK
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+async fn get_machine(
+    State(stub): State<Stub>,
+    Path((app, machine_id)): Path<(String, String)>,
+) -> Result<Json<Value>, StatusCode> {
+    let apps = stub.apps.lock().unwrap();
+    let state = apps
+        .get(&app)
+        .and_then(|machines| machines.get(&machine_id))
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(json!({ "id": machine_id, "state": state })))
+}
+
+async fn machine_action(
+    State(stub): State<Stub>,
+    Path((app, machine_id, action)): Path<(String, String, String)>,
+) -> StatusCode {
+    stub.calls.lock().unwrap().push(format!(
+        "POST /v1/apps/{}/machines/{}/{}",
+        app, machine_id, action
+    ));
+    let mut apps = stub.apps.lock().unwrap();
+    let Some(state) = apps
+        .get_mut(&app)
+        .and_then(|machines| machines.get_mut(&machine_id))
+    else {
+        return StatusCode::NOT_FOUND;
+    };
+    *state = match action.as_str() {
+        "stop" => "stopped".to_string(),
+        _ => "started".to_string(),
+    };
+    StatusCode::OK
+}
+
+async fn delete_machine(
+    State(stub): State<Stub>,
+    Path((app, machine_id)): Path<(String, String)>,
+) -> StatusCode {
+    stub.calls
+        .lock()
+        .unwrap()
+        .push(format!("DELETE /v1/apps/{}/machines/{}", app, machine_id));
+    if let Some(status) = *stub.delete_error.lock().unwrap() {
+        return status;
+    }
+    let mut apps = stub.apps.lock().unwrap();
+    match apps
+        .get_mut(&app)
+        .and_then(|machines| machines.remove(&machine_id))
+    {
+        Some(_) => StatusCode::OK,
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+/// One page of five log lines for every app, all from `m-1`.
+async fn app_logs(Path(app): Path<String>) -> Json<Value> {
+    let data: Vec<Value> = (1..=5)
+        .map(|n| {
+            json!({
+                "attributes": {
+                    "timestamp": format!("2026-01-01T00:00:0{}Z", n),
+                    "instance": "m-1",
+                    "message": format!("{} line {}", app, n),
+                }
+            })
+        })
+        .collect();
+    Json(json!({ "data": data, "meta": { "next_token": "" } }))
+}
+
+/// A Machines API holding `apps`, served at the returned base URL.
+async fn stub_api(apps: &[(&str, &[(&str, &str)])]) -> (String, Stub) {
+    let apps = Mutex::new(
+        apps.iter()
+            .map(|(app, machines)| {
+                let machines = machines
+                    .iter()
+                    .map(|(id, state)| (id.to_string(), state.to_string()))
+                    .collect();
+                (app.to_string(), machines)
+            })
+            .collect(),
+    );
+    let stub = Arc::new(FlyApi {
+        apps,
+        ..FlyApi::default()
+    });
+    let router = Router::new()
+        .route("/v1/apps", get(list_apps))
+        .route("/v1/apps/:app", get(get_app))
+        .route(
+            "/v1/apps/:app/machines/:machine_id",
+            get(get_machine).delete(delete_machine),
+        )
+        .route(
+            "/v1/apps/:app/machines/:machine_id/:action",
+            post(machine_action),
+        )
+        .route("/api/v1/apps/:app/logs", get(app_logs))
+        .with_state(stub.clone());
+    (common::serve(router).await, stub)
+}
+
+fn adapter(url: &str) -> FlyIoAdapter {
+    let config = engine::Config {
+        fly_api_token: Some("fly-test".to_string()),
+        fly_api_url: Some(url.to_string()),
+        ..common::config()
+    };
+    FlyIoAdapter::new(&config).unwrap()
+}
+
+/// A running Fly.io deployment of a new agent, recorded with `provider_id`.
+async fn insert_deployment(db: &Database, provider_id: &str) -> Deployment {
+    let agent = common::insert_agent(db, "scout").await;
+    let deployment = Deployment {
+        id: Uuid::new_v4(),
+        agent_id: agent.id,
+        agent_ids: None,
+        provider: VpsProvider::FlyIo,
+        region: None,
+        status: DeploymentStatus::Running,
+        provider_id: Some(provider_id.to_string()),
+        endpoint: Some("https://clawguild-scout.fly.dev".to_string()),
+        gateway_url: Some("https://clawguild-scout.fly.dev".to_string()),
+        volume_id: None,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    };
+    DeploymentRepository::new(db.db())
+        .create(&deployment)
+        .await
+        .unwrap();
+    let agent_repo = AgentRepository::new(db.db());
+    agent_repo
+        .update_deployment_id(agent.id, Some(deployment.id))
+        .await
+        .unwrap();
+    agent_repo
+        .update_status(agent.id, AgentStatus::Running)
+        .await
+        .unwrap();
+    deployment
+}
+
+fn reconciler(db: &Database, url: &str) -> DeploymentReconciler {
+    let adapters = VpsAdapters::default().with_provider(VpsProvider::FlyIo, Arc::new(adapter(url)));
+    DeploymentReconciler::new(db.clone(), adapters)
+}
+
+async fn deployment_status(db: &Database, id: Uuid) -> DeploymentStatus {
+    DeploymentRepository::new(db.db())
+        .get_by_id(id)
+        .await
+        .unwrap()
+        .expect("deployment not found")
+        .status
+}
+
+#[tokio::test]
+async fn reconciler_finds_the_app_of_a_legacy_machine_id() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (url, stub) = stub_api(&[
+        ("unrelated", &[("m-legacy", "failed")]),
+        ("clawguild-scout", &[("m-legacy", "started")]),
+    ])
+    .await;
+    let deployment = insert_deployment(&db, "flyio-m-legacy").await;
+    let reconciler = reconciler(&db, &url);
+
+    let summary = reconciler.reconcile_once().await.unwrap();
+
+    assert_eq!((summary.checked, summary.lost, summary.errors), (1, 0, 0));
+    assert_eq!(
+        deployment_status(&db, deployment.id).await,
+        DeploymentStatus::Running
+    );
+    assert_eq!(
+        common::get_agent(&db, deployment.agent_id).await.status,
+        AgentStatus::Running
+    );
+
+    // Once the machine stops, the status is still read from its app
+    stub.apps
+        .lock()
+        .unwrap()
+        .get_mut("clawguild-scout")
+        .unwrap()
+        .insert("m-legacy".to_string(), "stopped".to_string());
+    reconciler.reconcile_once().await.unwrap();
+    assert_eq!(
+        deployment_status(&db, deployment.id).await,
+        DeploymentStatus::Stopped
+    );
+}
+
+#[tokio::test]
+async fn reconciler_does_not_mark_an_unresolvable_legacy_id_lost() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (url, _) = stub_api(&[("clawguild-builder", &[("m-other", "started")])]).await;
+    let deployment = insert_deployment(&db, "flyio-m-legacy").await;
+
+    let summary = reconciler(&db, &url).reconcile_once().await.unwrap();
+
+    assert_eq!((summary.lost, summary.errors), (0, 1));
+    assert_eq!(
+        deployment_status(&db, deployment.id).await,
+        DeploymentStatus::Running
+    );
+    assert_eq!(
+        common::get_agent(&db, deployment.agent_id).await.status,
+        AgentStatus::Running
+    );
+}
+
+#[tokio::test]
+async fn reconciler_marks_a_missing_machine_lost() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (url, _) = stub_api(&[("clawguild-scout", &[])]).await;
+    let deployment = insert_deployment(&db, "flyio-clawguild-scout/m-gone").await;
+
+    let summary = reconciler(&db, &url).reconcile_once().await.unwrap();
+
+    assert_eq!(summary.lost, 1);
+    assert_eq!(
+        deployment_status(&db, deployment.id).await,
+        DeploymentStatus::Lost
+    );
+    assert_eq!(
+        common::get_agent(&db, deployment.agent_id).await.status,
+        AgentStatus::Error
+    );
+}
+
+fn legacy_id() -> DeploymentId {
+    DeploymentId {
+        id: Uuid::new_v4(),
+        provider_id: "flyio-m-legacy".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn lifecycle_actions_on_a_legacy_id_target_the_machine_in_its_app() {
+    let (url, stub) = stub_api(&[("clawguild-scout", &[("m-legacy", "started")])]).await;
+    let adapter = adapter(&url);
+
+    adapter.stop_agent(&legacy_id()).await.unwrap();
+    adapter.start_agent(&legacy_id()).await.unwrap();
+
+    assert_eq!(
+        *stub.calls.lock().unwrap(),
+        [
+            "POST /v1/apps/clawguild-scout/machines/m-legacy/stop",
+            "POST /v1/apps/clawguild-scout/machines/m-legacy/start",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn destroying_a_legacy_id_deletes_its_machine() {
+    let (url, stub) = stub_api(&[("clawguild-scout", &[("m-legacy", "started")])]).await;
+
+    adapter(&url).destroy_agent(&legacy_id()).await.unwrap();
+
+    assert_eq!(
+        *stub.calls.lock().unwrap(),
+        ["DELETE /v1/apps/clawguild-scout/machines/m-legacy"]
+    );
+    assert!(stub.apps.lock().unwrap()["clawguild-scout"].is_empty());
+}
+
+#[tokio::test]
+async fn destroy_fails_when_the_machine_is_not_deleted() {
+    let (url, stub) = stub_api(&[("clawguild-scout", &[("m-1", "started")])]).await;
+    *stub.delete_error.lock().unwrap() = Some(StatusCode::INTERNAL_SERVER_ERROR);
+    let deployment_id = DeploymentId {
+        id: Uuid::new_v4(),
+        provider_id: "flyio-clawguild-scout/m-1".to_string(),
+    };
+
+    let error = adapter(&url)
+        .destroy_agent(&deployment_id)
+        .await
+        .unwrap_err();
+
+    assert!(error
+        .to_string()
+        .contains("Failed to destroy Fly.io machine m-1"));
+
+    // A machine that is already gone counts as destroyed
+    *stub.delete_error.lock().unwrap() = Some(StatusCode::NOT_FOUND);
+    adapter(&url).destroy_agent(&deployment_id).await.unwrap();
+}
+
+async fn streamed_messages(adapter: &FlyIoAdapter, options: LogStreamOptions) -> Vec<String> {
+    let deployment_id = DeploymentId {
+        id: Uuid::new_v4(),
+        provider_id: "flyio-clawguild-scout/m-1".to_string(),
+    };
+    adapter
+        .stream_logs(&deployment_id, options)
+        .await
+        .unwrap()
+        .map_ok(|entry| entry.message)
+        .try_collect()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn stream_logs_starts_from_the_requested_tail() {
+    let (url, _) = stub_api(&[("clawguild-scout", &[("m-1", "started")])]).await;
+    let adapter = adapter(&url);
+
+    let tail = LogStreamOptions {
+        tail: Some(2),
+        ..LogStreamOptions::default()
+    };
+    assert_eq!(
+        streamed_messages(&adapter, tail).await,
+        ["clawguild-scout line 4", "clawguild-scout line 5"]
+    );
+
+    let since = LogStreamOptions {
+        since: Some(Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 2).unwrap()),
+        ..LogStreamOptions::default()
+    };
+    assert_eq!(streamed_messages(&adapter, since).await.len(), 4);
+}
@@ -0,0 +1,209 @@
+//! Splitting tasks into routed subtasks, and queuing the ones no slave can take until
+//! `dispatch_queued` finds room. Needs `TEST_DATABASE_URL`; see `common`.
+
+mod common;
+
+use engine::coordinator::master::MasterCoordinator;
+use engine::coordinator::transport::{InProcessTransport, MessageKind, Transports};
+use engine::models::{Agent, AgentStatus, PlannerConfig, Task, TaskStatus, Team, TransportConfig};
+use engine::storage::repositories::{AgentRepository, TaskRepository};
+use engine::Database;
+use uuid::Uuid;
+
+/// A running slave with `skills` that takes one task at a time.
+async fn insert_slave(db: &Database, name: &str, skills: &[&str]) -> Agent {
+    let mut agent = common::agent(name);
+    agent.status = AgentStatus::Running;
+    agent.skills = skills.iter().map(|skill| skill.to_string()).collect();
+    agent.max_concurrent_tasks = Some(1);
+    AgentRepository::new(db.db())
+        .create(&agent)
+        .await
+        .expect("failed to insert slave");
+    agent
+}
+
+/// A team splitting tasks by line and coordinating in process.
+async fn insert_team(db: &Database, slaves: &[&Agent]) -> Team {
+    let mut team = common::team(
+        Uuid::new_v4(),
+        slaves.iter().map(|slave| slave.id).collect(),
+    );
+    team.settings.planner = PlannerConfig::Lines;
+    team.settings.transport = TransportConfig::InProcess;
+    common::insert_team(db, team).await
+}
+
+fn master(db: &Database) -> (MasterCoordinator, InProcessTransport) {
+    let in_process = InProcessTransport::new();
+    let transports = Transports::new(None, None, in_process.clone());
+    (MasterCoordinator::new(db.clone(), transports), in_process)
+}
+
+/// Takes up the slave's only slot with a task of its own.
+async fn occupy(db: &Database, team: &Team, slave: &Agent) -> Task {
+    let mut task = common::task(team.id, TaskStatus::InProgress, vec![]);
+    task.assigned_to = Some(slave.id);
+    common::insert_task(db, task).await
+}
+
+async fn subtasks_of(db: &Database, parent: &Task) -> Vec<Task> {
+    let mut subtasks = TaskRepository::new(db.db())
+        .get_by_parent_id(parent.id)
+        .await
+        .unwrap();
+    subtasks.sort_by_key(|subtask| subtask.created_at);
+    subtasks
+}
+
+#[tokio::test]
+async fn tasks_are_split_and_routed_by_skill() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let writer = insert_slave(&db, "writer", &["docs"]).await;
+    let coder = insert_slave(&db, "coder", &["rust"]).await;
+    let team = insert_team(&db, &[&writer, &coder]).await;
+    let mut task = common::task(team.id, TaskStatus::Pending, vec![]);
+    task.description = "- fix the rust parser\n- write the #docs".to_string();
+    let task = common::insert_task(&db, task).await;
+    let (master, in_process) = master(&db);
+
+    let subtasks = master.delegate_task(&team, &task).await.unwrap();
+
+    assert_eq!(subtasks.len(), 2);
+    assert_eq!(subtasks[0].description, "Subtask: fix the rust parser");
+    assert_eq!(subtasks[0].assigned_to, Some(coder.id));
+    assert_eq!(subtasks[1].description, "Subtask: write the #docs");
+    assert_eq!(subtasks[1].assigned_to, Some(writer.id));
+    for subtask in &subtasks {
+        assert_eq!(subtask.parent_task_id, Some(task.id));
+        assert_eq!(subtask.status, TaskStatus::Pending);
+        assert_eq!(subtask.attempts, 1);
+        assert!(subtask
+            .routing_reason
+            .as_deref()
+            .unwrap()
+            .starts_with("matched skills"));
+    }
+    assert_eq!(subtasks_of(&db, &task).await.len(), 2);
+
+    let kinds: Vec<_> = in_process
+        .messages()
+        .iter()
+        .map(|message| message.kind)
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            MessageKind::Order,
+            MessageKind::SlaveMessage,
+            MessageKind::SlaveMessage
+        ]
+    );
+}
+
+#[tokio::test]
+async fn tasks_the_planner_does_not_split_stay_with_the_master() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let slave = insert_slave(&db, "coder", &["rust"]).await;
+    let team = insert_team(&db, &[&slave]).await;
+    let mut task = common::task(team.id, TaskStatus::Pending, vec![]);
+    task.description = "fix the rust parser".to_string();
+    let task = common::insert_task(&db, task).await;
+    let (master, in_process) = master(&db);
+
+    let subtasks = master.delegate_task(&team, &task).await.unwrap();
+
+    assert!(subtasks.is_empty());
+    assert!(subtasks_of(&db, &task).await.is_empty());
+    assert_eq!(in_process.messages().len(), 1);
+}
+
+#[tokio::test]
+async fn subtasks_are_queued_while_every_slave_is_at_its_limit() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let writer = insert_slave(&db, "writer", &["docs"]).await;
+    let coder = insert_slave(&db, "coder", &["rust"]).await;
+    let team = insert_team(&db, &[&writer, &coder]).await;
+    occupy(&db, &team, &writer).await;
+    occupy(&db, &team, &coder).await;
+    let mut task = common::task(team.id, TaskStatus::Pending, vec![]);
+    task.description = "- fix the rust parser\n- write the docs\n- tag a release".to_string();
+    let task = common::insert_task(&db, task).await;
+    let (master, in_process) = master(&db);
+
+    let subtasks = master.delegate_task(&team, &task).await.unwrap();
+
+    assert_eq!(subtasks.len(), 3);
+    for subtask in &subtasks {
+        assert_eq!(subtask.status, TaskStatus::Pending);
+        assert_eq!(subtask.assigned_to, None);
+        assert_eq!(
+            subtask.routing_reason.as_deref(),
+            Some("queued: no slave available (0 not running, 2 at their task limit)")
+        );
+    }
+    // Only the order went out; nobody was told about the queued subtasks
+    assert_eq!(in_process.messages().len(), 1);
+    assert!(master.dispatch_queued(&team).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn queued_subtasks_are_dispatched_oldest_first_as_slots_free_up() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let writer = insert_slave(&db, "writer", &["docs"]).await;
+    let coder = insert_slave(&db, "coder", &["rust"]).await;
+    let team = insert_team(&db, &[&writer, &coder]).await;
+    let writer_task = occupy(&db, &team, &writer).await;
+    let coder_task = occupy(&db, &team, &coder).await;
+    let mut task = common::task(team.id, TaskStatus::Pending, vec![]);
+    task.description = "- fix the rust parser\n- write the docs\n- tag a release".to_string();
+    let task = common::insert_task(&db, task).await;
+    let (master, in_process) = master(&db);
+    master.delegate_task(&team, &task).await.unwrap();
+    let queued = subtasks_of(&db, &task).await;
+    let task_repo = TaskRepository::new(db.db());
+
+    // The writer frees up first and takes the oldest subtask, even though it's the coder's
+    task_repo
+        .finish_open(writer_task.id, TaskStatus::Completed, "done")
+        .await
+        .unwrap();
+    let dispatched = master.dispatch_queued(&team).await.unwrap();
+
+    assert_eq!(dispatched.len(), 1);
+    assert_eq!(dispatched[0].id, queued[0].id);
+    assert_eq!(dispatched[0].assigned_to, Some(writer.id));
+    assert_eq!(dispatched[0].status, TaskStatus::Pending);
+    assert_eq!(dispatched[0].attempts, 1);
+    assert!(dispatched[0]
+        .routing_reason
+        .as_deref()
+        .unwrap()
+        .starts_with("no skill or responsibility match"));
+    assert_eq!(
+        in_process.messages().last().unwrap().kind,
+        MessageKind::SlaveMessage
+    );
+
+    task_repo
+        .finish_open(coder_task.id, TaskStatus::Completed, "done")
+        .await
+        .unwrap();
+    let dispatched = master.dispatch_queued(&team).await.unwrap();
+
+    assert_eq!(dispatched.len(), 1);
+    assert_eq!(dispatched[0].id, queued[1].id);
+    assert_eq!(dispatched[0].assigned_to, Some(coder.id));
+    let last = common::get_task(&db, queued[2].id).await;
+    assert_eq!(last.status, TaskStatus::Pending);
+    assert_eq!(last.assigned_to, None);
+    assert!(master.dispatch_queued(&team).await.unwrap().is_empty());
+}
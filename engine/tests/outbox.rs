@@ -0,0 +1,383 @@
+//! `DiscordOutbox` delivering to a stub Discord API: chunking, rate limits, retries and
+//! closing task threads.
+//! Needs `TEST_DATABASE_URL`; see `common`.
+
+mod common;
+
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{patch, post};
+use axum::{Json, Router};
+use engine::coordinator::discord::{DiscordClient, DiscordConfig};
+use engine::coordinator::outbox::{DiscordOutbox, MAX_MESSAGE_CHARS};
+use engine::coordinator::transport::CoordinationTransport;
+use engine::models::TaskStatus;
+use engine::Database;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// A message as Discord received it.
+#[derive(Debug, Clone)]
+struct Post {
+    channel_id: String,
+    /// `content` of a JSON post, or the whole body of a multipart one.
+    body: String,
+    multipart: bool,
+}
+
+#[derive(Default)]
+struct DiscordApi {
+    posts: Mutex<Vec<Post>>,
+    /// Statuses to answer the next requests with before accepting any.
+    failures: Mutex<VecDeque<StatusCode>>,
+    /// `(channel_id, body)` of each thread created.
+    threads: Mutex<Vec<(String, Value)>>,
+    /// Threads archived, each with how many messages had been posted by then.
+    archived: Mutex<Vec<(String, usize)>>,
+}
+
+type Stub = Arc<DiscordApi>;
+
+fn authorized(headers: &HeaderMap) -> bool {
+    headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        == Some("Bot test-token")
+}
+
+async fn create_message(
+    State(stub): State<Stub>,
+    Path(channel_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    if let Some(status) = stub.failures.lock().unwrap().pop_front() {
+        return match status {
+            StatusCode::TOO_MANY_REQUESTS => (
+                status,
+                Json(json!({ "retry_after": 0.05, "global": false })),
+            )
+                .into_response(),
+            status => (status, "nope").into_response(),
+        };
+    }
+
+    let multipart = headers
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("multipart/form-data"));
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    let body = if multipart {
+        body
+    } else {
+        let payload: Value = serde_json::from_str(&body).unwrap();
+        payload["content"].as_str().unwrap().to_string()
+    };
+    stub.posts.lock().unwrap().push(Post {
+        channel_id,
+        body,
+        multipart,
+    });
+    Json(json!({ "id": "1" })).into_response()
+}
+
+/// Creates thread 777 in whichever channel it is asked to.
+async fn create_thread(
+    State(stub): State<Stub>,
+    Path(channel_id): Path<String>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    stub.threads.lock().unwrap().push((channel_id, body));
+    Json(json!({ "id": "777" })).into_response()
+}
+
+async fn modify_channel(
+    State(stub): State<Stub>,
+    Path(channel_id): Path<String>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    if !authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    assert_eq!(body, json!({ "archived": true }));
+    let posted = stub.posts.lock().unwrap().len();
+    stub.archived
+        .lock()
+        .unwrap()
+        .push((channel_id.clone(), posted));
+    Json(json!({ "id": channel_id })).into_response()
+}
+
+/// Starts a stub Discord API and returns its URL.
+async fn discord_api() -> (Stub, String) {
+    let stub = Stub::default();
+    let router = Router::new()
+        .route("/channels/:channel_id/messages", post(create_message))
+        .route("/channels/:channel_id/threads", post(create_thread))
+        .route("/channels/:channel_id", patch(modify_channel))
+        .with_state(stub.clone());
+    (stub, common::serve(router).await)
+}
+
+/// Starts a stub Discord API and an outbox delivering to it.
+async fn outbox(db: &Database) -> (Stub, DiscordOutbox, JoinHandle<()>) {
+    let (stub, url) = discord_api().await;
+    let outbox = DiscordOutbox::new(
+        db.clone(),
+        reqwest::Client::new(),
+        "test-token".to_string(),
+        url,
+    );
+    let sender = outbox.clone().start();
+    (stub, outbox, sender)
+}
+
+/// `(status, attempts, last_error)` of the outbox entry with `content`.
+async fn entry(db: &Database, content: &str) -> (String, i32, Option<String>) {
+    sqlx::query_as("SELECT status, attempts, last_error FROM discord_outbox WHERE content = $1")
+        .bind(content)
+        .fetch_one(&db.db())
+        .await
+        .unwrap()
+}
+
+/// Waits for the entry with `content` to be marked `status`.
+async fn wait_for(db: &Database, content: &str, status: &str) -> (String, i32, Option<String>) {
+    for _ in 0..300 {
+        let entry = entry(db, content).await;
+        if entry.0 == status {
+            return entry;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!(
+        "outbox entry never became {}: {:?}",
+        status,
+        entry(db, content).await
+    );
+}
+
+fn posts(stub: &Stub) -> Vec<Post> {
+    stub.posts.lock().unwrap().clone()
+}
+
+#[tokio::test]
+async fn long_messages_are_split_at_line_breaks() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, outbox, sender) = outbox(&db).await;
+    let line = format!("{}\n", "x".repeat(99));
+    let content = line.repeat(30);
+
+    outbox.enqueue(42, &content, None).await.unwrap();
+    wait_for(&db, &content, "sent").await;
+
+    let posts = posts(&stub);
+    assert_eq!(posts.len(), 2);
+    assert!(posts
+        .iter()
+        .all(|post| post.channel_id == "42" && !post.multipart));
+    assert_eq!(posts[0].body, line.repeat(20));
+    assert_eq!(posts[1].body, line.repeat(10));
+    sender.abort();
+}
+
+#[tokio::test]
+async fn very_long_messages_are_attached_with_a_preview() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, outbox, sender) = outbox(&db).await;
+    let content = format!("{}\n", "y".repeat(99)).repeat(120);
+
+    outbox.enqueue(42, &content, None).await.unwrap();
+    wait_for(&db, &content, "sent").await;
+
+    let posts = posts(&stub);
+    assert_eq!(posts.len(), 1);
+    assert!(posts[0].multipart);
+    assert!(posts[0].body.contains("(full message attached)"));
+    assert!(posts[0].body.contains("filename=\"message.txt\""));
+    assert!(posts[0].body.contains(&content));
+    assert!(content.chars().count() > 5 * MAX_MESSAGE_CHARS);
+    sender.abort();
+}
+
+#[tokio::test]
+async fn rate_limited_messages_wait_without_counting_as_failures() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, outbox, sender) = outbox(&db).await;
+    stub.failures
+        .lock()
+        .unwrap()
+        .push_back(StatusCode::TOO_MANY_REQUESTS);
+
+    outbox.enqueue(42, "first", None).await.unwrap();
+    // The 429 leaves the message pending; queuing another wakes the sender once the
+    // rate limit has passed
+    for _ in 0..300 {
+        if stub.failures.lock().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(posts(&stub).is_empty());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    outbox.enqueue(42, "second", None).await.unwrap();
+
+    assert_eq!(
+        wait_for(&db, "first", "sent").await,
+        ("sent".to_string(), 0, None)
+    );
+    wait_for(&db, "second", "sent").await;
+    let bodies: Vec<_> = posts(&stub).into_iter().map(|post| post.body).collect();
+    assert_eq!(bodies, ["first", "second"]);
+    sender.abort();
+}
+
+#[tokio::test]
+async fn server_errors_are_retried_with_backoff() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, outbox, sender) = outbox(&db).await;
+    stub.failures
+        .lock()
+        .unwrap()
+        .push_back(StatusCode::BAD_GATEWAY);
+
+    outbox.enqueue(42, "hello", None).await.unwrap();
+    let mut retrying = entry(&db, "hello").await;
+    for _ in 0..300 {
+        if retrying.1 > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        retrying = entry(&db, "hello").await;
+    }
+
+    assert_eq!(retrying.0, "pending");
+    assert_eq!(retrying.1, 1);
+    assert!(retrying.2.unwrap().contains("502"));
+    let backed_off: bool = sqlx::query_scalar(
+        "SELECT next_attempt_at > now() + interval '1 second' FROM discord_outbox WHERE content = 'hello'",
+    )
+    .fetch_one(&db.db())
+    .await
+    .unwrap();
+    assert!(backed_off);
+
+    // Bring the retry forward rather than waiting out the backoff
+    sqlx::query("UPDATE discord_outbox SET next_attempt_at = now() WHERE content = 'hello'")
+        .execute(&db.db())
+        .await
+        .unwrap();
+    outbox.enqueue(42, "wake up", None).await.unwrap();
+
+    let (_, attempts, _) = wait_for(&db, "hello", "sent").await;
+    assert_eq!(attempts, 1);
+    assert_eq!(posts(&stub)[0].body, "hello");
+    sender.abort();
+}
+
+#[tokio::test]
+async fn client_errors_give_up_right_away() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, outbox, sender) = outbox(&db).await;
+    stub.failures
+        .lock()
+        .unwrap()
+        .push_back(StatusCode::FORBIDDEN);
+
+    outbox.enqueue(42, "hello", None).await.unwrap();
+
+    let (_, attempts, error) = wait_for(&db, "hello", "failed").await;
+    assert_eq!(attempts, 1);
+    assert!(error.unwrap().contains("403"));
+    assert!(posts(&stub).is_empty());
+    sender.abort();
+}
+
+#[tokio::test]
+async fn task_threads_get_the_outcome_and_are_then_archived() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, url) = discord_api().await;
+    let discord = DiscordClient::new(
+        db.clone(),
+        DiscordConfig {
+            bot_token: "test-token".to_string(),
+            api_url: url.clone(),
+        },
+    )
+    .await
+    .unwrap();
+    let mut team = common::team(Uuid::new_v4(), Vec::new());
+    team.discord_channels.master_orders = "300".to_string();
+    let mut task = common::task(team.id, TaskStatus::InProgress, vec![]);
+
+    let thread_id = discord.open_thread(&team, &task).await.unwrap();
+
+    assert_eq!(thread_id.as_deref(), Some("777"));
+    let threads = stub.threads.lock().unwrap().clone();
+    assert_eq!(threads.len(), 1);
+    let (channel_id, body) = &threads[0];
+    assert_eq!(channel_id, "300");
+    assert_eq!(
+        body["name"],
+        format!("Task {}: test task", &task.id.to_string()[..8])
+    );
+    assert_eq!(body["type"], 11);
+
+    task.thread_id = thread_id;
+    task.status = TaskStatus::Completed;
+    task.result = Some("all done".to_string());
+    discord.close_thread(&team, &task).await.unwrap();
+    // Nothing goes out until the outbox sends it
+    assert!(stub.archived.lock().unwrap().is_empty());
+
+    let sender = DiscordOutbox::new(
+        db.clone(),
+        reqwest::Client::new(),
+        "test-token".to_string(),
+        url,
+    )
+    .start();
+    for _ in 0..300 {
+        if !stub.archived.lock().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let posts = posts(&stub);
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].channel_id, "777");
+    assert!(posts[0].body.contains("**Task completed**"));
+    assert!(posts[0].body.contains("all done"));
+    assert_eq!(
+        stub.archived.lock().unwrap().clone(),
+        [("777".to_string(), 1)]
+    );
+    sender.abort();
+}
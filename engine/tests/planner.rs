@@ -0,0 +1,135 @@
+mod common;
+
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::{Json, Router};
+use engine::coordinator::planner::{ModelPlanner, PlannedSubtask, TaskPlanner};
+use engine::models::{ModelPlannerConfig, TaskStatus};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// Requests the stub endpoint received, as (authorization header, body).
+type Received = Arc<Mutex<Vec<(Option<String>, Value)>>>;
+
+/// A chat completions endpoint that answers every request with `reply` as the message.
+async fn stub_endpoint(status: StatusCode, reply: &str) -> (String, Received) {
+    let received = Received::default();
+    let reply = reply.to_string();
+    let router = Router::new()
+        .route(
+            "/v1/chat/completions",
+            post(
+                move |State(received): State<Received>,
+                      headers: HeaderMap,
+                      Json(body): Json<Value>| {
+                    let reply = reply.clone();
+                    async move {
+                        let authorization = headers
+                            .get("authorization")
+                            .and_then(|value| value.to_str().ok())
+                            .map(str::to_string);
+                        received.lock().unwrap().push((authorization, body));
+                        let completion = json!({
+                            "choices": [{ "message": { "role": "assistant", "content": reply } }],
+                        });
+                        (status, Json(completion))
+                    }
+                },
+            ),
+        )
+        .with_state(received.clone());
+    let url = common::serve(router).await;
+    (format!("{}/v1/", url), received)
+}
+
+fn planner(endpoint: String, api_key: Option<&str>, max_subtasks: Option<usize>) -> ModelPlanner {
+    ModelPlanner::new(
+        reqwest::Client::new(),
+        ModelPlannerConfig {
+            endpoint,
+            model: "planner-model".to_string(),
+            api_key: api_key.map(str::to_string),
+            max_subtasks,
+        },
+    )
+}
+
+fn subtask(description: &str, skills: &[&str]) -> PlannedSubtask {
+    PlannedSubtask {
+        description: description.to_string(),
+        skills: skills.iter().map(|skill| skill.to_string()).collect(),
+    }
+}
+
+#[tokio::test]
+async fn model_planner_sends_the_task_and_parses_the_reply() {
+    let reply = r#"Here is the plan:
+```json
+[{"description": "write the parser", "skills": ["rust"]}, "write the docs"]
+```"#;
+    let (endpoint, received) = stub_endpoint(StatusCode::OK, reply).await;
+    let mut task = common::task(Uuid::new_v4(), TaskStatus::Pending, Vec::new());
+    task.description = "build the parser and document it".to_string();
+
+    let subtasks = planner(endpoint, Some("sk-planner"), None)
+        .plan(&task)
+        .await
+        .expect("plan failed");
+
+    assert_eq!(
+        subtasks,
+        vec![
+            subtask("write the parser", &["rust"]),
+            subtask("write the docs", &[]),
+        ]
+    );
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    let (authorization, body) = &received[0];
+    assert_eq!(authorization.as_deref(), Some("Bearer sk-planner"));
+    assert_eq!(body["model"], "planner-model");
+    assert_eq!(body["messages"][1]["role"], "user");
+    assert_eq!(body["messages"][1]["content"], task.description);
+}
+
+#[tokio::test]
+async fn model_planner_caps_the_plan_at_max_subtasks() {
+    let (endpoint, received) = stub_endpoint(StatusCode::OK, r#"["one", "two", "three"]"#).await;
+    let task = common::task(Uuid::new_v4(), TaskStatus::Pending, Vec::new());
+
+    let subtasks = planner(endpoint, None, Some(2))
+        .plan(&task)
+        .await
+        .expect("plan failed");
+
+    assert_eq!(subtasks, vec![subtask("one", &[]), subtask("two", &[])]);
+    assert_eq!(received.lock().unwrap()[0].0, None);
+}
+
+#[tokio::test]
+async fn model_planner_reports_endpoint_errors() {
+    let (endpoint, _) = stub_endpoint(StatusCode::UNAUTHORIZED, "").await;
+    let task = common::task(Uuid::new_v4(), TaskStatus::Pending, Vec::new());
+
+    let error = planner(endpoint, Some("sk-wrong"), None)
+        .plan(&task)
+        .await
+        .expect_err("plan should fail");
+
+    assert!(error.to_string().contains("401"), "{}", error);
+}
+
+#[tokio::test]
+async fn model_planner_rejects_replies_that_are_not_a_plan() {
+    let (endpoint, _) = stub_endpoint(StatusCode::OK, "I can't split this.").await;
+    let task = common::task(Uuid::new_v4(), TaskStatus::Pending, Vec::new());
+
+    let error = planner(endpoint, None, None)
+        .plan(&task)
+        .await
+        .expect_err("plan should fail");
+
+    assert!(error.to_string().contains("invalid plan"), "{}", error);
+}
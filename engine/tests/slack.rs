@@ -0,0 +1,383 @@
+//! `SlackClient` against a stub Web API: task threads, rate limits and `!task-` commands
+//! received as Events API callbacks. Needs `TEST_DATABASE_URL`; see `common`.
+
+mod common;
+
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use engine::coordinator::slack::{SlackClient, SlackConfig};
+use engine::coordinator::transport::{CommandReceiver, CoordinationTransport};
+use engine::models::{SlackChannels, Task, TaskStatus, Team};
+use engine::storage::repositories::TaskRepository;
+use engine::Database;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+#[derive(Default)]
+struct SlackApi {
+    /// `chat.postMessage` bodies, in the order they were accepted.
+    posts: Mutex<Vec<Value>>,
+    /// Answers this many `chat.postMessage` calls with 429 before accepting any.
+    rate_limited: Mutex<u32>,
+}
+
+type Stub = Arc<SlackApi>;
+
+async fn auth_test() -> Json<Value> {
+    Json(json!({ "ok": true, "user_id": "UBOT" }))
+}
+
+async fn post_message(
+    State(stub): State<Stub>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    if headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        != Some("Bearer xoxb-test")
+    {
+        return Json(json!({ "ok": false, "error": "invalid_auth" })).into_response();
+    }
+    {
+        let mut rate_limited = stub.rate_limited.lock().unwrap();
+        if *rate_limited > 0 {
+            *rate_limited -= 1;
+            return (StatusCode::TOO_MANY_REQUESTS, [("retry-after", "0")]).into_response();
+        }
+    }
+
+    let mut posts = stub.posts.lock().unwrap();
+    posts.push(body);
+    Json(json!({ "ok": true, "ts": format!("{}.000100", posts.len()) })).into_response()
+}
+
+async fn slack_api() -> (Stub, String) {
+    let stub = Stub::default();
+    let router = Router::new()
+        .route("/auth.test", post(auth_test))
+        .route("/chat.postMessage", post(post_message))
+        .with_state(stub.clone());
+    let url = common::serve(router).await;
+    (stub, url)
+}
+
+fn client(db: &Database, api_url: String) -> SlackClient {
+    SlackClient::new(
+        db.clone(),
+        SlackConfig {
+            bot_token: "xoxb-test".to_string(),
+            app_token: None,
+            signing_secret: None,
+            api_url,
+        },
+    )
+}
+
+/// A team coordinating in Slack channels `C-LOGS`, `C-SLAVES` and `C-ORDERS`.
+async fn slack_team(db: &Database, operators: &[&str]) -> Team {
+    let mut team = common::team(Uuid::new_v4(), Vec::new());
+    team.slack_channels = Some(SlackChannels {
+        coordination_logs: "C-LOGS".to_string(),
+        slave_communication: "C-SLAVES".to_string(),
+        master_orders: "C-ORDERS".to_string(),
+    });
+    team.settings.slack_operator_user_ids = operators.iter().map(|user| user.to_string()).collect();
+    common::insert_team(db, team).await
+}
+
+/// Opens the task's thread and stores it, as the coordinator does for new tasks.
+async fn open_thread(db: &Database, slack: &SlackClient, team: &Team, task: Task) -> Task {
+    let thread_id = slack
+        .open_thread(team, &task)
+        .await
+        .unwrap()
+        .expect("Slack threads have an id");
+    TaskRepository::new(db.db())
+        .set_thread(task.id, &thread_id)
+        .await
+        .unwrap();
+    common::get_task(db, task.id).await
+}
+
+fn message(channel: &str, user: &str, text: &str, thread_ts: Option<&str>) -> Vec<u8> {
+    serde_json::to_vec(&json!({
+        "type": "event_callback",
+        "event": {
+            "type": "message",
+            "user": user,
+            "channel": channel,
+            "text": text,
+            "ts": "99.000100",
+            "thread_ts": thread_ts,
+        }
+    }))
+    .unwrap()
+}
+
+/// Waits for the stub to have received `count` posts and returns them.
+async fn wait_for_posts(stub: &Stub, count: usize) -> Vec<Value> {
+    for _ in 0..200 {
+        let posts = stub.posts.lock().unwrap().clone();
+        if posts.len() >= count {
+            return posts;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("expected {} Slack posts", count);
+}
+
+#[tokio::test]
+async fn task_messages_go_to_the_task_thread() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, url) = slack_api().await;
+    let slack = client(&db, url);
+    let team = slack_team(&db, &[]).await;
+    let task = common::insert_task(&db, common::task(team.id, TaskStatus::Pending, vec![])).await;
+
+    let task = open_thread(&db, &slack, &team, task).await;
+    assert_eq!(task.thread_id.as_deref(), Some("1.000100"));
+
+    slack.send_order(&team, &task, "do it").await.unwrap();
+    slack.log(&team, None, "team update").await.unwrap();
+
+    let posts = stub.posts.lock().unwrap().clone();
+    assert_eq!(posts.len(), 3);
+    assert_eq!(posts[0]["channel"], "C-ORDERS");
+    assert!(posts[0].get("thread_ts").is_none());
+    assert_eq!(posts[1]["channel"], "C-ORDERS");
+    assert_eq!(posts[1]["thread_ts"], "1.000100");
+    assert_eq!(posts[1]["text"], "👑 [MASTER] do it");
+    assert_eq!(posts[2]["channel"], "C-LOGS");
+    assert!(posts[2].get("thread_ts").is_none());
+}
+
+#[tokio::test]
+async fn closing_a_thread_posts_the_outcome_as_mrkdwn() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, url) = slack_api().await;
+    let slack = client(&db, url);
+    let team = slack_team(&db, &[]).await;
+    let task = common::insert_task(&db, common::task(team.id, TaskStatus::Pending, vec![])).await;
+    let mut task = open_thread(&db, &slack, &team, task).await;
+
+    task.status = TaskStatus::Completed;
+    task.result = Some("shipped <v2> & done".to_string());
+    slack.close_thread(&team, &task).await.unwrap();
+
+    let posts = stub.posts.lock().unwrap().clone();
+    assert_eq!(posts[1]["thread_ts"], "1.000100");
+    assert_eq!(
+        posts[1]["text"],
+        format!(
+            "*Task completed*\nTask ID: `{}`\nshipped &lt;v2&gt; &amp; done",
+            task.id
+        )
+    );
+}
+
+#[tokio::test]
+async fn rate_limited_posts_are_retried() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, url) = slack_api().await;
+    let slack = client(&db, url);
+    *stub.rate_limited.lock().unwrap() = 2;
+
+    let ts = slack.post_message("C-LOGS", "hello", None).await.unwrap();
+
+    assert_eq!(ts, "1.000100");
+    assert_eq!(stub.posts.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn posts_fail_once_the_retries_run_out() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, url) = slack_api().await;
+    let slack = client(&db, url);
+    *stub.rate_limited.lock().unwrap() = 3;
+
+    let error = slack
+        .post_message("C-LOGS", "hello", None)
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("429"), "{:#}", error);
+    assert!(stub.posts.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn slack_errors_in_ok_responses_are_reported() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (_stub, url) = slack_api().await;
+    let slack = SlackClient::new(
+        db.clone(),
+        SlackConfig {
+            bot_token: "xoxb-revoked".to_string(),
+            app_token: None,
+            signing_secret: None,
+            api_url: url,
+        },
+    );
+
+    let error = slack
+        .post_message("C-LOGS", "hello", None)
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("invalid_auth"), "{:#}", error);
+}
+
+#[tokio::test]
+async fn commands_in_the_task_thread_are_answered_there() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, url) = slack_api().await;
+    let slack = client(&db, url);
+    let (finished, _finished_rx) = mpsc::unbounded_channel();
+    slack
+        .start(CommandReceiver::new(db.clone(), finished))
+        .await
+        .unwrap();
+    let team = slack_team(&db, &["UOPS"]).await;
+    let task = common::insert_task(&db, common::task(team.id, TaskStatus::Pending, vec![])).await;
+    let task = open_thread(&db, &slack, &team, task).await;
+    let thread = task.thread_id.clone();
+
+    slack
+        .handle_callback(&message(
+            "C-ORDERS",
+            "UOPS",
+            &format!("!task-claim {}", task.id),
+            thread.as_deref(),
+        ))
+        .unwrap();
+
+    let posts = wait_for_posts(&stub, 2).await;
+    assert_eq!(posts[1]["channel"], "C-ORDERS");
+    assert_eq!(posts[1]["thread_ts"], "1.000100");
+    assert_eq!(posts[1]["text"], format!("Task {} claimed.", task.id));
+    assert_eq!(
+        common::get_task(&db, task.id).await.status,
+        TaskStatus::InProgress
+    );
+    // Sent in the thread, so the reply is the only post
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(stub.posts.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn only_operators_can_change_tasks() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, url) = slack_api().await;
+    let slack = client(&db, url);
+    let (finished, _finished_rx) = mpsc::unbounded_channel();
+    slack
+        .start(CommandReceiver::new(db.clone(), finished))
+        .await
+        .unwrap();
+    let team = slack_team(&db, &["UOPS"]).await;
+    let task = common::insert_task(&db, common::task(team.id, TaskStatus::Pending, vec![])).await;
+
+    slack
+        .handle_callback(&message(
+            "C-LOGS",
+            "USOMEONE",
+            &format!("!task-claim {}", task.id),
+            None,
+        ))
+        .unwrap();
+
+    let posts = wait_for_posts(&stub, 1).await;
+    assert_eq!(posts[0]["channel"], "C-LOGS");
+    assert_eq!(posts[0]["thread_ts"], "99.000100");
+    assert!(posts[0]["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("Not allowed: only the team's Slack operators"));
+    assert_eq!(
+        common::get_task(&db, task.id).await.status,
+        TaskStatus::Pending
+    );
+
+    // Commands from channels outside the team are ignored without a reply
+    slack
+        .handle_callback(&message(
+            "C-ELSEWHERE",
+            "UOPS",
+            &format!("!task-claim {}", task.id),
+            None,
+        ))
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(stub.posts.lock().unwrap().len(), 1);
+    assert_eq!(
+        common::get_task(&db, task.id).await.status,
+        TaskStatus::Pending
+    );
+}
+
+#[tokio::test]
+async fn teams_without_operators_cannot_change_tasks_from_slack() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let (stub, url) = slack_api().await;
+    let slack = client(&db, url);
+    let (finished, _finished_rx) = mpsc::unbounded_channel();
+    slack
+        .start(CommandReceiver::new(db.clone(), finished))
+        .await
+        .unwrap();
+    let team = slack_team(&db, &[]).await;
+    let task = common::insert_task(&db, common::task(team.id, TaskStatus::Pending, vec![])).await;
+
+    slack
+        .handle_callback(&message(
+            "C-SLAVES",
+            "USOMEONE",
+            &format!("!task-complete {}", task.id),
+            None,
+        ))
+        .unwrap();
+
+    let posts = wait_for_posts(&stub, 1).await;
+    assert_eq!(
+        posts[0]["text"],
+        "Not allowed: the team has no Slack operators, so tasks can't be changed from Slack."
+    );
+    assert_eq!(
+        common::get_task(&db, task.id).await.status,
+        TaskStatus::Pending
+    );
+
+    // Asking for the status is still fine
+    slack
+        .handle_callback(&message(
+            "C-SLAVES",
+            "USOMEONE",
+            &format!("!task-status {}", task.id),
+            None,
+        ))
+        .unwrap();
+    let posts = wait_for_posts(&stub, 2).await;
+    assert!(posts[1]["text"].as_str().unwrap().contains("pending"));
+}
@@ -0,0 +1,199 @@
+//! Timing out overdue tasks: retries on another slave, queuing and giving up. Deadlines are
+//! backdated rather than waited out. Needs `TEST_DATABASE_URL`; see `common`.
+
+mod common;
+
+use engine::coordinator::sweeper::TaskSweeper;
+use engine::coordinator::Coordinator;
+use engine::models::{Agent, AgentStatus, Task, TaskStatus, Team, TransportConfig};
+use engine::storage::repositories::{AgentRepository, TaskRepository};
+use engine::Database;
+use uuid::Uuid;
+
+async fn insert_slave(db: &Database, name: &str, status: AgentStatus) -> Agent {
+    let mut agent = common::agent(name);
+    agent.status = status;
+    AgentRepository::new(db.db())
+        .create(&agent)
+        .await
+        .expect("failed to insert slave");
+    agent
+}
+
+async fn insert_team(db: &Database, slaves: &[&Agent]) -> Team {
+    let mut team = common::team(
+        Uuid::new_v4(),
+        slaves.iter().map(|slave| slave.id).collect(),
+    );
+    team.settings.transport = TransportConfig::InProcess;
+    common::insert_team(db, team).await
+}
+
+/// Dispatches a task for `slave` with a minute per attempt.
+async fn dispatch(
+    db: &Database,
+    coordinator: &Coordinator,
+    mut task: Task,
+    slave: &Agent,
+    max_attempts: i32,
+) -> Task {
+    task.assigned_to = Some(slave.id);
+    task.timeout_secs = Some(60);
+    task.max_attempts = max_attempts;
+    let task = common::insert_task(db, task).await;
+    let task = coordinator.dispatch_task(&task).await.unwrap();
+    assert!(task.deadline_at.is_some());
+    task
+}
+
+async fn backdate_deadline(db: &Database, task: &Task) {
+    sqlx::query("UPDATE tasks SET deadline_at = now() - interval '1 second' WHERE id = $1")
+        .bind(task.id)
+        .execute(&db.db())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn overdue_tasks_are_retried_on_another_slave_then_given_up() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let coordinator = Coordinator::new(db.clone(), None, None).await.unwrap();
+    let sweeper = TaskSweeper::new(db.clone(), coordinator.clone());
+    let first = insert_slave(&db, "first", AgentStatus::Running).await;
+    let second = insert_slave(&db, "second", AgentStatus::Running).await;
+    let team = insert_team(&db, &[&first, &second]).await;
+    let task = common::task(team.id, TaskStatus::Pending, vec![]);
+    let task = dispatch(&db, &coordinator, task, &first, 2).await;
+    let dependent = common::insert_task(
+        &db,
+        common::task(team.id, TaskStatus::Blocked, vec![task.id]),
+    )
+    .await;
+
+    // Not overdue yet
+    let summary = sweeper.sweep_once().await.unwrap();
+    assert_eq!(summary.timed_out, 0);
+
+    backdate_deadline(&db, &task).await;
+    let summary = sweeper.sweep_once().await.unwrap();
+
+    assert_eq!((summary.timed_out, summary.retried), (1, 1));
+    let retried = common::get_task(&db, task.id).await;
+    assert_eq!(retried.status, TaskStatus::Pending);
+    assert_eq!(retried.assigned_to, Some(second.id));
+    assert_eq!(retried.attempts, 2);
+    assert!(retried.deadline_at.is_some());
+    assert!(retried
+        .routing_reason
+        .as_deref()
+        .unwrap()
+        .starts_with("retry: "));
+
+    backdate_deadline(&db, &retried).await;
+    let summary = sweeper.sweep_once().await.unwrap();
+
+    assert_eq!((summary.timed_out, summary.given_up), (1, 1));
+    let given_up = common::get_task(&db, task.id).await;
+    assert_eq!(given_up.status, TaskStatus::TimedOut);
+    assert_eq!(
+        given_up.result.as_deref(),
+        Some("timed out after 2 attempts")
+    );
+    assert_eq!(given_up.deadline_at, None);
+    assert_eq!(
+        common::get_task(&db, dependent.id).await.status,
+        TaskStatus::Cancelled
+    );
+
+    let attempts = TaskRepository::new(db.db())
+        .list_attempts(task.id)
+        .await
+        .unwrap();
+    let history: Vec<_> = attempts
+        .iter()
+        .map(|attempt| (attempt.attempt, attempt.agent_id, attempt.outcome.clone()))
+        .collect();
+    assert_eq!(
+        history,
+        [
+            (1, Some(first.id), Some(TaskStatus::TimedOut)),
+            (2, Some(second.id), Some(TaskStatus::TimedOut)),
+        ]
+    );
+
+    // The master heard about both timeouts
+    let reports = coordinator
+        .in_process()
+        .messages()
+        .into_iter()
+        .filter(|message| message.message.starts_with("**Task Timed Out**"))
+        .count();
+    assert_eq!(reports, 2);
+}
+
+#[tokio::test]
+async fn single_attempt_tasks_time_out_right_away() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let coordinator = Coordinator::new(db.clone(), None, None).await.unwrap();
+    let sweeper = TaskSweeper::new(db.clone(), coordinator.clone());
+    let slave = insert_slave(&db, "slave", AgentStatus::Running).await;
+    let team = insert_team(&db, &[&slave]).await;
+    let task = common::task(team.id, TaskStatus::Pending, vec![]);
+    let task = dispatch(&db, &coordinator, task, &slave, 1).await;
+
+    backdate_deadline(&db, &task).await;
+    let summary = sweeper.sweep_once().await.unwrap();
+
+    assert_eq!((summary.retried, summary.given_up), (0, 1));
+    let task = common::get_task(&db, task.id).await;
+    assert_eq!(task.status, TaskStatus::TimedOut);
+    assert_eq!(task.result.as_deref(), Some("timed out"));
+}
+
+#[tokio::test]
+async fn overdue_subtasks_are_queued_when_no_slave_is_available() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let coordinator = Coordinator::new(db.clone(), None, None).await.unwrap();
+    let sweeper = TaskSweeper::new(db.clone(), coordinator.clone());
+    let slave = insert_slave(&db, "slave", AgentStatus::Running).await;
+    let team = insert_team(&db, &[&slave]).await;
+    let parent =
+        common::insert_task(&db, common::task(team.id, TaskStatus::InProgress, vec![])).await;
+    let mut subtask = common::task(team.id, TaskStatus::Pending, vec![]);
+    subtask.parent_task_id = Some(parent.id);
+    let subtask = dispatch(&db, &coordinator, subtask, &slave, 3).await;
+    AgentRepository::new(db.db())
+        .update_status(slave.id, AgentStatus::Stopped)
+        .await
+        .unwrap();
+
+    backdate_deadline(&db, &subtask).await;
+    let summary = sweeper.sweep_once().await.unwrap();
+
+    assert_eq!((summary.timed_out, summary.queued), (1, 1));
+    let queued = common::get_task(&db, subtask.id).await;
+    assert_eq!(queued.status, TaskStatus::Pending);
+    assert_eq!(queued.assigned_to, None);
+    assert_eq!(queued.deadline_at, None);
+    assert_eq!(
+        queued.routing_reason.as_deref(),
+        Some("queued: retry waiting for an available slave")
+    );
+}
+
+#[test]
+fn timed_out_tasks_are_spelled_the_same_in_the_api_and_the_database() {
+    let status = TaskStatus::TimedOut;
+
+    assert_eq!(serde_json::to_value(&status).unwrap(), status.as_str());
+    assert_eq!(
+        serde_json::from_str::<TaskStatus>("\"timed_out\"").unwrap(),
+        status
+    );
+}
@@ -0,0 +1,199 @@
+//! `!task-` commands: parsing, and how each one moves a task. Tests touching tasks need
+//! `TEST_DATABASE_URL`; see `common`.
+
+mod common;
+
+use engine::coordinator::commands::{self, TaskCommand};
+use engine::coordinator::transport::MessageKind;
+use engine::coordinator::Coordinator;
+use engine::models::{TaskStatus, TransportConfig};
+use uuid::Uuid;
+
+#[test]
+fn commands_are_parsed_with_their_arguments() {
+    let id = Uuid::new_v4();
+
+    assert_eq!(
+        TaskCommand::parse(&format!("!task-progress {} 40% parsing done", id)),
+        Some(Ok(TaskCommand::Progress {
+            task_id: id,
+            percent: 40,
+            note: Some("parsing done".to_string()),
+        }))
+    );
+    assert_eq!(
+        TaskCommand::parse(&format!("  !task-CLAIM {}", id)),
+        Some(Ok(TaskCommand::Claim { task_id: id }))
+    );
+    assert_eq!(
+        TaskCommand::parse(&format!("!task-cancel {}", id)),
+        Some(Ok(TaskCommand::Cancel {
+            task_id: id,
+            reason: None,
+        }))
+    );
+    // Whichever transport the command came over
+    assert_eq!(
+        TaskCommand::parse(&format!("!task-complete {}", id)),
+        Some(Ok(TaskCommand::Complete {
+            task_id: id,
+            result: "Completed via task command".to_string(),
+        }))
+    );
+    assert_eq!(TaskCommand::parse("hello there"), None);
+}
+
+#[test]
+fn malformed_commands_get_their_usage() {
+    let id = Uuid::new_v4();
+
+    assert_eq!(
+        TaskCommand::parse(&format!("!task-progress {} 140", id)),
+        Some(Err(
+            "Usage: `!task-progress <task id> <percent> [note]`".to_string()
+        ))
+    );
+    assert_eq!(
+        TaskCommand::parse("!task-claim not-a-uuid"),
+        Some(Err("Usage: `!task-claim <task id>`".to_string()))
+    );
+    let unknown = TaskCommand::parse(&format!("!task-pause {}", id))
+        .unwrap()
+        .unwrap_err();
+    assert!(unknown.starts_with("Unknown task command. Available: `!task-complete"));
+}
+
+#[tokio::test]
+async fn claim_progress_and_fail_move_the_task_along() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let task = common::insert_task(
+        &db,
+        common::task(Uuid::new_v4(), TaskStatus::Pending, vec![]),
+    )
+    .await;
+    let task_id = task.id;
+
+    let claimed = commands::execute(&db, &TaskCommand::Claim { task_id })
+        .await
+        .unwrap();
+    assert_eq!(claimed.reply, format!("Task {} claimed.", task_id));
+    assert_eq!(claimed.task.unwrap().status, TaskStatus::InProgress);
+    assert!(!claimed.finished);
+
+    let progress = commands::execute(
+        &db,
+        &TaskCommand::Progress {
+            task_id,
+            percent: 60,
+            note: Some("halfway".to_string()),
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        progress.log.as_deref(),
+        Some(format!("Task `{}` is 60% done: halfway", task_id).as_str())
+    );
+    let status = commands::execute(&db, &TaskCommand::Status { task_id })
+        .await
+        .unwrap();
+    assert!(status.reply.contains("in_progress"));
+    assert!(status.reply.contains("Progress: 60% (halfway)"));
+    assert_eq!(status.log, None);
+
+    let failed = commands::execute(
+        &db,
+        &TaskCommand::Fail {
+            task_id,
+            reason: "upstream API is down".to_string(),
+        },
+    )
+    .await
+    .unwrap();
+    assert!(failed.finished);
+    let stored = common::get_task(&db, task_id).await;
+    assert_eq!(stored.status, TaskStatus::Failed);
+    assert_eq!(stored.result.as_deref(), Some("upstream API is down"));
+
+    // Finished tasks don't move again
+    let reclaimed = commands::execute(&db, &TaskCommand::Claim { task_id })
+        .await
+        .unwrap();
+    assert_eq!(
+        reclaimed.reply,
+        format!("Task {} is failed; nothing changed.", task_id)
+    );
+    assert_eq!(reclaimed.log, None);
+}
+
+#[tokio::test]
+async fn commands_for_unknown_tasks_change_nothing() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let task_id = Uuid::new_v4();
+
+    let outcome = commands::execute(
+        &db,
+        &TaskCommand::Cancel {
+            task_id,
+            reason: None,
+        },
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(outcome.reply, format!("Task {} not found.", task_id));
+    assert!(outcome.task.is_none());
+    assert!(!outcome.finished);
+}
+
+#[tokio::test]
+async fn cancelling_logs_to_the_team_and_cancels_dependents() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let coordinator = Coordinator::new(db.clone(), None, None).await.unwrap();
+    let mut team = common::team(Uuid::new_v4(), Vec::new());
+    team.settings.transport = TransportConfig::InProcess;
+    let team = common::insert_team(&db, team).await;
+    let task =
+        common::insert_task(&db, common::task(team.id, TaskStatus::InProgress, vec![])).await;
+    let dependent = common::insert_task(
+        &db,
+        common::task(team.id, TaskStatus::Blocked, vec![task.id]),
+    )
+    .await;
+
+    let outcome = coordinator
+        .run_command(
+            &team,
+            &TaskCommand::Cancel {
+                task_id: task.id,
+                reason: Some("no longer needed".to_string()),
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(outcome.reply, format!("Task {} cancelled.", task.id));
+    assert_eq!(
+        common::get_task(&db, task.id).await.status,
+        TaskStatus::Cancelled
+    );
+    assert_eq!(
+        common::get_task(&db, dependent.id).await.status,
+        TaskStatus::Cancelled
+    );
+    let logged = coordinator
+        .in_process()
+        .messages()
+        .into_iter()
+        .any(|message| {
+            message.kind == MessageKind::Log
+                && message.message == format!("Task `{}` cancelled: no longer needed", task.id)
+        });
+    assert!(logged);
+}
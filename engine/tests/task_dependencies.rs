@@ -0,0 +1,63 @@
+//! Catch-up handling of blocked tasks. Needs `TEST_DATABASE_URL`; see `common`.
+
+mod common;
+
+use engine::coordinator::Coordinator;
+use engine::models::TaskStatus;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn dispatch_pending_cancels_tasks_blocked_on_unfinished_dependencies() {
+    let Some(db) = common::test_db().await else {
+        return;
+    };
+    let coordinator = Coordinator::new(db.clone(), None, None).await.unwrap();
+    let team_id = Uuid::new_v4();
+
+    // Created after the upstream task's follow-up already cancelled its dependents
+    let mut upstreams = Vec::new();
+    for status in [
+        TaskStatus::Failed,
+        TaskStatus::Cancelled,
+        TaskStatus::TimedOut,
+    ] {
+        upstreams.push(common::insert_task(&db, common::task(team_id, status, vec![])).await);
+    }
+    let mut blocked = Vec::new();
+    for upstream in &upstreams {
+        blocked.push(
+            common::insert_task(
+                &db,
+                common::task(team_id, TaskStatus::Blocked, vec![upstream.id]),
+            )
+            .await,
+        );
+    }
+    let downstream = common::insert_task(
+        &db,
+        common::task(team_id, TaskStatus::Blocked, vec![blocked[0].id]),
+    )
+    .await;
+    let running =
+        common::insert_task(&db, common::task(team_id, TaskStatus::InProgress, vec![])).await;
+    let waiting = common::insert_task(
+        &db,
+        common::task(team_id, TaskStatus::Blocked, vec![running.id]),
+    )
+    .await;
+
+    assert_eq!(coordinator.dispatch_pending().await.unwrap(), 0);
+
+    for task in blocked.iter().chain([&downstream]) {
+        let task = common::get_task(&db, task.id).await;
+        assert_eq!(task.status, TaskStatus::Cancelled);
+        assert_eq!(
+            task.result.as_deref(),
+            Some("cancelled: an upstream task did not complete")
+        );
+    }
+    assert_eq!(
+        common::get_task(&db, waiting.id).await.status,
+        TaskStatus::Blocked
+    );
+}
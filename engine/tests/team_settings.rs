@@ -0,0 +1,92 @@
+use engine::models::{
+    ModelPlannerConfig, PlannerConfig, TeamSettings, TransportConfig, WebhookTransportConfig,
+    REDACTED,
+};
+
+fn settings(
+    endpoint: &str,
+    api_key: Option<&str>,
+    url: &str,
+    secret: Option<&str>,
+) -> TeamSettings {
+    TeamSettings {
+        planner: PlannerConfig::Model(ModelPlannerConfig {
+            endpoint: endpoint.to_string(),
+            model: "planner-model".to_string(),
+            api_key: api_key.map(str::to_string),
+            max_subtasks: None,
+        }),
+        transport: TransportConfig::Webhook(WebhookTransportConfig {
+            url: url.to_string(),
+            secret: secret.map(str::to_string),
+        }),
+        ..TeamSettings::default()
+    }
+}
+
+fn credentials(settings: &TeamSettings) -> (Option<&str>, Option<&str>) {
+    let api_key = match &settings.planner {
+        PlannerConfig::Model(model) => model.api_key.as_deref(),
+        _ => None,
+    };
+    let secret = match &settings.transport {
+        TransportConfig::Webhook(webhook) => webhook.secret.as_deref(),
+        _ => None,
+    };
+    (api_key, secret)
+}
+
+const PLANNER: &str = "https://planner.example/v1";
+const WEBHOOK: &str = "https://hooks.example/team";
+
+fn stored() -> TeamSettings {
+    settings(PLANNER, Some("sk-stored"), WEBHOOK, Some("whsec-stored"))
+}
+
+#[test]
+fn redacted_settings_sent_back_keep_the_stored_credentials() {
+    let mut incoming = stored().redacted();
+    assert_eq!(credentials(&incoming), (Some(REDACTED), Some(REDACTED)));
+
+    incoming.keep_credentials(&stored());
+
+    assert_eq!(
+        credentials(&incoming),
+        (Some("sk-stored"), Some("whsec-stored"))
+    );
+}
+
+#[test]
+fn missing_credentials_keep_the_stored_ones() {
+    let mut incoming = settings(PLANNER, None, WEBHOOK, None);
+
+    incoming.keep_credentials(&stored());
+
+    assert_eq!(
+        credentials(&incoming),
+        (Some("sk-stored"), Some("whsec-stored"))
+    );
+}
+
+#[test]
+fn new_and_empty_credentials_replace_the_stored_ones() {
+    let mut incoming = settings(PLANNER, Some("sk-new"), WEBHOOK, Some(""));
+
+    incoming.keep_credentials(&stored());
+
+    assert_eq!(credentials(&incoming), (Some("sk-new"), None));
+}
+
+#[test]
+fn credentials_are_not_carried_over_to_a_new_endpoint() {
+    let mut incoming = settings(
+        "https://other.example/v1",
+        Some(REDACTED),
+        "https://other.example/hook",
+        None,
+    );
+
+    incoming.keep_credentials(&stored());
+
+    assert_eq!(credentials(&incoming), (Some(REDACTED), None));
+}
@@ -0,0 +1,112 @@
+//! `WebhookTransport` posting to a stub receiver.
+
+mod common;
+
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use engine::coordinator::transport::{CoordinationTransport, WebhookTransport};
+use engine::models::{TaskStatus, WebhookTransportConfig};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+#[derive(Default)]
+struct Receiver {
+    /// `(authorization header, body)` of each request.
+    requests: Mutex<Vec<(Option<String>, Value)>>,
+    /// Answers every request with this instead of accepting it.
+    error: Mutex<Option<StatusCode>>,
+}
+
+type Stub = Arc<Receiver>;
+
+async fn receive(
+    State(stub): State<Stub>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    if let Some(status) = *stub.error.lock().unwrap() {
+        return (status, "receiver is down").into_response();
+    }
+    let authorization = headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    stub.requests.lock().unwrap().push((authorization, body));
+    StatusCode::NO_CONTENT.into_response()
+}
+
+async fn webhook(secret: Option<&str>) -> (Stub, WebhookTransport) {
+    let stub = Stub::default();
+    let router = Router::new()
+        .route("/hooks/team", post(receive))
+        .with_state(stub.clone());
+    let url = common::serve(router).await;
+    let transport = WebhookTransport::new(
+        reqwest::Client::new(),
+        WebhookTransportConfig {
+            url: format!("{}/hooks/team", url),
+            secret: secret.map(|secret| secret.to_string()),
+        },
+    );
+    (stub, transport)
+}
+
+fn requests(stub: &Stub) -> Vec<(Option<String>, Value)> {
+    stub.requests.lock().unwrap().clone()
+}
+
+#[tokio::test]
+async fn messages_are_posted_as_json_with_the_secret() {
+    let (stub, webhook) = webhook(Some("s3cret")).await;
+    let team = common::team(Uuid::new_v4(), Vec::new());
+    let task = common::task(team.id, TaskStatus::Pending, vec![]);
+
+    webhook.send_order(&team, &task, "build it").await.unwrap();
+    webhook
+        .send_slave_message(&team, &task, "on it")
+        .await
+        .unwrap();
+    webhook.log(&team, None, "team idle").await.unwrap();
+
+    let requests = requests(&stub);
+    assert!(requests
+        .iter()
+        .all(|(authorization, _)| authorization.as_deref() == Some("Bearer s3cret")));
+    let bodies: Vec<_> = requests.into_iter().map(|(_, body)| body).collect();
+    let order = &bodies[0];
+    assert_eq!(order["kind"], "order");
+    assert_eq!(order["team_id"], team.id.to_string());
+    assert_eq!(order["task_id"], task.id.to_string());
+    assert_eq!(order["message"], "build it");
+    assert!(order["sent_at"].is_string());
+    assert_eq!(bodies[1]["kind"], "slave_message");
+    assert_eq!(bodies[2]["kind"], "log");
+    assert!(bodies[2]["task_id"].is_null());
+}
+
+#[tokio::test]
+async fn receivers_without_a_secret_get_no_authorization() {
+    let (stub, webhook) = webhook(None).await;
+    let team = common::team(Uuid::new_v4(), Vec::new());
+
+    webhook.log(&team, None, "team idle").await.unwrap();
+
+    assert_eq!(requests(&stub)[0].0, None);
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_reported() {
+    let (stub, webhook) = webhook(Some("s3cret")).await;
+    *stub.error.lock().unwrap() = Some(StatusCode::SERVICE_UNAVAILABLE);
+    let team = common::team(Uuid::new_v4(), Vec::new());
+
+    let error = webhook.log(&team, None, "team idle").await.unwrap_err();
+
+    let error = error.to_string();
+    assert!(error.contains("503"), "{}", error);
+    assert!(error.contains("receiver is down"), "{}", error);
+}
@@ -0,0 +1,22 @@
+-- Persisted queue for deployments so API requests return immediately and
+-- in-flight work survives restarts
+
+CREATE TABLE IF NOT EXISTS deployment_jobs (
+    id uuid PRIMARY KEY,
+    kind text NOT NULL,
+    agent_ids uuid[] NOT NULL,
+    provider text NOT NULL,
+    region text,
+    railway_api_key text,
+    status text NOT NULL,
+    attempts integer NOT NULL DEFAULT 0,
+    max_attempts integer NOT NULL,
+    scheduled_at timestamptz NOT NULL,
+    deployment_id uuid,
+    last_error text,
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_deployment_jobs_status_scheduled_at
+    ON deployment_jobs(status, scheduled_at);
@@ -0,0 +1,18 @@
+-- Immutable history of the runtime plan applied to each deployment, used for rollback
+
+CREATE TABLE IF NOT EXISTS deployment_revisions (
+    id uuid PRIMARY KEY,
+    deployment_id uuid NOT NULL,
+    revision integer NOT NULL,
+    runtime text NOT NULL,
+    provider text NOT NULL,
+    region text,
+    env jsonb NOT NULL,
+    init_script text NOT NULL,
+    init_script_hash text NOT NULL,
+    services jsonb NOT NULL,
+    agent_configs jsonb NOT NULL,
+    restored_from integer,
+    created_at timestamptz NOT NULL,
+    UNIQUE (deployment_id, revision)
+);
@@ -0,0 +1,10 @@
+-- `stopped` now means paused but resumable; rows that were stopped by a destroy become `destroyed`
+
+UPDATE agents
+SET status = 'destroyed'
+WHERE status = 'stopped' AND deployment_id IS NULL;
+
+UPDATE deployments d
+SET status = 'destroyed'
+WHERE d.status = 'stopped'
+  AND NOT EXISTS (SELECT 1 FROM agents a WHERE a.deployment_id = d.id);
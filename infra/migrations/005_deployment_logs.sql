@@ -0,0 +1,21 @@
+-- Provider logs copied into the database so they outlive the deployment that produced them
+
+CREATE TABLE IF NOT EXISTS deployment_logs (
+    id uuid PRIMARY KEY,
+    deployment_id uuid NOT NULL,
+    agent_id uuid NOT NULL,
+    provider text NOT NULL,
+    logged_at timestamptz NOT NULL,
+    level text,
+    source text NOT NULL,
+    message text NOT NULL,
+    -- SHA-256 of the provider timestamp, source and message; re-archived lines are skipped
+    fingerprint text NOT NULL,
+    archived_at timestamptz NOT NULL,
+    UNIQUE (deployment_id, fingerprint)
+);
+
+CREATE INDEX IF NOT EXISTS idx_deployment_logs_agent_id ON deployment_logs(agent_id, logged_at);
+CREATE INDEX IF NOT EXISTS idx_deployment_logs_deployment_id
+    ON deployment_logs(deployment_id, logged_at);
+CREATE INDEX IF NOT EXISTS idx_deployment_logs_logged_at ON deployment_logs(logged_at);
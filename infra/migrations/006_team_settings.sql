@@ -0,0 +1,3 @@
+-- Per-team coordination settings, such as how the master decomposes tasks
+
+ALTER TABLE teams ADD COLUMN IF NOT EXISTS settings jsonb NOT NULL DEFAULT '{}';
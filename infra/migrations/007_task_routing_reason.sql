@@ -0,0 +1,3 @@
+-- Why the master assigned a subtask to its agent
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS routing_reason text;
@@ -0,0 +1,8 @@
+-- Load-aware assignment: per-agent concurrency limits and queued (unassigned) subtasks
+
+ALTER TABLE agents ADD COLUMN IF NOT EXISTS max_concurrent_tasks integer;
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS required_skills text[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX IF NOT EXISTS idx_tasks_queued ON tasks(team_id, created_at)
+    WHERE assigned_to IS NULL AND status = 'pending' AND parent_task_id IS NOT NULL;
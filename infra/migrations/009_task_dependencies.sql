@@ -0,0 +1,5 @@
+-- Task dependency graph: a task is dispatched once every task in `depends_on` has completed
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS depends_on uuid[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX IF NOT EXISTS idx_tasks_depends_on ON tasks USING GIN (depends_on);
@@ -0,0 +1,22 @@
+-- Task deadlines and retries: each dispatch to an agent is an attempt with its own deadline
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS timeout_secs integer;
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS max_attempts integer NOT NULL DEFAULT 1;
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS attempts integer NOT NULL DEFAULT 0;
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS deadline_at timestamptz;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_deadline ON tasks(deadline_at)
+    WHERE deadline_at IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS task_attempts (
+    id uuid PRIMARY KEY,
+    task_id uuid NOT NULL,
+    attempt integer NOT NULL,
+    agent_id uuid,
+    started_at timestamptz NOT NULL,
+    deadline_at timestamptz,
+    ended_at timestamptz,
+    -- Final task status the attempt ended with; NULL while it is running
+    outcome text,
+    UNIQUE (task_id, attempt)
+);
@@ -0,0 +1,4 @@
+-- Progress reported by agents while they work on a task (`!task-progress`)
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS progress integer;
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS progress_note text;
@@ -0,0 +1,19 @@
+-- Outbound Discord messages, delivered in the background with rate limiting and retries
+
+CREATE TABLE IF NOT EXISTS discord_outbox (
+    id uuid PRIMARY KEY,
+    channel_id text NOT NULL,
+    content text NOT NULL,
+    embed jsonb,
+    -- Chunks of an over-long message already delivered, so a retry resumes after them
+    chunks_sent integer NOT NULL DEFAULT 0,
+    status text NOT NULL DEFAULT 'pending',
+    attempts integer NOT NULL DEFAULT 0,
+    next_attempt_at timestamptz NOT NULL,
+    last_error text,
+    created_at timestamptz NOT NULL,
+    sent_at timestamptz
+);
+
+CREATE INDEX IF NOT EXISTS idx_discord_outbox_due ON discord_outbox(next_attempt_at)
+    WHERE status = 'pending';
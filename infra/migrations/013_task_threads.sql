@@ -0,0 +1,6 @@
+-- One Discord thread per top-level task, shared by its subtasks
+
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS discord_thread_id text;
+
+-- Outbox entries can also archive a thread once the messages queued before it are sent
+ALTER TABLE discord_outbox ADD COLUMN IF NOT EXISTS action text NOT NULL DEFAULT 'message';
@@ -0,0 +1,3 @@
+-- Slack channels for teams coordinating over Slack instead of Discord
+
+ALTER TABLE teams ADD COLUMN IF NOT EXISTS slack_channels jsonb;
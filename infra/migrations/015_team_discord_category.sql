@@ -0,0 +1,3 @@
+-- Discord category of teams whose channels the bot provisioned, removed with the team
+
+ALTER TABLE teams ADD COLUMN IF NOT EXISTS discord_category_id text;
@@ -0,0 +1,5 @@
+-- Railway keys are only needed while a job can still run; drop those left on finished jobs
+
+UPDATE deployment_jobs
+SET railway_api_key = NULL
+WHERE status IN ('succeeded', 'failed') AND railway_api_key IS NOT NULL;
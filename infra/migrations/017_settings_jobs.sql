@@ -0,0 +1,7 @@
+-- Agent settings changes are pushed to their deployment by the job queue; the job holds the
+-- unsaved settings until they are pushed
+
+ALTER TABLE deployment_jobs ADD COLUMN IF NOT EXISTS agent_settings jsonb;
+
+-- Without an index the check for an open settings job scans every job of the agent
+CREATE INDEX IF NOT EXISTS idx_deployment_jobs_agent_ids ON deployment_jobs USING gin (agent_ids);
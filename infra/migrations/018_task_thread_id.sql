@@ -0,0 +1,3 @@
+-- Task threads live on whichever transport the team uses (Discord or Slack)
+
+ALTER TABLE tasks RENAME COLUMN discord_thread_id TO thread_id;
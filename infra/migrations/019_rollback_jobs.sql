@@ -0,0 +1,3 @@
+-- Rollbacks run on the job queue too; the job records which revision to go back to
+
+ALTER TABLE deployment_jobs ADD COLUMN IF NOT EXISTS revision integer;